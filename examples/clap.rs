@@ -0,0 +1,36 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Parses key-values arguments with clap, using `KeyValueParser` as their value parser.
+//!
+//! ```text
+//! cargo run --example clap -- --disk path=/x,ro=true --disk path=/y
+//! ```
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use serde::Deserialize;
+use serde_keyvalue::KeyValueParser;
+
+#[derive(Debug, Clone, Deserialize)]
+struct Disk {
+    path: PathBuf,
+    #[serde(default)]
+    ro: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Disk to attach, e.g. `--disk path=/x,ro=true`.
+    #[arg(long, value_parser = KeyValueParser::<Disk>::new())]
+    disk: Vec<Disk>,
+}
+
+fn main() {
+    let args = Args::parse();
+    for disk in args.disk {
+        println!("{} (read-only: {})", disk.path.display(), disk.ro);
+    }
+}
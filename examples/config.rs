@@ -0,0 +1,38 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Uses a key-values string as a layered source of a config-rs configuration, overriding the
+//! defaults of the keys it contains.
+//!
+//! ```text
+//! $ cargo run --example config --features config -- port=8000,tls=true
+//! Server { host: "localhost", port: 8000, tls: true }
+//! ```
+
+use config::Config;
+use config::ConfigError;
+use serde::Deserialize;
+use serde_keyvalue::KeyValueSource;
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Server {
+    host: String,
+    port: u16,
+    tls: bool,
+}
+
+fn main() -> Result<(), ConfigError> {
+    let overrides = std::env::args().nth(1).unwrap_or_default();
+    let server: Server = Config::builder()
+        .set_default("host", "localhost")?
+        .set_default("port", 80)?
+        .set_default("tls", false)?
+        .add_source(KeyValueSource::new(overrides))
+        .build()?
+        .try_deserialize()?;
+    println!("{:?}", server);
+
+    Ok(())
+}
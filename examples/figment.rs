@@ -0,0 +1,48 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Merges a key-values string into a figment configuration with `KeyValueProvider`, which nests
+//! dotted keys.
+//!
+//! ```text
+//! $ cargo run --example figment --features figment -- port=8000,tls.enabled
+//! Server { port: 8000, tls: Tls { enabled: true, cert: "cert.pem" } }
+//! ```
+
+use figment::providers::Serialized;
+use figment::Figment;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_keyvalue::KeyValueProvider;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Tls {
+    enabled: bool,
+    cert: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Server {
+    port: u16,
+    tls: Tls,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let defaults = Server {
+        port: 80,
+        tls: Tls {
+            enabled: false,
+            cert: "cert.pem".into(),
+        },
+    };
+
+    let input = std::env::args().nth(1).unwrap_or_default();
+    let server: Server = Figment::new()
+        .merge(Serialized::defaults(defaults))
+        .merge(KeyValueProvider::new(input))
+        .extract()?;
+    println!("{:?}", server);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Writes the records logged through the log facade as logfmt lines, rendering their key-values
+//! with `to_string_from_kv_source_with`, and logs a serialized structure with `KvSource`.
+//!
+//! ```text
+//! $ cargo run --example log --features log
+//! level=INFO msg="disk attached" path="/dev/vda 1" ro=true
+//! ```
+
+use log::Log;
+use log::Metadata;
+use log::Record;
+use serde::Serialize;
+use serde_keyvalue::to_string_from_kv_source_with;
+use serde_keyvalue::KvBuilder;
+use serde_keyvalue::KvSource;
+use serde_keyvalue::Preset;
+
+#[derive(Serialize)]
+struct Disk {
+    path: String,
+    ro: bool,
+}
+
+struct Logfmt;
+
+impl Log for Logfmt {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let head = KvBuilder::with_options(Preset::Logfmt)
+            .field("level", record.level().as_str())
+            .field("msg", record.args().to_string())
+            .finish();
+        let pairs = to_string_from_kv_source_with(record.key_values(), Preset::Logfmt);
+        if let (Ok(head), Ok(pairs)) = (head, pairs) {
+            println!("{} {}", head, pairs);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn main() {
+    log::set_logger(&Logfmt).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    let disk = Disk {
+        path: "/dev/vda 1".into(),
+        ro: true,
+    };
+    log::logger().log(
+        &Record::builder()
+            .level(log::Level::Info)
+            .args(format_args!("disk attached"))
+            .key_values(&KvSource::new(&disk).unwrap())
+            .build(),
+    );
+}
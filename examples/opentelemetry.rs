@@ -0,0 +1,28 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Converts OpenTelemetry attributes from and to key-values strings: the resource attributes of
+//! `OTEL_RESOURCE_ATTRIBUTES`, which follow the W3C baggage syntax of `Preset::Baggage`, are
+//! turned into `KeyValue`s, which are then written back as logfmt.
+//!
+//! ```text
+//! $ OTEL_RESOURCE_ATTRIBUTES=service.name=api,replicas=3 \
+//!     cargo run --example opentelemetry --features opentelemetry
+//! replicas=3 service.name=api
+//! ```
+
+use serde_keyvalue::from_otel_attributes_with;
+use serde_keyvalue::to_otel_attributes_with;
+use serde_keyvalue::Preset;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::env::var("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default();
+    let attributes = to_otel_attributes_with(&input, Preset::Baggage)?;
+    println!(
+        "{}",
+        from_otel_attributes_with(&attributes, Preset::Logfmt)?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Streams logfmt records with tokio: each line of the standard input is deserialized as soon as
+//! it has been read, and written back to the standard output at once.
+//!
+//! ```text
+//! $ printf 'level=info msg="disk full"\n\nlevel=warn\n' \
+//!     | cargo run --example tokio --features tokio
+//! level=info msg="disk full"
+//! level=warn msg=""
+//! ```
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_keyvalue::to_async_writer_with;
+use serde_keyvalue::Preset;
+use serde_keyvalue::RecordStream;
+use tokio::io;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    level: String,
+    #[serde(default)]
+    msg: String,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> io::Result<()> {
+    let stdin = BufReader::new(io::stdin());
+    let mut records = RecordStream::<_, Record>::with_options(stdin, Preset::Logfmt);
+    let mut stdout = io::stdout();
+    loop {
+        match records.next().await {
+            Ok(Some(record)) => to_async_writer_with(&mut stdout, &record, Preset::Logfmt).await?,
+            Ok(None) => break,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => eprintln!("invalid record: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    stdout.flush().await
+}
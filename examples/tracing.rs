@@ -0,0 +1,22 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Writes tracing events as logfmt lines with `LogfmtFormat`, along with the fields of their spans.
+//!
+//! ```text
+//! $ cargo run --example tracing --features tracing
+//! level=INFO id=1 message="disk attached" path="/dev/vda 1" ro=true
+//! ```
+
+use serde_keyvalue::LogfmtFormat;
+
+fn main() {
+    tracing_subscriber::fmt()
+        .event_format(LogfmtFormat)
+        .fmt_fields(LogfmtFormat)
+        .init();
+
+    let _vm = tracing::info_span!("vm", id = 1).entered();
+    tracing::info!(path = "/dev/vda 1", ro = true, "disk attached");
+}
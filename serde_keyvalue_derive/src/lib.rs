@@ -0,0 +1,783 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Procedural macros for the `serde_keyvalue` crate.
+//!
+//! These macros are re-exported by `serde_keyvalue` when its `derive` feature is enabled and
+//! should be used through it.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::format_ident;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::parse_macro_input;
+use syn::punctuated::Punctuated;
+use syn::Attribute;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Expr;
+use syn::ExprLit;
+use syn::ExprPath;
+use syn::Field;
+use syn::Fields;
+use syn::GenericArgument;
+use syn::Ident;
+use syn::Lit;
+use syn::LitStr;
+use syn::Meta;
+use syn::PathArguments;
+use syn::Token;
+use syn::Type;
+
+/// Prefix of the newtype struct names carrying formatting directives to the serializer. Must match
+/// `FIELD_FORMAT` in `serde_keyvalue`.
+const FIELD_FORMAT: &str = "$serde_keyvalue::private::FieldFormat";
+
+/// Implements `argh::FromArgValue` for a struct by parsing the argument with
+/// `serde_keyvalue::from_key_values`.
+#[proc_macro_derive(FromKeyValues)]
+pub fn keyvalues_derive(input: TokenStream) -> TokenStream {
+    let DeriveInput {
+        ident, generics, ..
+    } = parse_macro_input!(input);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::serde_keyvalue::argh::FromArgValue for #ident #ty_generics #where_clause {
+            fn from_arg_value(value: &str) -> ::std::result::Result<Self, ::std::string::String> {
+                ::serde_keyvalue::from_key_values(value).map_err(|e| e.to_string())
+            }
+        }
+    }
+    .into()
+}
+
+/// Applies the `#[keyvalue(...)]` field attributes of a struct or enum.
+///
+/// This attribute must be placed before `#[derive(Serialize)]`. It turns the `#[keyvalue(...)]`
+/// attributes of the fields into `#[serde(serialize_with = ...)]` attributes that pass the
+/// formatting directives to `serde_keyvalue::KeyValueSerializer`. Other serializers are not
+/// affected.
+///
+/// The following field attributes are supported:
+///
+/// * `#[keyvalue(quote)]`: always quote the string value of the field.
+/// * `#[keyvalue(format = "hex")]`: write integers in hexadecimal. `"octal"` and `"binary"` are
+///   also supported.
+/// * `#[keyvalue(redact)]`: write `***` instead of the value of the field.
+/// * `#[keyvalue(separator = ",")]`: separate the elements of a tuple field with the given
+///   character.
+#[proc_macro_attribute]
+pub fn keyvalue(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            Span::call_site(),
+            "the `keyvalue` attribute does not take arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut input = parse_macro_input!(item as DeriveInput);
+
+    match expand_keyvalue(&mut input) {
+        Ok(helpers) => quote! {
+            #input
+            #(#helpers)*
+        }
+        .into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Rewrites the `#[keyvalue(...)]` attributes of all fields of `input`, and returns the helper
+/// functions referenced by the `serialize_with` attributes that replace them.
+fn expand_keyvalue(input: &mut DeriveInput) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let type_name = input.ident.to_string();
+    let mut helpers = Vec::new();
+
+    let fields: Vec<(String, &mut Fields)> = match &mut input.data {
+        Data::Struct(data) => vec![(type_name, &mut data.fields)],
+        Data::Enum(data) => data
+            .variants
+            .iter_mut()
+            .map(|variant| {
+                (
+                    format!("{}_{}", type_name, variant.ident),
+                    &mut variant.fields,
+                )
+            })
+            .collect(),
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "`keyvalue` cannot be used on unions",
+            ))
+        }
+    };
+
+    for (prefix, fields) in fields {
+        for (index, field) in fields.iter_mut().enumerate() {
+            let directives = match take_directives(field)? {
+                Some(directives) => directives,
+                None => continue,
+            };
+
+            let field_name = field
+                .ident
+                .as_ref()
+                .map_or_else(|| index.to_string(), |ident| ident.to_string());
+            let helper = format_ident!("__serde_keyvalue_format_{}_{}", prefix, field_name);
+            let helper_path = LitStr::new(&helper.to_string(), Span::call_site());
+            let name = LitStr::new(
+                &format!("{}:{}", FIELD_FORMAT, directives),
+                Span::call_site(),
+            );
+
+            field
+                .attrs
+                .push(syn::parse_quote!(#[serde(serialize_with = #helper_path)]));
+            helpers.push(format_helper(&helper, &name));
+        }
+    }
+
+    Ok(helpers)
+}
+
+/// Removes the `#[keyvalue(...)]` attributes from `field` and returns the formatting directives
+/// they specify, if any.
+fn take_directives(field: &mut Field) -> syn::Result<Option<String>> {
+    let mut directives = Vec::new();
+    let mut found = false;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("keyvalue")) {
+        found = true;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("quote") {
+                directives.push("quote".to_string());
+            } else if meta.path.is_ident("redact") {
+                directives.push("redact".to_string());
+            } else if meta.path.is_ident("format") {
+                let format: LitStr = meta.value()?.parse()?;
+                let radix = match format.value().as_str() {
+                    "hex" => 16,
+                    "octal" => 8,
+                    "binary" => 2,
+                    _ => {
+                        return Err(syn::Error::new(
+                            format.span(),
+                            "expected one of \"hex\", \"octal\" or \"binary\"",
+                        ))
+                    }
+                };
+                directives.push(format!("radix={}", radix));
+            } else if meta.path.is_ident("separator") {
+                let separator: LitStr = meta.value()?.parse()?;
+                let value = separator.value();
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => directives.push(format!("separator={}", c as u32)),
+                    _ => {
+                        return Err(syn::Error::new(
+                            separator.span(),
+                            "the separator must be a single character",
+                        ))
+                    }
+                }
+            } else {
+                return Err(meta.error("unsupported `keyvalue` attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    field.attrs.retain(|a| !a.path().is_ident("keyvalue"));
+
+    Ok(found.then(|| directives.join(";")))
+}
+
+/// Generates the `serialize_with` function passing the formatting directives in `name` to the
+/// serializer.
+fn format_helper(helper: &Ident, name: &LitStr) -> proc_macro2::TokenStream {
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        fn #helper<T, S>(value: &T, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+        where
+            T: ?Sized + ::serde_keyvalue::__private::serde::Serialize,
+            S: ::serde_keyvalue::__private::serde::Serializer,
+        {
+            ::serde_keyvalue::__private::serialize_formatted(#name, value, serializer)
+        }
+    }
+}
+
+/// Implements `serde_keyvalue::KeyValueHelp` for a struct with named fields.
+///
+/// The help contains one line per field, in declaration order, made of:
+///
+/// * the key of the field and the type of its value, e.g. `mtu=<u16>`. Fields of type `Option<T>`
+///   are shown with type `T`.
+/// * its default value, formatted with `Debug`, if the field or the struct has a
+///   `#[serde(default)]` attribute, or `(optional)` for `Option` fields without default.
+/// * its documentation comment, if any.
+///
+/// The `rename`, `rename_all`, `default`, `skip`, `skip_deserializing` and `flatten` serde
+/// attributes are honored. The help of flattened fields is inserted in place.
+#[proc_macro_derive(KeyValueHelp)]
+pub fn keyvalue_help_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_keyvalue_help(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Implements `FromStr` and `Display` for a type by delegating to `serde_keyvalue`, so it can be
+/// parsed with `str::parse` and formatted with `format!`.
+///
+/// `FromStr` deserializes the string with `serde_keyvalue::from_key_values` and returns a
+/// `serde_keyvalue::ParseError` on failure, so the type must implement `DeserializeOwned`.
+/// `Display` serializes the value with `serde_keyvalue::KeyValueDisplay`, so the type must
+/// implement `Serialize`.
+#[proc_macro_derive(KeyValueStr)]
+pub fn keyvalue_str_derive(input: TokenStream) -> TokenStream {
+    let DeriveInput {
+        ident, generics, ..
+    } = parse_macro_input!(input);
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+
+    let mut from_str_generics = generics.clone();
+    from_str_generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(
+            Self: ::serde_keyvalue::__private::serde::de::DeserializeOwned
+        ));
+    let from_str_where = &from_str_generics.where_clause;
+    let mut display_generics = generics.clone();
+    display_generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(Self: ::serde_keyvalue::__private::serde::Serialize));
+    let display_where = &display_generics.where_clause;
+
+    quote! {
+        impl #impl_generics ::core::str::FromStr for #ident #ty_generics #from_str_where {
+            type Err = ::serde_keyvalue::ParseError;
+
+            fn from_str(s: &str) -> ::core::result::Result<Self, Self::Err> {
+                ::serde_keyvalue::from_key_values(s)
+            }
+        }
+
+        impl #impl_generics ::core::fmt::Display for #ident #ty_generics #display_where {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Display::fmt(&::serde_keyvalue::KeyValueDisplay(self), f)
+            }
+        }
+    }
+    .into()
+}
+
+/// Serde attributes relevant to the help of a container or field.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    /// `Some(None)` for `#[serde(default)]`, `Some(Some(path))` for `#[serde(default = "path")]`.
+    default: Option<Option<ExprPath>>,
+    skip: bool,
+    flatten: bool,
+}
+
+/// Consumes the value of a serde attribute that is not relevant to the help.
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|meta| skip_meta(&meta))?;
+    }
+
+    Ok(())
+}
+
+/// Parses the value of a serde attribute that can be given either directly or for
+/// deserialization only, e.g. `rename = "a"` or `rename(deserialize = "a")`.
+fn deserialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<String>> {
+    if meta.input.peek(syn::token::Paren) {
+        let mut name = None;
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("deserialize") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                skip_meta(&meta)
+            }
+        })?;
+        Ok(name)
+    } else {
+        Ok(Some(meta.value()?.parse::<LitStr>()?.value()))
+    }
+}
+
+fn parse_serde_attrs(attrs: &[Attribute]) -> syn::Result<SerdeAttrs> {
+    let mut serde_attrs = SerdeAttrs::default();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                serde_attrs.rename = deserialize_name(&meta)?.or(serde_attrs.rename.take());
+            } else if meta.path.is_ident("rename_all") {
+                serde_attrs.rename_all = deserialize_name(&meta)?.or(serde_attrs.rename_all.take());
+            } else if meta.path.is_ident("default") {
+                serde_attrs.default = Some(if meta.input.peek(syn::Token![=]) {
+                    Some(meta.value()?.parse::<LitStr>()?.parse()?)
+                } else {
+                    None
+                });
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                serde_attrs.skip = true;
+            } else if meta.path.is_ident("flatten") {
+                serde_attrs.flatten = true;
+            } else {
+                skip_meta(&meta)?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(serde_attrs)
+}
+
+/// Applies the serde `rename_all` rule to the snake case name of a field.
+fn rename_field(name: &str, rule: &str, span: Span) -> syn::Result<String> {
+    Ok(match rule {
+        "lowercase" | "snake_case" => name.to_owned(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.replace('_', "-").to_ascii_uppercase(),
+        "camelCase" | "PascalCase" => {
+            let mut renamed = String::new();
+            let mut capitalize = rule == "PascalCase";
+            for c in name.chars() {
+                if c == '_' {
+                    capitalize = true;
+                } else if std::mem::take(&mut capitalize) {
+                    renamed.push(c.to_ascii_uppercase());
+                } else {
+                    renamed.push(c);
+                }
+            }
+            renamed
+        }
+        _ => {
+            return Err(syn::Error::new(
+                span,
+                format!("unsupported `rename_all` rule \"{}\"", rule),
+            ))
+        }
+    })
+}
+
+/// Returns the documentation of an item as a single line.
+fn doc_line(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(doc), ..
+                }) => Some(doc.value().trim().to_owned()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns `T` if `ty` is `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expand_keyvalue_help(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`KeyValueHelp` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`KeyValueHelp` can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let container = parse_serde_attrs(&input.attrs)?;
+    let mut lines = Vec::new();
+
+    for field in fields {
+        let attrs = parse_serde_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+
+        let ty = &field.ty;
+        if attrs.flatten {
+            lines.push(quote! {
+                let help = <#ty as ::serde_keyvalue::KeyValueHelp>::key_value_help();
+                if !help.is_empty() {
+                    lines.push(help);
+                }
+            });
+            continue;
+        }
+
+        // Fields are always named here.
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let name = name.strip_prefix("r#").unwrap_or(&name);
+        let key = match (&attrs.rename, &container.rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rename_field(name, rule, ident.span())?,
+            (None, None) => name.to_owned(),
+        };
+
+        let (value_ty, optional) = match option_inner(ty) {
+            Some(inner) => (inner, true),
+            None => (ty, false),
+        };
+        let value_ty = quote!(#value_ty).to_string().replace(' ', "");
+        let head = format!("{}=<{}>", key, value_ty);
+
+        let default = match (&attrs.default, &container.default) {
+            (Some(Some(path)), _) => Some(quote!(#path())),
+            (Some(None), _) => Some(quote!(<#ty as ::core::default::Default>::default())),
+            (None, Some(_)) => Some(quote!(defaults.#ident)),
+            (None, None) => None,
+        };
+        let status = match default {
+            Some(default) => quote! {
+                line.push_str(&::serde_keyvalue::__private::alloc::format!(
+                    " (default {:?})",
+                    #default
+                ));
+            },
+            None if optional => quote! {
+                line.push_str(" (optional)");
+            },
+            None => quote!(),
+        };
+
+        let doc = doc_line(&field.attrs);
+        let doc = if doc.is_empty() {
+            quote!()
+        } else {
+            let doc = format!("  {}", doc);
+            quote!(line.push_str(#doc);)
+        };
+
+        lines.push(quote! {
+            let mut line = ::serde_keyvalue::__private::alloc::string::String::from(#head);
+            #status
+            #doc
+            lines.push(line);
+        });
+    }
+
+    let defaults = container.default.as_ref().map(|default| match default {
+        Some(path) => quote!(let defaults: Self = #path();),
+        None => quote!(let defaults = <Self as ::core::default::Default>::default();),
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::serde_keyvalue::KeyValueHelp for #ident #ty_generics #where_clause {
+            fn key_value_help() -> ::serde_keyvalue::__private::alloc::string::String {
+                #defaults
+                let mut lines = ::serde_keyvalue::__private::alloc::vec::Vec::new();
+                #(#lines)*
+                lines.join("\n")
+            }
+        }
+    })
+}
+
+/// Generates a companion `<Name>Partial` struct for a struct with named fields, whose fields are
+/// the same but wrapped in `Option`, so configuration layers that only set some fields can be
+/// deserialized and merged in order.
+///
+/// The partial struct implements `Default` and `Deserialize`, and has a `merge` method returning
+/// its fields overridden by those set in another partial struct. The struct itself gets a `merge`
+/// method returning it with the fields set in a partial struct replaced.
+///
+/// The `rename_all` and `deny_unknown_fields` container attributes, and the `rename`, `alias`,
+/// `skip`, `skip_deserializing` and `flatten` field attributes are copied to the partial struct.
+/// Flattened fields are replaced as a whole when merged.
+#[proc_macro_derive(Partial)]
+pub fn partial_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_partial(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Returns the serde attributes of `attrs` keeping only the items named in `kept`.
+fn kept_serde_attrs(attrs: &[Attribute], kept: &[&str]) -> syn::Result<Vec<Attribute>> {
+    let mut kept_attrs = Vec::new();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        let metas: Vec<&Meta> = metas
+            .iter()
+            .filter(|meta| kept.iter().any(|name| meta.path().is_ident(name)))
+            .collect();
+        if !metas.is_empty() {
+            kept_attrs.push(syn::parse_quote!(#[serde(#(#metas),*)]));
+        }
+    }
+
+    Ok(kept_attrs)
+}
+
+fn expand_partial(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`Partial` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`Partial` can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let container_attrs = kept_serde_attrs(&input.attrs, &["rename_all", "deny_unknown_fields"])?;
+    let mut partial_fields = Vec::new();
+    let mut idents = Vec::new();
+
+    for field in fields {
+        let attrs = kept_serde_attrs(
+            &field.attrs,
+            &["rename", "alias", "skip", "skip_deserializing", "flatten"],
+        )?;
+        let vis = &field.vis;
+        // Fields are always named here.
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+
+        partial_fields.push(quote! {
+            #(#attrs)*
+            #vis #ident: ::core::option::Option<#ty>,
+        });
+        idents.push(ident);
+    }
+
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let partial = format_ident!("{}Partial", ident);
+    let doc = format!(
+        "Fields of [`{}`] that may be set or not, to be merged into it with [`{}::merge`].",
+        ident, ident
+    );
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(::core::default::Default, ::serde_keyvalue::__private::serde::Deserialize)]
+        #[serde(crate = "::serde_keyvalue::__private::serde")]
+        #(#container_attrs)*
+        #vis struct #partial #generics #where_clause {
+            #(#partial_fields)*
+        }
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Returns `self` with the fields set in `partial` replaced.
+            #vis fn merge(mut self, partial: #partial #ty_generics) -> Self {
+                #(
+                    if let ::core::option::Option::Some(value) = partial.#idents {
+                        self.#idents = value;
+                    }
+                )*
+                self
+            }
+        }
+
+        impl #impl_generics #partial #ty_generics #where_clause {
+            /// Returns `self` with the fields set in `other` replaced.
+            #vis fn merge(self, other: Self) -> Self {
+                Self {
+                    #(#idents: other.#idents.or(self.#idents),)*
+                }
+            }
+        }
+    })
+}
+
+/// Parses a key-values string literal at compile time and expands to a
+/// `&'static [(&'static str, &'static str)]` table of its pairs, failing the build if the string
+/// is malformed, e.g. so the default options baked into a program cannot fail to parse at runtime.
+///
+/// The string must follow the default syntax of `serde_keyvalue::pairs`: pairs separated by a
+/// space or a comma, and values either unquoted, quoted with single quotes, double-quoted with
+/// `\"`, `\\`, `\n`, `\r` and `\t` escapes, or enclosed in brackets. The values are those returned
+/// by `serde_keyvalue::pairs`: unquoted and unescaped strings, bracketed values as written, and an
+/// empty string for keys without a value. Only the syntax is checked, not the types of the values.
+#[proc_macro]
+pub fn key_values(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let input = literal.value();
+
+    match scan_pairs(&input) {
+        Ok(pairs) => {
+            let (keys, values): (Vec<_>, Vec<_>) = pairs.into_iter().unzip();
+            quote! {
+                {
+                    const PAIRS: &[(&str, &str)] = &[#((#keys, #values)),*];
+                    PAIRS
+                }
+            }
+            .into()
+        }
+        Err((message, pos)) => {
+            syn::Error::new(literal.span(), format!("{} at position {}", message, pos))
+                .to_compile_error()
+                .into()
+        }
+    }
+}
+
+/// Returns the pairs of `input` written in the default syntax of `serde_keyvalue`, or the message
+/// and position of the first error, worded as the matching `serde_keyvalue::ErrorKind`.
+fn scan_pairs(input: &str) -> Result<Vec<(String, String)>, (&'static str, usize)> {
+    let is_key_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut pairs = Vec::new();
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let rest = &input[pos..];
+        if !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            return Err(("expected an identifier", pos));
+        }
+        let key_len = rest.find(|c| !is_key_char(c)).unwrap_or(rest.len());
+        let key = &rest[..key_len];
+        pos += key_len;
+
+        let value = if input[pos..].starts_with('=') {
+            pos += 1;
+            let (value, len) = scan_value(&input[pos..]).map_err(|(e, i)| (e, pos + i))?;
+            pos += len;
+            value
+        } else {
+            String::new()
+        };
+        pairs.push((key.to_string(), value));
+
+        match input[pos..].chars().next() {
+            None => (),
+            Some(' ' | ',') => pos += 1,
+            Some('"' | '\'') => return Err(("\" and ' can only be used in quoted strings", pos)),
+            Some(_) => return Err(("remaining characters in input", pos)),
+        }
+    }
+
+    Ok(pairs)
+}
+
+/// Returns the value at the start of `s` along with its length in `s`, or the message and
+/// position of the error met within it.
+fn scan_value(s: &str) -> Result<(String, usize), (&'static str, usize)> {
+    match s.chars().next() {
+        Some('"') => {
+            let mut value = String::new();
+            let mut chars = s.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => return Ok((value, i + 1)),
+                    '\\' => match chars.next() {
+                        Some((_, '"')) => value.push('"'),
+                        Some((_, '\\')) => value.push('\\'),
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 'r')) => value.push('\r'),
+                        Some((_, 't')) => value.push('\t'),
+                        _ => return Err(("expected a string", 0)),
+                    },
+                    c => value.push(c),
+                }
+            }
+            Err(("expected a string", 0))
+        }
+        Some('\'') => match s[1..].find('\'') {
+            Some(len) => Ok((s[1..len + 1].to_string(), len + 2)),
+            None => Err(("expected a string", 0)),
+        },
+        Some('[') => {
+            // Bracketed values are kept as written, up to their matching closing bracket.
+            let mut depth = 0;
+            let mut quote = None;
+            let mut escaped = false;
+            for (i, c) in s.char_indices() {
+                match (quote, c) {
+                    _ if escaped => escaped = false,
+                    (Some('"'), '\\') => escaped = true,
+                    (Some(q), _) if c == q => quote = None,
+                    (Some(_), _) => (),
+                    (None, '"' | '\'') => quote = Some(c),
+                    (None, '[') => depth += 1,
+                    (None, ']') => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok((s[..i + 1].to_string(), i + 1));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            Err(("expected ']'", s.len()))
+        }
+        _ => {
+            let len = s.find([' ', ',', '[', ']', '"', '\'']).unwrap_or(s.len());
+            Ok((s[..len].to_string(), len))
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use serde::Deserialize;
+use serde_keyvalue::KeyValueHelp;
+
+#[test]
+fn field_help() {
+    #[allow(dead_code)]
+    #[derive(Deserialize, KeyValueHelp)]
+    #[serde(rename_all = "kebab-case")]
+    struct Disk {
+        /// Path to the disk image.
+        path: std::path::PathBuf,
+        /// Whether the disk is
+        /// read-only.
+        #[serde(default)]
+        read_only: bool,
+        #[serde(rename = "id")]
+        serial_id: Option<String>,
+        #[serde(skip)]
+        internal: u32,
+        #[serde(flatten)]
+        io: Io,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, KeyValueHelp)]
+    struct Io {
+        /// Number of queues.
+        queues: Vec<u8>,
+    }
+
+    assert_eq!(
+        Disk::key_value_help(),
+        "path=<std::path::PathBuf>  Path to the disk image.\n\
+         read-only=<bool> (default false)  Whether the disk is read-only.\n\
+         id=<String> (optional)\n\
+         queues=<Vec<u8>>  Number of queues."
+    );
+}
+
+#[test]
+fn container_default() {
+    #[allow(dead_code)]
+    #[derive(Deserialize, KeyValueHelp)]
+    #[serde(default)]
+    struct Net {
+        tap: String,
+        mtu: u16,
+    }
+
+    impl Default for Net {
+        fn default() -> Self {
+            Self {
+                tap: "tap0".into(),
+                mtu: 1500,
+            }
+        }
+    }
+
+    assert_eq!(
+        Net::key_value_help(),
+        "tap=<String> (default \"tap0\")\nmtu=<u16> (default 1500)"
+    );
+}
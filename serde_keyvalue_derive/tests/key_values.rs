@@ -0,0 +1,36 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use serde_keyvalue::key_values;
+use serde_keyvalue::pairs;
+
+const DEFAULTS: &[(&str, &str)] = key_values!("mtu=1500,tap=tap0 vhost");
+
+#[test]
+fn key_values_table() {
+    assert_eq!(DEFAULTS, [("mtu", "1500"), ("tap", "tap0"), ("vhost", "")]);
+    assert!(key_values!("").is_empty());
+
+    let table = key_values!(r#"a="x\ty" b='p q',c=[1,[2]] d=[x="]"] e= f-g_h=1 "#);
+    assert_eq!(
+        table,
+        [
+            ("a", "x\ty"),
+            ("b", "p q"),
+            ("c", "[1,[2]]"),
+            ("d", r#"[x="]"]"#),
+            ("e", ""),
+            ("f-g_h", "1"),
+        ]
+    );
+
+    // The table holds the same pairs as the ones found at runtime.
+    let input = r#"a="x\ty" b='p q',c=[1,[2]] d=[x="]"] e= f-g_h=1 "#;
+    let runtime: Vec<_> = pairs(input).map(Result::unwrap).collect();
+    assert!(runtime
+        .iter()
+        .zip(table)
+        .all(|((key, value), (k, v))| key == k && value == v));
+    assert_eq!(runtime.len(), table.len());
+}
@@ -0,0 +1,89 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use serde::Serialize;
+use serde_keyvalue::keyvalue;
+use serde_keyvalue::KeyValueSerializer;
+
+fn to_output<T: Serialize>(value: &T) -> String {
+    let mut serializer = KeyValueSerializer::new();
+    value.serialize(&mut serializer).unwrap();
+    serializer.into_output()
+}
+
+#[test]
+fn field_attributes() {
+    #[keyvalue]
+    #[derive(Serialize)]
+    struct Device {
+        #[keyvalue(quote)]
+        name: String,
+        #[keyvalue(format = "hex")]
+        addr: u32,
+        #[keyvalue(format = "octal")]
+        mode: i16,
+        #[keyvalue(redact)]
+        password: String,
+        #[keyvalue(separator = ",")]
+        irqs: (u8, u8, u8),
+        #[keyvalue(format = "binary", separator = "|")]
+        masks: (u8, u8),
+        plain: String,
+    }
+
+    let device = Device {
+        name: "eth0".into(),
+        addr: 0xfe00,
+        mode: -0o644,
+        password: "hunter2".into(),
+        irqs: (5, 7, 9),
+        masks: (0b101, 0b11),
+        plain: "x".into(),
+    };
+
+    assert_eq!(
+        to_output(&device),
+        r#"name="eth0" addr=0xfe00 mode=-0o644 password=*** irqs=5,7,9 masks=0b101|0b11 plain=x"#
+    );
+}
+
+#[test]
+fn enum_and_tuple_fields() {
+    #[keyvalue]
+    #[derive(Serialize)]
+    enum Id {
+        Raw(#[keyvalue(format = "hex")] u64),
+    }
+
+    #[derive(Serialize)]
+    struct Record {
+        id: Id,
+    }
+
+    assert_eq!(to_output(&Record { id: Id::Raw(255) }), "id=0xff");
+}
+
+#[test]
+fn formatted_option() {
+    #[keyvalue]
+    #[derive(Serialize)]
+    struct Disk {
+        #[keyvalue(quote)]
+        label: Option<String>,
+        size: u64,
+    }
+
+    let disk = Disk {
+        label: Some("data".into()),
+        size: 16,
+    };
+    assert_eq!(to_output(&disk), r#"label="data" size=16"#);
+
+    // `None` values are still skipped.
+    let disk = Disk {
+        label: None,
+        size: 16,
+    };
+    assert_eq!(to_output(&disk), "size=16");
+}
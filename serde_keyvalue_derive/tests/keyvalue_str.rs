@@ -0,0 +1,46 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_keyvalue::ErrorKind;
+use serde_keyvalue::KeyValueStr;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, KeyValueStr)]
+struct Net {
+    tap: String,
+    mtu: u16,
+    queues: Vec<u8>,
+}
+
+#[test]
+fn parse_and_display() {
+    let net: Net = "tap=tap0,mtu=1500,queues=[1,2]".parse().unwrap();
+    assert_eq!(
+        net,
+        Net {
+            tap: "tap0".into(),
+            mtu: 1500,
+            queues: vec![1, 2],
+        }
+    );
+    assert_eq!(net.to_string(), "tap=tap0 mtu=1500 queues=[1,2]");
+    assert_eq!(net.to_string().parse::<Net>().unwrap(), net);
+
+    assert_eq!(
+        "tap=tap0,mtu=x".parse::<Net>().unwrap_err().kind,
+        ErrorKind::InvalidNumber
+    );
+}
+
+#[test]
+fn generic_struct() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize, KeyValueStr)]
+    struct Limit<T> {
+        max: T,
+    }
+
+    assert_eq!("max=3".parse::<Limit<u8>>().unwrap(), Limit { max: 3 });
+    assert_eq!(format!("limit: {}", Limit { max: 3 }), "limit: max=3");
+}
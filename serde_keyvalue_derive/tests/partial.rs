@@ -0,0 +1,52 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use serde::Deserialize;
+use serde_keyvalue::from_key_values;
+use serde_keyvalue::Partial;
+
+#[derive(Debug, PartialEq, Deserialize, Partial)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Disk {
+    path: String,
+    #[serde(default)]
+    read_only: bool,
+    #[serde(rename = "id", alias = "serial")]
+    serial_id: Option<String>,
+    queues: Vec<u8>,
+}
+
+#[test]
+fn partial_layers() {
+    let defaults = Disk {
+        path: "/dev/vda".into(),
+        read_only: false,
+        serial_id: None,
+        queues: vec![1],
+    };
+
+    let file: DiskPartial = from_key_values("read-only=true,id=a").unwrap();
+    let cli: DiskPartial = from_key_values("serial=b,queues=[1,2]").unwrap();
+    assert_eq!(file.path, None);
+    assert_eq!(cli.serial_id, Some(Some("b".into())));
+
+    assert_eq!(
+        defaults.merge(file.merge(cli)),
+        Disk {
+            path: "/dev/vda".into(),
+            read_only: true,
+            serial_id: Some("b".into()),
+            queues: vec![1, 2],
+        }
+    );
+}
+
+#[test]
+fn partial_attributes() {
+    let partial = DiskPartial::default();
+    assert_eq!(partial.read_only, None);
+
+    assert!(from_key_values::<DiskPartial>("read_only=true").is_err());
+    assert!(from_key_values::<DiskPartial>("size=1").is_err());
+}
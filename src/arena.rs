@@ -0,0 +1,81 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Parsing of key-values strings into pairs allocated in a bump arena.
+
+use alloc::borrow::Cow;
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+use crate::pairs_with;
+use crate::DeserializeOptions;
+use crate::ParseError;
+
+/// Parses the key-value pairs of `input`, with the syntax described by `options`, into a vector
+/// allocated in `bump`, so parsing large batches of short-lived records does not go through the
+/// global allocator.
+///
+/// The pairs are the ones returned by [`pairs_with`]. Keys and values are borrowed from `input`
+/// when they appear verbatim in it, and copied into `bump` when they had to be unescaped. All the
+/// memory used for a batch of records is released at once by resetting the arena.
+///
+/// ```
+/// use bumpalo::Bump;
+/// use serde_keyvalue::{pairs_in, Preset};
+///
+/// let mut bump = Bump::new();
+/// let pairs = pairs_in(r#"level=info msg="disk \"vda\" full""#, Preset::Logfmt, &bump).unwrap();
+/// assert_eq!(pairs.as_slice(), [("level", "info"), ("msg", "disk \"vda\" full")]);
+/// drop(pairs);
+/// bump.reset();
+/// ```
+pub fn pairs_in<'b>(
+    input: &'b str,
+    options: impl Into<DeserializeOptions>,
+    bump: &'b Bump,
+) -> Result<BumpVec<'b, (&'b str, &'b str)>, ParseError> {
+    let in_arena = |s: Cow<'b, str>| match s {
+        Cow::Borrowed(s) => s,
+        Cow::Owned(s) => &*bump.alloc_str(&s),
+    };
+
+    let mut pairs = BumpVec::new_in(bump);
+    for pair in pairs_with(input, options) {
+        let (key, value) = pair?;
+        pairs.push((in_arena(key), in_arena(value)));
+    }
+
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn pairs_in_arena() {
+        let bump = Bump::new();
+        let input = r#"a="x\ty",b=plain c=[1,2] d"#;
+        let pairs = pairs_in(input, DeserializeOptions::default(), &bump).unwrap();
+
+        assert_eq!(
+            pairs.as_slice(),
+            [("a", "x\ty"), ("b", "plain"), ("c", "[1,2]"), ("d", "")]
+        );
+        // Values found verbatim in the input are borrowed from it, unescaped ones from the arena.
+        let in_input = |s: &str| input.as_bytes().as_ptr_range().contains(&s.as_ptr());
+        assert!(in_input(pairs[1].1));
+        assert!(!in_input(pairs[0].1));
+
+        assert_eq!(
+            pairs_in("a=1,=2", DeserializeOptions::default(), &bump).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            }
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Expansion of `@path` arguments into the contents of files.
+
+use crate::key_values::skip_trivia;
+use crate::key_values::BYTE_ORDER_MARK;
+use crate::options::DeserializeOptions;
+use crate::scanner::Scanner;
+use crate::ErrorKind;
+use crate::ParseError;
+
+/// Maximum nesting depth of argument files, so files including each other are not expanded
+/// forever.
+const MAX_DEPTH: usize = 8;
+
+/// Replaces each `@path` pair of `input` with the pairs read from the file at `path`, so long
+/// key-values strings can be stored in files, e.g. `@disk.conf,ro`.
+///
+/// Each non-empty line of the file holds one or more pairs, lines starting with `#` are ignored,
+/// and lines are joined with the first pair separator of `options`. Files may themselves contain
+/// `@path` pairs, up to 8 levels deep. Relative paths are relative to the current directory.
+///
+/// Files that cannot be read are reported as [`ErrorKind::ArgFile`] errors, and files nested too
+/// deeply as [`ErrorKind::ArgFileNesting`] errors, both at the position of the `@` in `input`.
+/// The pairs themselves are not validated, so the positions of the errors met when
+/// deserializing the returned string are relative to it rather than to `input`.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::{expand_argfiles, from_key_values, DeserializeOptions};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Disk {
+///     path: String,
+///     #[serde(default)]
+///     ro: bool,
+///     size: u64,
+/// }
+///
+/// let file = std::env::temp_dir().join(format!("argfile-doc-{}", std::process::id()));
+/// std::fs::write(&file, "# Main disk\npath=/dev/vda\nsize=1024\n").unwrap();
+///
+/// let input = format!("@{},ro", file.display());
+/// let expanded = expand_argfiles(&input, DeserializeOptions::default()).unwrap();
+/// assert_eq!(expanded, "path=/dev/vda size=1024,ro");
+///
+/// let disk: Disk = from_key_values(&expanded).unwrap();
+/// assert_eq!(disk, Disk { path: "/dev/vda".into(), ro: true, size: 1024 });
+/// # std::fs::remove_file(&file).unwrap();
+/// ```
+pub fn expand_argfiles(
+    input: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<String, ParseError> {
+    expand(input, &options.into(), 0)
+}
+
+fn expand(input: &str, options: &DeserializeOptions, depth: usize) -> Result<String, ParseError> {
+    let mut output = String::with_capacity(input.len());
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let start = input.len() - skip_trivia(&input[pos..], options).len();
+        if start == input.len() {
+            output.push_str(&input[pos..]);
+            break;
+        }
+        output.push_str(&input[pos..start]);
+
+        let (end, expanded) = match input[start..].strip_prefix('@') {
+            Some(path) => {
+                let path = match path.find(options.pair_separators.as_slice()) {
+                    Some(len) => &path[..len],
+                    None => path,
+                };
+                let contents = read_argfile(path, options, depth)
+                    .map_err(|kind| ParseError { kind, pos: start })?;
+                // Errors met in nested files are reported at the `@` of the outermost one.
+                let expanded = expand(&contents, options, depth + 1)
+                    .map_err(|e| ParseError { pos: start, ..e })?;
+
+                (start + 1 + path.len(), expanded)
+            }
+            None => {
+                let end = token_end(input, start, options)?;
+                (end, input[start..end].to_string())
+            }
+        };
+        output.push_str(&expanded);
+
+        // Empty files expand to no pairs, so they do not need a separator either.
+        match input[end..].chars().next() {
+            None => {
+                if expanded.is_empty() && output.ends_with(options.pair_separators.as_slice()) {
+                    output.pop();
+                }
+                pos = end;
+            }
+            Some(c) if options.pair_separators.contains(&c) => {
+                if !expanded.is_empty() {
+                    output.push(c);
+                }
+                pos = end + c.len_utf8();
+            }
+            Some(_) => {
+                return Err(ParseError {
+                    kind: ErrorKind::TrailingCharacters,
+                    pos: end,
+                })
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Reads the argument file at `path` and returns its lines joined as a key-values string.
+fn read_argfile(
+    path: &str,
+    options: &DeserializeOptions,
+    depth: usize,
+) -> Result<String, ErrorKind> {
+    if depth >= MAX_DEPTH {
+        return Err(ErrorKind::ArgFileNesting);
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| ErrorKind::ArgFile(format!("{}: {}", path, e)))?;
+    let separator = options.pair_separators.first().copied().unwrap_or(',');
+
+    let lines: Vec<&str> = contents
+        .strip_prefix(BYTE_ORDER_MARK)
+        .unwrap_or(&contents)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    Ok(lines.join(separator.encode_utf8(&mut [0; 4])))
+}
+
+/// Returns the end of the pair or positional value starting at `start`, excluding the separator
+/// that follows it.
+pub(crate) fn token_end(
+    input: &str,
+    start: usize,
+    options: &DeserializeOptions,
+) -> Result<usize, ParseError> {
+    match Scanner::at(input, start, options.clone()).next() {
+        Some(Ok(pair)) => Ok(pair.span.end),
+        // Positional values have no key, so they are scanned as values.
+        Some(Err(e)) => Scanner::at(input, start, options.clone())
+            .scan_raw_value()
+            .map(|value| start + value.len())
+            .map_err(|_| e),
+        None => Ok(input.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::Preset;
+
+    /// Writes `contents` to a temporary file named after `name` and returns its path.
+    fn argfile(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("argfile-{}-{}", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn argfiles_expanded() {
+        let inner = argfile("inner", "size=1024\n\n  # comment\n  ro\n");
+        let outer = argfile("outer", &format!("path=/dev/vda\n@{}\n", inner.display()));
+        let empty = argfile("empty", "# nothing\n");
+
+        let input = format!("/x,@{},name=\"a,@b\",@{}", outer.display(), empty.display());
+        assert_eq!(
+            expand_argfiles(&input, DeserializeOptions::default()).unwrap(),
+            "/x,path=/dev/vda size=1024 ro,name=\"a,@b\""
+        );
+
+        let input = format!("a=1 @{} b=[1,2]", inner.display());
+        assert_eq!(
+            expand_argfiles(&input, Preset::KernelCmdline).unwrap(),
+            "a=1 size=1024 ro b=[1,2]"
+        );
+
+        for path in [inner, outer, empty] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn argfiles_errors() {
+        let err = expand_argfiles("a=1,@/nonexistent/argfile", DeserializeOptions::default())
+            .unwrap_err();
+        assert_eq!(err.pos, 4);
+        assert!(
+            matches!(err.kind, ErrorKind::ArgFile(msg) if msg.starts_with("/nonexistent/argfile: "))
+        );
+
+        let looping = std::env::temp_dir().join(format!("argfile-loop-{}", std::process::id()));
+        std::fs::write(&looping, format!("a=1\n@{}", looping.display())).unwrap();
+        assert_eq!(
+            expand_argfiles(
+                &format!("b=2,@{}", looping.display()),
+                DeserializeOptions::default()
+            )
+            .unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ArgFileNesting,
+                pos: 4,
+            }
+        );
+        std::fs::remove_file(looping).unwrap();
+
+        assert_eq!(
+            expand_argfiles("a=\"1\"x,@b", DeserializeOptions::default()).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidCharInString,
+                pos: 5,
+            }
+        );
+    }
+}
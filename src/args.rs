@@ -0,0 +1,302 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Command-line arguments given as `key=value` pairs.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::BoolStyle;
+use crate::options::DeserializeOptions;
+use crate::options::Quoting;
+use crate::options::SerializeOptions;
+use crate::ErrorKind;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Deserializes `T` from command-line arguments that are each a `key=value` pair, as in the
+/// variable overrides of `make`, e.g. `CC=clang CFLAGS="-O2 -g"` as passed by the shell.
+///
+/// Values are taken verbatim up to the end of their argument, so they may contain quotes and
+/// separators and be empty, but not brackets, which enclose sequences. Dots are accepted in keys,
+/// and a sequence can be given by repeating its key in consecutive arguments. Arguments without a
+/// `=` are reported as [`ErrorKind::ExpectedEqual`] errors. The positions of errors are relative
+/// to the arguments joined by single spaces, as in a command line.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_args;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// #[serde(rename_all = "UPPERCASE")]
+/// struct Overrides {
+///     cc: String,
+///     cflags: String,
+///     #[serde(default)]
+///     jobs: u32,
+/// }
+///
+/// let args = ["CC=clang", "CFLAGS=-O2 -g"].map(String::from);
+/// let overrides: Overrides = from_args(args).unwrap();
+/// assert_eq!(overrides, Overrides { cc: "clang".into(), cflags: "-O2 -g".into(), jobs: 0 });
+///
+/// let err = from_args::<Overrides>(["CC=gcc", "install"].map(String::from)).unwrap_err();
+/// assert_eq!(err.to_string(), "expected '=' at position 14");
+/// ```
+///
+/// In a program, the arguments would be given as `std::env::args().skip(1)`.
+pub fn from_args<T: DeserializeOwned>(
+    args: impl IntoIterator<Item = String>,
+) -> Result<T, ParseError> {
+    let args: Vec<String> = args.into_iter().collect();
+
+    let mut pos = 0;
+    for arg in &args {
+        if !arg.contains('=') {
+            return Err(ParseError {
+                kind: ErrorKind::ExpectedEqual,
+                pos: pos + arg.len(),
+            });
+        }
+        pos += arg.len() + 1;
+    }
+
+    // Arguments cannot contain NUL characters, which can therefore separate them unambiguously.
+    let options = DeserializeOptions::new()
+        .pair_separators(['\0'])
+        .quoting(Quoting::Never)
+        .key_chars(['.'])
+        .repeated_keys(true);
+
+    from_key_values_with(&args.join("\0"), options)
+}
+
+/// Deserializes `T` from the values of a repeated Docker-style flag such as `-e KEY=VALUE` or
+/// `--label key=value`, as collected by an argument parser.
+///
+/// Values are parsed as the arguments of [`from_args`], except that a value made of a key alone,
+/// e.g. `HOME` in `-e HOME`, takes the value returned by `lookup` for this key, as `docker run`
+/// inherits the variable from its own environment, and is skipped if `lookup` returns `None`. Use
+/// [`from_docker_args_env`] to look up the environment of the current process, or a `lookup`
+/// returning an empty string to follow `--label`, for which a key alone sets an empty label. The
+/// positions of errors are relative to the values, with the looked up values filled in, joined by
+/// single spaces.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use serde_keyvalue::from_docker_args;
+///
+/// let env = ["TZ=UTC", "HOME", "USER"].map(String::from);
+/// let lookup = |key: &str| (key == "HOME").then(|| "/root".to_string());
+/// let env: BTreeMap<String, String> = from_docker_args(env, lookup).unwrap();
+/// assert_eq!(env["TZ"], "UTC");
+/// assert_eq!(env["HOME"], "/root");
+/// assert!(!env.contains_key("USER"));
+///
+/// let labels = ["tier=web", "canary"].map(String::from);
+/// let labels: BTreeMap<String, String> =
+///     from_docker_args(labels, |_| Some(String::new())).unwrap();
+/// assert_eq!(labels["canary"], "");
+/// ```
+pub fn from_docker_args<T: DeserializeOwned>(
+    values: impl IntoIterator<Item = String>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<T, ParseError> {
+    let args = values.into_iter().filter_map(|value| {
+        if value.contains('=') {
+            return Some(value);
+        }
+        lookup(&value).map(|inherited| format!("{}={}", value, inherited))
+    });
+
+    from_args(args)
+}
+
+/// Deserializes `T` from the values of a repeated Docker-style flag, taking the value of keys
+/// given alone from the environment variables of the current process. See [`from_docker_args`]
+/// for details.
+///
+/// Variables whose value is not valid Unicode are considered not set.
+#[cfg(feature = "std")]
+pub fn from_docker_args_env<T: DeserializeOwned>(
+    values: impl IntoIterator<Item = String>,
+) -> Result<T, ParseError> {
+    from_docker_args(values, |key| std::env::var(key).ok())
+}
+
+/// Serializes `value` as the arguments of a repeated Docker-style flag, i.e. `flag` followed by a
+/// `key=value` pair for each field, e.g. `["-e", "TZ=UTC", "-e", "DEBUG=true"]`, ready to be
+/// passed to `Command::args`.
+///
+/// Values are written verbatim, as the arguments of a command are not interpreted by a shell,
+/// and sequences are written as one pair per element, all with the key of the sequence. The
+/// arguments can be parsed back with [`from_docker_args`], or with [`from_args`] once the flags
+/// are removed.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_docker_args;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "UPPERCASE")]
+/// struct Env {
+///     tz: String,
+///     greeting: String,
+/// }
+///
+/// let env = Env { tz: "UTC".into(), greeting: "hello world".into() };
+/// assert_eq!(
+///     to_docker_args("-e", &env).unwrap(),
+///     ["-e", "TZ=UTC", "-e", "GREETING=hello world"]
+/// );
+/// ```
+pub fn to_docker_args<T: Serialize + ?Sized>(
+    flag: &str,
+    value: &T,
+) -> Result<Vec<String>, SerializeError> {
+    // Arguments cannot contain NUL characters, which can therefore separate them unambiguously.
+    let options = SerializeOptions::new()
+        .pair_separator("\0")
+        .bool_style(BoolStyle::Lowercase)
+        .repeated_keys(true);
+    let mut serializer = KeyValueSerializer::with_options(options);
+    value.serialize(&mut serializer)?;
+    let output = serializer.into_output();
+
+    Ok(output
+        .split('\0')
+        .filter(|pair| !pair.is_empty())
+        .flat_map(|pair| [flag.into(), pair.into()])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn args<const N: usize>(args: [&str; N]) -> Vec<String> {
+        args.into_iter().map(String::from).collect()
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Overrides {
+        prefix: String,
+        #[serde(default)]
+        verbose: bool,
+        #[serde(default, rename = "opt.level")]
+        opt_level: u8,
+        #[serde(default)]
+        feature: Vec<String>,
+    }
+
+    #[test]
+    fn args_values() {
+        assert_eq!(
+            from_args::<Overrides>(args([
+                "prefix=",
+                "feature=a, b",
+                "feature=\"d\"",
+                "opt.level=3",
+                "verbose=true",
+            ]))
+            .unwrap(),
+            Overrides {
+                prefix: "".into(),
+                verbose: true,
+                opt_level: 3,
+                feature: vec!["a, b".into(), "\"d\"".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn args_errors() {
+        assert_eq!(
+            from_args::<Overrides>(args(["prefix=/usr", "=1"])).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 12,
+            }
+        );
+        assert_eq!(
+            from_args::<Overrides>(args(["prefix=/usr", "opt.level=x"])).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 22,
+            }
+        );
+        assert_eq!(
+            from_args::<Overrides>(args([])).unwrap_err().kind,
+            ErrorKind::SerdeError("missing field `prefix`".into())
+        );
+    }
+
+    #[test]
+    fn docker_args_roundtrip() {
+        let overrides = Overrides {
+            prefix: "/opt/a b".into(),
+            verbose: true,
+            opt_level: 2,
+            feature: vec!["x=1".into(), "".into()],
+        };
+        let flags = to_docker_args("--label", &overrides).unwrap();
+
+        assert_eq!(
+            flags,
+            args([
+                "--label",
+                "prefix=/opt/a b",
+                "--label",
+                "verbose=true",
+                "--label",
+                "opt.level=2",
+                "--label",
+                "feature=x=1",
+                "--label",
+                "feature=",
+            ])
+        );
+        let values = flags.into_iter().skip(1).step_by(2);
+        assert_eq!(
+            from_docker_args::<Overrides>(values, |_| None).unwrap(),
+            overrides
+        );
+    }
+
+    #[test]
+    fn docker_args_inherited() {
+        let lookup = |key: &str| match key {
+            "prefix" => Some("/usr".into()),
+            "verbose" => Some("yes".into()),
+            _ => None,
+        };
+
+        assert_eq!(
+            from_docker_args::<Overrides>(args(["prefix", "feature", "opt.level=1"]), lookup)
+                .unwrap(),
+            Overrides {
+                prefix: "/usr".into(),
+                verbose: false,
+                opt_level: 1,
+                feature: vec![],
+            }
+        );
+        // Errors are reported in the values with the inherited ones filled in.
+        assert_eq!(
+            from_docker_args::<Overrides>(args(["prefix", "verbose"]), lookup).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedBoolean,
+                pos: 20,
+            }
+        );
+    }
+}
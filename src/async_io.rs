@@ -0,0 +1,234 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Asynchronous writing and reading of records with [tokio](https://docs.rs/tokio).
+
+use std::io;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::Lines;
+
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+use crate::KeyValueSerializer;
+use crate::SerializeOptions;
+
+/// Serializes `value` and writes it to `writer` as one record, followed by the record separator,
+/// e.g. `key1=value1 key2=42\n`.
+///
+/// The record is serialized before anything is written, so the serializer never waits for the
+/// writer, and values that cannot be serialized are reported as [`io::ErrorKind::InvalidData`]
+/// errors without writing anything.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_async_writer;
+///
+/// #[derive(Serialize)]
+/// struct Event {
+///     level: &'static str,
+///     code: u32,
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut output = Vec::new();
+/// to_async_writer(&mut output, &Event { level: "info", code: 7 }).await.unwrap();
+/// assert_eq!(output, b"level=info code=7\n");
+/// # });
+/// ```
+pub async fn to_async_writer<W, T>(writer: &mut W, value: &T) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    T: Serialize + ?Sized,
+{
+    to_async_writer_with(writer, value, SerializeOptions::default()).await
+}
+
+/// Serializes `value` according to `options` and writes it to `writer` as one record. See
+/// [`to_async_writer`] for details.
+pub async fn to_async_writer_with<W, T>(
+    writer: &mut W,
+    value: &T,
+    options: impl Into<SerializeOptions>,
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    T: Serialize + ?Sized,
+{
+    let options = options.into();
+    let separator = options.record_separator.clone();
+    let mut serializer = KeyValueSerializer::with_options(options);
+    value
+        .serialize(&mut serializer)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "cannot serialize record"))?;
+
+    let mut record = serializer.into_output();
+    record.push_str(&separator);
+    writer.write_all(record.as_bytes()).await
+}
+
+/// Stream of the records of type `T` read from the lines of an asynchronous reader.
+///
+/// Each record is deserialized as soon as its line has been read, and empty lines are skipped.
+/// Records that cannot be deserialized are reported as [`io::ErrorKind::InvalidData`] errors
+/// wrapping their [`ParseError`](crate::ParseError), after which the following lines can still be
+/// read.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::Preset;
+/// use serde_keyvalue::RecordStream;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Event {
+///     level: String,
+///     msg: String,
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let input: &[u8] = b"level=info msg=\"disk full\"\n\nlevel=warn msg=retry\n";
+/// let mut events = RecordStream::<_, Event>::with_options(input, Preset::Logfmt);
+/// while let Some(event) = events.next().await.unwrap() {
+///     println!("{:?}", event);
+/// }
+/// # });
+/// ```
+pub struct RecordStream<R, T> {
+    lines: Lines<R>,
+    options: DeserializeOptions,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R: AsyncBufRead + Unpin, T: DeserializeOwned> RecordStream<R, T> {
+    /// Creates a stream deserializing the lines of `reader` with the default options.
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DeserializeOptions::default())
+    }
+
+    /// Creates a stream deserializing the lines of `reader` with the syntax described by
+    /// `options`, which can also be a [`Preset`](crate::Preset).
+    pub fn with_options(reader: R, options: impl Into<DeserializeOptions>) -> Self {
+        Self {
+            lines: reader.lines(),
+            options: options.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Reads and deserializes the next record, or returns `None` at the end of the stream.
+    pub async fn next(&mut self) -> io::Result<Option<T>> {
+        while let Some(line) = self.lines.next_line().await? {
+            if !line.is_empty() {
+                return from_key_values_with(&line, self.options.clone())
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Consumes the stream and returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.lines.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::ParseError;
+    use crate::Preset;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        level: String,
+        code: u32,
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn async_roundtrip() {
+        let events = [
+            Event {
+                level: "info".into(),
+                code: 1,
+            },
+            Event {
+                level: "disk full".into(),
+                code: 2,
+            },
+        ];
+
+        let output = block_on(async {
+            let mut output = Vec::new();
+            for event in &events {
+                to_async_writer_with(&mut output, event, Preset::Logfmt)
+                    .await
+                    .unwrap();
+            }
+            output
+        });
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "level=info code=1\nlevel=\"disk full\" code=2\n"
+        );
+
+        let read = block_on(async {
+            let mut stream =
+                RecordStream::<_, Event>::with_options(output.as_slice(), Preset::Logfmt);
+            let mut read = Vec::new();
+            while let Some(event) = stream.next().await.unwrap() {
+                read.push(event);
+            }
+            read
+        });
+        assert_eq!(read, events);
+    }
+
+    #[test]
+    fn stream_errors() {
+        block_on(async {
+            let input: &[u8] = b"level=info,code=x\r\n\nlevel=warn,code=3\r\n";
+            let mut stream = RecordStream::<_, Event>::new(input);
+
+            let err = stream.next().await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.into_inner()
+                    .unwrap()
+                    .downcast::<ParseError>()
+                    .unwrap()
+                    .pos,
+                16
+            );
+            assert_eq!(
+                stream.next().await.unwrap(),
+                Some(Event {
+                    level: "warn".into(),
+                    code: 3,
+                })
+            );
+            assert_eq!(stream.next().await.unwrap(), None);
+        });
+
+        let mut output = Vec::new();
+        let err = block_on(to_async_writer(&mut output, &[1, 2])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Shorthand syntax of AWS CLI parameters.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::Preset;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Deserializes `T` from the value of an AWS CLI parameter written in shorthand syntax, e.g.
+/// `Name=tag:env,Values=prod,staging` or `DeviceName=/dev/sda1,Ebs={VolumeSize=20}`.
+///
+/// Nested structs are enclosed in braces, and sequences are enclosed in brackets or written as
+/// their elements separated by commas, up to the next pair. Values may be double- or
+/// single-quoted. See [`Preset::AwsShorthand`] for details.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_shorthand;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct Filter {
+///     name: String,
+///     values: Vec<String>,
+/// }
+///
+/// let filter: Filter = from_shorthand("Name=tag:env,Values=prod,staging").unwrap();
+/// assert_eq!(filter.values, ["prod", "staging"]);
+/// ```
+pub fn from_shorthand<T: DeserializeOwned>(input: &str) -> Result<T, ParseError> {
+    // Braces are rewritten as the brackets enclosing nested structs, which keeps the positions of
+    // errors unchanged.
+    let input = braces_to_brackets(input);
+    from_key_values_with(&input, Preset::AwsShorthand)
+}
+
+/// Serializes `value` as the value of an AWS CLI parameter written in shorthand syntax, which can
+/// be parsed back with [`from_shorthand`].
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_shorthand;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct Ebs {
+///     volume_size: u32,
+///     delete_on_termination: bool,
+/// }
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct BlockDeviceMapping {
+///     device_name: String,
+///     ebs: Ebs,
+/// }
+///
+/// let mapping = BlockDeviceMapping {
+///     device_name: "/dev/sda1".into(),
+///     ebs: Ebs { volume_size: 20, delete_on_termination: true },
+/// };
+/// assert_eq!(
+///     to_shorthand(&mapping).unwrap(),
+///     "DeviceName=/dev/sda1,Ebs={VolumeSize=20,DeleteOnTermination=true}"
+/// );
+/// ```
+pub fn to_shorthand<T: Serialize + ?Sized>(value: &T) -> Result<String, SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(Preset::AwsShorthand);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_output())
+}
+
+/// Deserializes a list of `T` from the arguments following an AWS CLI option that takes a list
+/// of structs, one per argument, e.g. `ParameterKey=A,ParameterValue=1` and
+/// `ParameterKey=B,ParameterValue=2` for `--parameters`.
+///
+/// The positions of errors are relative to the start of the argument in which they are met.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_shorthand_args;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct Parameter {
+///     parameter_key: String,
+///     parameter_value: String,
+/// }
+///
+/// let args = ["ParameterKey=Env,ParameterValue=prod", "ParameterKey=Size,ParameterValue=3"];
+/// let parameters: Vec<Parameter> = from_shorthand_args(args).unwrap();
+/// assert_eq!(parameters[1].parameter_value, "3");
+/// ```
+pub fn from_shorthand_args<T, I>(args: I) -> Result<Vec<T>, ParseError>
+where
+    T: DeserializeOwned,
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    args.into_iter()
+        .map(|arg| from_shorthand(arg.as_ref()))
+        .collect()
+}
+
+/// Serializes `values` as the arguments following an AWS CLI option that takes a list of
+/// structs, one per argument, which can be passed to
+/// [`Command::args`](std::process::Command::args) without further quoting.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_shorthand_args;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct Tag {
+///     key: String,
+///     value: String,
+/// }
+///
+/// let tags = [
+///     Tag { key: "team".into(), value: "infra".into() },
+///     Tag { key: "owner".into(), value: "Jane Doe".into() },
+/// ];
+/// assert_eq!(
+///     to_shorthand_args(&tags).unwrap(),
+///     ["Key=team,Value=infra", r#"Key=owner,Value="Jane Doe""#]
+/// );
+/// ```
+pub fn to_shorthand_args<'a, T, I>(values: I) -> Result<Vec<String>, SerializeError>
+where
+    T: Serialize + ?Sized + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    values.into_iter().map(to_shorthand).collect()
+}
+
+/// Returns `input` with its braces that are not quoted replaced with brackets.
+fn braces_to_brackets(input: &str) -> String {
+    let mut quote = None;
+    let mut escaped = false;
+
+    input
+        .chars()
+        .map(|c| {
+            match (quote, c) {
+                _ if escaped => escaped = false,
+                (Some('"'), '\\') => escaped = true,
+                (Some(q), _) if c == q => quote = None,
+                (Some(_), _) => (),
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '{') => return '[',
+                (None, '}') => return ']',
+                _ => (),
+            }
+            c
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::ErrorKind;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Ebs {
+        volume_size: u32,
+        snapshot_id: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Mapping {
+        device_name: String,
+        ebs: Ebs,
+        tags: Vec<String>,
+        encrypted: bool,
+    }
+
+    #[test]
+    fn shorthand_roundtrip() {
+        let mapping = Mapping {
+            device_name: "/dev/sda1".into(),
+            ebs: Ebs {
+                volume_size: 20,
+                snapshot_id: Some("snap-{1}".into()),
+            },
+            tags: vec!["a b".into(), "c".into()],
+            encrypted: false,
+        };
+        let text = to_shorthand(&mapping).unwrap();
+
+        assert_eq!(
+            text,
+            "DeviceName=/dev/sda1,Ebs={VolumeSize=20,SnapshotId=\"snap-{1}\"},\
+             Tags=[\"a b\",c],Encrypted=false"
+        );
+        assert_eq!(from_shorthand::<Mapping>(&text).unwrap(), mapping);
+    }
+
+    #[test]
+    fn shorthand_syntax() {
+        assert_eq!(
+            from_shorthand::<Mapping>(
+                "Tags=a,'b}',c,DeviceName=xvdf,Ebs={VolumeSize=8},Encrypted=true"
+            )
+            .unwrap(),
+            Mapping {
+                device_name: "xvdf".into(),
+                ebs: Ebs {
+                    volume_size: 8,
+                    snapshot_id: None,
+                },
+                tags: vec!["a".into(), "b}".into(), "c".into()],
+                encrypted: true,
+            }
+        );
+        assert_eq!(
+            from_shorthand::<Mapping>("DeviceName=xvdf,Ebs={VolumeSize=x}")
+                .unwrap_err()
+                .pos,
+            32
+        );
+    }
+
+    #[test]
+    fn shorthand_args() {
+        let ebs = [
+            Ebs {
+                volume_size: 1,
+                snapshot_id: None,
+            },
+            Ebs {
+                volume_size: 2,
+                snapshot_id: Some("s".into()),
+            },
+        ];
+        let args = to_shorthand_args(&ebs).unwrap();
+
+        assert_eq!(args, ["VolumeSize=1", "VolumeSize=2,SnapshotId=s"]);
+        let list = to_shorthand(&[("Ebs", &ebs[..])]).unwrap();
+        assert_eq!(list, "Ebs=[{VolumeSize=1},{VolumeSize=2,SnapshotId=s}]");
+        assert_eq!(
+            from_shorthand::<std::collections::BTreeMap<String, Vec<Ebs>>>(&list).unwrap()["Ebs"],
+            ebs
+        );
+        assert_eq!(from_shorthand_args::<Ebs, _>(&args).unwrap(), ebs);
+        assert_eq!(
+            from_shorthand_args::<Ebs, _>(["VolumeSize=1", "VolumeSize=-1"]).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::OutOfRange {
+                    key: "VolumeSize".into()
+                },
+                pos: 11,
+            }
+        );
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! `skv`: converts, canonicalizes, queries and validates key-values strings read from the
+//! standard input, in any of the dialects of the crate.
+//!
+//! ```text
+//! $ echo 'mtu=1500,name="tap 0",vhost' | skv to-json
+//! {"mtu":1500,"name":"tap 0","vhost":true}
+//! $ echo '{"level":"info","msg":"a b"}' | skv --preset logfmt from-json
+//! level=info msg="a b"
+//! $ echo 'b=2 a=1' | skv canonical
+//! a=1 b=2
+//! $ echo 'root=/dev/sda1 ro quiet' | skv --preset kernel-cmdline get root
+//! /dev/sda1
+//! ```
+
+use std::io::Read;
+use std::process::ExitCode;
+
+use argh::FromArgs;
+use serde_keyvalue::canonicalize;
+use serde_keyvalue::pairs_with;
+use serde_keyvalue::to_json_with;
+use serde_keyvalue::to_string_from_json_with;
+use serde_keyvalue::validate;
+use serde_keyvalue::DeserializeOptions;
+use serde_keyvalue::Preset;
+use serde_keyvalue::Quoting;
+use serde_keyvalue::SerializeOptions;
+
+/// Names of the presets accepted by `--preset`.
+const PRESETS: [(&str, Preset); 27] = [
+    ("logfmt", Preset::Logfmt),
+    ("kernel-cmdline", Preset::KernelCmdline),
+    ("module-params", Preset::ModuleParams),
+    ("mount-options", Preset::MountOptions),
+    ("dotenv", Preset::Dotenv),
+    ("query-string", Preset::QueryString),
+    ("systemd-environment", Preset::SystemdEnvironment),
+    ("java-properties", Preset::JavaProperties),
+    ("qemu", Preset::Qemu),
+    ("crosvm", Preset::Crosvm),
+    ("http-parameters", Preset::HttpParameters),
+    ("cookie", Preset::Cookie),
+    ("baggage", Preset::Baggage),
+    ("prometheus-labels", Preset::PrometheusLabels),
+    ("kubernetes-labels", Preset::KubernetesLabels),
+    ("aws-shorthand", Preset::AwsShorthand),
+    ("syslog-structured-data", Preset::SyslogStructuredData),
+    ("influx-tags", Preset::InfluxTags),
+    ("postgres-dsn", Preset::PostgresDsn),
+    ("odbc-connection-string", Preset::OdbcConnectionString),
+    ("distinguished-name", Preset::DistinguishedName),
+    ("ffmpeg-options", Preset::FfmpegOptions),
+    ("gst-element", Preset::GstElement),
+    ("sysctl", Preset::Sysctl),
+    ("headers", Preset::Headers),
+    ("shell-export", Preset::ShellExport),
+    ("ini", Preset::Ini),
+];
+
+/// Converts, canonicalizes, queries and validates the key-values string read from the standard
+/// input.
+#[derive(FromArgs)]
+struct Args {
+    /// dialect of the input and output, e.g. `logfmt` or `kernel-cmdline`; by default, pairs are
+    /// separated by spaces or commas and written separated by spaces with values quoted when needed
+    #[argh(option, from_str_fn(parse_preset))]
+    preset: Option<Preset>,
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    ToJson(ToJson),
+    FromJson(FromJson),
+    Canonical(Canonical),
+    Get(Get),
+    Validate(Validate),
+}
+
+/// Print the input as a JSON object.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "to-json")]
+struct ToJson {}
+
+/// Print the JSON object read from the input as a key-values string.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "from-json")]
+struct FromJson {}
+
+/// Print the canonical form of the input, with pairs sorted by key.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "canonical")]
+struct Canonical {}
+
+/// Print the value of a key, or fail if the key is absent.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "get")]
+struct Get {
+    /// key to look up
+    #[argh(positional)]
+    key: String,
+}
+
+/// Check the syntax of the input and print the errors found.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "validate")]
+struct Validate {}
+
+fn parse_preset(name: &str) -> Result<Preset, String> {
+    PRESETS
+        .iter()
+        .find(|(preset_name, _)| *preset_name == name)
+        .map(|&(_, preset)| preset)
+        .ok_or_else(|| {
+            let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+            format!("unknown preset, expected one of: {}", names.join(", "))
+        })
+}
+
+fn run(args: Args, input: &str) -> Result<String, String> {
+    let (serialize_options, deserialize_options) = match args.preset {
+        Some(preset) => (preset.serialize_options(), preset.deserialize_options()),
+        None => (
+            SerializeOptions::new().quoting(Quoting::WhenNeeded),
+            DeserializeOptions::default(),
+        ),
+    };
+
+    match args.command {
+        Command::ToJson(_) => to_json_with(input, deserialize_options)
+            .map(|value| value.to_string())
+            .map_err(|e| e.to_string()),
+        Command::FromJson(_) => {
+            let value: serde_json::Value =
+                serde_json::from_str(input).map_err(|e| e.to_string())?;
+            to_string_from_json_with(&value, serialize_options)
+                .map_err(|_| "expected a JSON object without nested objects".to_string())
+        }
+        Command::Canonical(_) => canonicalize(input, serialize_options).map_err(|e| e.to_string()),
+        Command::Get(Get { key }) => {
+            // As when deserializing, the last value of a repeated key wins.
+            let mut found = None;
+            for pair in pairs_with(input, deserialize_options) {
+                let (pair_key, value) = pair.map_err(|e| e.to_string())?;
+                if pair_key == key {
+                    found = Some(value.into_owned());
+                }
+            }
+            found.ok_or_else(|| format!("key `{}` not found", key))
+        }
+        Command::Validate(_) => validate(input, deserialize_options)
+            .map(|()| String::new())
+            .map_err(|errors| {
+                let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                errors.join("\n")
+            }),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Args = argh::from_env();
+
+    let mut input = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+        eprintln!("skv: cannot read the input: {}", e);
+        return ExitCode::FAILURE;
+    }
+    // The newline ending the input is not part of the last value.
+    let input = input.strip_suffix('\n').unwrap_or(&input);
+    let input = input.strip_suffix('\r').unwrap_or(input);
+
+    match run(args, input) {
+        Ok(output) if output.is_empty() => ExitCode::SUCCESS,
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            for line in message.lines() {
+                eprintln!("skv: {}", line);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(preset: Option<&str>, command: Command, input: &str) -> Result<String, String> {
+        let preset = preset.map(|name| parse_preset(name).unwrap());
+        run(Args { preset, command }, input)
+    }
+
+    #[test]
+    fn skv_commands() {
+        assert_eq!(
+            run_with(None, Command::ToJson(ToJson {}), "b=x,a=[1,2]").unwrap(),
+            r#"{"a":[1,2],"b":"x"}"#
+        );
+        assert_eq!(
+            run_with(
+                Some("dotenv"),
+                Command::FromJson(FromJson {}),
+                r#"{"A":"a b"}"#
+            )
+            .unwrap(),
+            r#"A="a b""#
+        );
+        assert_eq!(
+            run_with(None, Command::Canonical(Canonical {}), "b=2 a='x y'").unwrap(),
+            r#"a="x y" b=2"#
+        );
+        let get = |key: &str| Command::Get(Get { key: key.into() });
+        assert_eq!(run_with(None, get("a"), "a=1 b a=2").unwrap(), "2");
+        assert_eq!(run_with(None, get("b"), "a=1 b a=2").unwrap(), "");
+        assert!(run_with(None, get("c"), "a=1").is_err());
+        assert_eq!(
+            run_with(None, Command::Validate(Validate {}), "a=\"x b=1").unwrap_err(),
+            "expected a string at position 2"
+        );
+        assert!(run_with(None, Command::FromJson(FromJson {}), "[1]").is_err());
+        assert!(parse_preset("kernel").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Manual construction of key-values strings.
+
+use alloc::string::String;
+use serde::Serialize;
+
+use crate::options::SerializeOptions;
+use crate::serializer::serialize_formatted;
+use crate::KeyValueSerializer;
+use crate::SerializeError;
+
+/// Per-field format of the values written by [`KvBuilder::field_quoted`].
+const QUOTED: &str = "$serde_keyvalue::private::FieldFormat:quote";
+
+/// Builds a key-values string pair by pair, for when the pairs are not known in advance.
+///
+/// Values are written exactly like the fields of a struct serialized with
+/// [`KeyValueSerializer`], using the same options.
+///
+/// ```
+/// use serde_keyvalue::KvBuilder;
+///
+/// let name = "my disk";
+/// let output = KvBuilder::new()
+///     .field("cpu", 4)
+///     .flag("ro")
+///     .field_quoted("name", name)
+///     .field("size", None::<u64>)
+///     .finish()
+///     .unwrap();
+///
+/// assert_eq!(output, r#"cpu=4 ro name="my disk""#);
+/// ```
+///
+/// This also allows the key-values of other structured data to be rendered, such as the
+/// `log::kv::Source` of a record logged through the [log](https://docs.rs/log) facade, which the
+/// `log` feature does with `to_string_from_kv_source`.
+pub struct KvBuilder {
+    serializer: KeyValueSerializer,
+    /// Number of pairs written so far.
+    count: usize,
+    /// First error met while serializing a value.
+    error: Option<SerializeError>,
+}
+
+impl Default for KvBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KvBuilder {
+    /// Creates a builder using the default options.
+    pub fn new() -> Self {
+        Self::with_options(SerializeOptions::default())
+    }
+
+    /// Creates a builder producing its output according to `options`, which can also be a
+    /// [`Preset`](crate::Preset).
+    pub fn with_options(options: impl Into<SerializeOptions>) -> Self {
+        Self {
+            serializer: KeyValueSerializer::with_options(options),
+            count: 0,
+            error: None,
+        }
+    }
+
+    /// Appends a pair with key `key` and value `value`.
+    pub fn field<T: Serialize>(mut self, key: &str, value: T) -> Self {
+        self.push(key, &value);
+        self
+    }
+
+    /// Appends a pair with key `key` and value `value`, always quoting its string value.
+    pub fn field_quoted<T: Serialize>(mut self, key: &str, value: T) -> Self {
+        self.push(key, &Quoted(&value));
+        self
+    }
+
+    /// Appends the bare key `key`, without a value.
+    pub fn flag(mut self, key: &str) -> Self {
+        self.serializer.serialize_flag(&mut self.count, key);
+        self
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Err(e) = self.serializer.serialize_pair(&mut self.count, key, value) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Returns the built string, or an error if one of the values could not be serialized.
+    pub fn finish(self) -> Result<String, SerializeError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.serializer.into_output()),
+        }
+    }
+}
+
+/// Serializes a value with the `quote` per-field format.
+struct Quoted<'a, T: ?Sized>(&'a T);
+
+impl<T: Serialize + ?Sized> Serialize for Quoted<'_, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_formatted(QUOTED, self.0, serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoolStyle;
+    use crate::NoneStyle;
+    use crate::Preset;
+
+    #[test]
+    fn build_pairs() {
+        assert_eq!(KvBuilder::new().finish().unwrap(), "");
+        assert_eq!(
+            KvBuilder::with_options(Preset::QueryString)
+                .field("user", "bob smith")
+                .field("page", 2)
+                .field("range", (1, 2))
+                .field("tags", vec!["a", "b"])
+                .field_quoted("q", "x")
+                .finish()
+                .unwrap(),
+            r#"user=bob+smith&page=2&range=1:2&tags=a&tags=b&q="x""#
+        );
+        assert_eq!(
+            KvBuilder::with_options(SerializeOptions::new().bool_style(BoolStyle::Flag))
+                .field("ro", false)
+                .field("rw", true)
+                .flag("quiet")
+                .finish()
+                .unwrap(),
+            "rw quiet"
+        );
+    }
+
+    #[test]
+    fn build_errors() {
+        let options = SerializeOptions::new().none_style(NoneStyle::Error);
+
+        assert!(KvBuilder::with_options(options)
+            .field("a", 1)
+            .field("b", None::<u8>)
+            .flag("c")
+            .finish()
+            .is_err());
+    }
+}
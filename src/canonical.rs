@@ -0,0 +1,251 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Canonical form of key-value strings.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+
+use serde::Serialize;
+
+use crate::options::DeserializeOptions;
+use crate::options::SerializeOptions;
+use crate::scanner::ScannedValue;
+use crate::scanner::Scanner;
+use crate::serializer::write_delimiter;
+use crate::serializer::write_string;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Offset basis of the 64-bit FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// Prime of the 64-bit FNV-1a hash.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a hash, whose value does not depend on the platform nor on the Rust version.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Writes `bytes` preceded by their length, so consecutive fields cannot be confused.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+/// Parses `input` and returns the pairs it contains, sorted by key, keeping the last value of keys
+/// that appear several times.
+pub(crate) fn canonical_pairs<'a>(
+    input: &'a str,
+    options: DeserializeOptions,
+) -> Result<BTreeMap<&'a str, ScannedValue<'a>>, ParseError> {
+    let mut pairs = BTreeMap::new();
+    for pair in Scanner::new(input, options) {
+        let pair = pair?;
+        pairs.insert(pair.key, pair.value);
+    }
+
+    Ok(pairs)
+}
+
+/// Parses the key-values string `input` and re-emits it in a canonical form.
+///
+/// The input is parsed and written using the dialect described by `options`, which can be
+/// [`SerializeOptions`] or a [`Preset`](crate::Preset). In the canonical form:
+///
+/// * pairs are sorted by key,
+/// * if a key appears several times, only its last value is kept,
+/// * string values are quoted according to the quoting option, regardless of how they were quoted
+///   in the input,
+/// * values enclosed in brackets are kept verbatim.
+///
+/// Two strings that deserialize into the same value thus usually have the same canonical form,
+/// which makes it suitable for use as a cache key.
+///
+/// ```
+/// use serde_keyvalue::{canonicalize, Preset};
+///
+/// let canonical = canonicalize(r#"msg='hello world' level=warn at=1 level=info"#, Preset::Logfmt)
+///     .unwrap();
+/// assert_eq!(canonical, r#"at=1 level=info msg="hello world""#);
+/// ```
+pub fn canonicalize<O>(input: &str, options: O) -> Result<String, ParseError>
+where
+    O: Into<SerializeOptions> + Into<DeserializeOptions> + Clone,
+{
+    let serialize_options: SerializeOptions = options.clone().into();
+    let pairs = canonical_pairs(input, options.into())?;
+
+    let mut output = String::with_capacity(input.len());
+    for (key, value) in pairs {
+        if !output.is_empty() {
+            output.push_str(&serialize_options.pair_separator);
+        }
+        output.push_str(key);
+
+        match value {
+            ScannedValue::None => (),
+            ScannedValue::String(s) => {
+                write_delimiter(&mut output, &serialize_options);
+                write_string(&mut output, &s, &serialize_options);
+            }
+            ScannedValue::Bracketed(s) => {
+                write_delimiter(&mut output, &serialize_options);
+                output.push_str(s);
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Parses the key-values string `input` with the syntax described by `options` and returns a hash
+/// of its pairs, so configurations can be compared or used as cache keys without storing them.
+///
+/// As with [`canonicalize`], the hash does not depend on the order of the keys nor on how values
+/// are quoted or escaped, and only the last value of keys appearing several times is taken into
+/// account. Values enclosed in brackets are hashed verbatim, and a flag differs from a key with an
+/// empty value. The hash is computed with 64-bit FNV-1a, so it is the same on all platforms and
+/// can be persisted.
+///
+/// ```
+/// use serde_keyvalue::{stable_hash, Preset};
+///
+/// let hash = stable_hash(r#"level=info msg="hello world""#, Preset::Logfmt).unwrap();
+/// assert_eq!(stable_hash("msg='hello world' level=info", Preset::Logfmt).unwrap(), hash);
+/// assert_ne!(stable_hash("msg='hello world' level=warn", Preset::Logfmt).unwrap(), hash);
+/// ```
+pub fn stable_hash(input: &str, options: impl Into<DeserializeOptions>) -> Result<u64, ParseError> {
+    let mut hasher = Fnv1a(FNV_OFFSET_BASIS);
+    for (key, value) in canonical_pairs(input, options.into())? {
+        hasher.write_field(key.as_bytes());
+        match value {
+            ScannedValue::None => hasher.write(&[0]),
+            ScannedValue::String(s) => {
+                hasher.write(&[1]);
+                hasher.write_field(s.as_bytes());
+            }
+            ScannedValue::Bracketed(s) => {
+                hasher.write(&[2]);
+                hasher.write_field(s.as_bytes());
+            }
+        }
+    }
+
+    Ok(hasher.0)
+}
+
+/// Serializes `value` with `options` and returns the [`stable_hash`] of the output, which is also
+/// the hash of the strings that deserialize into `value` with the same options.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::{stable_hash, stable_hash_of, SerializeOptions};
+///
+/// #[derive(Serialize)]
+/// struct Disk {
+///     path: String,
+///     ro: bool,
+/// }
+///
+/// let disk = Disk { path: "/dev/vda".into(), ro: true };
+/// let hash = stable_hash_of(&disk, SerializeOptions::new()).unwrap();
+/// assert_eq!(stable_hash("ro=True path=/dev/vda", SerializeOptions::new()).unwrap(), hash);
+/// ```
+pub fn stable_hash_of<T: Serialize + ?Sized>(
+    value: &T,
+    options: impl Into<SerializeOptions>,
+) -> Result<u64, SerializeError> {
+    let options = options.into();
+    let mut serializer = KeyValueSerializer::with_options(options.clone());
+    value.serialize(&mut serializer)?;
+
+    stable_hash(&serializer.into_output(), &options)
+        .map_err(|e| SerializeError::Custom(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+    use crate::Preset;
+    use crate::Quoting;
+    use crate::Unsupported;
+
+    #[test]
+    fn canonicalize_sorts_and_deduplicates() {
+        let options = SerializeOptions::new().quoting(Quoting::WhenNeeded);
+
+        assert_eq!(
+            canonicalize("b=2 a=1 ro c=[3,4] a=5", options.clone()).unwrap(),
+            "a=5 b=2 c=[3,4] ro"
+        );
+        assert_eq!(
+            canonicalize(r#"name='my disk' path="/a""#, options.clone()).unwrap(),
+            r#"name="my disk" path=/a"#
+        );
+        assert_eq!(canonicalize("", options).unwrap(), "");
+    }
+
+    #[test]
+    fn canonicalize_is_idempotent() {
+        let input = r#"user=bob&page=2&filter=a"b&user=alice"#;
+        let canonical = canonicalize(input, Preset::QueryString).unwrap();
+
+        assert_eq!(canonical, "filter=a%22b&page=2&user=alice");
+        assert_eq!(
+            canonicalize(&canonical, Preset::QueryString).unwrap(),
+            canonical
+        );
+    }
+
+    #[test]
+    fn stable_hash_ignores_order_and_quoting() {
+        let options = SerializeOptions::new().quoting(Quoting::WhenNeeded);
+        let hash = |input| stable_hash(input, &options).unwrap();
+
+        assert_eq!(hash("b=2 a=1 ro c=[3,4] a=5"), hash("c=[3,4] ro a='5' b=2"));
+        assert_ne!(hash("a=1 ro"), hash("a=1 ro="));
+        assert_ne!(hash("a=[1]"), hash("a='[1]'"));
+        assert_ne!(hash("a=1 b=2"), hash("a=1b b=2"));
+        // The hash is stable.
+        assert_eq!(hash(""), FNV_OFFSET_BASIS);
+        assert_eq!(hash("a=1"), 0x6692_f76f_1f45_97fe);
+
+        assert_eq!(
+            stable_hash_of(&BTreeMap::from([("b", "x y"), ("a", "1")]), options.clone()),
+            Ok(hash("a=1 b='x y'"))
+        );
+        assert_eq!(
+            stable_hash_of(
+                &BTreeMap::from([("a", None::<u32>)]),
+                options.none_style(crate::NoneStyle::Error)
+            ),
+            Err(SerializeError::Unsupported(Unsupported {
+                shape: "`None` with `NoneStyle::Error`",
+                key: "a".into(),
+            }))
+        );
+    }
+
+    #[test]
+    fn canonicalize_errors() {
+        let err = canonicalize("a=1 =2", SerializeOptions::new()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            }
+        );
+    }
+}
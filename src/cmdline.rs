@@ -0,0 +1,227 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Linux kernel command line.
+
+use alloc::string::String;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::Preset;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Deserializes `T` from a Linux kernel command line, as found in `/proc/cmdline`.
+///
+/// Parameters are separated by spaces, tabs or newlines, booleans may be given as bare flags, and
+/// values containing spaces may be double-quoted. Dots in parameter names are significant, so
+/// module parameters such as `module.param=value` can be mapped to fields with `#[serde(rename)]`.
+/// See [`Preset::KernelCmdline`] for details.
+///
+/// Parsing stops at a `--` argument, as the kernel passes the arguments following it to init
+/// instead of interpreting them.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_cmdline;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Boot {
+///     root: String,
+///     ro: bool,
+///     #[serde(rename = "i915.modeset", default)]
+///     modeset: u8,
+///     #[serde(default)]
+///     quiet: bool,
+/// }
+///
+/// let boot: Boot = from_cmdline("root=/dev/sda1 ro i915.modeset=1 -- single\n").unwrap();
+/// assert_eq!(boot, Boot { root: "/dev/sda1".into(), ro: true, modeset: 1, quiet: false });
+/// ```
+pub fn from_cmdline<'a, T: Deserialize<'a>>(input: &'a str) -> Result<T, ParseError> {
+    from_key_values_with(kernel_params(input), Preset::KernelCmdline)
+}
+
+/// Returns the start of `cmdline` up to its first unquoted `--` argument, if any.
+fn kernel_params(cmdline: &str) -> &str {
+    let mut quoted = false;
+    let mut at_argument = true;
+
+    for (i, c) in cmdline.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ' ' | '\t' | '\n' if !quoted => {
+                at_argument = true;
+                continue;
+            }
+            '-' if at_argument && !quoted => {
+                let rest = cmdline[i..].strip_prefix("--");
+                if rest.is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '\n'])) {
+                    return &cmdline[..i];
+                }
+            }
+            _ => (),
+        }
+        at_argument = false;
+    }
+
+    cmdline
+}
+
+/// Serializes `value` as a Linux kernel command line, which can be parsed back with
+/// [`from_cmdline`].
+///
+/// `true` booleans are written as bare flags, `false` booleans are omitted, and values containing
+/// spaces are double-quoted.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_cmdline;
+///
+/// #[derive(Serialize)]
+/// struct Boot {
+///     root: String,
+///     ro: bool,
+///     quiet: bool,
+///     #[serde(rename = "dyndbg")]
+///     debug: String,
+/// }
+///
+/// let boot = Boot {
+///     root: "/dev/sda1".into(),
+///     ro: true,
+///     quiet: false,
+///     debug: "file foo.c +p".into(),
+/// };
+/// assert_eq!(to_cmdline(&boot).unwrap(), r#"root=/dev/sda1 ro dyndbg="file foo.c +p""#);
+/// ```
+pub fn to_cmdline<T: Serialize + ?Sized>(value: &T) -> Result<String, SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(Preset::KernelCmdline);
+    value.serialize(&mut serializer)?;
+
+    Ok(serializer.into_output())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::ErrorKind;
+    use crate::Unsupported;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Cmdline {
+        console: (String, u32),
+        #[serde(rename = "nvme_core.io_timeout")]
+        io_timeout: u32,
+        #[serde(default)]
+        nokaslr: bool,
+        #[serde(default)]
+        init: Option<String>,
+    }
+
+    #[test]
+    fn cmdline_roundtrip() {
+        let cmdline: Cmdline =
+            from_cmdline("console=ttyS0,115200  nvme_core.io_timeout=30\tnokaslr\n").unwrap();
+
+        assert_eq!(
+            cmdline,
+            Cmdline {
+                console: ("ttyS0".into(), 115200),
+                io_timeout: 30,
+                nokaslr: true,
+                init: None,
+            }
+        );
+        assert_eq!(
+            to_cmdline(&cmdline).unwrap(),
+            "console=ttyS0,115200 nvme_core.io_timeout=30 nokaslr"
+        );
+    }
+
+    #[test]
+    fn cmdline_bare_flags() {
+        // Bare flags are empty strings when their parameter is not a boolean.
+        let params: BTreeMap<String, String> = from_cmdline("quiet root=/dev/sda1 splash").unwrap();
+        assert_eq!(
+            params,
+            BTreeMap::from([
+                ("quiet".into(), "".into()),
+                ("root".into(), "/dev/sda1".into()),
+                ("splash".into(), "".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn cmdline_quoting() {
+        // Only double quotes are recognized, and backslashes are ordinary characters.
+        let params: BTreeMap<String, String> =
+            from_cmdline(r#"dyndbg="file a\b.c +p" opt='a path="C:\dir\" b=\"#).unwrap();
+        assert_eq!(
+            params,
+            BTreeMap::from([
+                ("dyndbg".into(), r"file a\b.c +p".into()),
+                ("opt".into(), "'a".into()),
+                ("path".into(), r"C:\dir\".into()),
+                ("b".into(), r"\".into()),
+            ])
+        );
+        assert_eq!(
+            to_cmdline(&params).unwrap(),
+            r#"b=\ dyndbg="file a\b.c +p" opt='a path=C:\dir\"#
+        );
+
+        // The kernel has no escapes for double quotes.
+        let params = BTreeMap::from([("init", r#"/bin/sh -c "true""#)]);
+        let mut serializer = KeyValueSerializer::with_options(Preset::KernelCmdline);
+        assert!(params.serialize(&mut serializer).is_err());
+        assert_eq!(
+            serializer.unsupported(),
+            Some(&Unsupported {
+                shape: "string containing `\"` on a kernel command line",
+                key: "init".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn cmdline_brackets() {
+        // Brackets would start a list if they were not quoted.
+        let params = BTreeMap::from([("dyndbg", "module[1] +p"), ("opt", "[x]")]);
+        let output = to_cmdline(&params).unwrap();
+        assert_eq!(output, r#"dyndbg="module[1] +p" opt="[x]""#);
+        let parsed: BTreeMap<&str, &str> = from_cmdline(&output).unwrap();
+        assert_eq!(parsed, params);
+    }
+
+    #[test]
+    fn cmdline_init_arguments() {
+        // Arguments following `--` are passed to init.
+        let params: BTreeMap<String, String> =
+            from_cmdline("ro dyndbg=\"a -- b\" -- single --verbose=2").unwrap();
+        assert_eq!(
+            params,
+            BTreeMap::from([("dyndbg".into(), "a -- b".into()), ("ro".into(), "".into())])
+        );
+        let params: BTreeMap<String, String> = from_cmdline("ro --").unwrap();
+        assert_eq!(params, BTreeMap::from([("ro".into(), "".into())]));
+        assert_eq!(kernel_params("-- a"), "");
+        assert_eq!(kernel_params("a=--"), "a=--");
+    }
+
+    #[test]
+    fn cmdline_errors() {
+        assert_eq!(
+            from_cmdline::<Cmdline>("console=ttyS0,115200 io_timeout=30")
+                .unwrap_err()
+                .kind,
+            ErrorKind::SerdeError("missing field `nvme_core.io_timeout`".into())
+        );
+    }
+}
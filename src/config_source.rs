@@ -0,0 +1,196 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Key-values strings and files as sources of a [config-rs](https://docs.rs/config)
+//! configuration.
+
+use std::fs;
+use std::path::PathBuf;
+
+use config::ConfigError;
+use config::Map;
+use config::Source;
+use config::Value;
+
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+
+/// Where the key-values string of a [`KeyValueSource`] comes from.
+#[derive(Debug, Clone)]
+enum Input {
+    String(String),
+    File(PathBuf),
+}
+
+/// Configuration source reading its values from a key-values string or file, which can be layered
+/// with the other sources of a config-rs configuration such as TOML or YAML files and environment
+/// variables.
+///
+/// Keys containing dots designate nested values, as with the other sources, e.g. `db.port=5432`
+/// sets the `port` member of the `db` table, unless other [options](Self::options) are set. Files are read each time the configuration is built,
+/// and a file that cannot be read fails the build.
+///
+/// ```
+/// use config::Config;
+/// use serde::Deserialize;
+/// use serde_keyvalue::KeyValueSource;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Server {
+///     host: String,
+///     port: u16,
+///     tls: bool,
+/// }
+///
+/// let server: Server = Config::builder()
+///     .set_default("host", "localhost")
+///     .unwrap()
+///     .set_default("tls", false)
+///     .unwrap()
+///     .add_source(KeyValueSource::new("port=8000,tls"))
+///     .build()
+///     .unwrap()
+///     .try_deserialize()
+///     .unwrap();
+/// assert_eq!(server, Server { host: "localhost".into(), port: 8000, tls: true });
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyValueSource {
+    input: Input,
+    options: DeserializeOptions,
+}
+
+impl KeyValueSource {
+    /// Creates a source reading its values from the key-values string `input`.
+    pub fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: Input::String(input.into()),
+            options: DeserializeOptions::new().nested_key_separator('.'),
+        }
+    }
+
+    /// Creates a source reading its values from the key-values file at `path`.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            input: Input::File(path.into()),
+            options: DeserializeOptions::new().nested_key_separator('.'),
+        }
+    }
+
+    /// Sets the syntax of the key-values string, which can also be a [`Preset`](crate::Preset).
+    /// Keys are only nested if `options` has a
+    /// [nested key separator](DeserializeOptions::nested_key_separator).
+    pub fn options(mut self, options: impl Into<DeserializeOptions>) -> Self {
+        self.options = options.into();
+        self
+    }
+}
+
+impl Source for KeyValueSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        match &self.input {
+            Input::String(input) => from_key_values_with(input, self.options.clone())
+                .map_err(|e| ConfigError::Foreign(Box::new(e))),
+            Input::File(path) => {
+                let file_error = |cause| ConfigError::FileParse {
+                    uri: Some(path.display().to_string()),
+                    cause,
+                };
+                let input = fs::read_to_string(path).map_err(|e| file_error(Box::new(e)))?;
+                from_key_values_with(&input, self.options.clone())
+                    .map_err(|e| file_error(Box::new(e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Config;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::Preset;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Db {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Settings {
+        name: String,
+        db: Db,
+    }
+
+    fn settings(source: KeyValueSource) -> Result<Settings, ConfigError> {
+        Config::builder()
+            .set_default("name", "app")?
+            .set_default("db.host", "localhost")?
+            .set_default("db.port", 5432)?
+            .add_source(source)
+            .build()?
+            .try_deserialize()
+    }
+
+    #[test]
+    fn layered_string() {
+        assert_eq!(
+            settings(KeyValueSource::new("db.port=6432")).unwrap(),
+            Settings {
+                name: "app".into(),
+                db: Db {
+                    host: "localhost".into(),
+                    port: 6432,
+                },
+            }
+        );
+        assert_eq!(
+            settings(
+                KeyValueSource::new("name=\"my app\" db.host=db")
+                    .options(DeserializeOptions::from(Preset::Logfmt).nested_key_separator('.'))
+            )
+            .unwrap(),
+            Settings {
+                name: "my app".into(),
+                db: Db {
+                    host: "db".into(),
+                    port: 5432,
+                },
+            }
+        );
+        assert!(matches!(
+            settings(KeyValueSource::new("db.port=[")),
+            Err(ConfigError::Foreign(_))
+        ));
+    }
+
+    #[test]
+    fn layered_file() {
+        let path = std::env::temp_dir().join(format!("config-{}", std::process::id()));
+        fs::write(&path, "name=server,db.host=db.internal").unwrap();
+        let result = settings(KeyValueSource::file(&path));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            result.unwrap(),
+            Settings {
+                name: "server".into(),
+                db: Db {
+                    host: "db.internal".into(),
+                    port: 5432,
+                },
+            }
+        );
+        match settings(KeyValueSource::file(&path)).unwrap_err() {
+            ConfigError::FileParse { uri, .. } => assert_eq!(uri, Some(path.display().to_string())),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
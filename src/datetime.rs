@@ -0,0 +1,235 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! RFC 3339 timestamps, such as `2024-05-01T10:00:00.5+02:00`, for [chrono] date and time types.
+//!
+//! This module is meant to be used with `#[serde(with = "serde_keyvalue::datetime")]` on
+//! [`DateTime`] fields. Timestamps are written in RFC 3339 form, which does not need to be quoted,
+//! with as many fractional digits as needed and `Z` for UTC:
+//!
+//! ```
+//! use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+//! use serde::{Deserialize, Serialize};
+//! use serde_keyvalue::{from_key_values, KeyValueSerializer};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Event {
+//!     #[serde(with = "serde_keyvalue::datetime")]
+//!     at: DateTime<Utc>,
+//!     #[serde(with = "serde_keyvalue::datetime")]
+//!     local: DateTime<FixedOffset>,
+//! }
+//!
+//! let event: Event =
+//!     from_key_values("at=2024-05-01T08:00:00Z,local=2024-05-01T10:00:00.5+02:00").unwrap();
+//! assert_eq!(event.at, Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap());
+//!
+//! let mut serializer = KeyValueSerializer::new();
+//! event.serialize(&mut serializer).unwrap();
+//! assert_eq!(
+//!     serializer.into_output(),
+//!     "at=2024-05-01T08:00:00Z local=2024-05-01T10:00:00.500+02:00"
+//! );
+//! ```
+//!
+//! Timestamps written in another form are parsed with [`deserialize_with_format`], which accepts
+//! RFC 3339 timestamps as well as those matching the strptime-like format of a [`Format`], e.g. the
+//! one of web server logs:
+//!
+//! ```
+//! use chrono::{DateTime, Utc};
+//! use serde::Deserialize;
+//! use serde_keyvalue::datetime::Format;
+//! use serde_keyvalue::from_key_values;
+//!
+//! struct Clf;
+//!
+//! impl Format for Clf {
+//!     const FORMAT: &'static str = "%d/%b/%Y:%H:%M:%S %z";
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Request {
+//!     #[serde(
+//!         deserialize_with = "serde_keyvalue::datetime::deserialize_with_format::<Clf, _, _>"
+//!     )]
+//!     at: DateTime<Utc>,
+//! }
+//!
+//! let request: Request = from_key_values(r#"at="10/Oct/2000:13:55:36 -0700""#).unwrap();
+//! assert_eq!(request.at.to_rfc3339(), "2000-10-10T20:55:36+00:00");
+//! ```
+//!
+//! Formats without a time zone are read as UTC. [time](https://docs.rs/time) types are serialized
+//! with their own serde implementations.
+
+use alloc::string::String;
+use core::marker::PhantomData;
+
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::NaiveDateTime;
+use chrono::SecondsFormat;
+use chrono::TimeZone;
+use serde::de::Error;
+use serde::de::Unexpected;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serializer;
+
+/// A strptime-like format of timestamps, as understood by [`chrono::format::strftime`], accepted
+/// by [`deserialize_with_format`] in addition to RFC 3339.
+pub trait Format {
+    /// The format, e.g. `%d/%b/%Y:%H:%M:%S %z`.
+    const FORMAT: &'static str;
+}
+
+/// Accepts RFC 3339 timestamps only.
+struct Rfc3339;
+
+impl Format for Rfc3339 {
+    const FORMAT: &'static str = "";
+}
+
+/// Serializes `datetime` as an RFC 3339 timestamp, e.g. `2024-05-01T10:00:00.5+02:00`.
+pub fn serialize<Tz: TimeZone, S: Serializer>(
+    datetime: &DateTime<Tz>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Deserializes a datetime from an RFC 3339 timestamp, e.g. `2024-05-01T10:00:00.5+02:00`.
+pub fn deserialize<'de, Tz, D>(deserializer: D) -> Result<DateTime<Tz>, D::Error>
+where
+    Tz: TimeZone,
+    DateTime<Tz>: From<DateTime<FixedOffset>>,
+    D: Deserializer<'de>,
+{
+    deserialize_with_format::<Rfc3339, Tz, D>(deserializer)
+}
+
+/// Deserializes a datetime from an RFC 3339 timestamp or from a timestamp in the format of `F`.
+pub fn deserialize_with_format<'de, F, Tz, D>(deserializer: D) -> Result<DateTime<Tz>, D::Error>
+where
+    F: Format,
+    Tz: TimeZone,
+    DateTime<Tz>: From<DateTime<FixedOffset>>,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    parse(&s, F::FORMAT)
+        .map(DateTime::from)
+        .ok_or_else(|| D::Error::invalid_value(Unexpected::Str(&s), &Expected::<F>(PhantomData)))
+}
+
+/// Describes the timestamps accepted with the format of `F`.
+struct Expected<F>(PhantomData<F>);
+
+impl<F: Format> serde::de::Expected for Expected<F> {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match F::FORMAT {
+            "" => formatter.write_str("an RFC 3339 timestamp"),
+            format => write!(
+                formatter,
+                "an RFC 3339 timestamp or one formatted as `{}`",
+                format
+            ),
+        }
+    }
+}
+
+/// Parses `s` as an RFC 3339 timestamp or, if `format` is not empty, as a timestamp in that
+/// format, read as UTC if it has no time zone.
+fn parse(s: &str, format: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime);
+    }
+    if format.is_empty() {
+        return None;
+    }
+
+    DateTime::parse_from_str(s, format).ok().or_else(|| {
+        NaiveDateTime::parse_from_str(s, format)
+            .ok()
+            .map(|datetime| datetime.and_utc().fixed_offset())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::from_key_values;
+    use crate::KeyValueSerializer;
+
+    struct Syslog;
+
+    impl Format for Syslog {
+        const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        #[serde(with = "crate::datetime")]
+        at: DateTime<FixedOffset>,
+        #[serde(
+            serialize_with = "serialize",
+            deserialize_with = "deserialize_with_format::<Syslog, _, _>"
+        )]
+        seen: DateTime<Utc>,
+    }
+
+    #[test]
+    fn datetime_parse() {
+        let utc = |s| parse(s, "").map(|datetime| datetime.with_timezone(&Utc));
+        let expected = Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap();
+        assert_eq!(utc("2024-05-01T08:00:00Z"), Some(expected));
+        assert_eq!(utc("2024-05-01T10:00:00+02:00"), Some(expected));
+        assert_eq!(utc("2024-05-01t08:00:00z"), Some(expected));
+        assert_eq!(utc("2024-05-01 08:00:00Z"), Some(expected));
+        assert_eq!(utc("2024-05-01T08:00:00"), None);
+        assert_eq!(utc("2024-05-01"), None);
+        assert_eq!(utc("1714550400"), None);
+
+        assert_eq!(
+            parse("2024-05-01 08:00:00", Syslog::FORMAT),
+            Some(expected.fixed_offset())
+        );
+        assert_eq!(
+            parse("01/May/2024:10:00:00 +0200", "%d/%b/%Y:%H:%M:%S %z"),
+            Some(expected.with_timezone(&FixedOffset::east_opt(7200).unwrap()))
+        );
+        assert_eq!(parse("2024-05-01 08:00", Syslog::FORMAT), None);
+    }
+
+    #[test]
+    fn datetime_round_trip() {
+        let record: Record =
+            from_key_values(r#"at=2024-05-01T10:00:00.123456+02:00,seen="2024-05-01 08:00:00""#)
+                .unwrap();
+        assert_eq!(
+            record.seen,
+            Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap()
+        );
+        assert_eq!(record.at.offset().local_minus_utc(), 7200);
+
+        let mut serializer = KeyValueSerializer::new();
+        record.serialize(&mut serializer).unwrap();
+        let output = serializer.into_output();
+        assert_eq!(
+            output,
+            "at=2024-05-01T10:00:00.123456+02:00 seen=2024-05-01T08:00:00Z"
+        );
+        assert_eq!(from_key_values::<Record>(&output).unwrap(), record);
+
+        let err = from_key_values::<Record>("at=2024-05-01,seen=2024-05-01T08:00:00Z").unwrap_err();
+        assert_eq!(err.pos, 0);
+        let err = from_key_values::<Record>("at=2024-05-01T08:00:00Z,seen=yesterday").unwrap_err();
+        assert!(err.to_string().contains("formatted as `%Y-%m-%d %H:%M:%S`"));
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Comparison of key-values strings.
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::canonical::canonical_pairs;
+use crate::options::DeserializeOptions;
+use crate::scanner::ScannedValue;
+use crate::scanner::Scanner;
+use crate::ParseError;
+
+/// A key whose value differs between two key-values strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// The key.
+    pub key: String,
+    /// Value of the key in the first string.
+    pub old: String,
+    /// Value of the key in the second string.
+    pub new: String,
+}
+
+/// Differences between two key-values strings, as returned by [`diff`].
+///
+/// Keys are sorted in each list. Values are unquoted and unescaped, values enclosed in brackets
+/// are compared verbatim, and keys given without a value, i.e. flags, have an empty value.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KvDiff {
+    /// Keys only present in the second string, with their value.
+    pub added: Vec<(String, String)>,
+    /// Keys only present in the first string, with their value.
+    pub removed: Vec<(String, String)>,
+    /// Keys present in both strings with a different value.
+    pub changed: Vec<Change>,
+}
+
+impl KvDiff {
+    /// Returns `true` if both strings have the same pairs.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Writes a pair, omitting the delimiter of flags.
+fn write_pair(f: &mut fmt::Formatter<'_>, key: &str, value: &str) -> fmt::Result {
+    if value.is_empty() {
+        f.write_str(key)
+    } else {
+        write!(f, "{}={}", key, value)
+    }
+}
+
+impl fmt::Display for KvDiff {
+    /// Writes the differences on a single line, e.g. `mtu=1500->9000 +vhost -debug=1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        let mut separate = |f: &mut fmt::Formatter<'_>| -> fmt::Result {
+            if !core::mem::take(&mut first) {
+                f.write_str(" ")?;
+            }
+            Ok(())
+        };
+
+        for change in &self.changed {
+            separate(f)?;
+            write!(f, "{}={}->{}", change.key, change.old, change.new)?;
+        }
+        for (key, value) in &self.added {
+            separate(f)?;
+            f.write_str("+")?;
+            write_pair(f, key, value)?;
+        }
+        for (key, value) in &self.removed {
+            separate(f)?;
+            f.write_str("-")?;
+            write_pair(f, key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the differences between the key-values strings `a` and `b`, parsed with the default
+/// options of the deserializer.
+///
+/// Only the values of the pairs are compared, so differences in quoting, order or separators are
+/// ignored. If a key appears several times, its last value is used.
+///
+/// ```
+/// use serde_keyvalue::diff;
+///
+/// let diff = diff("mtu=1500,tap=tap0,debug", r#"tap="tap0" mtu=9000 vhost"#).unwrap();
+///
+/// assert_eq!(diff.changed[0].key, "mtu");
+/// assert_eq!(diff.to_string(), "mtu=1500->9000 +vhost -debug");
+/// ```
+pub fn diff(a: &str, b: &str) -> Result<KvDiff, ParseError> {
+    diff_with(a, b, DeserializeOptions::default())
+}
+
+/// Returns the differences between the key-values strings `a` and `b`, parsed with the syntax
+/// described by `options`. See [`diff`] for details.
+pub fn diff_with(
+    a: &str,
+    b: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<KvDiff, ParseError> {
+    let options = options.into();
+    let collect = |input| {
+        Scanner::new(input, options.clone())
+            .map(|pair| pair.map(|pair| (pair.key, pair.value.into_text())))
+            .collect::<Result<BTreeMap<_, _>, _>>()
+    };
+    let mut old = collect(a)?;
+    let new = collect(b)?;
+
+    let mut diff = KvDiff::default();
+    for (key, new) in new {
+        match old.remove(key) {
+            None => diff.added.push((key.to_owned(), new.into_owned())),
+            Some(old) if old != new => diff.changed.push(Change {
+                key: key.to_owned(),
+                old: old.into_owned(),
+                new: new.into_owned(),
+            }),
+            Some(_) => (),
+        }
+    }
+    diff.removed = old
+        .into_iter()
+        .map(|(key, old)| (key.to_owned(), old.into_owned()))
+        .collect();
+
+    Ok(diff)
+}
+
+/// Returns `true` if all the pairs of the key-values string `a` are also in `b`, parsed with the
+/// default options of the deserializer.
+///
+/// As with [`diff`], only the values of the pairs are compared, and the last value of keys
+/// appearing several times is used. Flags have an empty value.
+///
+/// ```
+/// use serde_keyvalue::is_subset;
+///
+/// assert!(is_subset("tier=db,ro", "app=web tier='db' ro").unwrap());
+/// assert!(!is_subset("tier=db,ro", "app=web tier=db").unwrap());
+/// ```
+pub fn is_subset(a: &str, b: &str) -> Result<bool, ParseError> {
+    is_subset_with(a, b, DeserializeOptions::default())
+}
+
+/// Returns `true` if all the pairs of the key-values string `a` are also in `b`, parsed with the
+/// syntax described by `options`. See [`is_subset`] for details.
+pub fn is_subset_with(
+    a: &str,
+    b: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<bool, ParseError> {
+    contains_pairs(a, b, options.into(), false)
+}
+
+/// Returns `true` if the key-values string `labels` satisfies the constraints of `selector`,
+/// parsed with the default options of the deserializer, e.g. to select the records or the routing
+/// rules that apply to a set of labels.
+///
+/// Each pair of `selector` requires `labels` to have the same value for its key, and each flag
+/// requires `labels` to have its key, whatever its value. Other keys of `labels` are ignored. As
+/// with [`is_subset`], the last value of keys appearing several times is used.
+///
+/// ```
+/// use serde_keyvalue::matches;
+///
+/// assert!(matches("app=web,canary", "app=web canary=1 zone=eu").unwrap());
+/// assert!(!matches("app=web,canary", "app=web zone=eu").unwrap());
+/// assert!(!matches("app=web,canary", "app=db canary").unwrap());
+/// ```
+pub fn matches(selector: &str, labels: &str) -> Result<bool, ParseError> {
+    matches_with(selector, labels, DeserializeOptions::default())
+}
+
+/// Returns `true` if the key-values string `labels` satisfies the constraints of `selector`,
+/// parsed with the syntax described by `options`. See [`matches`] for details.
+pub fn matches_with(
+    selector: &str,
+    labels: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<bool, ParseError> {
+    contains_pairs(selector, labels, options.into(), true)
+}
+
+/// Returns `true` if `b` has the pairs of `a`. If `flags_match_any` is set, the flags of `a` only
+/// require `b` to have their key.
+fn contains_pairs(
+    a: &str,
+    b: &str,
+    options: DeserializeOptions,
+    flags_match_any: bool,
+) -> Result<bool, ParseError> {
+    let required = canonical_pairs(a, options.clone())?;
+    let available = canonical_pairs(b, options)?;
+
+    Ok(required
+        .into_iter()
+        .all(|(key, value)| match (value, available.get(key)) {
+            (_, None) => false,
+            (ScannedValue::None, Some(_)) if flags_match_any => true,
+            (value, Some(other)) => value.into_text() == other.clone().into_text(),
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn diff_pairs() {
+        let res = diff("c=3 a=1 b=2 a=4 d", "b=2,c='5',e=[1,2]").unwrap();
+
+        assert_eq!(
+            res,
+            KvDiff {
+                added: vec![("e".into(), "[1,2]".into())],
+                removed: vec![("a".into(), "4".into()), ("d".into(), "".into())],
+                changed: vec![Change {
+                    key: "c".into(),
+                    old: "3".into(),
+                    new: "5".into(),
+                }],
+            }
+        );
+        assert_eq!(res.to_string(), "c=3->5 +e=[1,2] -a=4 -d");
+
+        let res = diff(r#"a="x" b"#, "b a=x").unwrap();
+        assert!(res.is_empty());
+        assert_eq!(res.to_string(), "");
+    }
+
+    #[test]
+    fn subset_and_selectors() {
+        assert!(is_subset("", "a=1").unwrap());
+        assert!(is_subset("b=\"x y\" a", "a c=3 b='x y'").unwrap());
+        assert!(is_subset("a=1 a=2", "a=2").unwrap());
+        assert!(!is_subset("a", "a=1").unwrap());
+        assert!(!is_subset("a=1", "a=1 a=2").unwrap());
+
+        assert!(matches("a", "a=1").unwrap());
+        assert!(matches("a b=''", "a b").unwrap());
+        assert!(!matches("a=1 c", "a=1 b").unwrap());
+
+        let options = DeserializeOptions::new().pair_separators(['&']);
+        assert!(matches_with("env=prod&eu", "eu&env=prod&zone=eu-1", options.clone()).unwrap());
+        assert!(!is_subset_with("env=prod&eu", "eu=1&env=prod", options).unwrap());
+
+        assert_eq!(
+            matches("a=1", "a=1,,b").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn diff_errors() {
+        assert_eq!(
+            diff("a=1", "a=1,,b").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            }
+        );
+    }
+}
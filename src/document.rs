@@ -0,0 +1,266 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Editing of key-values strings in place.
+
+use alloc::borrow::Cow;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use crate::options::DeserializeOptions;
+use crate::options::Quoting;
+use crate::options::SerializeOptions;
+use crate::scanner::ScannedPair;
+use crate::scanner::Scanner;
+use crate::serializer::write_delimiter;
+use crate::serializer::write_string;
+use crate::ParseError;
+
+/// A key-values string that can be queried and edited without being deserialized into a type.
+///
+/// Edits only touch the text of the pairs they apply to, so the formatting, order and quoting of
+/// the other pairs is preserved. If a key appears several times, its last occurrence is the one
+/// being read or replaced, as it is the one that takes effect when deserializing.
+///
+/// ```
+/// use serde_keyvalue::{KvDocument, Preset};
+///
+/// let mut cmdline =
+///     KvDocument::parse_with("root=/dev/sda1 ro quiet mtu=1500", Preset::KernelCmdline).unwrap();
+///
+/// assert_eq!(cmdline.get("mtu").as_deref(), Some("1500"));
+/// cmdline.set("mtu", "9000");
+/// cmdline.remove("quiet");
+/// cmdline.set("console", "ttyS0,115200");
+///
+/// assert_eq!(cmdline.to_string(), "root=/dev/sda1 ro mtu=9000 console=ttyS0,115200");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvDocument {
+    text: String,
+    serialize_options: SerializeOptions,
+    deserialize_options: DeserializeOptions,
+}
+
+impl KvDocument {
+    /// Parses `input` using the default options of the deserializer. Values set on the document
+    /// are quoted when needed.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        Self::new(
+            input,
+            SerializeOptions::new().quoting(Quoting::WhenNeeded),
+            DeserializeOptions::default(),
+        )
+    }
+
+    /// Parses `input` using the dialect described by `options`, which can be
+    /// [`SerializeOptions`] or a [`Preset`](crate::Preset). The same dialect is used to write the
+    /// values set on the document.
+    pub fn parse_with<O>(input: &str, options: O) -> Result<Self, ParseError>
+    where
+        O: Into<SerializeOptions> + Into<DeserializeOptions> + Clone,
+    {
+        Self::new(input, options.clone().into(), options.into())
+    }
+
+    fn new(
+        input: &str,
+        serialize_options: SerializeOptions,
+        deserialize_options: DeserializeOptions,
+    ) -> Result<Self, ParseError> {
+        let document = Self {
+            text: input.to_owned(),
+            serialize_options,
+            deserialize_options,
+        };
+
+        // Validate the whole input once, so later scans cannot fail.
+        Scanner::new(&document.text, document.deserialize_options.clone())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(document)
+    }
+
+    /// Returns the span of the last occurrence of `key`.
+    fn find(&self, key: &str) -> Option<Range<usize>> {
+        self.pairs()
+            .filter(|pair| pair.key == key)
+            .last()
+            .map(|pair| pair.span)
+    }
+
+    /// Returns `true` if the document contains `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs().any(|pair| pair.key == key)
+    }
+
+    /// Returns the value of `key`, unquoted and unescaped.
+    ///
+    /// Values enclosed in brackets are returned verbatim, and keys given without a value, i.e.
+    /// flags, have an empty value.
+    pub fn get(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.pairs()
+            .filter(|pair| pair.key == key)
+            .last()
+            .map(|pair| pair.value.into_text())
+    }
+
+    /// Sets the value of `key`, quoting it as required by the options of the document.
+    ///
+    /// The last occurrence of `key` is replaced in place, or the pair is appended to the document
+    /// if `key` is not present.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let mut pair = key.to_owned();
+        write_delimiter(&mut pair, &self.serialize_options);
+        write_string(&mut pair, value, &self.serialize_options);
+
+        self.set_pair(key, &pair);
+    }
+
+    /// Sets `key` as a flag, i.e. a key without a value.
+    ///
+    /// The last occurrence of `key` is replaced in place, or the key is appended to the document
+    /// if it is not present.
+    pub fn set_flag(&mut self, key: &str) {
+        self.set_pair(key, key);
+    }
+
+    /// Sets the value of `key` to `value` as-is, without quoting it.
+    pub(crate) fn set_verbatim(&mut self, key: &str, value: &str) {
+        let mut pair = key.to_owned();
+        write_delimiter(&mut pair, &self.serialize_options);
+        pair.push_str(value);
+
+        self.set_pair(key, &pair);
+    }
+
+    /// Returns the options used to write the values set on the document.
+    pub(crate) fn serialize_options(&self) -> &SerializeOptions {
+        &self.serialize_options
+    }
+
+    /// Returns the scanning options of the document.
+    pub(crate) fn deserialize_options(&self) -> &DeserializeOptions {
+        &self.deserialize_options
+    }
+
+    /// Returns the pairs of the document.
+    pub(crate) fn pairs(&self) -> impl Iterator<Item = ScannedPair<'_>> {
+        Scanner::new(&self.text, self.deserialize_options.clone()).flatten()
+    }
+
+    fn set_pair(&mut self, key: &str, pair: &str) {
+        match self.find(key) {
+            Some(span) => self.text.replace_range(span, pair),
+            None => {
+                let terminated = match self.text.chars().next_back() {
+                    Some(c) => self.deserialize_options.pair_separators.contains(&c),
+                    None => true,
+                };
+                if !terminated {
+                    self.text.push_str(&self.serialize_options.pair_separator);
+                }
+                self.text.push_str(pair);
+            }
+        }
+    }
+
+    /// Removes all occurrences of `key`, along with their separators. Returns `true` if `key` was
+    /// present.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let mut removed = false;
+
+        while let Some(span) = self.find(key) {
+            let is_separator = |c: char| self.deserialize_options.pair_separators.contains(&c);
+            let range = match self.text[span.end..].chars().next() {
+                // Remove the separator following the pair...
+                Some(c) if is_separator(c) => span.start..span.end + c.len_utf8(),
+                // ... or the one preceding it if it was the last pair.
+                _ => match self.text[..span.start].chars().next_back() {
+                    Some(c) if is_separator(c) => span.start - c.len_utf8()..span.end,
+                    _ => span,
+                },
+            };
+
+            self.text.replace_range(range, "");
+            removed = true;
+        }
+
+        removed
+    }
+
+    /// Returns the text of the document.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Consumes the document and returns its text.
+    pub fn into_string(self) -> String {
+        self.text
+    }
+}
+
+impl fmt::Display for KvDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+    use crate::Preset;
+
+    #[test]
+    fn document_get() {
+        let document = KvDocument::parse(r#"a=1,name="x y" a=2 ro list=[1,2]"#).unwrap();
+
+        assert_eq!(document.get("a").as_deref(), Some("2"));
+        assert_eq!(document.get("name").as_deref(), Some("x y"));
+        assert_eq!(document.get("ro").as_deref(), Some(""));
+        assert_eq!(document.get("list").as_deref(), Some("[1,2]"));
+        assert_eq!(document.get("missing"), None);
+        assert!(document.contains_key("ro"));
+        assert!(!document.contains_key("missing"));
+    }
+
+    #[test]
+    fn document_edit() {
+        let mut document = KvDocument::parse_with("A=1\nB=2\nA=3\n", Preset::Dotenv).unwrap();
+
+        document.set("A", "x y");
+        assert_eq!(document.as_str(), "A=1\nB=2\nA=\"x y\"\n");
+
+        assert!(document.remove("A"));
+        assert!(!document.remove("A"));
+        assert_eq!(document.as_str(), "B=2\n");
+
+        document.set("C", "3");
+        document.set_flag("D");
+        assert_eq!(document.into_string(), "B=2\nC=3\nD");
+
+        let mut document = KvDocument::parse("a=1 b=2").unwrap();
+        document.remove("b");
+        assert_eq!(document.as_str(), "a=1");
+        document.remove("a");
+        assert_eq!(document.as_str(), "");
+        document.set("c", "4");
+        assert_eq!(document.as_str(), "c=4");
+    }
+
+    #[test]
+    fn document_errors() {
+        assert_eq!(
+            KvDocument::parse("a=1 =2").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            }
+        );
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Human-readable durations, such as `30s` or `1h30m`.
+//!
+//! This module is meant to be used with `#[serde(with = "serde_keyvalue::duration")]` on
+//! [`Duration`] fields, which are otherwise serialized as a struct of seconds and nanoseconds:
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use serde::{Deserialize, Serialize};
+//! use serde_keyvalue::{from_key_values, KeyValueSerializer};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Timeouts {
+//!     #[serde(with = "serde_keyvalue::duration")]
+//!     connect: Duration,
+//!     #[serde(with = "serde_keyvalue::duration")]
+//!     idle: Duration,
+//! }
+//!
+//! let timeouts: Timeouts = from_key_values("connect=500ms,idle=1h30m").unwrap();
+//! assert_eq!(
+//!     timeouts,
+//!     Timeouts { connect: Duration::from_millis(500), idle: Duration::from_secs(5400) }
+//! );
+//!
+//! let mut serializer = KeyValueSerializer::new();
+//! timeouts.serialize(&mut serializer).unwrap();
+//! assert_eq!(serializer.into_output(), "connect=500ms idle=1h30m");
+//! ```
+//!
+//! A duration is written as a sequence of integers each followed by a unit, from the largest to
+//! the smallest: `d` (days), `h`, `m`, `s`, `ms`, `us` (or `µs`) and `ns`. Units may appear in any
+//! order when parsing, and a zero duration is written as `0s`.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use core::time::Duration;
+
+use serde::de::Error;
+use serde::de::Unexpected;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serializer;
+
+/// Units of durations, from the largest to the smallest, with their length in nanoseconds.
+const UNITS: [(&str, u128); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Serializes `duration` as a human-readable string, e.g. `1h30m`.
+pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(*duration))
+}
+
+/// Deserializes a duration from a human-readable string, e.g. `1h30m`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+
+    parse(&s).ok_or_else(|| {
+        D::Error::invalid_value(Unexpected::Str(&s), &"a duration such as `30s` or `1h30m`")
+    })
+}
+
+/// Returns the human-readable form of `duration`.
+fn format(duration: Duration) -> String {
+    let mut nanos = duration.as_nanos();
+    if nanos == 0 {
+        return "0s".to_string();
+    }
+
+    let mut output = String::new();
+    for (unit, length) in UNITS {
+        if nanos >= length {
+            output.push_str(&(nanos / length).to_string());
+            output.push_str(unit);
+            nanos %= length;
+        }
+    }
+
+    output
+}
+
+/// Parses the human-readable form of a duration, or returns `None` if `s` is not valid.
+fn parse(s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+
+    let mut nanos: u128 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: u128 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "µs" => "us",
+            unit => unit,
+        };
+        let (_, length) = UNITS.iter().find(|(name, _)| *name == unit)?;
+        rest = &rest[unit_len..];
+
+        nanos = nanos.checked_add(value.checked_mul(*length)?)?;
+    }
+
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_format() {
+        assert_eq!(format(Duration::ZERO), "0s");
+        assert_eq!(format(Duration::from_secs(30)), "30s");
+        assert_eq!(format(Duration::from_secs(300)), "5m");
+        assert_eq!(format(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format(Duration::from_secs(90_000)), "1d1h");
+        assert_eq!(format(Duration::new(1, 500_000_001)), "1s500ms1ns");
+    }
+
+    #[test]
+    fn duration_parse() {
+        assert_eq!(parse("0s"), Some(Duration::ZERO));
+        assert_eq!(parse("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse("30m1h"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse("250µs"), Some(Duration::from_micros(250)));
+        assert_eq!(parse("1s500ms1ns"), Some(Duration::new(1, 500_000_001)));
+
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("30"), None);
+        assert_eq!(parse("s"), None);
+        assert_eq!(parse("5 m"), None);
+        assert_eq!(parse("1y"), None);
+        assert_eq!(parse("1.5s"), None);
+        assert_eq!(parse("99999999999999999999999d"), None);
+    }
+}
@@ -0,0 +1,495 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Environment variables of child processes and of other running processes.
+
+use std::process::Command;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::Preset;
+use crate::options::SerializeOptions;
+use crate::pairs_with;
+use crate::serializer::write_delimiter;
+use crate::serializer::write_string;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Serializes `value` and sets each of its pairs as an environment variable of `cmd`.
+///
+/// Values are written as in a dotenv file (see [`Preset::Dotenv`]) but without their quotes, so
+/// strings are set verbatim and sequences are set in brackets, e.g. `[a,b]`. Variables that are
+/// not part of `value` are left untouched.
+///
+/// ```
+/// use std::process::Command;
+///
+/// use serde::Serialize;
+/// use serde_keyvalue::apply_env;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "UPPERCASE")]
+/// struct Worker {
+///     name: String,
+///     threads: u32,
+/// }
+///
+/// let mut cmd = Command::new("worker");
+/// apply_env(&mut cmd, &Worker { name: "a b".into(), threads: 4 }).unwrap();
+///
+/// let envs: Vec<_> = cmd
+///     .get_envs()
+///     .map(|(k, v)| (k.to_str(), v.and_then(|v| v.to_str())))
+///     .collect();
+/// assert_eq!(envs, [(Some("NAME"), Some("a b")), (Some("THREADS"), Some("4"))]);
+/// ```
+pub fn apply_env<T: Serialize + ?Sized>(
+    cmd: &mut Command,
+    value: &T,
+) -> Result<(), SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(Preset::Dotenv);
+    value.serialize(&mut serializer)?;
+    let output = serializer.into_output();
+
+    for pair in pairs_with(&output, Preset::Dotenv) {
+        let (key, value) = pair.map_err(|e| SerializeError::Custom(e.to_string()))?;
+        cmd.env(&*key, &*value);
+    }
+
+    Ok(())
+}
+
+/// Deserializes `T` from the environment variables set on `cmd`, i.e. the environment that
+/// [`apply_env`] gave to the child process.
+///
+/// Only the variables explicitly set on `cmd` are considered, not the ones it inherits. Variables
+/// removed from `cmd` and variables that are not valid Unicode are ignored. Values starting with
+/// `[` are parsed as sequences. The positions of errors are relative to the variables written one
+/// per line as `KEY=value`.
+///
+/// ```
+/// use std::process::Command;
+///
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_command_env;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Worker {
+///     name: String,
+///     threads: u32,
+/// }
+///
+/// let mut cmd = Command::new("worker");
+/// cmd.env("name", "a b").env("threads", "4");
+///
+/// let worker: Worker = from_command_env(&cmd).unwrap();
+/// assert_eq!(worker, Worker { name: "a b".into(), threads: 4 });
+/// ```
+pub fn from_command_env<T: DeserializeOwned>(cmd: &Command) -> Result<T, ParseError> {
+    let vars = cmd
+        .get_envs()
+        .filter_map(|(key, value)| Some((key.to_str()?, value.and_then(|value| value.to_str())?)));
+
+    from_vars(vars)
+}
+
+/// Deserializes `T` from environment variables in the format of `/proc/<pid>/environ` on Linux,
+/// i.e. `KEY=value` entries each followed by a NUL character.
+///
+/// Entries that are empty, do not contain `=` or are not valid UTF-8 are ignored. Values starting
+/// with `[` are parsed as sequences. The positions of errors are relative to the variables written
+/// one per line as `KEY=value`.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_environ_bytes;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// #[serde(rename_all = "UPPERCASE")]
+/// struct Session {
+///     home: String,
+///     shlvl: u32,
+/// }
+///
+/// let environ = b"HOME=/home/alice\0TERM=xterm\0SHLVL=2\0";
+/// let session: Session = from_environ_bytes(environ).unwrap();
+/// assert_eq!(session, Session { home: "/home/alice".into(), shlvl: 2 });
+/// ```
+pub fn from_environ_bytes<T: DeserializeOwned>(environ: &[u8]) -> Result<T, ParseError> {
+    let vars = environ.split(|&b| b == 0).filter_map(|entry| {
+        let (key, value) = std::str::from_utf8(entry).ok()?.split_once('=')?;
+        Some((key, value))
+    });
+
+    from_vars(vars)
+}
+
+/// Case of the names of the environment variables written by [`to_env_vars`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnvCase {
+    /// Names are uppercased, e.g. `APP_DB_HOST`.
+    #[default]
+    Upper,
+    /// Names are lowercased, e.g. `app_db_host`.
+    Lower,
+    /// Names are made of the prefix and the keys as they are serialized.
+    Preserve,
+}
+
+/// Options mapping the keys of a serialized value to the names of environment variables, as used
+/// by [`to_env_vars`] and [`set_env_vars`].
+///
+/// By default, names have no prefix, are uppercased and the keys of nested structs and maps are
+/// joined to the key of their parent with an underscore.
+///
+/// ```
+/// use serde_keyvalue::{EnvCase, EnvOptions};
+///
+/// let options = EnvOptions::new().prefix("app").case(EnvCase::Upper).joiner('_');
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvOptions {
+    pub(crate) prefix: String,
+    pub(crate) case: EnvCase,
+    pub(crate) joiner: char,
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            case: EnvCase::default(),
+            joiner: '_',
+        }
+    }
+}
+
+impl EnvOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `prefix` followed by the joiner before each name, e.g. `APP` for `APP_DB_HOST`. An
+    /// empty prefix, the default, writes nothing.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the case of the names, including their prefix. Defaults to [`EnvCase::Upper`].
+    pub fn case(mut self, case: EnvCase) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Sets the character joining the prefix, the keys of nested structs and maps and the keys of
+    /// their parents. Defaults to `_`.
+    pub fn joiner(mut self, joiner: char) -> Self {
+        self.joiner = joiner;
+        self
+    }
+
+    /// Returns the name of the variable of the pair whose key, joined to the keys of its parents,
+    /// is `key`.
+    fn var_name(&self, key: &str) -> String {
+        let mut name = String::with_capacity(self.prefix.len() + 1 + key.len());
+        if !self.prefix.is_empty() {
+            name.push_str(&self.prefix);
+            name.push(self.joiner);
+        }
+        name.push_str(key);
+
+        match self.case {
+            EnvCase::Upper => name.to_uppercase(),
+            EnvCase::Lower => name.to_lowercase(),
+            EnvCase::Preserve => name,
+        }
+    }
+}
+
+/// Serializes `value` as environment variables, in the style of twelve-factor applications whose
+/// configuration is read from variables like `APP_DB_HOST`, and returns their names and values.
+///
+/// Names are made of the prefix, the keys of the nested structs and maps and the key of each
+/// pair, mapped as described by `options`. Values are written as by [`apply_env`], so strings are
+/// set verbatim and sequences are set in brackets, e.g. `[a,b]`.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::{to_env_vars, EnvOptions};
+///
+/// #[derive(Serialize)]
+/// struct Db {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     db: Db,
+///     workers: u32,
+/// }
+///
+/// let config = Config { db: Db { host: "db.local".into(), port: 5432 }, workers: 4 };
+/// let vars = to_env_vars(&config, EnvOptions::new().prefix("app")).unwrap();
+///
+/// assert_eq!(
+///     vars,
+///     [
+///         ("APP_DB_HOST".to_string(), "db.local".to_string()),
+///         ("APP_DB_PORT".to_string(), "5432".to_string()),
+///         ("APP_WORKERS".to_string(), "4".to_string()),
+///     ]
+/// );
+/// ```
+pub fn to_env_vars<T: Serialize + ?Sized>(
+    value: &T,
+    options: EnvOptions,
+) -> Result<Vec<(String, String)>, SerializeError> {
+    let serialize_options =
+        SerializeOptions::from(Preset::Dotenv).nested_key_separator(options.joiner);
+    let mut serializer = KeyValueSerializer::with_options(serialize_options.clone());
+    value.serialize(&mut serializer)?;
+    let output = serializer.into_output();
+
+    pairs_with(&output, serialize_options)
+        .map(|pair| {
+            let (key, value) = pair.map_err(|e| SerializeError::Custom(e.to_string()))?;
+            Ok((options.var_name(&key), value.into_owned()))
+        })
+        .collect()
+}
+
+/// Serializes `value` as environment variables like [`to_env_vars`] and sets them in the
+/// environment of the current process with [`std::env::set_var`].
+///
+/// No variable is set if one of them cannot be, i.e. if its name is empty or contains `=` or a
+/// NUL character, or if its value contains a NUL character. As with [`std::env::set_var`], this
+/// should only be done while no other thread reads or writes the environment.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::{set_env_vars, EnvOptions};
+///
+/// #[derive(Serialize)]
+/// struct Log {
+///     level: String,
+/// }
+///
+/// set_env_vars(&Log { level: "debug".into() }, EnvOptions::new().prefix("worker")).unwrap();
+/// assert_eq!(std::env::var("WORKER_LEVEL").unwrap(), "debug");
+/// ```
+pub fn set_env_vars<T: Serialize + ?Sized>(
+    value: &T,
+    options: EnvOptions,
+) -> Result<(), SerializeError> {
+    let vars = to_env_vars(value, options)?;
+    let is_valid = |(name, value): &(String, String)| {
+        !name.is_empty() && !name.contains(['=', '\0']) && !value.contains('\0')
+    };
+    if let Some((name, _)) = vars.iter().find(|var| !is_valid(var)) {
+        return Err(SerializeError::Custom(format!(
+            "`{}` cannot be set as an environment variable",
+            name
+        )));
+    }
+
+    for (name, value) in vars {
+        std::env::set_var(name, value);
+    }
+
+    Ok(())
+}
+
+/// Deserializes `T` from environment variables, written one per line as in a dotenv file.
+fn from_vars<'a, T: DeserializeOwned>(
+    vars: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Result<T, ParseError> {
+    let options = SerializeOptions::from(Preset::Dotenv);
+
+    let mut input = String::new();
+    for (key, value) in vars {
+        input.push_str(key);
+        write_delimiter(&mut input, &options);
+        if value.starts_with('[') {
+            input.push_str(value);
+        } else {
+            write_string(&mut input, value, &options);
+        }
+        input.push('\n');
+    }
+
+    from_key_values_with(&input, Preset::Dotenv)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::ErrorKind;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Worker {
+        name: String,
+        threads: u32,
+        verbose: bool,
+        queues: Vec<String>,
+    }
+
+    #[test]
+    fn env_roundtrip() {
+        let worker = Worker {
+            name: "say \"hi\"\n".into(),
+            threads: 8,
+            verbose: true,
+            queues: vec!["a".into(), "b c".into()],
+        };
+
+        let mut cmd = Command::new("worker");
+        cmd.env("PATH", "/bin");
+        apply_env(&mut cmd, &worker).unwrap();
+
+        assert_eq!(cmd.get_envs().count(), 5);
+        assert_eq!(
+            cmd.get_envs().find(|(key, _)| *key == "name").unwrap().1,
+            Some("say \"hi\"\n".as_ref())
+        );
+        assert_eq!(
+            cmd.get_envs().find(|(key, _)| *key == "verbose").unwrap().1,
+            Some("true".as_ref())
+        );
+
+        cmd.env_remove("PATH");
+        assert_eq!(from_command_env::<Worker>(&cmd).unwrap(), worker);
+    }
+
+    #[test]
+    fn env_errors() {
+        let mut cmd = Command::new("worker");
+        cmd.env("name", "x").env("threads", "many");
+
+        assert_eq!(
+            from_command_env::<Worker>(&cmd).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 15,
+            }
+        );
+
+        assert_eq!(
+            from_command_env::<Worker>(&Command::new("worker"))
+                .unwrap_err()
+                .kind,
+            ErrorKind::SerdeError("missing field `name`".into())
+        );
+    }
+
+    #[derive(Serialize)]
+    struct Service {
+        worker: Worker,
+        #[serde(rename = "logLevel")]
+        log_level: String,
+    }
+
+    #[test]
+    fn env_vars_names() {
+        let service = Service {
+            worker: Worker {
+                name: "a \"b\"".into(),
+                threads: 2,
+                verbose: false,
+                queues: vec!["x".into(), "y z".into()],
+            },
+            log_level: "info".into(),
+        };
+
+        assert_eq!(
+            to_env_vars(&service, EnvOptions::new()).unwrap(),
+            [
+                ("WORKER_NAME".to_string(), "a \"b\"".to_string()),
+                ("WORKER_THREADS".to_string(), "2".to_string()),
+                ("WORKER_VERBOSE".to_string(), "false".to_string()),
+                ("WORKER_QUEUES".to_string(), "[x,\"y z\"]".to_string()),
+                ("LOGLEVEL".to_string(), "info".to_string()),
+            ]
+        );
+
+        let options = EnvOptions::new()
+            .prefix("Svc")
+            .case(EnvCase::Preserve)
+            .joiner('.');
+        let names: Vec<String> = to_env_vars(&service, options)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Svc.worker.name",
+                "Svc.worker.threads",
+                "Svc.worker.verbose",
+                "Svc.worker.queues",
+                "Svc.logLevel",
+            ]
+        );
+
+        let options = EnvOptions::new().prefix("SVC").case(EnvCase::Lower);
+        assert_eq!(to_env_vars(&service, options).unwrap()[4].0, "svc_loglevel");
+    }
+
+    #[test]
+    fn set_env_vars_process() {
+        let service = Service {
+            worker: Worker {
+                name: "w".into(),
+                threads: 1,
+                verbose: true,
+                queues: Vec::new(),
+            },
+            log_level: "warn".into(),
+        };
+        set_env_vars(&service, EnvOptions::new().prefix("SKV_TEST_SET")).unwrap();
+        assert_eq!(
+            std::env::var("SKV_TEST_SET_WORKER_VERBOSE").unwrap(),
+            "true"
+        );
+        assert_eq!(std::env::var("SKV_TEST_SET_LOGLEVEL").unwrap(), "warn");
+
+        let service = Service {
+            log_level: "a\0b".into(),
+            ..service
+        };
+        assert!(set_env_vars(&service, EnvOptions::new().prefix("SKV_TEST_NUL")).is_err());
+        assert!(std::env::var("SKV_TEST_NUL_WORKER_NAME").is_err());
+    }
+
+    #[test]
+    fn environ_bytes() {
+        let environ = b"name=a=b\0\0queues=[x,y]\0BROKEN\0threads=4\0bad=\xff\0verbose=false\0";
+        assert_eq!(
+            from_environ_bytes::<Worker>(environ).unwrap(),
+            Worker {
+                name: "a=b".into(),
+                threads: 4,
+                verbose: false,
+                queues: vec!["x".into(), "y".into()],
+            }
+        );
+
+        assert_eq!(
+            from_environ_bytes::<Worker>(b"name=x\0threads=-1\0")
+                .unwrap_err()
+                .kind,
+            ErrorKind::OutOfRange {
+                key: "threads".into()
+            }
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Extension traits giving serializable and deserializable types key-values methods.
+
+use alloc::string::String;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::from_key_values;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Extension trait implemented for all serializable types, to serialize them into key-values
+/// strings with a method call.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::ToKeyValue;
+///
+/// #[derive(Serialize)]
+/// struct Disk {
+///     path: String,
+///     ro: bool,
+/// }
+///
+/// let disk = Disk { path: "/dev/vda".into(), ro: true };
+/// assert_eq!(disk.to_key_value().unwrap(), "path=/dev/vda ro=True");
+/// ```
+pub trait ToKeyValue: Serialize {
+    /// Serializes `self` with [`KeyValueSerializer::new`].
+    fn to_key_value(&self) -> Result<String, SerializeError> {
+        let mut serializer = KeyValueSerializer::new();
+        self.serialize(&mut serializer)?;
+        Ok(serializer.into_output())
+    }
+}
+
+impl<T: Serialize + ?Sized> ToKeyValue for T {}
+
+/// Extension trait implemented for all deserializable types, to deserialize them from key-values
+/// strings with an associated function.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::FromKeyValue;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Disk {
+///     path: String,
+///     ro: bool,
+/// }
+///
+/// let disk = Disk::from_key_value("path=/dev/vda,ro").unwrap();
+/// assert_eq!(disk, Disk { path: "/dev/vda".into(), ro: true });
+/// ```
+pub trait FromKeyValue<'de>: Deserialize<'de> {
+    /// Deserializes a value from `input` with [`from_key_values`].
+    fn from_key_value(input: &'de str) -> Result<Self, ParseError> {
+        from_key_values(input)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> FromKeyValue<'de> for T {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn extension_traits() {
+        let map = BTreeMap::from([("a", 1), ("b", 2)]);
+        let output = map.to_key_value().unwrap();
+        assert_eq!(output, "a=1 b=2");
+        assert_eq!(BTreeMap::<&str, u32>::from_key_value(&output).unwrap(), map);
+
+        assert_eq!(
+            BTreeMap::<&str, u32>::from_key_value("a=x")
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidNumber
+        );
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Key-values strings and files as providers of a [figment](https://docs.rs/figment)
+//! configuration.
+
+use std::fs;
+use std::path::PathBuf;
+
+use figment::value::Dict;
+use figment::value::Map;
+use figment::Error;
+use figment::Metadata;
+use figment::Profile;
+use figment::Provider;
+
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+
+/// Where the key-values string of a [`KeyValueProvider`] comes from.
+#[derive(Debug, Clone)]
+enum Input {
+    String(String),
+    File(PathBuf),
+}
+
+/// Provider of a figment configuration reading its values from a key-values string or file, which
+/// can be merged with the other providers of the configuration such as TOML files and environment
+/// variables.
+///
+/// Keys containing dots designate nested values, e.g. `tls.enabled` sets the `enabled` member of
+/// the `tls` dictionary, unless other [options](Self::options) are set. The values are emitted to
+/// the default profile unless another one is set with [`profile`](Self::profile).
+///
+/// ```
+/// use figment::providers::Serialized;
+/// use figment::Figment;
+/// use serde::Deserialize;
+/// use serde::Serialize;
+/// use serde_keyvalue::KeyValueProvider;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Tls {
+///     enabled: bool,
+///     cert: String,
+/// }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Server {
+///     port: u16,
+///     tls: Tls,
+/// }
+///
+/// let defaults = Server { port: 80, tls: Tls { enabled: false, cert: "cert.pem".into() } };
+/// let server: Server = Figment::from(Serialized::defaults(defaults))
+///     .merge(KeyValueProvider::new("port=8000,tls.enabled"))
+///     .extract()
+///     .unwrap();
+/// assert_eq!(server, Server { port: 8000, tls: Tls { enabled: true, cert: "cert.pem".into() } });
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyValueProvider {
+    input: Input,
+    options: DeserializeOptions,
+    profile: Profile,
+}
+
+impl KeyValueProvider {
+    /// Creates a provider reading its values from the key-values string `input`.
+    pub fn new(input: impl Into<String>) -> Self {
+        Self::with_input(Input::String(input.into()))
+    }
+
+    /// Creates a provider reading its values from the key-values file at `path`.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::with_input(Input::File(path.into()))
+    }
+
+    fn with_input(input: Input) -> Self {
+        Self {
+            input,
+            options: DeserializeOptions::new().nested_key_separator('.'),
+            profile: Profile::Default,
+        }
+    }
+
+    /// Sets the syntax of the key-values string, which can also be a [`Preset`](crate::Preset).
+    /// Keys are only nested if `options` has a
+    /// [nested key separator](DeserializeOptions::nested_key_separator).
+    pub fn options(mut self, options: impl Into<DeserializeOptions>) -> Self {
+        self.options = options.into();
+        self
+    }
+
+    /// Sets the profile the values are emitted to.
+    pub fn profile(mut self, profile: impl Into<Profile>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+}
+
+impl Provider for KeyValueProvider {
+    fn metadata(&self) -> Metadata {
+        match &self.input {
+            Input::String(_) => Metadata::named("key-values string"),
+            Input::File(path) => Metadata::from("key-values file", path.as_path()),
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let dict: Dict = match &self.input {
+            Input::String(input) => from_key_values_with(input, self.options.clone()),
+            Input::File(path) => {
+                let input = fs::read_to_string(path)
+                    .map_err(|e| Error::from(format!("{}: {}", path.display(), e)))?;
+                from_key_values_with(&input, self.options.clone())
+            }
+        }
+        .map_err(|e| Error::from(e.to_string()))?;
+
+        Ok(self.profile.collect(dict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::providers::Serialized;
+    use figment::Figment;
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::Preset;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Db {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Settings {
+        name: String,
+        db: Db,
+    }
+
+    fn defaults() -> Figment {
+        Figment::from(Serialized::defaults(Settings {
+            name: "app".into(),
+            db: Db {
+                host: "localhost".into(),
+                port: 5432,
+            },
+        }))
+    }
+
+    #[test]
+    fn merged_string() {
+        let settings: Settings = defaults()
+            .merge(KeyValueProvider::new("db.port=6432"))
+            .extract()
+            .unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                name: "app".into(),
+                db: Db {
+                    host: "localhost".into(),
+                    port: 6432,
+                },
+            }
+        );
+
+        let provider = KeyValueProvider::new("name=\"my app\" db.host=db")
+            .options(DeserializeOptions::from(Preset::Logfmt).nested_key_separator('.'))
+            .profile("debug");
+        let figment = defaults().merge(provider);
+        assert_eq!(figment.extract::<Settings>().unwrap().name, "app");
+        let settings: Settings = figment.select("debug").extract().unwrap();
+        assert_eq!(settings.name, "my app");
+        assert_eq!(settings.db.host, "db");
+
+        assert!(defaults()
+            .merge(KeyValueProvider::new("db.port=["))
+            .extract::<Settings>()
+            .is_err());
+    }
+
+    #[test]
+    fn merged_file() {
+        let path = std::env::temp_dir().join(format!("figment-{}", std::process::id()));
+        fs::write(&path, "name=server,db.host=db.internal").unwrap();
+        let provider = KeyValueProvider::file(&path);
+        let result = defaults().merge(provider.clone()).extract::<Settings>();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            result.unwrap(),
+            Settings {
+                name: "server".into(),
+                db: Db {
+                    host: "db.internal".into(),
+                    port: 5432,
+                },
+            }
+        );
+        assert_eq!(
+            provider.metadata().source.unwrap().file_path(),
+            Some(path.as_path())
+        );
+        assert!(defaults().merge(provider).extract::<Settings>().is_err());
+    }
+}
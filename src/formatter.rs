@@ -0,0 +1,82 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Rendering of scalar values by the serializer.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::string::ToString;
+
+use crate::options::BoolStyle;
+
+/// Renders the scalar values written by [`KeyValueSerializer`](crate::KeyValueSerializer).
+///
+/// Every method has a default implementation producing the standard output of the serializer, so
+/// implementations only need to override the methods for the values they want to render
+/// differently. The text returned for strings and characters is then quoted according to the
+/// serializer options, while the text returned for other values is written as-is.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::{KeyValueSerializer, ValueFormatter};
+///
+/// /// Writes floats with a fixed precision.
+/// struct Fixed;
+///
+/// impl ValueFormatter for Fixed {
+///     fn format_f64(&self, v: f64) -> String {
+///         format!("{:.2}", v)
+///     }
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Sample {
+///     ratio: f64,
+///     count: u32,
+/// }
+///
+/// let mut serializer = KeyValueSerializer::new().formatter(Fixed);
+/// Sample { ratio: 1.0 / 3.0, count: 3 }.serialize(&mut serializer).unwrap();
+/// assert_eq!(serializer.into_output(), "ratio=0.33 count=3");
+/// ```
+pub trait ValueFormatter {
+    /// Renders a boolean in the given `style`. This is not called for booleans written as bare
+    /// flags.
+    fn format_bool(&self, v: bool, style: BoolStyle) -> Cow<'static, str> {
+        let (t, f) = style.words();
+        Cow::Borrowed(if v { t } else { f })
+    }
+
+    /// Renders a signed integer.
+    fn format_i64(&self, v: i64) -> String {
+        v.to_string()
+    }
+
+    /// Renders an unsigned integer.
+    fn format_u64(&self, v: u64) -> String {
+        v.to_string()
+    }
+
+    /// Renders a single-precision float.
+    fn format_f32(&self, v: f32) -> String {
+        v.to_string()
+    }
+
+    /// Renders a double-precision float.
+    fn format_f64(&self, v: f64) -> String {
+        v.to_string()
+    }
+
+    /// Renders a string, before it gets quoted.
+    fn format_str<'a>(&self, v: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(v)
+    }
+}
+
+/// The [`ValueFormatter`] used by default, which renders values using their standard
+/// representation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFormatter;
+
+impl ValueFormatter for DefaultFormatter {}
@@ -0,0 +1,46 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Usage text describing the keys accepted by a type.
+
+use alloc::string::String;
+
+/// Types able to describe the keys they accept when deserialized from a key-values string.
+///
+/// This trait is usually implemented with the `KeyValueHelp` derive macro provided by the `derive`
+/// feature. The generated help has one line per field, containing its key, its type, its default
+/// value if it has one, and its documentation:
+///
+/// ```
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use serde::Deserialize;
+/// use serde_keyvalue::KeyValueHelp;
+///
+/// #[derive(Deserialize, KeyValueHelp)]
+/// struct Net {
+///     /// Name of the TAP device.
+///     tap: String,
+///     /// MTU of the interface.
+///     #[serde(default = "default_mtu")]
+///     mtu: u16,
+///     queues: Option<u8>,
+/// }
+///
+/// fn default_mtu() -> u16 {
+///     1500
+/// }
+///
+/// assert_eq!(
+///     Net::key_value_help(),
+///     "tap=<String>  Name of the TAP device.\n\
+///      mtu=<u16> (default 1500)  MTU of the interface.\n\
+///      queues=<u8> (optional)"
+/// );
+/// # }
+/// ```
+pub trait KeyValueHelp {
+    /// Returns the description of the accepted keys, one per line.
+    fn key_value_help() -> String;
+}
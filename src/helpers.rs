@@ -0,0 +1,439 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Modules for the `#[serde(with = "...")]` attribute, applying the behaviors of some dialects to
+//! individual fields instead of the whole output.
+//!
+//! They are plain functions, so they can be used alongside other attributes such as those of
+//! [serde_with](https://docs.rs/serde_with), and with serializers other than
+//! [`KeyValueSerializer`](crate::KeyValueSerializer).
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use serde_keyvalue::{from_key_values_with, KeyValueSerializer, Preset};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Device {
+//!     #[serde(with = "serde_keyvalue::helpers::comma_list")]
+//!     queues: Vec<u32>,
+//!     #[serde(with = "serde_keyvalue::helpers::hex_bytes")]
+//!     mac: Vec<u8>,
+//!     #[serde(with = "serde_keyvalue::helpers::flag", default)]
+//!     ro: bool,
+//! }
+//!
+//! let device = Device { queues: vec![1, 2], mac: vec![0x52, 0x54, 0, 0x12], ro: true };
+//! let mut serializer = KeyValueSerializer::with_options(Preset::Logfmt);
+//! device.serialize(&mut serializer).unwrap();
+//!
+//! let output = serializer.into_output();
+//! assert_eq!(output, r#"queues="1,2" mac=52540012 ro"#);
+//! assert_eq!(from_key_values_with::<Device>(&output, Preset::Logfmt).unwrap(), device);
+//! ```
+
+#[cfg(feature = "duration")]
+pub use crate::duration;
+
+/// Sequences written as a single string whose elements are separated by commas, e.g. `1,2,3`.
+///
+/// Elements are written with their `Display` implementation and parsed back with their `FromStr`
+/// implementation. An empty sequence is written as an empty string. Since commas separate pairs
+/// in some dialects, this is meant for dialects using other separators, or for quoted values.
+pub mod comma_list {
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use core::fmt::Display;
+    use core::str::FromStr;
+
+    use serde::de::Error;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes `values` as a comma-separated string.
+    pub fn serialize<T: Display, S: Serializer>(
+        values: &[T],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let list: Vec<String> = values.iter().map(ToString::to_string).collect();
+        serializer.serialize_str(&list.join(","))
+    }
+
+    /// Deserializes a comma-separated string into a vector.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let list = String::deserialize(deserializer)?;
+        if list.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        list.split(',')
+            .map(|element| element.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// Bytes written as a string of hexadecimal digits, two per byte, e.g. `52540012`.
+///
+/// Both lowercase and uppercase digits are accepted when deserializing.
+pub mod hex_bytes {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::fmt::Write;
+
+    use serde::de::Error;
+    use serde::de::Unexpected;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes `bytes` as a string of lowercase hexadecimal digits.
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for b in bytes {
+            let _ = write!(hex, "{:02x}", b);
+        }
+        serializer.serialize_str(&hex)
+    }
+
+    /// Deserializes a string of hexadecimal digits into bytes.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let invalid = || D::Error::invalid_value(Unexpected::Str(&hex), &"hexadecimal bytes");
+
+        if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect()
+    }
+}
+
+/// Booleans written as bare flags by [`KeyValueSerializer`](crate::KeyValueSerializer), i.e. a
+/// `true` value is written as its key alone and a `false` value is omitted, as with
+/// [`BoolStyle::Flag`](crate::BoolStyle::Flag).
+///
+/// The deserializer accepts flags for all booleans, so this only changes serialization. Fields
+/// using it should also have the `#[serde(default)]` attribute, so omitted flags are `false`.
+pub mod flag {
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    use crate::serializer::serialize_formatted;
+
+    /// Per-field format of the values written by [`serialize`].
+    const FLAG: &str = "$serde_keyvalue::private::FieldFormat:flag";
+
+    /// Serializes `v` as a bare flag.
+    pub fn serialize<S: Serializer>(v: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_formatted(FLAG, v, serializer)
+    }
+
+    /// Deserializes a boolean, which may be given as a bare flag.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        bool::deserialize(deserializer)
+    }
+}
+
+/// Ranges written as their bounds separated by `..`, e.g. `1024..2048`, or by `-`, e.g.
+/// `1024-2048` as found in lists of ports and CPUs.
+///
+/// [`Range`](core::ops::Range)s are written with `..` and [`RangeInclusive`]s with `-`, and
+/// `(start, end)` tuples are written with `-`. Inclusive ranges may also be given as `start..=end`
+/// or as a single value, e.g. `3` for `3-3`, while `start-end` is rejected for a `Range`, as its
+/// end is excluded. Tuples accept every form and hold the bounds as written.
+///
+/// Bounds are written with their `Display` implementation and parsed back with their `FromStr`
+/// implementation. They may be negative, e.g. `-5--1`.
+///
+/// [`RangeInclusive`]: core::ops::RangeInclusive
+///
+/// ```
+/// use std::ops::{Range, RangeInclusive};
+///
+/// use serde::{Deserialize, Serialize};
+/// use serde_keyvalue::{from_key_values, KeyValueSerializer};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Vm {
+///     #[serde(with = "serde_keyvalue::helpers::range")]
+///     ports: RangeInclusive<u16>,
+///     #[serde(with = "serde_keyvalue::helpers::range")]
+///     cpus: (u32, u32),
+///     #[serde(with = "serde_keyvalue::helpers::range")]
+///     slots: Range<u8>,
+/// }
+///
+/// let vm: Vm = from_key_values("ports=1024-2048,cpus=0..=3,slots=0..4").unwrap();
+/// assert_eq!(vm, Vm { ports: 1024..=2048, cpus: (0, 3), slots: 0..4 });
+///
+/// let mut serializer = KeyValueSerializer::new();
+/// vm.serialize(&mut serializer).unwrap();
+/// assert_eq!(serializer.into_output(), "ports=1024-2048 cpus=0-3 slots=0..4");
+/// ```
+pub mod range {
+    use alloc::string::String;
+    use core::fmt::Display;
+    use core::ops::Range;
+    use core::ops::RangeInclusive;
+    use core::str::FromStr;
+
+    use serde::de::Error;
+    use serde::de::Unexpected;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Types holding the two bounds of a range.
+    pub trait Bounds: Sized {
+        /// Type of the bounds.
+        type Bound;
+
+        /// Separator written between the bounds.
+        const SEPARATOR: &'static str;
+
+        /// Returns the start and end bounds.
+        fn bounds(&self) -> (&Self::Bound, &Self::Bound);
+
+        /// Builds a range from its bounds, or returns `None` if its end is `inclusive` but the
+        /// type cannot represent it.
+        fn from_bounds(start: Self::Bound, end: Self::Bound, inclusive: bool) -> Option<Self>;
+    }
+
+    impl<T> Bounds for Range<T> {
+        type Bound = T;
+
+        const SEPARATOR: &'static str = "..";
+
+        fn bounds(&self) -> (&T, &T) {
+            (&self.start, &self.end)
+        }
+
+        fn from_bounds(start: T, end: T, inclusive: bool) -> Option<Self> {
+            (!inclusive).then_some(start..end)
+        }
+    }
+
+    impl<T> Bounds for RangeInclusive<T> {
+        type Bound = T;
+
+        const SEPARATOR: &'static str = "-";
+
+        fn bounds(&self) -> (&T, &T) {
+            (self.start(), self.end())
+        }
+
+        fn from_bounds(start: T, end: T, inclusive: bool) -> Option<Self> {
+            inclusive.then_some(start..=end)
+        }
+    }
+
+    impl<T> Bounds for (T, T) {
+        type Bound = T;
+
+        const SEPARATOR: &'static str = "-";
+
+        fn bounds(&self) -> (&T, &T) {
+            (&self.0, &self.1)
+        }
+
+        fn from_bounds(start: T, end: T, _inclusive: bool) -> Option<Self> {
+            Some((start, end))
+        }
+    }
+
+    /// Serializes `range` as its bounds separated by `..` or `-`.
+    pub fn serialize<R, S>(range: &R, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        R: Bounds,
+        R::Bound: Display,
+        S: Serializer,
+    {
+        let (start, end) = range.bounds();
+        serializer.collect_str(&format_args!("{}{}{}", start, R::SEPARATOR, end))
+    }
+
+    /// Deserializes a range from its bounds separated by `..`, `..=` or `-`, or from a single
+    /// value.
+    pub fn deserialize<'de, R, D>(deserializer: D) -> Result<R, D::Error>
+    where
+        R: Bounds,
+        R::Bound: FromStr,
+        <R::Bound as FromStr>::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let range = String::deserialize(deserializer)?;
+        let invalid = || D::Error::invalid_value(Unexpected::Str(&range), &"a range");
+
+        let (start, end, inclusive) = split(&range).ok_or_else(invalid)?;
+        let parse = |bound: &str| bound.parse().map_err(D::Error::custom);
+        R::from_bounds(parse(start)?, parse(end)?, inclusive).ok_or_else(invalid)
+    }
+
+    /// Splits `range` into its start and end bounds, and whether its end is inclusive.
+    fn split(range: &str) -> Option<(&str, &str, bool)> {
+        if let Some((start, end)) = range.split_once("..=") {
+            return Some((start, end, true));
+        }
+        if let Some((start, end)) = range.split_once("..") {
+            return Some((start, end, false));
+        }
+
+        // The first `-` past the sign of the start bound separates the bounds.
+        let sign = usize::from(range.starts_with('-'));
+        match range[sign..].find('-') {
+            Some(i) => Some((&range[..sign + i], &range[sign + i + 1..], true)),
+            None => Some((range, range, true)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use std::ops::RangeInclusive;
+
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    use crate::from_key_values;
+    use crate::ErrorKind;
+    use crate::KeyValueSerializer;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Device {
+        #[serde(with = "super::comma_list")]
+        queues: Vec<String>,
+        #[serde(with = "super::hex_bytes")]
+        key: Vec<u8>,
+        #[serde(with = "super::flag", default)]
+        ro: bool,
+        #[serde(with = "super::flag", default)]
+        rw: bool,
+    }
+
+    #[test]
+    fn helpers_serialize() {
+        let device = Device {
+            queues: vec!["rx".into(), "tx".into()],
+            key: vec![0xde, 0xad, 0x0f],
+            ro: true,
+            rw: false,
+        };
+
+        let mut serializer = KeyValueSerializer::new();
+        device.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_output(), "queues=rx,tx key=dead0f ro");
+
+        let device = Device {
+            queues: vec![],
+            key: vec![],
+            ro: false,
+            rw: true,
+        };
+        let mut serializer = KeyValueSerializer::with_options(
+            crate::SerializeOptions::new().quoting(crate::Quoting::WhenNeeded),
+        );
+        device.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_output(), r#"queues="" key="" rw"#);
+    }
+
+    #[test]
+    fn helpers_deserialize() {
+        assert_eq!(
+            from_key_values::<Device>(r#"queues="a,b",key=00FF,ro"#).unwrap(),
+            Device {
+                queues: vec!["a".into(), "b".into()],
+                key: vec![0, 0xff],
+                ro: true,
+                rw: false,
+            }
+        );
+        assert_eq!(
+            from_key_values::<Device>(r#"queues="",key=abc"#)
+                .unwrap_err()
+                .kind,
+            ErrorKind::SerdeError(
+                "invalid value: string \"abc\", expected hexadecimal bytes".into()
+            )
+        );
+        assert_eq!(
+            from_key_values::<Device>(r#"queues="",key=+1"#)
+                .unwrap_err()
+                .kind,
+            ErrorKind::SerdeError(
+                "invalid value: string \"+1\", expected hexadecimal bytes".into()
+            )
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Limits {
+        #[serde(with = "super::range")]
+        offsets: (i32, i32),
+        #[serde(with = "super::range")]
+        cpus: RangeInclusive<u8>,
+        #[serde(with = "super::range")]
+        ports: Range<u16>,
+    }
+
+    #[test]
+    fn range_helper() {
+        let limits = Limits {
+            offsets: (-5, -1),
+            cpus: 3..=3,
+            ports: 80..81,
+        };
+        let mut serializer = KeyValueSerializer::new();
+        limits.serialize(&mut serializer).unwrap();
+        assert_eq!(
+            serializer.into_output(),
+            "offsets=-5--1 cpus=3-3 ports=80..81"
+        );
+
+        for input in [
+            "offsets=-5--1,cpus=3,ports=80..81",
+            "offsets=-5..-1,cpus=3..=3,ports=80..81",
+            "offsets=-5..=-1,cpus=3-3,ports=80..81",
+        ] {
+            assert_eq!(
+                from_key_values::<Limits>(input).unwrap(),
+                limits,
+                "{}",
+                input
+            );
+        }
+
+        for (input, error) in [
+            (
+                "offsets=0-1,cpus=1..2,ports=1..2",
+                "invalid value: string \"1..2\", expected a range",
+            ),
+            (
+                "offsets=0-1,cpus=1-2,ports=1-2",
+                "invalid value: string \"1-2\", expected a range",
+            ),
+            (
+                "offsets=0-x,cpus=1-2,ports=1..2",
+                "invalid digit found in string",
+            ),
+        ] {
+            assert_eq!(
+                from_key_values::<Limits>(input).unwrap_err().kind,
+                ErrorKind::SerdeError(error.into()),
+                "{}",
+                input
+            );
+        }
+    }
+}
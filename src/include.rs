@@ -0,0 +1,299 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Expansion of `include` directives in line-based configuration files.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::argfile::token_end;
+use crate::key_values::skip_trivia;
+use crate::key_values::BYTE_ORDER_MARK;
+use crate::options::DeserializeOptions;
+use crate::ErrorKind;
+use crate::ParseError;
+
+/// Maximum nesting depth of included files.
+const MAX_DEPTH: usize = 8;
+
+/// Directives replaced with the contents of the file they name.
+const DIRECTIVES: [&str; 2] = ["include", "source"];
+
+/// Reads the file at `path` and replaces each of its `include other.env` or `source other.env`
+/// lines with the contents of the named file, so configurations written in a line-based dialect
+/// like [`Preset::Dotenv`](crate::Preset::Dotenv) or
+/// [`Preset::JavaProperties`](crate::Preset::JavaProperties) can be split across several files
+/// and still be deserialized into one struct.
+///
+/// Directives are recognized where `options` expect a key, so `include` and `source` remain
+/// usable as keys when followed by a key-value delimiter. Relative paths are relative to the
+/// directory of the including file, and included files may themselves contain directives, up to
+/// 8 levels deep.
+///
+/// Files that cannot be read are reported as [`ErrorKind::IncludeFile`] errors, files including
+/// themselves, directly or not, as [`ErrorKind::IncludeCycle`] errors, and files nested too
+/// deeply as [`ErrorKind::IncludeNesting`] errors. Errors met in included files are reported at
+/// the position of the outermost directive in the file at `path`, and the positions of the errors
+/// met when deserializing the returned string are relative to it.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::{from_key_values_with, read_with_includes, Preset};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Config {
+///     host: String,
+///     port: u16,
+///     debug: bool,
+/// }
+///
+/// let dir = std::env::temp_dir().join(format!("include-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("common.env"), "host=db\nport=5432\n").unwrap();
+/// std::fs::write(dir.join("app.env"), "# Shared settings\ninclude common.env\ndebug=true\n")
+///     .unwrap();
+///
+/// let contents = read_with_includes(dir.join("app.env"), Preset::Dotenv).unwrap();
+/// assert_eq!(contents, "# Shared settings\nhost=db\nport=5432\ndebug=true\n");
+///
+/// let config: Config = from_key_values_with(&contents, Preset::Dotenv).unwrap();
+/// assert_eq!(config, Config { host: "db".into(), port: 5432, debug: true });
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn read_with_includes(
+    path: impl AsRef<Path>,
+    options: impl Into<DeserializeOptions>,
+) -> Result<String, ParseError> {
+    let mut stack = Vec::new();
+    read_file(path.as_ref(), &options.into(), &mut stack)
+        .map_err(|kind| ParseError { kind, pos: 0 })?
+}
+
+/// Reads the file at `path`, whose canonical path is pushed on `stack` while its directives are
+/// expanded. Errors met while opening the file are returned as the outer error.
+fn read_file(
+    path: &Path,
+    options: &DeserializeOptions,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Result<String, ParseError>, ErrorKind> {
+    let file_error =
+        |e: std::io::Error| ErrorKind::IncludeFile(format!("{}: {}", path.display(), e));
+
+    if stack.len() > MAX_DEPTH {
+        return Err(ErrorKind::IncludeNesting);
+    }
+    let canonical = path.canonicalize().map_err(file_error)?;
+    if stack.contains(&canonical) {
+        return Err(ErrorKind::IncludeCycle(path.display().to_string()));
+    }
+    let contents = std::fs::read_to_string(path).map_err(file_error)?;
+
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let expanded = expand(&contents, dir, options, stack);
+    stack.pop();
+
+    Ok(expanded)
+}
+
+fn expand(
+    contents: &str,
+    dir: &Path,
+    options: &DeserializeOptions,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, ParseError> {
+    let mut output = String::with_capacity(contents.len());
+    let mut pos = 0;
+
+    // Byte order marks are only kept at the start of the output, where parsers skip them.
+    if contents.starts_with(BYTE_ORDER_MARK) {
+        pos = BYTE_ORDER_MARK.len_utf8();
+        if stack.len() == 1 {
+            output.push(BYTE_ORDER_MARK);
+        }
+    }
+
+    while pos < contents.len() {
+        let start = contents.len() - skip_trivia(&contents[pos..], options).len();
+        output.push_str(&contents[pos..start]);
+        if start == contents.len() {
+            break;
+        }
+
+        let end = match directive(&contents[start..], options) {
+            Some((path, len)) => {
+                let included = read_file(&dir.join(path), options, stack)
+                    .map_err(|kind| ParseError { kind, pos: start })?
+                    // Errors met in nested files are reported at the outermost directive.
+                    .map_err(|e| ParseError { pos: start, ..e })?;
+                // The directive is followed by its own separator.
+                output.push_str(included.trim_end_matches(options.pair_separators.as_slice()));
+                start + len
+            }
+            None => {
+                let end = token_end(contents, start, options)?;
+                output.push_str(&contents[start..end]);
+                end
+            }
+        };
+
+        match contents[end..].chars().next() {
+            None => pos = end,
+            Some(c) if options.pair_separators.contains(&c) => {
+                output.push(c);
+                pos = end + c.len_utf8();
+            }
+            Some(_) => {
+                return Err(ParseError {
+                    kind: ErrorKind::TrailingCharacters,
+                    pos: end,
+                })
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Returns the path named by the directive at the start of `s`, if any, and the length of the
+/// directive up to the next pair separator.
+fn directive<'a>(s: &'a str, options: &DeserializeOptions) -> Option<(&'a str, usize)> {
+    let rest = DIRECTIVES
+        .iter()
+        .find_map(|directive| s.strip_prefix(directive))?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    let len = rest
+        .find(options.pair_separators.as_slice())
+        .unwrap_or(rest.len());
+    let path = rest[..len].trim();
+    if path.is_empty() || path.starts_with(options.key_value_delimiters.as_slice()) {
+        return None;
+    }
+
+    Some((path, s.len() - rest.len() + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::from_key_values_with;
+    use crate::Preset;
+
+    /// Creates an empty temporary directory named after `name`, with a `sub` subdirectory.
+    fn tempdir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("include-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn includes_expanded() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Config {
+            name: String,
+            #[serde(rename = "db.host")]
+            host: String,
+            #[serde(rename = "db.port")]
+            port: u16,
+            include: String,
+        }
+
+        let dir = tempdir("expanded");
+        std::fs::write(dir.join("sub/db.properties"), "db.host = db\r\nsource port").unwrap();
+        std::fs::write(dir.join("sub/port"), "! Port\ndb.port = 5432\n\n").unwrap();
+        std::fs::write(
+            dir.join("app.properties"),
+            "name=app\ninclude sub/db.properties\ninclude = x\n",
+        )
+        .unwrap();
+
+        let contents = read_with_includes(dir.join("app.properties"), Preset::JavaProperties);
+        assert_eq!(
+            contents.as_deref().unwrap(),
+            "name=app\ndb.host = db\r\n! Port\ndb.port = 5432\ninclude = x\n"
+        );
+        let config: Config =
+            from_key_values_with(&contents.unwrap(), Preset::JavaProperties).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "app".into(),
+                host: "db".into(),
+                port: 5432,
+                include: "x".into(),
+            }
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn includes_errors() {
+        let dir = tempdir("errors");
+        std::fs::write(dir.join("a.env"), "a=1\ninclude sub/b.env\n").unwrap();
+        std::fs::write(dir.join("sub/b.env"), "b=2\ninclude ../a.env\n").unwrap();
+        std::fs::write(dir.join("missing.env"), "a=1\nsource nonexistent.env").unwrap();
+        std::fs::write(dir.join("self.env"), "a=1\ninclude ./self.env").unwrap();
+        std::fs::write(dir.join("invalid.env"), "a=1\ninclude sub/invalid.env").unwrap();
+        std::fs::write(dir.join("sub/invalid.env"), "a=\"1\"x").unwrap();
+
+        assert_eq!(
+            read_with_includes(dir.join("a.env"), Preset::Dotenv).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::IncludeCycle(format!("{}", dir.join("sub/../a.env").display())),
+                pos: 4,
+            }
+        );
+        assert_eq!(
+            read_with_includes(dir.join("self.env"), Preset::Dotenv).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::IncludeCycle(format!("{}", dir.join("./self.env").display())),
+                pos: 4,
+            }
+        );
+        assert_eq!(
+            read_with_includes(dir.join("invalid.env"), Preset::Dotenv).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidCharInString,
+                pos: 4,
+            }
+        );
+
+        let err = read_with_includes(dir.join("missing.env"), Preset::Dotenv).unwrap_err();
+        assert_eq!(err.pos, 4);
+        assert!(matches!(err.kind, ErrorKind::IncludeFile(msg) if msg.contains("nonexistent.env")));
+        let err = read_with_includes(dir.join("nonexistent.env"), Preset::Dotenv).unwrap_err();
+        assert_eq!(err.pos, 0);
+        assert!(matches!(err.kind, ErrorKind::IncludeFile(_)));
+
+        // A chain of distinct files nested too deeply.
+        for i in 0..=MAX_DEPTH {
+            std::fs::write(
+                dir.join(format!("{}.env", i)),
+                format!("include {}.env", i + 1),
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.join(format!("{}.env", MAX_DEPTH + 1)), "a=1").unwrap();
+        assert_eq!(
+            read_with_includes(dir.join("0.env"), Preset::Dotenv).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::IncludeNesting,
+                pos: 0,
+            }
+        );
+        assert_eq!(
+            read_with_includes(dir.join("1.env"), Preset::Dotenv).unwrap(),
+            "a=1"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Deserialization of key-values strings whose repeated strings share their allocation.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::de;
+use serde::de::DeserializeSeed;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::ser::SerializeMap;
+use serde::ser::SerializeSeq;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::DeserializeOptions;
+use crate::KeyValueDeserializer;
+use crate::Map;
+use crate::ParseError;
+use crate::Value;
+
+/// Any value that can be found in a key-values string, whose strings are shared with the other
+/// values parsed by the same [`Interner`].
+///
+/// It is the counterpart of [`Value`], with its types guessed the same way, and can be converted
+/// into one. The pairs of maps are kept in their original order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InternedValue {
+    /// A boolean, or a key given without a value.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer too large to be represented as an `i64`.
+    UInt(u64),
+    /// A floating point number.
+    Float(f64),
+    /// A string, shared with the equal strings parsed by the same [`Interner`] if it interns
+    /// values.
+    String(Arc<str>),
+    /// A list of values.
+    List(Vec<InternedValue>),
+    /// A nested set of key-value pairs, whose keys are shared with the equal keys parsed by the
+    /// same [`Interner`].
+    Map(Vec<(Arc<str>, InternedValue)>),
+}
+
+impl InternedValue {
+    /// Returns the value of `key` if `self` is a map containing it.
+    pub fn get(&self, key: &str) -> Option<&InternedValue> {
+        match self {
+            InternedValue::Map(pairs) => pairs
+                .iter()
+                .find(|(pair_key, _)| **pair_key == *key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl From<InternedValue> for Value {
+    fn from(value: InternedValue) -> Self {
+        match value {
+            InternedValue::Bool(v) => Value::Bool(v),
+            InternedValue::Int(v) => Value::Int(v),
+            InternedValue::UInt(v) => Value::UInt(v),
+            InternedValue::Float(v) => Value::Float(v),
+            InternedValue::String(v) => Value::String(v.as_ref().into()),
+            InternedValue::List(list) => Value::List(list.into_iter().map(Value::from).collect()),
+            InternedValue::Map(pairs) => Value::Map(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| (String::from(key.as_ref()), Value::from(value)))
+                    .collect::<Map>(),
+            ),
+        }
+    }
+}
+
+impl Serialize for InternedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            InternedValue::Bool(v) => serializer.serialize_bool(*v),
+            InternedValue::Int(v) => serializer.serialize_i64(*v),
+            InternedValue::UInt(v) => serializer.serialize_u64(*v),
+            InternedValue::Float(v) => serializer.serialize_f64(*v),
+            InternedValue::String(v) => serializer.serialize_str(v),
+            InternedValue::List(list) => {
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for element in list {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            InternedValue::Map(pairs) => {
+                let mut map = serializer.serialize_map(Some(pairs.len()))?;
+                for (key, value) in pairs {
+                    map.serialize_entry(key.as_ref(), value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Parses key-values strings into [`InternedValue`]s whose equal keys, and optionally values,
+/// share a single allocation, e.g. to load millions of logfmt records with the same few keys
+/// without storing a copy of each key per record.
+///
+/// The strings are kept for as long as the `Interner` lives, so the values that are not repeated,
+/// e.g. timestamps or identifiers, should not be interned.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use serde_keyvalue::{Interner, InternedValue, Preset};
+///
+/// let mut interner = Interner::new().intern_values(true);
+/// let a = interner.parse("level=info msg=started", Preset::Logfmt).unwrap();
+/// let b = interner.parse("level=info msg=stopped", Preset::Logfmt).unwrap();
+///
+/// let (Some(InternedValue::String(level_a)), Some(InternedValue::String(level_b))) =
+///     (a.get("level"), b.get("level"))
+/// else {
+///     unreachable!()
+/// };
+/// assert!(Arc::ptr_eq(level_a, level_b));
+/// // `level`, `msg`, `info`, `started` and `stopped`.
+/// assert_eq!(interner.len(), 5);
+/// ```
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+    values: bool,
+}
+
+impl Interner {
+    /// Creates an `Interner` sharing the keys of the strings it parses, but not their values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether string values are shared as well as keys. Defaults to `false`.
+    pub fn intern_values(mut self, intern: bool) -> Self {
+        self.values = intern;
+        self
+    }
+
+    /// Returns the shared copy of `s`, allocating it if `s` has not been met before.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Returns the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns `true` if no string has been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Parses `input` with the syntax described by `options` into an [`InternedValue::Map`].
+    /// As in a [`Map`], the last value of a repeated key wins.
+    pub fn parse(
+        &mut self,
+        input: &str,
+        options: impl Into<DeserializeOptions>,
+    ) -> Result<InternedValue, ParseError> {
+        let mut deserializer = KeyValueDeserializer::with_options(input, options);
+        let value = (&mut deserializer).deserialize_map(ValueSeed { interner: self })?;
+        deserializer.finish()?;
+
+        Ok(value)
+    }
+}
+
+/// Deserializes an [`InternedValue`], interning its strings with `interner`.
+struct ValueSeed<'a> {
+    interner: &'a mut Interner,
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed<'_> {
+    type Value = InternedValue;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed<'_> {
+    type Value = InternedValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a key-values value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<InternedValue, E> {
+        Ok(InternedValue::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<InternedValue, E> {
+        Ok(InternedValue::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<InternedValue, E> {
+        Ok(i64::try_from(v).map_or(InternedValue::UInt(v), InternedValue::Int))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<InternedValue, E> {
+        Ok(InternedValue::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<InternedValue, E> {
+        Ok(InternedValue::String(if self.interner.values {
+            self.interner.intern(v)
+        } else {
+            Arc::from(v)
+        }))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<InternedValue, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<InternedValue, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(element) = seq.next_element_seed(ValueSeed {
+            interner: self.interner,
+        })? {
+            list.push(element);
+        }
+        Ok(InternedValue::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<InternedValue, A::Error> {
+        let mut pairs: Vec<(Arc<str>, InternedValue)> = Vec::new();
+        while let Some(key) = access.next_key_seed(KeySeed {
+            interner: self.interner,
+        })? {
+            let value = access.next_value_seed(ValueSeed {
+                interner: self.interner,
+            })?;
+            match pairs.iter_mut().find(|(pair_key, _)| *pair_key == key) {
+                Some(pair) => pair.1 = value,
+                None => pairs.push((key, value)),
+            }
+        }
+        Ok(InternedValue::Map(pairs))
+    }
+}
+
+/// Deserializes a key as an identifier, as for a [`Map`], and interns it with `interner`.
+struct KeySeed<'a> {
+    interner: &'a mut Interner,
+}
+
+impl<'de> DeserializeSeed<'de> for KeySeed<'_> {
+    type Value = Arc<str>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Arc<str>, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl Visitor<'_> for KeySeed<'_> {
+    type Value = Arc<str>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a key")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Arc<str>, E> {
+        Ok(self.interner.intern(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_key_values;
+    use crate::KeyValueSerializer;
+    use crate::Preset;
+
+    fn string(value: Option<&InternedValue>) -> &Arc<str> {
+        match value {
+            Some(InternedValue::String(s)) => s,
+            _ => panic!("not a string: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn interned_keys_and_values() {
+        let mut interner = Interner::new();
+        let a = interner.parse("host=db msg=x", Preset::Logfmt).unwrap();
+        let b = interner.parse("msg=x host=web", Preset::Logfmt).unwrap();
+
+        let (InternedValue::Map(a_pairs), InternedValue::Map(b_pairs)) = (&a, &b) else {
+            panic!("not maps");
+        };
+        assert!(Arc::ptr_eq(&a_pairs[0].0, &b_pairs[1].0));
+        assert!(!Arc::ptr_eq(string(a.get("msg")), string(b.get("msg"))));
+        assert_eq!(interner.len(), 2);
+
+        let mut interner = Interner::new().intern_values(true);
+        let a = interner.parse("host=db msg=x", Preset::Logfmt).unwrap();
+        let b = interner.parse("msg=x host=db", Preset::Logfmt).unwrap();
+        assert!(Arc::ptr_eq(string(a.get("msg")), string(b.get("msg"))));
+        assert!(Arc::ptr_eq(string(a.get("host")), string(b.get("host"))));
+        assert_eq!(interner.len(), 4);
+    }
+
+    #[test]
+    fn interned_value_model() {
+        let input = "b=2,a=[x=s,y=[1,-1]],b=3,c";
+        let mut interner = Interner::new().intern_values(true);
+        let value = interner
+            .parse(input, DeserializeOptions::default())
+            .unwrap();
+
+        assert_eq!(value.get("b"), Some(&InternedValue::Int(3)));
+        assert_eq!(value.get("c"), Some(&InternedValue::Bool(true)));
+        assert_eq!(
+            value.get("a").and_then(|a| a.get("y")),
+            Some(&InternedValue::List(vec![
+                InternedValue::Int(1),
+                InternedValue::Int(-1)
+            ]))
+        );
+        assert_eq!(
+            Value::from(value.clone()),
+            from_key_values::<Value>(input).unwrap()
+        );
+        let value = interner
+            .parse("b=2,a=x,c", DeserializeOptions::default())
+            .unwrap();
+        let mut serializer = KeyValueSerializer::new();
+        value.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_output(), "b=2 a=x c=True");
+
+        assert_eq!(
+            interner
+                .parse("a=[", DeserializeOptions::default())
+                .unwrap_err()
+                .pos,
+            3
+        );
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Substitution of `${NAME}` references in key-values strings.
+
+use alloc::string::String;
+
+use crate::ErrorKind;
+use crate::ParseError;
+
+/// Replaces the `${NAME}` references of `input` with the values returned by `lookup` for their
+/// name, following the rules of dotenv files and Docker Compose, so values can be taken from the
+/// environment or from another set of variables before `input` is deserialized.
+///
+/// * `${NAME}` is replaced with the value of `NAME`, or nothing if it is not set.
+/// * `${NAME:-default}` is replaced with `default` if `NAME` is not set or is empty.
+/// * `${NAME-default}` is replaced with `default` if `NAME` is not set.
+/// * `$$` is replaced with a single `$`.
+///
+/// Defaults may contain references themselves. Other `$` characters, and everything enclosed in
+/// single quotes, are kept as-is. In double-quoted strings, the `"` and `\` characters of the
+/// substituted values are escaped with a backslash, but elsewhere values are inserted verbatim,
+/// so values that may contain separators should be referenced from double-quoted strings.
+///
+/// References that are not terminated or whose name is not made of alphanumeric characters and
+/// underscores are reported as [`ErrorKind::InvalidVariable`] errors at the position of their `$`.
+/// The positions of the errors met when deserializing the returned string are relative to it
+/// rather than to `input`.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use serde_keyvalue::interpolate;
+///
+/// let vars = HashMap::from([("HOST", "db"), ("USER", "O\"Neil")]);
+/// let lookup = |name: &str| vars.get(name).map(|value| value.to_string());
+///
+/// let output = interpolate(r#"host=${HOST},port=${PORT:-5432},user="${USER}""#, lookup);
+/// assert_eq!(output.unwrap(), r#"host=db,port=5432,user="O\"Neil""#);
+/// ```
+pub fn interpolate(
+    input: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, ParseError> {
+    let mut output = String::with_capacity(input.len());
+    substitute(input, 0, &lookup, &mut output, None)?;
+    Ok(output)
+}
+
+/// Replaces the `${NAME}` references of `input` with the values of the environment variables of
+/// the current process. See [`interpolate`] for details.
+///
+/// Variables whose value is not valid Unicode are considered not set.
+#[cfg(feature = "std")]
+pub fn interpolate_env(input: &str) -> Result<String, ParseError> {
+    interpolate(input, |name| std::env::var(name).ok())
+}
+
+/// Writes `input`, which starts at position `pos` of the original input, to `output` with its
+/// references substituted. `quote` is the quote enclosing `input`, if any.
+fn substitute(
+    input: &str,
+    pos: usize,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    output: &mut String,
+    mut quote: Option<char>,
+) -> Result<(), ParseError> {
+    let mut chars = input.char_indices();
+    let mut escaped = false;
+
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some('"'), _) if escaped => escaped = false,
+            (Some('"'), '\\') => escaped = true,
+            (Some(q), _) if c == q => quote = None,
+            (Some('\''), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (_, '$') => {
+                let rest = &input[i + 1..];
+                if rest.starts_with('$') {
+                    chars.next();
+                    output.push('$');
+                    continue;
+                }
+                if !rest.starts_with('{') {
+                    output.push('$');
+                    continue;
+                }
+
+                let error = ParseError {
+                    kind: ErrorKind::InvalidVariable,
+                    pos: pos + i,
+                };
+                let len = reference_len(&rest[1..]).ok_or_else(|| error.clone())?;
+                let reference = &rest[1..1 + len];
+                // Skip the reference and its closing brace.
+                chars.nth(reference.chars().count() + 1);
+
+                let name_len = reference
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(reference.len());
+                let (name, operator) = reference.split_at(name_len);
+                let value = lookup(name);
+                let (default, use_default) = if let Some(default) = operator.strip_prefix(":-") {
+                    (default, value.as_deref().is_none_or(str::is_empty))
+                } else if let Some(default) = operator.strip_prefix('-') {
+                    (default, value.is_none())
+                } else if operator.is_empty() {
+                    ("", false)
+                } else {
+                    return Err(error);
+                };
+                if name.is_empty() {
+                    return Err(error);
+                }
+
+                if use_default {
+                    let default_pos = pos + i + 2 + reference.len() - default.len();
+                    substitute(default, default_pos, lookup, output, quote)?;
+                } else if let Some(value) = value {
+                    push_value(output, &value, quote);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        output.push(c);
+    }
+
+    Ok(())
+}
+
+/// Returns the length of the reference at the start of `s`, up to its closing brace, taking the
+/// references nested in its default into account.
+fn reference_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' if s[..i].ends_with('$') => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Appends `value` to `output`, escaping the characters that would end a string enclosed in
+/// `quote`.
+fn push_value(output: &mut String, value: &str, quote: Option<char>) {
+    for c in value.chars() {
+        if quote == Some('"') && (c == '"' || c == '\\') {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn vars(input: &str) -> Result<String, ParseError> {
+        let vars = BTreeMap::from([("A", "1"), ("EMPTY", ""), ("PATH_2", "/x y")]);
+        interpolate(input, |name| vars.get(name).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn interpolate_references() {
+        assert_eq!(vars("a=${A},b=${B}").unwrap(), "a=1,b=");
+        assert_eq!(vars("a=x${A}y$${A}$B$").unwrap(), "a=x1y${A}$B$");
+        assert_eq!(
+            vars("a=${EMPTY:-d},b=${EMPTY-d},c=${B-d},d=${B:-${A}${C:-e}}").unwrap(),
+            "a=d,b=,c=d,d=1e"
+        );
+        assert_eq!(vars("é=${A}").unwrap(), "é=1");
+    }
+
+    #[test]
+    fn interpolate_quotes() {
+        assert_eq!(
+            vars(r#"a='${A}',b="${PATH_2}",c="\"${A}",d=${PATH_2}"#).unwrap(),
+            r#"a='${A}',b="/x y",c="\"1",d=/x y"#
+        );
+
+        let quoted = interpolate(r#"a="${Q}""#, |_| Some(r#"\"#.to_string())).unwrap();
+        assert_eq!(quoted, r#"a="\\""#);
+    }
+
+    #[test]
+    fn interpolate_errors() {
+        for (input, pos) in [
+            ("a=${A", 2),
+            ("a=1,b=${}", 6),
+            ("a=${A:x}", 2),
+            ("a=${B:-${C}", 2),
+            ("a=${B:-${}}", 7),
+            ("a=${A.B}", 2),
+        ] {
+            assert_eq!(
+                vars(input),
+                Err(ParseError {
+                    kind: ErrorKind::InvalidVariable,
+                    pos,
+                }),
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Journal export format of systemd.
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::options::BoolStyle;
+use crate::options::Quoting;
+use crate::options::SerializeOptions;
+use crate::pairs_with;
+use crate::KeyValueSerializer;
+use crate::SerializeError;
+
+/// Serializes `value` as the fields of a systemd journal entry, in the journal export format that
+/// is also the one of the datagrams sent to the `/run/systemd/journal/socket` socket of journald.
+///
+/// Each pair is written as a `KEY=value` line, with its key uppercased. Values containing line
+/// breaks are written in the binary-safe form of the format instead: the key and a line break,
+/// followed by the length of the value as a 64-bit little-endian integer, the value and a line
+/// break. Sequences are written as one field per element, all with the key of the sequence, and
+/// the fields of nested structs have the key of the struct and an underscore as prefix, e.g.
+/// `ORIGIN_HOST`.
+///
+/// Keys must be made of ASCII letters, digits and underscores and must not start with a digit or
+/// an underscore, which journald reserves for trusted fields; other keys are rejected. Entries of
+/// a journal export stream are separated by an empty line, so a `\n` must be written after each
+/// entry but the last.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_journal_export;
+///
+/// #[derive(Serialize)]
+/// struct Entry {
+///     message: String,
+///     priority: u8,
+///     syslog_identifier: String,
+/// }
+///
+/// let entry = Entry {
+///     message: "disk attached\nat /dev/vdb".into(),
+///     priority: 6,
+///     syslog_identifier: "vmm".into(),
+/// };
+///
+/// let mut expected = b"MESSAGE\n".to_vec();
+/// expected.extend_from_slice(&25u64.to_le_bytes());
+/// expected.extend_from_slice(b"disk attached\nat /dev/vdb\nPRIORITY=6\nSYSLOG_IDENTIFIER=vmm\n");
+/// assert_eq!(to_journal_export(&entry).unwrap(), expected);
+/// ```
+pub fn to_journal_export<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerializeError> {
+    let options = SerializeOptions::new()
+        .pair_separator("\n")
+        .quoting(Quoting::WhenNeeded)
+        .bool_style(BoolStyle::Lowercase)
+        .repeated_keys(true)
+        .nested_key_separator('_');
+    let mut serializer = KeyValueSerializer::with_options(options.clone());
+    value.serialize(&mut serializer)?;
+    let output = serializer.into_output();
+
+    let mut export = Vec::with_capacity(output.len());
+    for pair in pairs_with(&output, options) {
+        let (key, value) = pair.map_err(|e| SerializeError::Custom(e.to_string()))?;
+        if !is_field_name(&key) {
+            return Err(SerializeError::Custom(format!(
+                "`{}` is not a journal field name",
+                key
+            )));
+        }
+
+        export.extend(key.bytes().map(|b| b.to_ascii_uppercase()));
+        if value.contains('\n') {
+            export.push(b'\n');
+            export.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            export.push(b'=');
+        }
+        export.extend_from_slice(value.as_bytes());
+        export.push(b'\n');
+    }
+
+    Ok(export)
+}
+
+/// Returns `true` if `key` can be the name of a field written by a client of journald once
+/// uppercased.
+fn is_field_name(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Origin {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Serialize)]
+    struct Entry {
+        message: String,
+        tag: Vec<&'static str>,
+        origin: Origin,
+        audit: bool,
+    }
+
+    #[test]
+    fn journal_export_fields() {
+        let entry = Entry {
+            message: "a \"b\"=c\r\n".into(),
+            tag: vec!["x", "y z"],
+            origin: Origin {
+                host: "db".into(),
+                port: 5432,
+            },
+            audit: false,
+        };
+
+        let mut expected = b"MESSAGE\n\x09\0\0\0\0\0\0\0a \"b\"=c\r\n\n".to_vec();
+        expected.extend_from_slice(b"TAG=x\nTAG=y z\nORIGIN_HOST=db\nORIGIN_PORT=5432\n");
+        expected.extend_from_slice(b"AUDIT=false\n");
+        assert_eq!(to_journal_export(&entry).unwrap(), expected);
+    }
+
+    #[test]
+    fn journal_export_invalid_keys() {
+        for key in ["_PID", "1A", "CODE-FILE", ""] {
+            let fields = BTreeMap::from([(key, "x")]);
+            assert!(to_journal_export(&fields).is_err(), "{}", key);
+        }
+        assert!(to_journal_export(&BTreeMap::<String, String>::new())
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Conversions between key-values strings and JSON values.
+
+use serde::Deserialize;
+use serde::Serializer;
+
+use crate::DeserializeOptions;
+use crate::KeyValueDeserializer;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+use crate::SerializeOptions;
+
+/// Parses the key-values string `input` into a JSON object.
+///
+/// Since the input carries no type information, the type of values is guessed from their content:
+/// a key without a value is a `true` boolean, numbers are integers, values enclosed in brackets are
+/// arrays, and everything else is a string.
+///
+/// ```
+/// use serde_json::json;
+/// use serde_keyvalue::to_json;
+///
+/// let value = to_json(r#"mtu=1500,name="tap 0",vhost,queues=[1,2]"#).unwrap();
+/// assert_eq!(
+///     value,
+///     json!({ "mtu": 1500, "name": "tap 0", "vhost": true, "queues": [1, 2] })
+/// );
+/// ```
+pub fn to_json(input: &str) -> Result<serde_json::Value, ParseError> {
+    to_json_with(input, DeserializeOptions::default())
+}
+
+/// Parses the key-values string `input` into a JSON object, using the syntax described by
+/// `options`. See [`to_json`] for details.
+pub fn to_json_with(
+    input: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<serde_json::Value, ParseError> {
+    let mut deserializer = KeyValueDeserializer::with_options(input, options);
+    let value = serde_json::Value::deserialize(&mut deserializer)?;
+    deserializer.finish()?;
+
+    Ok(value)
+}
+
+/// Writes the JSON object `value` as a key-values string.
+///
+/// The members of the object become the key-value pairs of the string, and `null` members are
+/// omitted. Fails if `value` is not an object, or if one of its members is a nested object.
+///
+/// ```
+/// use serde_json::json;
+/// use serde_keyvalue::to_string_from_json;
+///
+/// let output = to_string_from_json(&json!({ "mtu": 9000, "queues": [1, 2] })).unwrap();
+/// assert_eq!(output, "mtu=9000 queues=[1,2]");
+/// ```
+pub fn to_string_from_json(value: &serde_json::Value) -> Result<String, SerializeError> {
+    to_string_from_json_with(value, SerializeOptions::default())
+}
+
+/// Writes the JSON object `value` as a key-values string according to `options`. See
+/// [`to_string_from_json`] for details.
+pub fn to_string_from_json_with(
+    value: &serde_json::Value,
+    options: impl Into<SerializeOptions>,
+) -> Result<String, SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(options);
+    match value {
+        serde_json::Value::Object(map) => {
+            (&mut serializer).collect_map(map.iter().filter(|(_, value)| !value.is_null()))?
+        }
+        _ => return Err(SerializeError::Custom("expected a JSON object".into())),
+    }
+
+    Ok(serializer.into_output())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ErrorKind;
+    use crate::Preset;
+
+    #[test]
+    fn json_roundtrip() {
+        let value = json!({ "a": "x", "b": -3, "c": false, "d": ["y", 2] });
+        let output = to_string_from_json(&value).unwrap();
+
+        assert_eq!(output, "a=x b=-3 c=False d=[y,2]");
+        assert_eq!(to_json(&output).unwrap(), value);
+    }
+
+    #[test]
+    fn json_errors() {
+        assert_eq!(
+            to_json("a=1 b=[2").unwrap_err().kind,
+            ErrorKind::ExpectedCloseBracket
+        );
+        assert!(to_string_from_json(&json!([1, 2])).is_err());
+        assert!(to_string_from_json(&json!({ "a": { "b": 1 } })).is_err());
+    }
+
+    #[test]
+    fn json_with_options() {
+        let value = to_json_with("level=info msg=\"a b\" ok=true", Preset::Logfmt).unwrap();
+        assert_eq!(value, json!({ "level": "info", "msg": "a b", "ok": true }));
+        assert_eq!(
+            to_string_from_json_with(&value, Preset::Logfmt).unwrap(),
+            "level=info msg=\"a b\" ok=true"
+        );
+    }
+}
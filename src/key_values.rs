@@ -2,23 +2,32 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::borrow::Cow;
-use std::fmt;
-use std::fmt::Debug;
-use std::fmt::Display;
-use std::num::ParseIntError;
-
+use alloc::borrow::Cow;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::num::ParseIntError;
+use core::ops::Neg;
+
+use memchr::memchr;
+use memchr::memchr2;
 use nom::branch::alt;
 use nom::bytes::complete::escaped_transform;
 use nom::bytes::complete::is_not;
 use nom::bytes::complete::tag;
 use nom::bytes::complete::take_while;
 use nom::bytes::complete::take_while1;
+use nom::bytes::complete::take_while_m_n;
 use nom::character::complete::alphanumeric1;
 use nom::character::complete::anychar;
 use nom::character::complete::char;
 use nom::character::complete::none_of;
 use nom::combinator::map;
+use nom::combinator::map_opt;
 use nom::combinator::map_res;
 use nom::combinator::opt;
 use nom::combinator::peek;
@@ -27,54 +36,108 @@ use nom::combinator::value;
 use nom::combinator::verify;
 use nom::sequence::delimited;
 use nom::sequence::pair;
+use nom::sequence::preceded;
 use nom::sequence::tuple;
 use nom::AsChar;
 use nom::Finish;
 use nom::IResult;
 use nom::InputTakeAtPosition;
-use num_traits::Float;
+use num_traits::AsPrimitive;
+use num_traits::Bounded;
 use num_traits::Num;
 use num_traits::ParseFloatError;
 use remain::sorted;
 use serde::de;
+use serde::de::IntoDeserializer;
+use serde::forward_to_deserialize_any;
 use serde::Deserialize;
 use serde::Deserializer;
-use thiserror::Error;
 
-#[derive(Debug, Error, PartialEq, Eq)]
+use crate::options::DelimiterStyle;
+use crate::options::DeserializeOptions;
+use crate::options::EmptyValues;
+use crate::options::Overflow;
+use crate::options::Quoting;
+use crate::options::Whitespace;
+use crate::raw::RAW_VALUE;
+use crate::scanner::Scanner;
+use crate::search::find_any;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[sorted]
 #[non_exhaustive]
 #[allow(missing_docs)]
 /// Different kinds of errors that can be returned by the parser.
 pub enum ErrorKind {
-    #[error("unexpected end of input")]
+    ArgFile(String),
+    ArgFileNesting,
+    EmptyValue,
     Eof,
-    #[error("expected a boolean")]
     ExpectedBoolean,
-    #[error("expected ']'")]
     ExpectedCloseBracket,
-    #[error("expected ','")]
     ExpectedComma,
-    #[error("expected '='")]
     ExpectedEqual,
-    #[error("expected an identifier")]
     ExpectedIdentifier,
-    #[error("expected '['")]
     ExpectedOpenBracket,
-    #[error("expected a string")]
     ExpectedString,
-    #[error("\" and ' can only be used in quoted strings")]
+    ExpectedTupleSeparator,
+    IncludeCycle(String),
+    IncludeFile(String),
+    IncludeNesting,
     InvalidCharInString,
-    #[error("invalid characters for number or number does not fit into its destination type")]
     InvalidNumber,
-    #[error("serde error: {0}")]
+    InvalidUtf8,
+    InvalidValue { key: String, message: String },
+    InvalidVariable,
+    OutOfRange { key: String },
     SerdeError(String),
-    #[error("remaining characters in input")]
     TrailingCharacters,
+    Unsupported(&'static str),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::ArgFile(s) => write!(f, "cannot read argument file {}", s),
+            ErrorKind::ArgFileNesting => f.write_str("argument files are nested too deeply"),
+            ErrorKind::EmptyValue => f.write_str("empty value"),
+            ErrorKind::Eof => f.write_str("unexpected end of input"),
+            ErrorKind::ExpectedBoolean => f.write_str("expected a boolean"),
+            ErrorKind::ExpectedCloseBracket => f.write_str("expected ']'"),
+            ErrorKind::ExpectedComma => f.write_str("expected ','"),
+            ErrorKind::ExpectedEqual => f.write_str("expected '='"),
+            ErrorKind::ExpectedIdentifier => f.write_str("expected an identifier"),
+            ErrorKind::ExpectedOpenBracket => f.write_str("expected '['"),
+            ErrorKind::ExpectedString => f.write_str("expected a string"),
+            ErrorKind::ExpectedTupleSeparator => f.write_str("expected a tuple separator"),
+            ErrorKind::IncludeCycle(s) => write!(f, "file {} includes itself", s),
+            ErrorKind::IncludeFile(s) => write!(f, "cannot read file {}", s),
+            ErrorKind::IncludeNesting => f.write_str("included files are nested too deeply"),
+            ErrorKind::InvalidCharInString => {
+                f.write_str("\" and ' can only be used in quoted strings")
+            }
+            ErrorKind::InvalidNumber => f.write_str(
+                "invalid characters for number or number does not fit into its destination type",
+            ),
+            ErrorKind::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            ErrorKind::InvalidValue { key, message } => {
+                write!(f, "invalid value for key `{}`: {}", key, message)
+            }
+            ErrorKind::InvalidVariable => f.write_str("invalid variable reference"),
+            ErrorKind::OutOfRange { key } => {
+                write!(f, "number out of range for key `{}`", key)
+            }
+            ErrorKind::SerdeError(s) => write!(f, "serde error: {}", s),
+            ErrorKind::TrailingCharacters => f.write_str("remaining characters in input"),
+            ErrorKind::Unsupported(s) => write!(f, "{} cannot be deserialized from key-values", s),
+        }
+    }
 }
 
+impl core::error::Error for ErrorKind {}
+
 /// Error that may be thown while parsing a key-values string.
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseError {
     /// Detailed error that occurred.
     pub kind: ErrorKind,
@@ -91,6 +154,8 @@ impl Display for ParseError {
     }
 }
 
+impl core::error::Error for ParseError {}
+
 impl de::Error for ParseError {
     fn custom<T>(msg: T) -> Self
     where
@@ -103,18 +168,244 @@ impl de::Error for ParseError {
     }
 }
 
-type Result<T> = std::result::Result<T, ParseError>;
+type Result<T> = core::result::Result<T, ParseError>;
+
+/// Byte order mark starting some UTF-8 files.
+pub(crate) const BYTE_ORDER_MARK: char = '\u{feff}';
 
 /// Returns `true` if `c` is a valid separator character.
-fn is_separator(c: Option<char>) -> bool {
-    matches!(c, Some(' ') | Some(',') | Some(']') | None)
+pub(crate) fn is_separator(c: Option<char>, options: &DeserializeOptions) -> bool {
+    match c {
+        Some(']') | None => true,
+        Some(c) => options.pair_separators.contains(&c),
+    }
+}
+
+/// Returns `true` if `s` starts with a doubled pair separator standing for the separator itself,
+/// as in the values of QEMU option strings, rather than with an empty value.
+pub(crate) fn starts_with_doubled_separator(s: &str, options: &DeserializeOptions) -> bool {
+    options.quoting == Quoting::Qemu
+        && s.chars().next().is_some_and(|c| {
+            options.pair_separators.contains(&c) && s[c.len_utf8()..].starts_with(c)
+        })
+}
+
+/// Returns `true` if `s` is a valid Kubernetes label value, i.e. empty or made of at most 63
+/// alphanumeric ASCII characters, `-`, `_` and `.`, starting and ending with an alphanumeric one.
+pub(crate) fn is_label_value(s: &str) -> bool {
+    s.is_empty()
+        || (s.len() <= 63
+            && s.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && s.ends_with(|c: char| c.is_ascii_alphanumeric())
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)))
+}
+
+/// Returns `key` with its slashes and dots swapped if its first separator is a slash, as `sysctl`
+/// does with `/` separated keys.
+fn dotted_key(key: &str) -> Option<String> {
+    let separator = key.find(['.', '/'])?;
+    (key.as_bytes()[separator] == b'/').then(|| {
+        key.chars()
+            .map(|c| match c {
+                '/' => '.',
+                '.' => '/',
+                c => c,
+            })
+            .collect()
+    })
+}
+
+/// Returns the input following the key-value delimiter if `s` starts with one, skipping the
+/// spaces allowed around it by `options`.
+pub(crate) fn strip_delimiter<'a>(s: &'a str, options: &DeserializeOptions) -> Option<&'a str> {
+    let is_space = |c| c == ' ' || c == '\t';
+    let style = if options.trim_values {
+        DelimiterStyle::Spaced
+    } else {
+        options.delimiter_style
+    };
+
+    match style {
+        DelimiterStyle::Compact => s.strip_prefix(options.key_value_delimiters.as_slice()),
+        DelimiterStyle::SpaceAfter => s
+            .strip_prefix(options.key_value_delimiters.as_slice())
+            .map(|s| s.trim_start_matches(is_space)),
+        DelimiterStyle::Spaced => {
+            let delimiters = options.key_value_delimiters.as_slice();
+            let rest = s.trim_start_matches(is_space);
+            match rest.strip_prefix(delimiters) {
+                Some(rest) => Some(rest.trim_start_matches(is_space)),
+                // Spaces alone delimit the value if they are delimiters, as in Java properties.
+                None if s.starts_with(delimiters) => Some(rest),
+                None => None,
+            }
+        }
+    }
+}
+
+/// Returns the input following the pair separator at the start of `s`, along with the whitespace
+/// collapsed around it by `options`, or `None` if `s` does not start with one. Whitespace alone
+/// also separates pairs. The end of the input and the `]` ending a bracketed value are not
+/// consumed.
+pub(crate) fn strip_separator<'a>(s: &'a str, options: &DeserializeOptions) -> Option<&'a str> {
+    let blanks = options.whitespace.blanks();
+    let rest = s.trim_start_matches(blanks);
+    let len = separator_len(rest, options);
+
+    match rest.chars().next() {
+        Some(']') | None => Some(rest),
+        Some(_) if len > 0 => Some(rest[len..].trim_start_matches(blanks)),
+        Some(_) if rest.len() < s.len() => Some(rest),
+        Some(_) => None,
+    }
+}
+
+/// Returns the length of the pair separator at the start of `s`, or 0 if it does not start with
+/// one. A `\r\n` line ending is a single separator if newlines separate pairs.
+fn separator_len(s: &str, options: &DeserializeOptions) -> usize {
+    match s.chars().next() {
+        Some('\r') if s[1..].starts_with('\n') && options.pair_separators.contains(&'\n') => 2,
+        Some(c) if options.pair_separators.contains(&c) => c.len_utf8(),
+        _ => 0,
+    }
+}
+
+/// Returns `true` if `s` starts with one of the comment markers of `options`.
+fn is_comment(s: &str, options: &DeserializeOptions) -> bool {
+    options
+        .comment_markers
+        .iter()
+        .any(|marker| s.starts_with(marker.as_str()))
+}
+
+/// Returns the start and the end of the trailing comment of the pair at the start of `s`,
+/// including the spaces and tabs preceding it, if `options` accept trailing comments, or of its
+/// trailing spaces and tabs if `options` trim values. Comments end with the next pair separator
+/// other than a space or a tab.
+pub(crate) fn find_trailing_trivia(
+    s: &str,
+    options: &DeserializeOptions,
+) -> Option<(usize, usize)> {
+    if !options.trailing_comments && !options.trim_values {
+        return None;
+    }
+
+    let quotes: &[char] = match options.quoting {
+        Quoting::Never
+        | Quoting::Escaped
+        | Quoting::Percent
+        | Quoting::Uri
+        | Quoting::Braces
+        | Quoting::Qemu
+        | Quoting::Cookie
+        | Quoting::Label
+        | Quoting::SingleLine => &[],
+        Quoting::Token
+        | Quoting::Prometheus
+        | Quoting::Syslog
+        | Quoting::Doubled
+        | Quoting::Kernel => &['"'],
+        _ => &['"', '\''],
+    };
+    // Doubled quotes close and reopen the string, and the kernel command line has no escapes, so
+    // only backslashes need special care.
+    let backslash_escapes = !matches!(options.quoting, Quoting::Doubled | Quoting::Kernel);
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    let mut quote = None;
+    let mut escaped = false;
+    let mut depth = 0;
+
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(q), _) if c == q => quote = None,
+            (Some('\''), _) => (),
+            (_, '\\') if backslash_escapes => escaped = true,
+            (Some(_), _) => (),
+            (None, c) if quotes.contains(&c) => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') if depth == 0 => return None,
+            (None, ']') => depth -= 1,
+            (None, c) if depth == 0 && is_blank(c) => {
+                let rest = s[i..].trim_start_matches(is_blank);
+                if options.trailing_comments && is_comment(rest, options) {
+                    let end = s[i..]
+                        .find(|c| options.pair_separators.contains(&c) && !is_blank(c))
+                        .map_or(s.len(), |len| i + len);
+                    return Some((i, end));
+                }
+                let at_end = rest.is_empty() || rest.starts_with(']');
+                if options.trim_values && (at_end || separator_len(rest, options) > 0) {
+                    return Some((i, s.len() - rest.len()));
+                }
+                if options.pair_separators.contains(&c) {
+                    return None;
+                }
+            }
+            (None, c) if depth == 0 && options.pair_separators.contains(&c) => return None,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Returns the input following the backslash line continuation at the start of `s`, if any,
+/// including the spaces and tabs indenting the next line.
+fn strip_line_continuation(s: &str) -> Option<&str> {
+    let rest = s.strip_prefix('\\')?;
+    let rest = rest
+        .strip_prefix('\n')
+        .or_else(|| rest.strip_prefix("\r\n"))?;
+
+    Some(rest.trim_start_matches([' ', '\t']))
+}
+
+/// Returns `s` without the empty pairs, comments, leading spaces, line continuations and key
+/// prefix accepted by `options` at its start.
+pub(crate) fn skip_trivia<'a>(mut s: &'a str, options: &DeserializeOptions) -> &'a str {
+    let is_pair_separator = |c| options.pair_separators.contains(&c);
+    let blanks = options.whitespace.blanks();
+
+    loop {
+        match s.chars().next() {
+            Some(c) if options.empty_pairs && is_pair_separator(c) => s = &s[c.len_utf8()..],
+            Some(' ' | '\t') if options.leading_spaces => s = &s[1..],
+            Some(c) if blanks.contains(&c) => s = &s[c.len_utf8()..],
+            Some('\\') if options.line_continuations => match strip_line_continuation(s) {
+                Some(rest) => s = rest,
+                None => break,
+            },
+            // Comments extend up to the next separator, which is skipped as well. Spaces and tabs
+            // do not end comments when they are collapsed into separators or may precede trailing
+            // comments.
+            Some(_) if is_comment(s, options) => {
+                let end = match options.whitespace {
+                    Whitespace::Strict if !options.trailing_comments => {
+                        find_any(s, &[&options.pair_separators])
+                    }
+                    _ => s
+                        .find(|c| is_pair_separator(c) && !matches!(c, ' ' | '\t'))
+                        .unwrap_or(s.len()),
+                };
+                s = &s[end + separator_len(&s[end..], options)..];
+            }
+            _ => break,
+        }
+    }
+
+    match &options.key_prefix {
+        Some(prefix) => s.strip_prefix(prefix.as_str()).unwrap_or(s),
+        None => s,
+    }
 }
 
 /// Nom parser for valid separators.
-fn any_separator(s: &str) -> IResult<&str, Option<char>> {
+fn any_separator<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, Option<char>> {
     let next_char = s.chars().next();
 
-    if is_separator(next_char) {
+    if is_separator(next_char, options) {
         let pos = if let Some(c) = next_char {
             c.len_utf8()
         } else {
@@ -132,7 +423,7 @@ fn any_separator(s: &str) -> IResult<&str, Option<char>> {
 /// Nom parser for valid positive of negative float point numbers.
 ///
 /// Hexadecimal, octal, and binary values can be specified with the `0x`, `0o` and `0b` prefixes.
-fn any_number<T>(s: &str) -> IResult<&str, T>
+fn any_number<'a, T>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, T>
 where
     T: Num<FromStrRadixErr = ParseIntError>,
 {
@@ -141,7 +432,10 @@ where
     //
     // We move this non-generic part into its own function so it doesn't get monomorphized, which
     // would increase the binary size more than needed.
-    fn parse_number(s: &str) -> IResult<&str, (Cow<str>, u32)> {
+    fn parse_number<'a>(
+        s: &'a str,
+        options: &DeserializeOptions,
+    ) -> IResult<&'a str, (Cow<'a, str>, u32)> {
         // Recognizes the sign prefix.
         let sign = char('-');
 
@@ -153,7 +447,7 @@ where
         ));
 
         // Recognizes the trailing separator but do not consume it.
-        let separator = peek(any_separator);
+        let separator = peek(|s| any_separator(s, options));
 
         // Chain of parsers: sign (optional) and radix (optional), then sequence of alphanumerical
         // characters.
@@ -177,9 +471,10 @@ where
         )(s)
     }
 
-    map_res(parse_number, |(num_string, radix)| {
-        T::from_str_radix(&num_string, radix)
-    })(s)
+    map_res(
+        |s| parse_number(s, options),
+        |(num_string, radix)| T::from_str_radix(&num_string, radix),
+    )(s)
 }
 
 /// Nom parser for valid strings.
@@ -190,17 +485,32 @@ where
 ///
 /// The returned value is a slice into the current input if no characters to unescape were met,
 /// or a fully owned string if we had to unescape some characters.
-fn any_string(s: &str) -> IResult<&str, Cow<str>> {
-    // Double-quoted strings may escape " and \ characters. Since escaped strings are modified,
-    // we need to return an owned `String` instead of just a slice in the input string.
-    let double_quoted = delimited(
+pub(crate) fn any_string<'a>(
+    s: &'a str,
+    options: &DeserializeOptions,
+) -> IResult<&'a str, Cow<'a, str>> {
+    // Double-quoted strings may escape ", \, newline, carriage return and tab characters, and
+    // other characters as `\uXXXX`. Since escaped strings are modified, we need to return an owned
+    // `String` instead of just a slice in the input string, unless the string has no escapes at
+    // all.
+    let escaped_double_quoted = delimited(
         char('"'),
         alt((
             map(
                 escaped_transform(
                     none_of(r#"\""#),
                     '\\',
-                    alt((value("\"", char('"')), value("\\", char('\\')))),
+                    alt((
+                        value('"', char('"')),
+                        value('\\', char('\\')),
+                        value('\n', char('n')),
+                        value('\r', char('r')),
+                        value('\t', char('t')),
+                        map_opt(
+                            preceded(char('u'), take_while_m_n(4, 4, AsChar::is_hex_digit)),
+                            |hex| u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                        ),
+                    )),
                 ),
                 Cow::Owned,
             ),
@@ -208,6 +518,7 @@ fn any_string(s: &str) -> IResult<&str, Cow<str>> {
         )),
         char('"'),
     );
+    let mut double_quoted = alt((plain_quoted(b'"'), escaped_double_quoted));
 
     // Single-quoted strings do not escape characters.
     let single_quoted = map(
@@ -215,28 +526,705 @@ fn any_string(s: &str) -> IResult<&str, Cow<str>> {
         Cow::Borrowed,
     );
 
-    // Unquoted strings end with the next comma or bracket and may not contain a quote or bracket
-    // character or be empty.
-    let unquoted = map(
-        take_while1(|c: char| {
-            c != ' ' && c != ',' && c != '"' && c != '\'' && c != '[' && c != ']'
-        }),
+    // Double-quoted strings of the kernel command line do not escape characters either.
+    let kernel_quoted = map(
+        delimited(char('"'), alt((is_not(r#"""#), tag(""))), char('"')),
         Cow::Borrowed,
     );
 
-    alt((double_quoted, single_quoted, unquoted))(s)
+    // Double-quoted strings of HTTP headers may escape any character.
+    let escaped_token_quoted = delimited(
+        char('"'),
+        alt((
+            map(
+                escaped_transform(none_of(r#"\""#), '\\', anychar),
+                Cow::Owned,
+            ),
+            map(tag(""), Cow::Borrowed),
+        )),
+        char('"'),
+    );
+    let token_quoted = alt((plain_quoted(b'"'), escaped_token_quoted));
+
+    // Single-quoted strings of libpq connection strings may escape any character.
+    let escaped_libpq_quoted = delimited(
+        char('\''),
+        alt((
+            map(escaped_transform(none_of(r"\'"), '\\', anychar), Cow::Owned),
+            map(tag(""), Cow::Borrowed),
+        )),
+        char('\''),
+    );
+    let libpq_quoted = alt((plain_quoted(b'\''), escaped_libpq_quoted));
+
+    // Unquoted strings end with the next separator or bracket and may not contain a bracket
+    // character or be empty. They may not contain the recognized quotes either. They may start
+    // with an IPv6 address in brackets though, so socket addresses such as `[::1]:80` do not need
+    // to be quoted.
+    let quotes: &[char] = match options.quoting {
+        Quoting::Never
+        | Quoting::Braces
+        | Quoting::Qemu
+        | Quoting::Cookie
+        | Quoting::Label
+        | Quoting::SingleLine => &[],
+        Quoting::Token | Quoting::Syslog | Quoting::Doubled | Quoting::Kernel => &['"'],
+        _ => &['"', '\''],
+    };
+    let stops: [&[char]; 3] = [&options.pair_separators, &['[', ']'], quotes];
+    let take_unquoted = |s: &'a str| -> IResult<&'a str, &'a str> {
+        let end = find_any(s, &stops);
+        Ok((&s[end..], &s[..end]))
+    };
+    let take_continued = |s: &'a str| -> IResult<&'a str, Cow<'a, str>> {
+        if options.line_continuations {
+            continued_unquoted(s, &stops)
+        } else {
+            take_unquoted(s).map(|(rest, s)| (rest, Cow::Borrowed(s)))
+        }
+    };
+    let mut unquoted = alt((
+        map(recognize(pair(ipv6_literal, take_unquoted)), Cow::Borrowed),
+        verify(take_continued, |s: &str| !s.is_empty()),
+    ));
+
+    match options.quoting {
+        Quoting::Never | Quoting::Cookie | Quoting::SingleLine => {
+            alt((unquoted, map(tag(""), Cow::Borrowed)))(s)
+        }
+        Quoting::Label => verify(alt((unquoted, map(tag(""), Cow::Borrowed))), |s: &str| {
+            is_label_value(s)
+        })(s),
+        Quoting::WhenNeeded | Quoting::Always => alt((double_quoted, single_quoted, unquoted))(s),
+        Quoting::Escaped => escaped_unquoted(s, options),
+        Quoting::Percent | Quoting::Uri => percent_encoded(s, options),
+        Quoting::Prometheus => double_quoted(s),
+        Quoting::Syslog => sd_param_value(s),
+        Quoting::Influx => influx_escaped(s, options),
+        Quoting::DistinguishedName => dn_escaped(s, options),
+        Quoting::Folded => folded(s, options),
+        Quoting::Qemu => doubled_separators(s, options),
+        Quoting::SingleQuoted => shell_word(s, options),
+        Quoting::Ffmpeg => alt((single_quoted, |s| escaped_unquoted(s, options)))(s),
+        // An opening brace always starts a braced string.
+        Quoting::Braces if s.starts_with('{') => braced(s),
+        Quoting::Braces => unquoted(s),
+        Quoting::Libpq => alt((libpq_quoted, |s| escaped_unquoted(s, options)))(s),
+        Quoting::Token => alt((token_quoted, unquoted))(s),
+        Quoting::Doubled => alt((doubled_quoted, unquoted))(s),
+        Quoting::Kernel => alt((kernel_quoted, unquoted))(s),
+        Quoting::Shell => alt((shell_double_quoted, single_quoted, |s| {
+            escaped_unquoted(s, options)
+        }))(s),
+    }
+}
+
+/// Parses an unquoted string ending with the next of `stops`, whose lines may end with a backslash
+/// line continuation. Continuations are removed along with the indentation of the next line, in
+/// which case an owned `String` is returned.
+fn continued_unquoted<'a>(s: &'a str, stops: &[&[char]]) -> IResult<&'a str, Cow<'a, str>> {
+    // Joined string, only allocated once a continuation is met.
+    let mut owned: Option<String> = None;
+    let mut rest = s;
+
+    loop {
+        let end = find_any(rest, stops);
+        let continuation = rest[..end]
+            .match_indices('\\')
+            .find_map(|(i, _)| Some((i, strip_line_continuation(&rest[i..])?)));
+        match continuation {
+            Some((i, next)) => {
+                owned.get_or_insert_with(String::new).push_str(&rest[..i]);
+                rest = next;
+            }
+            None => {
+                let value = match owned {
+                    Some(mut owned) => {
+                        owned.push_str(&rest[..end]);
+                        Cow::Owned(owned)
+                    }
+                    None => Cow::Borrowed(&rest[..end]),
+                };
+                return Ok((&rest[end..], value));
+            }
+        }
+    }
+}
+
+/// Returns a nom parser for strings enclosed in the ASCII `quote` character and not containing
+/// backslash escapes, which are borrowed from the input.
+fn plain_quoted(quote: u8) -> impl Fn(&str) -> IResult<&str, Cow<'_, str>> {
+    move |s| {
+        let error = || nom::Err::Error(nom::error::Error::new(s, nom::error::ErrorKind::Char));
+        let content = s.strip_prefix(quote as char).ok_or_else(error)?;
+
+        match memchr2(quote, b'\\', content.as_bytes()) {
+            Some(end) if content.as_bytes()[end] == quote => {
+                Ok((&content[end + 1..], Cow::Borrowed(&content[..end])))
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Nom parser for double-quoted strings where `""` stands for a quote, as in CSV files. Strings
+/// without doubled quotes are borrowed from the input.
+fn doubled_quoted(s: &str) -> IResult<&str, Cow<'_, str>> {
+    let error = || nom::Err::Error(nom::error::Error::new(s, nom::error::ErrorKind::Char));
+    let mut rest = s.strip_prefix('"').ok_or_else(error)?;
+    // Unescaped string, only allocated once a doubled quote is met.
+    let mut owned: Option<String> = None;
+
+    loop {
+        let end = memchr(b'"', rest.as_bytes()).ok_or_else(error)?;
+        let (content, remainder) = (&rest[..end], &rest[end + 1..]);
+        match remainder.strip_prefix('"') {
+            Some(remainder) => {
+                let owned = owned.get_or_insert_with(String::new);
+                owned.push_str(content);
+                owned.push('"');
+                rest = remainder;
+            }
+            None => {
+                let value = match owned {
+                    Some(mut owned) => {
+                        owned.push_str(content);
+                        Cow::Owned(owned)
+                    }
+                    None => Cow::Borrowed(content),
+                };
+                return Ok((remainder, value));
+            }
+        }
+    }
+}
+
+/// Nom parser for an IPv6 address enclosed in brackets, as written in URIs and socket addresses,
+/// e.g. `[fe80::1%eth0]`.
+fn ipv6_literal(s: &str) -> IResult<&str, &str> {
+    recognize(delimited(
+        char('['),
+        verify(
+            take_while1(|c: char| c.is_ascii_alphanumeric() || matches!(c, ':' | '.' | '%')),
+            |addr: &str| addr.contains(':'),
+        ),
+        char(']'),
+    ))(s)
+}
+
+/// Nom parser for unquoted strings with backslash escapes, as found in Java properties files or,
+/// with [`Quoting::Shell`], in systemd environment files.
+///
+/// The string ends with the next unescaped separator or bracket and may not be empty. A backslash
+/// at the end of a line continues the string on the next line, and any other escaped character
+/// stands for itself, with the following exceptions for Java properties files: `\n`, `\r`, `\t`,
+/// `\f` and `\uXXXX` stand for the corresponding characters, and the leading spaces of continuation
+/// lines are skipped. With [`Quoting::Shell`], trailing unescaped whitespace is dropped. With
+/// [`Quoting::Libpq`] and [`Quoting::Ffmpeg`], the rules of [`Quoting::Shell`] apply.
+fn escaped_unquoted<'a>(
+    s: &'a str,
+    options: &DeserializeOptions,
+) -> IResult<&'a str, Cow<'a, str>> {
+    let error = |input| {
+        nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Escaped,
+        ))
+    };
+    let shell = matches!(
+        options.quoting,
+        Quoting::Shell | Quoting::Libpq | Quoting::Ffmpeg
+    );
+    // Unescaped string, only allocated once an escape is met.
+    let mut owned: Option<String> = None;
+    // Length of the string up to its last character that is not trailing whitespace.
+    let mut kept = 0;
+    let mut pos = 0;
+
+    while let Some(c) = s[pos..].chars().next() {
+        if options.pair_separators.contains(&c) || matches!(c, '[' | ']') {
+            break;
+        }
+        pos += c.len_utf8();
+
+        if c != '\\' {
+            let len = match &mut owned {
+                Some(owned) => {
+                    owned.push(c);
+                    owned.len()
+                }
+                None => pos,
+            };
+            if !(shell && matches!(c, ' ' | '\t')) {
+                kept = len;
+            }
+            continue;
+        }
+
+        let owned = owned.get_or_insert_with(|| s[..pos - 1].to_owned());
+        let escaped = s[pos..]
+            .chars()
+            .next()
+            .ok_or_else(|| error(&s[pos - 1..]))?;
+        pos += escaped.len_utf8();
+
+        match escaped {
+            'n' if !shell => owned.push('\n'),
+            'r' if !shell => owned.push('\r'),
+            't' if !shell => owned.push('\t'),
+            'f' if !shell => owned.push('\u{c}'),
+            'u' if !shell => {
+                let c = s
+                    .get(pos..pos + 4)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| error(&s[pos - 2..]))?;
+                owned.push(c);
+                pos += 4;
+            }
+            // Line continuation: the line break is skipped, and so are the leading spaces of the
+            // next line in Java properties files.
+            '\r' | '\n' => {
+                if escaped == '\r' && s[pos..].starts_with('\n') {
+                    pos += 1;
+                }
+                if !shell {
+                    pos = s.len() - s[pos..].trim_start_matches([' ', '\t']).len();
+                }
+                continue;
+            }
+            c => owned.push(c),
+        }
+        kept = owned.len();
+    }
+
+    if pos == 0 {
+        return Err(error(s));
+    }
+
+    let value = match owned {
+        Some(mut owned) => {
+            owned.truncate(kept);
+            Cow::Owned(owned)
+        }
+        None => Cow::Borrowed(&s[..kept]),
+    };
+
+    Ok((&s[pos..], value))
+}
+
+/// Nom parser for percent-encoded strings, where `%XX` escapes stand for a byte of the UTF-8
+/// encoding of the string and, with [`Quoting::Percent`], `+` for a space.
+///
+/// The string ends with the next separator or bracket and may be empty.
+fn percent_encoded<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, Cow<'a, str>> {
+    let error = |input| {
+        nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Escaped,
+        ))
+    };
+    let end = s
+        .find(|c: char| options.pair_separators.contains(&c) || matches!(c, '[' | ']'))
+        .unwrap_or(s.len());
+    let (encoded, remainder) = s.split_at(end);
+    let plus_as_space = options.quoting == Quoting::Percent;
+
+    if !(encoded.contains('%') || plus_as_space && encoded.contains('+')) {
+        return Ok((remainder, Cow::Borrowed(encoded)));
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while let Some(&b) = encoded.as_bytes().get(i) {
+        match b {
+            b'+' if plus_as_space => bytes.push(b' '),
+            b'%' => {
+                let byte = encoded
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| error(&s[i..]))?;
+                bytes.push(byte);
+                i += 2;
+            }
+            b => bytes.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8(bytes)
+        .map(|decoded| (remainder, Cow::Owned(decoded)))
+        .map_err(|_| error(s))
+}
+
+/// Nom parser for unquoted strings of the InfluxDB line protocol, where `\,`, `\=`, `\ ` and `\\`
+/// stand for the escaped character and other backslashes are kept verbatim.
+///
+/// The string ends with the next unescaped separator, equals sign or space and may not be empty.
+fn influx_escaped<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, Cow<'a, str>> {
+    // Unescaped string, only allocated once an escape is met.
+    let mut owned: Option<String> = None;
+    let mut chars = s.char_indices().peekable();
+    let mut end = s.len();
+
+    while let Some((i, c)) = chars.next() {
+        if options.pair_separators.contains(&c) || matches!(c, '=' | ' ') {
+            end = i;
+            break;
+        }
+
+        match chars.peek() {
+            Some(&(_, escaped @ (',' | '=' | ' ' | '\\'))) if c == '\\' => {
+                owned.get_or_insert_with(|| s[..i].to_owned()).push(escaped);
+                chars.next();
+            }
+            _ => {
+                if let Some(owned) = &mut owned {
+                    owned.push(c);
+                }
+            }
+        }
+    }
+
+    if end == 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            s,
+            nom::error::ErrorKind::Escaped,
+        )));
+    }
+
+    let value = match owned {
+        Some(owned) => Cow::Owned(owned),
+        None => Cow::Borrowed(&s[..end]),
+    };
+
+    Ok((&s[end..], value))
+}
+
+/// Nom parser for attribute values of distinguished names as defined by RFC 4514, where a
+/// backslash followed by a character stands for that character and one followed by two
+/// hexadecimal digits for a byte of the UTF-8 encoding of the string.
+///
+/// The string ends with the next unescaped separator or `+` and may be empty.
+fn dn_escaped<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, Cow<'a, str>> {
+    let error = |input| {
+        nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Escaped,
+        ))
+    };
+    let end = |s: &str| {
+        s.find(|c: char| options.pair_separators.contains(&c) || c == '+' || c == '\\')
+            .unwrap_or(s.len())
+    };
+
+    let mut pos = end(s);
+    if !s[pos..].starts_with('\\') {
+        return Ok((&s[pos..], Cow::Borrowed(&s[..pos])));
+    }
+
+    let mut bytes = s.as_bytes()[..pos].to_vec();
+    while s[pos..].starts_with('\\') {
+        let hex = s
+            .get(pos + 1..pos + 3)
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (hex, s[pos + 1..].chars().next()) {
+            (Some(byte), _) => {
+                bytes.push(byte);
+                pos += 3;
+            }
+            (None, Some(c)) => {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                pos += 1 + c.len_utf8();
+            }
+            (None, None) => return Err(error(&s[pos..])),
+        }
+
+        let len = end(&s[pos..]);
+        bytes.extend_from_slice(&s.as_bytes()[pos..pos + len]);
+        pos += len;
+    }
+
+    String::from_utf8(bytes)
+        .map(|decoded| (&s[pos..], Cow::Owned(decoded)))
+        .map_err(|_| error(s))
+}
+
+/// Nom parser for shell words made of single-quoted strings, backslash-escaped characters and
+/// unquoted characters, e.g. `'it'\''s'`.
+///
+/// The word ends with the next unquoted separator, space or tab, and may not be empty.
+fn shell_word<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, Cow<'a, str>> {
+    let error = |input| {
+        nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Escaped,
+        ))
+    };
+
+    // Words without quotes nor escapes are borrowed from the input.
+    let end = find_any(s, &[&options.pair_separators, &[' ', '\t', '\'', '\\']]);
+    if end > 0 && !s[end..].starts_with(['\'', '\\']) {
+        return Ok((&s[end..], Cow::Borrowed(&s[..end])));
+    }
+
+    let mut word = String::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        if options.pair_separators.contains(&c) || matches!(c, ' ' | '\t') {
+            break;
+        }
+        rest = &rest[c.len_utf8()..];
+
+        match c {
+            '\'' => {
+                let end = rest.find('\'').ok_or_else(|| error(rest))?;
+                word.push_str(&rest[..end]);
+                rest = &rest[end + 1..];
+            }
+            '\\' => {
+                let escaped = rest.chars().next().ok_or_else(|| error(rest))?;
+                word.push(escaped);
+                rest = &rest[escaped.len_utf8()..];
+            }
+            c => word.push(c),
+        }
+    }
+
+    if rest.len() == s.len() {
+        return Err(error(s));
+    }
+
+    Ok((rest, Cow::Owned(word)))
+}
+
+/// Nom parser for the values of mail headers, which end with the next separator that is not a
+/// line break followed by a space or tab. Such folding line breaks are removed, and so are
+/// trailing spaces and tabs.
+///
+/// The string may be empty.
+fn folded<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, Cow<'a, str>> {
+    let is_space = |c| c == ' ' || c == '\t';
+    let mut value = Cow::Borrowed("");
+    let mut rest = s;
+
+    loop {
+        let end = rest
+            .find(|c: char| options.pair_separators.contains(&c))
+            .unwrap_or(rest.len());
+        let line = rest[..end].trim_end_matches(is_space);
+        let next = rest[end..]
+            .strip_prefix("\r\n")
+            .or_else(|| rest[end..].strip_prefix(['\n', '\r']));
+
+        match next.filter(|next| next.starts_with(is_space)) {
+            Some(next) => {
+                value.to_mut().push_str(&rest[..end]);
+                rest = next;
+            }
+            None if value.is_empty() => return Ok((&rest[end..], Cow::Borrowed(line))),
+            None => {
+                value.to_mut().push_str(line);
+                return Ok((&rest[end..], value));
+            }
+        }
+    }
+}
+
+/// Nom parser for unquoted strings of QEMU option strings, where a doubled pair separator stands
+/// for the separator itself, e.g. `a,,b` for `a,b`. The string ends with the next single separator
+/// or bracket and may be empty. Strings without doubled separators are borrowed from the input.
+fn doubled_separators<'a>(
+    s: &'a str,
+    options: &DeserializeOptions,
+) -> IResult<&'a str, Cow<'a, str>> {
+    let stops: [&[char]; 2] = [&options.pair_separators, &['[', ']']];
+    let mut value = String::new();
+    let mut rest = s;
+
+    loop {
+        let end = find_any(rest, &stops);
+        let doubled = rest[end..].chars().next().filter(|&c| {
+            options.pair_separators.contains(&c) && rest[end + c.len_utf8()..].starts_with(c)
+        });
+
+        match doubled {
+            Some(c) => {
+                value.push_str(&rest[..end]);
+                value.push(c);
+                rest = &rest[end + 2 * c.len_utf8()..];
+            }
+            None if rest.len() == s.len() => {
+                return Ok((&rest[end..], Cow::Borrowed(&rest[..end])))
+            }
+            None => {
+                value.push_str(&rest[..end]);
+                return Ok((&rest[end..], Cow::Owned(value)));
+            }
+        }
+    }
+}
+
+/// Nom parser for strings enclosed in braces as found in ODBC connection strings, where `}}` stands
+/// for `}`.
+fn braced(s: &str) -> IResult<&str, Cow<'_, str>> {
+    let error = || nom::Err::Error(nom::error::Error::new(s, nom::error::ErrorKind::Char));
+    let mut rest = s.strip_prefix('{').ok_or_else(error)?;
+    let mut value = String::new();
+
+    loop {
+        let end = rest.find('}').ok_or_else(error)?;
+        value.push_str(&rest[..end]);
+        match rest[end + 1..].strip_prefix('}') {
+            Some(remainder) => {
+                value.push('}');
+                rest = remainder;
+            }
+            None => return Ok((&rest[end + 1..], Cow::Owned(value))),
+        }
+    }
+}
+
+/// Returns the length of the JSON object or array starting `s`, up to its closing brace or bracket,
+/// or `None` if it is not closed.
+#[cfg(feature = "json")]
+fn json_len(s: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if quoted => (),
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Nom parser for double-quoted strings following the rules of shell scripts and systemd
+/// environment files.
+///
+/// `\"`, `\\`, `` \` `` and `\$` stand for the escaped character, a backslash at the end of a line
+/// continues the string on the next line, and other backslashes are kept verbatim. The string may
+/// contain any other character, including newlines.
+fn shell_double_quoted(s: &str) -> IResult<&str, Cow<'_, str>> {
+    let error = |input| {
+        nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Escaped,
+        ))
+    };
+    let content = s.strip_prefix('"').ok_or_else(|| error(s))?;
+    // Unescaped string, only allocated once an escape is met.
+    let mut owned: Option<String> = None;
+    let mut chars = content.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let value = owned.map_or(Cow::Borrowed(&content[..i]), Cow::Owned);
+                return Ok((&content[i + 1..], value));
+            }
+            '\\' => {
+                let owned = owned.get_or_insert_with(|| content[..i].to_owned());
+                match chars.next() {
+                    Some((_, c @ ('"' | '\\' | '`' | '$'))) => owned.push(c),
+                    Some((_, '\n')) => (),
+                    Some((j, '\r')) => {
+                        if content[j + 1..].starts_with('\n') {
+                            chars.next();
+                        }
+                    }
+                    Some((_, c)) => {
+                        owned.push('\\');
+                        owned.push(c);
+                    }
+                    None => break,
+                }
+            }
+            c => {
+                if let Some(owned) = &mut owned {
+                    owned.push(c);
+                }
+            }
+        }
+    }
+
+    Err(error(s))
+}
+
+/// Nom parser for the double-quoted parameter values of the structured data of syslog messages.
+///
+/// `\"`, `\\` and `\]` stand for the escaped character, and other backslashes are kept verbatim as
+/// required by RFC 5424. The string may contain any other character, including newlines.
+fn sd_param_value(s: &str) -> IResult<&str, Cow<'_, str>> {
+    let error = |input| {
+        nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Escaped,
+        ))
+    };
+    let content = s.strip_prefix('"').ok_or_else(|| error(s))?;
+    // Unescaped string, only allocated once an escape is met.
+    let mut owned: Option<String> = None;
+    let mut chars = content.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let value = owned.map_or(Cow::Borrowed(&content[..i]), Cow::Owned);
+                return Ok((&content[i + 1..], value));
+            }
+            '\\' => {
+                let owned = owned.get_or_insert_with(|| content[..i].to_owned());
+                match chars.next() {
+                    Some((_, c @ ('"' | '\\' | ']'))) => owned.push(c),
+                    Some((_, c)) => {
+                        owned.push('\\');
+                        owned.push(c);
+                    }
+                    None => break,
+                }
+            }
+            c => {
+                if let Some(owned) = &mut owned {
+                    owned.push(c);
+                }
+            }
+        }
+    }
+
+    Err(error(s))
 }
 
 /// Nom parser for valid float point numbers.
-fn any_float<T>(s: &str) -> IResult<&str, T>
+fn any_float<'a, T>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, T>
 where
-    T: Float<FromStrRadixErr = ParseFloatError>,
+    T: Num<FromStrRadixErr = ParseFloatError> + Neg<Output = T>,
 {
     // Parses the number input and returns a tuple containing the number itself (with its sign).
     //
     // We move this non-generic part into its own function so it doesn't get monomorphized, which
     // would increase the binary size more than needed.
-    fn parse_float(s: &str) -> IResult<&str, (bool, &str)> {
+    fn parse_float<'a>(
+        s: &'a str,
+        options: &DeserializeOptions,
+    ) -> IResult<&'a str, (bool, &'a str)> {
         // Recognizes the sign prefix.
         let sign = char('-');
 
@@ -252,7 +1240,7 @@ where
         }
 
         // Recognizes the trailing separator but do not consume it.
-        let separator = peek(any_separator);
+        let separator = peek(|s| any_separator(s, options));
 
         // Chain of parsers: sign (optional), then sequence of numerical characters or dots.
         //
@@ -264,31 +1252,50 @@ where
         )(s)
     }
 
-    map_res(parse_float, |(neg, number)| {
-        let number = T::from_str_radix(&number, 10);
-        number.map(|number| if neg { -number } else { number })
-    })(s)
+    map_res(
+        |s| parse_float(s, options),
+        |(neg, number)| {
+            let number = T::from_str_radix(number, 10);
+            number.map(|number| if neg { -number } else { number })
+        },
+    )(s)
 }
 
 /// Nom parser for booleans.
-fn any_bool(s: &str) -> IResult<&str, bool> {
-    let mut boolean = alt((
-        value(true, alt((tag("true"), tag("True")))),
-        value(false, alt((tag("false"), tag("False")))),
-    ));
-
-    boolean(s)
+///
+/// The longest of the words accepted for `true` and `false` by `options` that starts the input
+/// is recognized.
+fn any_bool<'a>(s: &'a str, options: &DeserializeOptions) -> IResult<&'a str, bool> {
+    let true_words = options.true_values.iter().map(|w| (true, w));
+    let false_words = options.false_values.iter().map(|w| (false, w));
+
+    true_words
+        .chain(false_words)
+        .filter(|(_, w)| !w.is_empty() && s.starts_with(w.as_str()))
+        .max_by_key(|(_, w)| w.len())
+        .map(|(val, w)| (&s[w.len()..], val))
+        .ok_or_else(|| nom::Err::Error(nom::error::Error::new(s, nom::error::ErrorKind::Tag)))
 }
 
 /// Nom parser for identifiers. An identifier may contain any alphanumeric character, as well as
-/// '_' and '-' at any place excepted the first one which cannot be '-'.
+/// '_' and '-' at any place excepted the first one which cannot be '-'. The additional key
+/// characters and the nested key separator of `options` are accepted after the first character.
 ///
 /// Usually identifiers are not allowed to start with a number, but we chose to allow this
 /// here otherwise options like "mode=2d" won't parse if "2d" is an alias for an enum variant.
-fn any_identifier(s: &str) -> IResult<&str, &str> {
+pub(crate) fn any_identifier<'a>(
+    s: &'a str,
+    options: &DeserializeOptions,
+) -> IResult<&'a str, &'a str> {
     let mut ident = recognize(pair(
         verify(anychar, |&c| c.is_alphanum() || c == '_'),
-        take_while(|c: char| c.is_alphanum() || c == '_' || c == '-'),
+        take_while(|c: char| {
+            c.is_alphanum()
+                || c == '_'
+                || c == '-'
+                || options.key_chars.contains(&c)
+                || options.nested_key_separator == Some(c)
+        }),
     ));
 
     ident(s)
@@ -296,10 +1303,14 @@ fn any_identifier(s: &str) -> IResult<&str, &str> {
 
 /// Serde deserializer for key-values strings.
 pub struct KeyValueDeserializer<'de> {
-    /// Full input originally received for parsing.
+    /// Full input originally received for parsing, without the trailing comment of the current
+    /// pair while they are cut.
     original_input: &'de str,
     /// Input currently remaining to parse.
     input: &'de str,
+    /// Set while the trailing comment or spaces of the current pair are cut from the input, to the
+    /// full input originally received and the input following them.
+    trailing_trivia: Option<(&'de str, &'de str)>,
     /// If set, then `deserialize_identifier` will take and return its content the next time it is
     /// called instead of trying to parse an identifier from the input. This is needed to allow the
     /// name of the first field of a struct to be omitted, e.g.
@@ -316,21 +1327,108 @@ pub struct KeyValueDeserializer<'de> {
     /// Whether the top structure has been parsed yet or not. The top structure is the only one
     /// that does not require to be enclosed within braces.
     top_struct_parsed: bool,
+    /// Whether a key is being deserialized, in which case `deserialize_any` and `deserialize_str`
+    /// parse an identifier.
+    parsing_key: bool,
+    /// Keys of the nested structures being deserialized from pairs with dotted keys, e.g.
+    /// `["cache"]` while deserializing the fields of `cache.direct=on,cache.no-flush=off`.
+    nested_keys: Vec<&'de str>,
+    /// Set when the key just deserialized is only the first component of a dotted key, in which
+    /// case the value is a nested structure made of the pairs sharing this key as prefix.
+    dotted_key: Option<&'de str>,
+    /// Key of the pair whose value is being deserialized, as written in the input. Empty if the
+    /// key has been omitted.
+    current_key: &'de str,
+    /// Name of the INI section whose pairs are being deserialized, if any.
+    section: Option<&'de str>,
+    /// Set when the key just deserialized is the name of a section, whose pairs make its value.
+    opening_section: bool,
+    /// Length of the input remaining after the opening bracket of the sequence being
+    /// deserialized, if any.
+    list_start: Option<usize>,
+    /// Set when the opening bracket of the sequence being deserialized is the one of its first
+    /// element, a struct, e.g. in `disks=[path=/a],[path=/b]`. Its elements are then enclosed in
+    /// brackets and separated by commas, and the sequence has no closing bracket of its own.
+    bare_list: bool,
+    /// Syntax accepted by the deserializer.
+    options: DeserializeOptions,
 }
 
 impl<'de> From<&'de str> for KeyValueDeserializer<'de> {
     fn from(input: &'de str) -> Self {
+        Self::with_options(input, DeserializeOptions::default())
+    }
+}
+
+impl<'de> KeyValueDeserializer<'de> {
+    /// Creates a deserializer for `input` accepting the syntax described by `options`. A UTF-8 byte
+    /// order mark starting `input`, as written by some Windows programs, is skipped.
+    pub fn with_options(input: &'de str, options: impl Into<DeserializeOptions>) -> Self {
         Self {
             original_input: input,
-            input,
+            input: input.strip_prefix(BYTE_ORDER_MARK).unwrap_or(input),
+            trailing_trivia: None,
             next_identifier: None,
             has_equal: false,
             top_struct_parsed: false,
+            parsing_key: false,
+            nested_keys: Vec::new(),
+            dotted_key: None,
+            current_key: "",
+            section: None,
+            opening_section: false,
+            list_start: None,
+            bare_list: false,
+            options: options.into().with_implied_separators(),
         }
     }
-}
 
-impl<'de> KeyValueDeserializer<'de> {
+    /// Creates a deserializer for the value of a pair starting at position `pos` of `input`.
+    /// `has_equal` tells whether the key was followed by a key-value delimiter.
+    pub(crate) fn for_value(
+        input: &'de str,
+        pos: usize,
+        has_equal: bool,
+        options: impl Into<DeserializeOptions>,
+    ) -> Self {
+        Self {
+            input: &input[pos..],
+            has_equal,
+            top_struct_parsed: true,
+            ..Self::with_options(input, options)
+        }
+    }
+
+    /// Deserializes the value of a pair, which must be followed by a separator.
+    pub(crate) fn deserialize_value<T: Deserialize<'de>>(&mut self) -> Result<T> {
+        let value = T::deserialize(&mut *self)?;
+
+        if is_separator(self.peek_char(), &self.options) {
+            Ok(value)
+        } else {
+            Err(self.error_here(ErrorKind::TrailingCharacters))
+        }
+    }
+
+    /// Deserializes the JSON value starting the remaining input with `deserialize`, which is given
+    /// a `serde_json` deserializer reading it.
+    #[cfg(feature = "json")]
+    fn deserialize_embedded_json<T>(
+        &mut self,
+        deserialize: impl FnOnce(
+            &mut serde_json::Deserializer<serde_json::de::StrRead<'de>>,
+        ) -> serde_json::Result<T>,
+    ) -> Result<T> {
+        let len = json_len(self.input).unwrap_or(self.input.len());
+        let mut de = serde_json::Deserializer::from_str(&self.input[..len]);
+        let value = deserialize(&mut de)
+            .and_then(|value| de.end().map(|()| value))
+            .map_err(|e| self.error_here(ErrorKind::SerdeError(e.to_string())))?;
+        self.input = &self.input[len..];
+
+        Ok(value)
+    }
+
     /// Return an `kind` error for the current position of the input.
     pub fn error_here(&self, kind: ErrorKind) -> ParseError {
         ParseError {
@@ -361,20 +1459,304 @@ impl<'de> KeyValueDeserializer<'de> {
     /// Confirm that we have a separator (i.e. ',' or ']') character or have reached the end of the
     /// input string.
     fn confirm_separator(&mut self) -> Result<()> {
-        // We must have a comma or end of input after a value.
-        match self.peek_char() {
-            Some(',') | Some(' ') => {
-                let _ = self.next_char();
+        self.restore_trailing_trivia();
+        // We must have a separator or end of input after a value.
+        match strip_separator(self.input, &self.options) {
+            Some(remainder) => {
+                self.input = remainder;
                 Ok(())
             }
-            Some(']') | None => Ok(()),
-            Some(_) => Err(self.error_here(ErrorKind::ExpectedComma)),
+            None => Err(self.error_here(ErrorKind::ExpectedComma)),
+        }
+    }
+
+    /// Parses an INI section header, e.g. `[server]`, and the separator following it, and returns
+    /// the name of the section.
+    fn parse_section_header(&mut self) -> Result<&'de str> {
+        self.skip_char();
+        let len = self
+            .input
+            .find(|c| c == ']' || self.options.pair_separators.contains(&c))
+            .unwrap_or(self.input.len());
+        let name = self.input[..len].trim_matches([' ', '\t']);
+        if name.is_empty() {
+            return Err(self.error_here(ErrorKind::ExpectedIdentifier));
+        }
+
+        self.input = &self.input[len..];
+        if self.next_char() != Some(']') {
+            return Err(self.error_here(ErrorKind::ExpectedCloseBracket));
+        }
+        self.input = self.input.trim_start_matches([' ', '\t']);
+        match self.peek_char() {
+            None => (),
+            Some(_) => match separator_len(self.input, &self.options) {
+                0 => return Err(self.error_here(ErrorKind::TrailingCharacters)),
+                len => self.input = &self.input[len..],
+            },
+        }
+
+        Ok(name)
+    }
+
+    /// Runs the validators of the options on the value of the pair whose key is `key`, which
+    /// starts at the start of `value_input` and ends at the current position.
+    fn validate_value(
+        &self,
+        key: &str,
+        value_input: &'de str,
+        resolved: Option<&str>,
+    ) -> Result<()> {
+        if self.options.validators.is_empty() {
+            return Ok(());
+        }
+
+        let raw = &value_input[..value_input.len() - self.input.len()];
+        // Strings are validated unquoted, other values as written, and resolved values as returned
+        // by their resolver.
+        let value = match (resolved, any_string(raw, &self.options).finish()) {
+            (Some(resolved), _) => Cow::Borrowed(resolved),
+            (None, Ok(("", value))) => value,
+            _ => Cow::Borrowed(raw),
+        };
+
+        for validator in &self.options.validators {
+            if let Err(message) = validator(key, &value) {
+                return Err(ParseError {
+                    kind: ErrorKind::InvalidValue {
+                        key: key.to_owned(),
+                        message,
+                    },
+                    pos: self.original_input.len() - value_input.len(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value returned by the resolver registered for the scheme of the value starting
+    /// the input, if any, in which case the input is moved past the value. Values are matched
+    /// unquoted, and only if they are followed by a separator.
+    fn resolve_value(&mut self, key: &str) -> Result<Option<String>> {
+        if self.options.resolvers.is_empty() {
+            return Ok(None);
+        }
+
+        let (remainder, value) = match any_string(self.input, &self.options).finish() {
+            Ok((remainder, value)) if is_separator(remainder.chars().next(), &self.options) => {
+                (remainder, value)
+            }
+            _ => return Ok(None),
+        };
+        let (reference, resolver) = match self
+            .options
+            .resolvers
+            .iter()
+            .find_map(|(scheme, resolver)| Some((value.strip_prefix(scheme.as_str())?, resolver)))
+        {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let resolved = resolver(reference).map_err(|message| {
+            self.error_here(ErrorKind::InvalidValue {
+                key: key.to_owned(),
+                message,
+            })
+        })?;
+        self.input = remainder;
+
+        Ok(Some(resolved))
+    }
+
+    /// Skips the pairs whose key does not start with the selected key prefix, if any, so the input
+    /// starts with a selected key, the end of the input or the end of a structure.
+    fn skip_unselected_pairs(&mut self) -> Result<()> {
+        loop {
+            let selected = match (&self.options.selected_key_prefix, self.peek_char()) {
+                (None, _) | (_, None | Some(']')) => true,
+                (_, Some('[')) if self.options.sections => true,
+                (Some(prefix), _) => self.input.starts_with(prefix.as_str()),
+            };
+            if selected {
+                return Ok(());
+            }
+
+            self.cut_trailing_trivia();
+            self.skip_pair()?;
+            self.input = skip_trivia(self.input, &self.options);
+        }
+    }
+
+    /// Cuts the trailing comment or spaces of the pair starting at the current position from the
+    /// input, if it has any, so the input ends with the pair. Positions are unchanged, as the
+    /// original input is cut as well.
+    fn cut_trailing_trivia(&mut self) {
+        if self.trailing_trivia.is_some() {
+            return;
+        }
+
+        if let Some((start, end)) = find_trailing_trivia(self.input, &self.options) {
+            let len = self.input.len() - start;
+            self.trailing_trivia = Some((self.original_input, &self.input[end..]));
+            self.original_input = &self.original_input[..self.original_input.len() - len];
+            self.input = &self.input[..start];
+        }
+    }
+
+    /// Returns whether the input starts with an opening bracket followed by a key and a key-value
+    /// delimiter.
+    fn starts_bracketed_pairs(&self) -> bool {
+        let input = skip_trivia(&self.input[1..], &self.options);
+        match any_identifier(input, &self.options) {
+            Ok((remainder, _)) => strip_delimiter(remainder, &self.options).is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns whether the input starts with a pair separator followed by a key and a key-value
+    /// delimiter.
+    fn separator_precedes_pair(&self) -> bool {
+        let separator = match self.peek_char() {
+            Some(c) if self.options.pair_separators.contains(&c) => c,
+            _ => return false,
+        };
+        let input = skip_trivia(&self.input[separator.len_utf8()..], &self.options);
+        match any_identifier(input, &self.options) {
+            Ok((remainder, _)) => strip_delimiter(remainder, &self.options).is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns whether the value of the current pair is empty, e.g. `key=`.
+    fn at_empty_value(&self) -> bool {
+        self.has_equal
+            && is_separator(self.peek_char(), &self.options)
+            && !starts_with_doubled_separator(self.input, &self.options)
+    }
+
+    /// Restores the input following the trailing comment or spaces cut from the input once the
+    /// pair has been parsed.
+    fn restore_trailing_trivia(&mut self) {
+        if self.input.is_empty() {
+            if let Some((original_input, input)) = self.trailing_trivia.take() {
+                self.original_input = original_input;
+                self.input = input;
+            }
+        }
+    }
+
+    /// Skips a pair and the separator following it. The key does not need to be an identifier.
+    fn skip_pair(&mut self) -> Result<()> {
+        let end = find_any(
+            self.input,
+            &[
+                &self.options.key_value_delimiters,
+                &self.options.pair_separators,
+                &[']'],
+            ],
+        );
+        self.input = &self.input[end..];
+
+        if let Some(remainder) = strip_delimiter(self.input, &self.options) {
+            self.input = remainder;
+            self.has_equal = true;
+            de::IgnoredAny::deserialize(&mut *self)?;
+        }
+        self.confirm_separator()
+    }
+
+    /// Skips the properties following a value, which start with `separator`, as well as the spaces
+    /// and tabs preceding them or the next pair separator.
+    fn skip_properties(&mut self, separator: char) {
+        let rest = self.input.trim_start_matches([' ', '\t']);
+
+        match rest.chars().next() {
+            Some(c) if c == separator => {
+                let end = rest
+                    .find(|c| self.options.pair_separators.contains(&c))
+                    .unwrap_or(rest.len());
+                self.input = &rest[end..];
+            }
+            c if is_separator(c, &self.options) => self.input = rest,
+            _ => (),
+        }
+    }
+
+    /// Runs `f` with `separator` being accepted as a pair separator in addition to the configured
+    /// ones.
+    fn with_separator<T>(&mut self, separator: char, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.with_separators(&[separator], f)
+    }
+
+    /// Runs `f` with `separators` being accepted as pair separators in addition to the configured
+    /// ones.
+    fn with_separators<T>(&mut self, separators: &[char], f: impl FnOnce(&mut Self) -> T) -> T {
+        let len = self.options.pair_separators.len();
+        for &separator in separators {
+            if !self.options.pair_separators.contains(&separator) {
+                self.options.pair_separators.push(separator);
+            }
+        }
+
+        let ret = f(self);
+        self.options.pair_separators.truncate(len);
+
+        ret
+    }
+
+    /// Strips the components of the next key that are the keys of the nested structures being
+    /// deserialized, and sets `next_identifier` to the following component. If more components
+    /// follow, they are left in the input and `dotted_key` is set.
+    ///
+    /// Returns `false` if the next key does not belong to the innermost nested structure.
+    fn strip_nested_key(&mut self, separator: char) -> bool {
+        // Invalid keys are reported when deserializing them.
+        let (remainder, key) = match any_identifier(self.input, &self.options) {
+            Ok(res) => res,
+            Err(_) => return true,
+        };
+
+        let mut components = key.split(separator);
+        if !self
+            .nested_keys
+            .iter()
+            .all(|&parent| components.next() == Some(parent))
+        {
+            return false;
+        }
+        let component = match components.next() {
+            Some(component) => component,
+            None => return false,
+        };
+
+        if components.next().is_some() {
+            self.dotted_key = Some(component);
+        } else {
+            self.input = remainder;
         }
+        self.next_identifier = Some(component);
+
+        true
+    }
+
+    /// Visits the nested structure made of the pairs whose keys start with `key` followed by the
+    /// nested key separator.
+    fn visit_nested<V>(&mut self, key: &'de str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.nested_keys.push(key);
+        let ret = visitor.visit_map(&mut *self);
+        self.nested_keys.pop();
+
+        ret
     }
 
     /// Attempts to parse an identifier, either for a key or for the value of an enum type.
     pub fn parse_identifier(&mut self) -> Result<&'de str> {
-        let (remainder, res) = any_identifier(self.input)
+        let (remainder, res) = any_identifier(self.input, &self.options)
             .finish()
             .map_err(|_| self.error_here(ErrorKind::ExpectedIdentifier))?;
 
@@ -385,7 +1767,7 @@ impl<'de> KeyValueDeserializer<'de> {
     /// Attempts to parse a string.
     pub fn parse_string(&mut self) -> Result<Cow<'de, str>> {
         let (remainder, res) =
-            any_string(self.input)
+            any_string(self.input, &self.options)
                 .finish()
                 .map_err(|e: nom::error::Error<_>| {
                     self.input = e.input;
@@ -397,24 +1779,59 @@ impl<'de> KeyValueDeserializer<'de> {
 
         // The character following a string will be either a comma, a closing bracket, or EOS. If
         // we have something else, this means an unquoted string should probably have been quoted.
-        if is_separator(self.peek_char()) {
+        if is_separator(self.peek_char(), &self.options) {
             Ok(res)
         } else {
             Err(self.error_here(ErrorKind::InvalidCharInString))
         }
     }
 
+    /// Returns the input of the scalar value at the start of the input. If the options quote all
+    /// values, this is the content of the double quotes around the value, returned along with the
+    /// input following the closing quote.
+    fn scalar_input(&self) -> (&'de str, Option<&'de str>) {
+        if matches!(self.options.quoting, Quoting::Prometheus | Quoting::Syslog) {
+            if let Some((content, remainder)) =
+                self.input.strip_prefix('"').and_then(|s| s.split_once('"'))
+            {
+                return (content, Some(remainder));
+            }
+        }
+
+        (self.input, None)
+    }
+
+    /// Moves past a scalar value parsed from the input returned by `scalar_input`, `remainder`
+    /// being what was left unparsed from it. Quoted values must be fully parsed.
+    fn end_scalar(
+        &mut self,
+        remainder: &'de str,
+        quoted: Option<&'de str>,
+        kind: ErrorKind,
+    ) -> Result<()> {
+        match quoted {
+            None => self.input = remainder,
+            Some(quoted_remainder) if remainder.is_empty() => self.input = quoted_remainder,
+            Some(_) => return Err(self.error_here(kind)),
+        }
+
+        Ok(())
+    }
+
     /// Attempt to parse a boolean.
     pub fn parse_bool(&mut self) -> Result<bool> {
+        let (input, quoted) = self.scalar_input();
         let (remainder, res) =
-            any_bool(self.input)
+            any_bool(input, &self.options)
                 .finish()
                 .map_err(|e: nom::error::Error<_>| {
-                    self.input = e.input;
+                    if quoted.is_none() {
+                        self.input = e.input;
+                    }
                     self.error_here(ErrorKind::ExpectedBoolean)
                 })?;
 
-        self.input = remainder;
+        self.end_scalar(remainder, quoted, ErrorKind::ExpectedBoolean)?;
         Ok(res)
     }
 
@@ -423,27 +1840,59 @@ impl<'de> KeyValueDeserializer<'de> {
     where
         T: Num<FromStrRadixErr = ParseIntError>,
     {
-        let (remainder, val) = any_number(self.input)
+        let (input, quoted) = self.scalar_input();
+        let (remainder, val) = any_number(input, &self.options)
             .finish()
             .map_err(|_| self.error_here(ErrorKind::InvalidNumber))?;
 
-        self.input = remainder;
+        self.end_scalar(remainder, quoted, ErrorKind::InvalidNumber)?;
         Ok(val)
     }
 
     /// Attempt to parse a float point number.
     pub fn parse_float<T>(&mut self) -> Result<T>
     where
-        T: Float<FromStrRadixErr = ParseFloatError>,
+        T: Num<FromStrRadixErr = ParseFloatError> + Neg<Output = T>,
     {
-        let (remainder, val) = any_float(self.input)
+        let (input, quoted) = self.scalar_input();
+        let (remainder, val) = any_float(input, &self.options)
             .finish()
             .map_err(|_| self.error_here(ErrorKind::InvalidNumber))?;
 
-        self.input = remainder;
+        self.end_scalar(remainder, quoted, ErrorKind::InvalidNumber)?;
         Ok(val)
     }
 
+    /// Attempt to parse an integer, handling the values that do not fit `T` and the numbers with a
+    /// fractional part as set by the options.
+    fn parse_integer<T>(&mut self) -> Result<T>
+    where
+        T: Bounded + TryFrom<i128> + Copy + 'static,
+        i128: AsPrimitive<T>,
+    {
+        let value_input = self.input;
+        // Values are parsed as the widest integer, so the ones that do not fit `T` can be told
+        // apart from malformed ones.
+        let wide = match self.parse_number::<i128>() {
+            Ok(number) => number,
+            Err(e) if !self.options.truncate_floats => return Err(e),
+            Err(e) => self.parse_float::<f64>().map_err(|_| e)? as i128,
+        };
+
+        match (T::try_from(wide), self.options.overflow) {
+            (Ok(number), _) => Ok(number),
+            (Err(_), Overflow::Error) => Err(ParseError {
+                kind: ErrorKind::OutOfRange {
+                    key: self.current_key.into(),
+                },
+                pos: self.original_input.len() - value_input.len(),
+            }),
+            (Err(_), Overflow::Saturate) if wide < 0 => Ok(T::min_value()),
+            (Err(_), Overflow::Saturate) => Ok(T::max_value()),
+            (Err(_), Overflow::Wrap) => Ok(wide.as_()),
+        }
+    }
+
     /// Consume this deserializer and return a `TrailingCharacters` error if some input was
     /// remaining.
     ///
@@ -464,32 +1913,72 @@ impl<'de> de::MapAccess<'de> for KeyValueDeserializer<'de> {
     where
         K: de::DeserializeSeed<'de>,
     {
+        self.input = skip_trivia(self.input, &self.options);
+        if self.next_identifier.is_none() {
+            self.skip_unselected_pairs()?;
+        }
+
         // Detect end of input or struct.
         match self.peek_char() {
             None | Some(']') => return Ok(None),
+            // A section header ends the current section, or starts one whose name is the key.
+            Some('[') if self.options.sections => {
+                if self.section.is_some() {
+                    return Ok(None);
+                }
+                let name = self.parse_section_header()?;
+                self.section = Some(name);
+                self.opening_section = true;
+                self.dotted_key = Some(name);
+                self.next_identifier = Some(name);
+            }
             _ => (),
         }
 
+        self.cut_trailing_trivia();
         self.has_equal = false;
 
+        let key_input = self.input;
         let had_implicit_identifier = self.next_identifier.is_some();
-        let val = seed.deserialize(&mut *self).map(Some)?;
+        match &self.options.selected_key_prefix {
+            Some(prefix) if !had_implicit_identifier => self.input = &self.input[prefix.len()..],
+            _ => (),
+        }
+        if let Some(separator) = self.options.nested_key_separator {
+            if !had_implicit_identifier && !self.strip_nested_key(separator) {
+                self.input = key_input;
+                return Ok(None);
+            }
+            // The first pair of the nested structure starting with this key is parsed again,
+            // including its selected key prefix.
+            if self.dotted_key.is_some() {
+                self.input = key_input;
+            }
+        }
+
+        self.parsing_key = true;
+        let val = seed.deserialize(&mut *self).map(Some);
+        self.parsing_key = false;
+        let val = val?;
+        self.current_key = &key_input[..key_input.len() - self.input.len()];
         // We just "deserialized" the content of `next_identifier`, so there should be no equal
-        // character in the input. We can return now.
-        if had_implicit_identifier {
+        // character in the input. We can return now. The same goes for the first component of a
+        // dotted key, which is followed by the rest of the key.
+        if had_implicit_identifier || self.dotted_key.is_some() {
+            self.has_equal = true;
+            return Ok(val);
+        }
+
+        // We expect an equal after an identifier.
+        if let Some(remainder) = strip_delimiter(self.input, &self.options) {
+            self.input = remainder;
             self.has_equal = true;
             return Ok(val);
         }
 
         match self.peek_char() {
-            // We expect an equal after an identifier.
-            Some('=') => {
-                self.skip_char();
-                self.has_equal = true;
-                Ok(val)
-            }
             // Ok if we are parsing a boolean where an empty value means true.
-            c if is_separator(c) => Ok(val),
+            c if is_separator(c, &self.options) => Ok(val),
             _ => Err(self.error_here(ErrorKind::ExpectedEqual)),
         }
     }
@@ -498,9 +1987,54 @@ impl<'de> de::MapAccess<'de> for KeyValueDeserializer<'de> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let val = seed.deserialize(&mut *self)?;
+        // The pairs of a nested structure with dotted keys have consumed their separators.
+        let dotted = self.dotted_key.is_some();
+        let opening_section = core::mem::take(&mut self.opening_section);
+        let key = self.current_key;
+        let value_input = self.input;
+        if self.options.empty_values == EmptyValues::Error && !dotted && self.at_empty_value() {
+            return Err(self.error_here(ErrorKind::EmptyValue));
+        }
+        let resolved = if dotted || opening_section {
+            None
+        } else {
+            self.resolve_value(key)?
+        };
+        let val = match (&resolved, self.options.property_separator) {
+            // Errors met when parsing a resolved value are reported at the start of the value.
+            (Some(value), _) => seed
+                .deserialize(ResolvedValue {
+                    value: value.clone(),
+                    key,
+                    options: &self.options,
+                })
+                .map_err(|e| ParseError {
+                    pos: self.original_input.len() - value_input.len(),
+                    ..e
+                }),
+            // The properties following the value, and the spaces preceding them, terminate it.
+            (None, Some(separator)) if !dotted => {
+                self.with_separators(&[separator, ' ', '\t'], |de| seed.deserialize(de))
+            }
+            (None, _) => seed.deserialize(&mut *self),
+        };
+        self.dotted_key = None;
+        if opening_section {
+            self.section = None;
+        }
+        let val = val?;
+
+        // Values made of the pairs of a nested structure or section are validated pair by pair.
+        if !dotted && !opening_section {
+            self.validate_value(key, value_input, resolved.as_deref())?;
+        }
 
-        self.confirm_separator()?;
+        if !dotted {
+            if let Some(separator) = self.options.property_separator {
+                self.skip_properties(separator);
+            }
+            self.confirm_separator()?;
+        }
 
         Ok(val)
     }
@@ -555,12 +2089,12 @@ impl<'de> de::MapAccess<'de> for EmptyMapAccess {
     where
         V: de::DeserializeSeed<'de>,
     {
-        // Never reached because `next_key_seed` never returns a valid key.
-        unreachable!()
+        // Only reached by visitors asking for a value although `next_key_seed` returned no key.
+        Err(de::Error::custom("no value in an empty map"))
     }
 }
 
-impl<'a, 'de> de::EnumAccess<'de> for &'a mut KeyValueDeserializer<'de> {
+impl<'de> de::EnumAccess<'de> for &mut KeyValueDeserializer<'de> {
     type Error = ParseError;
     type Variant = Self;
 
@@ -573,7 +2107,7 @@ impl<'a, 'de> de::EnumAccess<'de> for &'a mut KeyValueDeserializer<'de> {
     }
 }
 
-impl<'a, 'de> de::VariantAccess<'de> for &'a mut KeyValueDeserializer<'de> {
+impl<'de> de::VariantAccess<'de> for &mut KeyValueDeserializer<'de> {
     type Error = ParseError;
 
     fn unit_variant(self) -> Result<()> {
@@ -584,7 +2118,7 @@ impl<'a, 'de> de::VariantAccess<'de> for &'a mut KeyValueDeserializer<'de> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        unimplemented!()
+        Err(self.error_here(ErrorKind::Unsupported("newtype variant")))
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
@@ -618,125 +2152,416 @@ impl<'a, 'de> de::VariantAccess<'de> for &'a mut KeyValueDeserializer<'de> {
     }
 }
 
-impl<'de> de::SeqAccess<'de> for KeyValueDeserializer<'de> {
+/// `SeqAccess` for a tuple written without brackets, as its elements separated by the tuple
+/// separator, e.g. `1024:2048`.
+struct TupleAccess<'a, 'de> {
+    de: &'a mut KeyValueDeserializer<'de>,
+    /// Number of elements remaining to be parsed.
+    len: usize,
+    separator: char,
+}
+
+impl<'de> de::SeqAccess<'de> for TupleAccess<'_, 'de> {
     type Error = ParseError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: de::DeserializeSeed<'de>,
     {
-        if self.peek_char() == Some(']') {
+        if self.len == 0 {
             return Ok(None);
         }
 
-        let value = seed.deserialize(&mut *self)?;
+        let value = seed.deserialize(&mut *self.de)?;
+        self.len -= 1;
 
-        self.confirm_separator()?;
+        if self.len > 0 {
+            if self.de.peek_char() == Some(self.separator) {
+                self.de.skip_char();
+            } else {
+                return Err(self.de.error_here(ErrorKind::ExpectedTupleSeparator));
+            }
+        }
 
         Ok(Some(value))
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+/// `SeqAccess` for a sequence written without brackets, as its elements separated by the list
+/// separator, e.g. `5,7,9`.
+struct ListAccess<'a, 'de> {
+    de: &'a mut KeyValueDeserializer<'de>,
+    separator: char,
+    /// Set once the last element has been parsed.
+    done: bool,
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
+impl<'de> de::SeqAccess<'de> for ListAccess<'_, 'de> {
     type Error = ParseError;
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
-        V: serde::de::Visitor<'de>,
+        T: de::DeserializeSeed<'de>,
     {
-        match self.peek_char() {
-            // If we have no value following, then we are dealing with a boolean flag.
-            c if is_separator(c) => return self.deserialize_bool(visitor),
-            // Opening bracket means we have a sequence.
-            Some('[') => return self.deserialize_seq(visitor),
-            _ => (),
+        // An empty value is an empty sequence.
+        if self.done || is_separator(self.de.peek_char(), &self.de.options) {
+            return Ok(None);
         }
 
-        // This is ambiguous as technically any argument could be an unquoted string. However we
-        // don't have any type information here, so try to guess it on a best-effort basis...
-        if any_number::<i64>(self.input).is_ok() {
-            self.deserialize_i64(visitor)
-        } else if any_number::<u64>(self.input).is_ok() {
-            self.deserialize_u64(visitor)
-        } else if any_bool(self.input).is_ok() {
-            self.deserialize_bool(visitor)
+        let value = seed.deserialize(&mut *self.de)?;
+
+        // When elements are separated like pairs, the list ends before the next pair, e.g. in
+        // `values=a,b,name=c`.
+        if self.de.peek_char() == Some(self.separator) && !self.de.separator_precedes_pair() {
+            self.de.skip_char();
         } else {
-            self.deserialize_str(visitor)
+            self.done = true;
         }
-    }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        // It is valid to just mention a bool as a flag and not specify its value - in this case
-        // the value is set as `true`.
-        let val = if self.has_equal {
-            self.parse_bool()?
-        } else {
-            true
-        };
-        visitor.visit_bool(val)
+        Ok(Some(value))
     }
+}
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_i8(self.parse_number()?)
-    }
+/// `SeqAccess` for a sequence written as consecutive pairs with the same key, one per element,
+/// e.g. `tag=a&tag=b`.
+struct RepeatedAccess<'a, 'de> {
+    de: &'a mut KeyValueDeserializer<'de>,
+    /// Key of the pairs, as written in the input.
+    key: &'de str,
+    /// Set once the last element has been parsed.
+    done: bool,
+}
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_i16(self.parse_number()?)
-    }
+impl<'de> de::SeqAccess<'de> for RepeatedAccess<'_, 'de> {
+    type Error = ParseError;
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
-        V: serde::de::Visitor<'de>,
+        T: de::DeserializeSeed<'de>,
     {
-        visitor.visit_i32(self.parse_number()?)
-    }
+        if self.done {
+            return Ok(None);
+        }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_i64(self.parse_number()?)
+        let value = seed.deserialize(&mut *self.de)?;
+
+        // Continue with the value of the next pair if it has the same key.
+        self.de.restore_trailing_trivia();
+        let next_value = strip_separator(self.de.input, &self.de.options)
+            .and_then(|s| s.strip_prefix(self.key))
+            .filter(|_| !self.key.is_empty())
+            .and_then(|s| strip_delimiter(s, &self.de.options));
+        match next_value {
+            Some(remainder) => self.de.input = remainder,
+            None => self.done = true,
+        }
+
+        Ok(Some(value))
     }
+}
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+/// Access to the elements of a sequence written as pairs keyed by the key of the sequence, the
+/// nested key separator and their index, e.g. `route.0=a route.1=b`. The key of the sequence is
+/// the last of the nested keys of the deserializer.
+struct IndexedAccess<'a, 'de> {
+    de: &'a mut KeyValueDeserializer<'de>,
+    separator: char,
+    /// Index of the next element.
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for IndexedAccess<'_, 'de> {
+    type Error = ParseError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
-        V: serde::de::Visitor<'de>,
+        T: de::DeserializeSeed<'de>,
     {
-        visitor.visit_u8(self.parse_number()?)
+        let de = &mut *self.de;
+        de.input = skip_trivia(de.input, &de.options);
+        if matches!(de.peek_char(), Some(']') | None) {
+            return Ok(None);
+        }
+
+        // The sequence ends with the first pair that is not keyed by the index of the next
+        // element, which is left for the enclosing structure.
+        de.cut_trailing_trivia();
+        let key_input = de.input;
+        let index = self.index.to_string();
+        if !de.strip_nested_key(self.separator) || de.next_identifier.take() != Some(&index) {
+            de.input = key_input;
+            de.dotted_key = None;
+            return Ok(None);
+        }
+        self.index += 1;
+
+        // The fields of an element that is a structure are nested in its indexed key.
+        let dotted = de.dotted_key.is_some();
+        if !dotted {
+            de.input = strip_delimiter(de.input, &de.options)
+                .ok_or_else(|| de.error_here(ErrorKind::ExpectedEqual))?;
+            de.has_equal = true;
+        }
+        let value = seed.deserialize(&mut *de);
+        de.dotted_key = None;
+        let value = value?;
+
+        if !dotted {
+            de.confirm_separator()?;
+        }
+
+        Ok(Some(value))
     }
+}
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_u16(self.parse_number()?)
+/// Deserializer of the value returned by a resolver for the value of a pair.
+///
+/// Strings are the resolved value verbatim, while numbers and booleans are parsed from it like
+/// the values of the input. Other types cannot be deserialized from a resolved value.
+struct ResolvedValue<'a> {
+    value: String,
+    /// Key of the pair whose value was resolved.
+    key: &'a str,
+    options: &'a DeserializeOptions,
+}
+
+impl ResolvedValue<'_> {
+    /// Parses the whole resolved value as a `T`.
+    fn parse<T: de::DeserializeOwned>(&self) -> Result<T> {
+        let mut deserializer =
+            KeyValueDeserializer::with_options(&self.value, self.options.clone());
+        deserializer.current_key = self.key;
+        deserializer.has_equal = true;
+        let value = T::deserialize(&mut deserializer)?;
+        deserializer.finish()?;
+
+        Ok(value)
     }
+}
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_u32(self.parse_number()?)
+/// Implements deserialization methods by parsing the resolved value and visiting the result.
+macro_rules! parse_resolved {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ResolvedValue<'_> {
+    type Error = ParseError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.value)
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_u64(self.parse_number()?)
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
-    where
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self.value.into_deserializer())
+    }
+
+    parse_resolved! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for KeyValueDeserializer<'de> {
+    type Error = ParseError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        // A sequence without brackets of its own ends with its last bracketed element.
+        if self.bare_list && self.peek_char() != Some('[') {
+            return Ok(None);
+        }
+        self.input = self
+            .input
+            .trim_start_matches(self.options.whitespace.blanks());
+        if self.options.line_continuations {
+            while let Some(rest) = strip_line_continuation(self.input) {
+                self.input = rest;
+            }
+        }
+        // A missing closing bracket is reported by `deserialize_seq`.
+        if matches!(self.peek_char(), Some(']') | None) {
+            return Ok(None);
+        }
+
+        let value = seed.deserialize(&mut *self)?;
+
+        if self.bare_list {
+            if self.input.starts_with(",[") {
+                self.skip_char();
+            }
+        } else {
+            self.confirm_separator()?;
+        }
+
+        Ok(Some(value))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut KeyValueDeserializer<'de> {
+    type Error = ParseError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // Without type information, the top-level element can only be a set of key-value pairs,
+        // and a key an identifier.
+        if !self.top_struct_parsed || self.dotted_key.is_some() {
+            return self.deserialize_map(visitor);
+        }
+        if self.parsing_key {
+            return self.deserialize_identifier(visitor);
+        }
+
+        match self.peek_char() {
+            // If we have no value following, then we are dealing with a boolean flag, unless the
+            // key was followed by a delimiter and empty values have a meaning of their own.
+            c if is_separator(c, &self.options)
+                && !starts_with_doubled_separator(self.input, &self.options) =>
+            {
+                return match self.options.empty_values {
+                    EmptyValues::EmptyString if self.has_equal => visitor.visit_borrowed_str(""),
+                    EmptyValues::None if self.has_equal => visitor.visit_none(),
+                    _ => self.deserialize_bool(visitor),
+                };
+            }
+            // Opening bracket means we have a sequence, or a struct if it starts with a pair, e.g.
+            // the content of an adjacently tagged enum.
+            Some('[') if self.starts_bracketed_pairs() => {
+                return self.deserialize_struct("", &[], visitor)
+            }
+            Some('[') => return self.deserialize_seq(visitor),
+            _ => (),
+        }
+
+        // This is ambiguous as technically any argument could be an unquoted string. However we
+        // don't have any type information here, so try to guess it on a best-effort basis...
+        if any_number::<i64>(self.input, &self.options).is_ok() {
+            self.deserialize_i64(visitor)
+        } else if any_number::<u64>(self.input, &self.options).is_ok() {
+            self.deserialize_u64(visitor)
+        } else if any_bool(self.input, &self.options).is_ok() {
+            self.deserialize_bool(visitor)
+        } else {
+            self.deserialize_str(visitor)
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // It is valid to just mention a bool as a flag and not specify its value - in this case
+        // the value is set as `true`.
+        let val = if self.has_equal {
+            self.parse_bool()?
+        } else {
+            true
+        };
+        visitor.visit_bool(val)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_i8(self.parse_integer()?)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_i16(self.parse_integer()?)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_i32(self.parse_integer()?)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_i64(self.parse_integer()?)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_u8(self.parse_integer()?)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_u16(self.parse_integer()?)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_u32(self.parse_integer()?)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_u64(self.parse_integer()?)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
         V: serde::de::Visitor<'de>,
     {
         visitor.visit_f64(self.parse_float()?)
@@ -763,6 +2588,21 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        // String keys of maps are identifiers, which are not delimited like string values.
+        if self.parsing_key {
+            return self.deserialize_identifier(visitor);
+        }
+
+        if self.options.bare_keys
+            && !self.has_equal
+            && is_separator(self.peek_char(), &self.options)
+        {
+            return visitor.visit_borrowed_str("");
+        }
+        if self.options.empty_values == EmptyValues::EmptyString && self.at_empty_value() {
+            return visitor.visit_borrowed_str("");
+        }
+
         match self.parse_string()? {
             Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
             Cow::Owned(s) => visitor.visit_string(s),
@@ -780,7 +2620,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        unimplemented!()
+        Err(self.error_here(ErrorKind::Unsupported("bytes")))
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
@@ -795,7 +2635,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
         V: serde::de::Visitor<'de>,
     {
         // The fact that an option is specified implies that is exists, hence we always visit
-        // Some() here.
+        // Some() here, unless its value is empty and empty values stand for `None`.
+        if self.options.empty_values == EmptyValues::None && self.at_empty_value() {
+            return visitor.visit_none();
+        }
         visitor.visit_some(self)
     }
 
@@ -813,10 +2656,19 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
+        if name == RAW_VALUE {
+            let pos = self.original_input.len() - self.input.len();
+            let mut scanner = Scanner::at(self.original_input, pos, self.options.clone());
+            let raw = scanner.scan_raw_value()?;
+            self.input = &self.original_input[scanner.pos()..];
+
+            return visitor.visit_borrowed_str(raw);
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
@@ -824,16 +2676,52 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        if let Some(separator) = self.options.nested_key_separator {
+            if let Some(key) = self.dotted_key.filter(|_| self.options.indexed_lists) {
+                self.dotted_key = None;
+                self.nested_keys.push(key);
+                let ret = visitor.visit_seq(IndexedAccess {
+                    de: &mut *self,
+                    separator,
+                    index: 0,
+                });
+                self.nested_keys.pop();
+                return ret;
+            }
+        }
+
         if self.peek_char() == Some('[') {
             self.next_char();
-            let val = visitor.visit_seq(&mut *self)?;
-
-            if self.peek_char() != Some(']') {
+            let list_start = self.list_start.replace(self.input.len());
+            let bare_list = core::mem::replace(&mut self.bare_list, false);
+            // Elements of a sequence are always separated by commas.
+            let val = self.with_separator(',', |de| visitor.visit_seq(de));
+            self.list_start = list_start;
+            let val = val?;
+
+            if core::mem::replace(&mut self.bare_list, bare_list) {
+                Ok(val)
+            } else if self.peek_char() != Some(']') {
                 Err(self.error_here(ErrorKind::ExpectedCloseBracket))
             } else {
                 self.next_char();
                 Ok(val)
             }
+        } else if let Some(separator) = self.options.list_separator {
+            // The list separator terminates the value of each element.
+            self.with_separator(separator, |de| {
+                visitor.visit_seq(ListAccess {
+                    de,
+                    separator,
+                    done: false,
+                })
+            })
+        } else if self.options.repeated_keys {
+            visitor.visit_seq(RepeatedAccess {
+                key: self.current_key,
+                de: self,
+                done: false,
+            })
         } else {
             // The `EmptyMapAccess` failing to parse means that this sequence must take arguments,
             // i.e. that an opening bracket is expected.
@@ -843,23 +2731,31 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
         }
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let separator = match self.options.tuple_separator {
+            Some(separator) if self.peek_char() != Some('[') => separator,
+            _ => return self.deserialize_seq(visitor),
+        };
+
+        // The tuple separator terminates the value of each element.
+        self.with_separator(separator, |de| {
+            visitor.visit_seq(TupleAccess { de, len, separator })
+        })
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
-        _visitor: V,
+        len: usize,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
@@ -871,8 +2767,16 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
         //
         // We need to do this here as well as in `deserialize_struct` because the top-element of
         // flattened structs will be a map, not a struct.
+        #[cfg(feature = "json")]
+        if self.top_struct_parsed && self.options.embedded_json && self.peek_char() == Some('{') {
+            return self.deserialize_embedded_json(|de| de.deserialize_map(visitor));
+        }
         self.top_struct_parsed = true;
 
+        if let Some(key) = self.dotted_key.take() {
+            return self.visit_nested(key, visitor);
+        }
+
         visitor.visit_map(self)
     }
 
@@ -887,11 +2791,25 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
     {
         // The top structure (i.e. the first structure that we will ever parse) does not need to be
         // enclosed in braces, but inner structures do.
-        let top_struct_parsed = std::mem::replace(&mut self.top_struct_parsed, true);
+        if let Some(key) = self.dotted_key.take() {
+            return self.visit_nested(key, visitor);
+        }
+
+        let top_struct_parsed = core::mem::replace(&mut self.top_struct_parsed, true);
+
+        #[cfg(feature = "json")]
+        if top_struct_parsed && self.options.embedded_json && self.peek_char() == Some('{') {
+            return self
+                .deserialize_embedded_json(|de| de.deserialize_struct(_name, fields, visitor));
+        }
 
         if top_struct_parsed {
             if self.peek_char() == Some('[') {
                 self.next_char();
+            } else if self.list_start == Some(self.input.len()) {
+                // The opening bracket of the sequence is the one of this struct, its first
+                // element.
+                self.bare_list = true;
             } else {
                 // The `EmptyMapAccess` failing to parse means that this struct must take
                 // arguments, i.e. that an opening bracket is expected.
@@ -901,28 +2819,46 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
             }
         }
 
+        self.input = skip_trivia(self.input, &self.options);
+
+        // The keys of a structure enclosed in brackets are not filtered by the selected key prefix.
+        let selected_key_prefix = if top_struct_parsed {
+            self.options.selected_key_prefix.take()
+        } else {
+            None
+        };
+
         // The name of the first field of a struct can be omitted (see documentation of
         // `next_identifier` for details).
         //
         // To detect this, peek the next identifier, and check if the character following is '='. If
         // it is not, then we may have a value in first position, unless the value is identical to
         // one of the field's name - in this case, assume this is a boolean using the flag syntax.
-        self.next_identifier = match any_identifier(self.input) {
-            Ok((_, s)) => match self.input.chars().nth(s.chars().count()) {
-                Some('=') => None,
-                _ => {
-                    if fields.contains(&s) {
-                        None
-                    } else {
-                        fields.first().copied()
-                    }
+        // Keys cannot be omitted when only the keys with a selected prefix are considered.
+        self.next_identifier = match any_identifier(self.input, &self.options) {
+            _ if self.options.selected_key_prefix.is_some() => None,
+            Ok((remainder, s)) => {
+                if strip_delimiter(remainder, &self.options).is_some() || fields.contains(&s) {
+                    None
+                } else {
+                    fields.first().copied()
                 }
-            },
+            }
+            // A section header, which cannot be the value of the first field.
+            Err(_) if self.options.sections && self.peek_char() == Some('[') => None,
             // Not an identifier, probably means this is a value for the first field then.
             Err(_) => fields.first().copied(),
         };
 
-        let ret = visitor.visit_map(&mut *self)?;
+        // The keys of a structure enclosed in brackets are not prefixed by the keys of the nested
+        // structures containing it.
+        let nested_keys = core::mem::take(&mut self.nested_keys);
+        let ret = visitor.visit_map(&mut *self);
+        self.nested_keys = nested_keys;
+        if selected_key_prefix.is_some() {
+            self.options.selected_key_prefix = selected_key_prefix;
+        }
+        let ret = ret?;
 
         if top_struct_parsed {
             if self.peek_char() == Some(']') {
@@ -956,7 +2892,26 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut KeyValueDeserializer<'de> {
             .take()
             .map_or_else(|| self.parse_identifier(), Ok)?;
 
-        visitor.visit_borrowed_str(identifier)
+        let dotted = if self.parsing_key && self.options.slash_separated_keys {
+            dotted_key(identifier)
+        } else {
+            None
+        };
+        let key = dotted.as_deref().unwrap_or(identifier);
+
+        let renamed = self
+            .options
+            .key_renames
+            .iter()
+            .find(|(name, _)| self.parsing_key && name == key);
+        match (renamed, self.options.key_case) {
+            (Some((_, name)), _) => visitor.visit_str(name),
+            (None, Some(case)) if self.parsing_key => visitor.visit_string(case.convert(key)),
+            (None, _) => match dotted {
+                Some(key) => visitor.visit_string(key),
+                None => visitor.visit_borrowed_str(identifier),
+            },
+        }
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
@@ -972,19 +2927,106 @@ pub fn from_key_values<'a, T>(input: &'a str) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = KeyValueDeserializer::from(input);
+    from_key_values_with(input, DeserializeOptions::default())
+}
+
+/// Attempts to deserialize `T` from the key-values string `input`, using the syntax described by
+/// `options`.
+///
+/// A [`Preset`](crate::Preset) can be passed directly to parse a known dialect:
+///
+/// ```
+/// # use serde_keyvalue::{from_key_values_with, Preset};
+/// # use serde::Deserialize;
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Request {
+///     user: String,
+///     page: u32,
+/// }
+///
+/// let req: Request = from_key_values_with("user=alice&page=2", Preset::QueryString).unwrap();
+/// assert_eq!(req, Request { user: "alice".into(), page: 2 });
+/// ```
+pub fn from_key_values_with<'a, T>(
+    input: &'a str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = KeyValueDeserializer::with_options(input, options);
     let ret = T::deserialize(&mut deserializer)?;
     deserializer.finish()?;
 
     Ok(ret)
 }
 
+/// Deserializes the value of `key` in the key-values string `input` as a `T`, without
+/// deserializing the other pairs.
+///
+/// Returns `None` if `key` is not present. If `key` appears several times, its last value is used.
+/// The rest of the input must be well-formed.
+///
+/// ```
+/// use serde_keyvalue::get;
+///
+/// let cmdline = "console=ttyS0 root=/dev/vda ro mem=4096 init=/bin/sh";
+///
+/// assert_eq!(get::<u32>(cmdline, "mem").unwrap(), Some(4096));
+/// assert_eq!(get::<bool>(cmdline, "ro").unwrap(), Some(true));
+/// assert_eq!(get::<&str>(cmdline, "quiet").unwrap(), None);
+/// assert!(get::<u32>(cmdline, "root").is_err());
+/// ```
+pub fn get<'a, T>(input: &'a str, key: &str) -> Result<Option<T>>
+where
+    T: Deserialize<'a>,
+{
+    get_with(input, key, DeserializeOptions::default())
+}
+
+/// Deserializes the value of `key` in the key-values string `input` as a `T`, using the syntax
+/// described by `options`. See [`get`] for details.
+pub fn get_with<'a, T>(
+    input: &'a str,
+    key: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<Option<T>>
+where
+    T: Deserialize<'a>,
+{
+    let options = options.into();
+    let mut found = None;
+    for pair in Scanner::new(input, options.clone()) {
+        let pair = pair?;
+        if pair.key == key {
+            found = Some(pair.span);
+        }
+    }
+
+    let span = match found {
+        Some(span) => span,
+        None => return Ok(None),
+    };
+    let (pos, has_equal) = match strip_delimiter(&input[span.start + key.len()..], &options) {
+        Some(value) => (input.len() - value.len(), true),
+        None => (span.end, false),
+    };
+
+    KeyValueDeserializer::for_value(input, pos, has_equal, options)
+        .deserialize_value()
+        .map(Some)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::collections::BTreeSet;
+    use std::net::IpAddr;
+    use std::net::SocketAddr;
     use std::path::PathBuf;
 
     use super::*;
+    use crate::options::KeyCase;
 
     #[derive(Deserialize, PartialEq, Debug)]
     struct SingleStruct<T> {
@@ -994,15 +3036,24 @@ mod tests {
     #[test]
     fn nom_any_separator() {
         let test_str = ",foo";
-        assert_eq!(any_separator(test_str), Ok((&test_str[1..], Some(','))));
+        assert_eq!(
+            any_separator(test_str, &DeserializeOptions::default()),
+            Ok((&test_str[1..], Some(',')))
+        );
         let test_str = "]bar";
-        assert_eq!(any_separator(test_str), Ok((&test_str[1..], Some(']'))));
+        assert_eq!(
+            any_separator(test_str, &DeserializeOptions::default()),
+            Ok((&test_str[1..], Some(']')))
+        );
         let test_str = "";
-        assert_eq!(any_separator(test_str), Ok((test_str, None)));
+        assert_eq!(
+            any_separator(test_str, &DeserializeOptions::default()),
+            Ok((test_str, None))
+        );
 
         let test_str = "something,anything";
         assert_eq!(
-            any_separator(test_str),
+            any_separator(test_str, &DeserializeOptions::default()),
             Err(nom::Err::Error(nom::error::Error::new(
                 test_str,
                 nom::error::ErrorKind::Char
@@ -1023,7 +3074,7 @@ mod tests {
         assert_eq!(
             res,
             ParseError {
-                kind: ErrorKind::InvalidNumber,
+                kind: ErrorKind::OutOfRange { key: "m".into() },
                 pos: 2
             }
         );
@@ -1034,7 +3085,7 @@ mod tests {
         assert_eq!(
             res,
             ParseError {
-                kind: ErrorKind::InvalidNumber,
+                kind: ErrorKind::OutOfRange { key: "m".into() },
                 pos: 2
             }
         );
@@ -1101,34 +3152,323 @@ mod tests {
     }
 
     #[test]
-    fn deserialize_string() {
-        let kv = "m=John";
-        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
-        assert_eq!(res.m, "John".to_string());
+    fn deserialize_indexed_lists() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Disk {
+            path: String,
+            #[serde(default)]
+            ro: bool,
+        }
 
-        // Spaces are valid (but not recommended) in unquoted strings.
-        let kv = "m=John Doe";
-        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
-        assert_eq!(res.m, "John Doe".to_string());
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Net {
+            route: Vec<String>,
+            mtu: u16,
+        }
 
-        // Empty string is not valid if unquoted
-        let kv = "m=";
-        let err = from_key_values::<SingleStruct<String>>(kv).unwrap_err();
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Vm {
+            net: Net,
+            disks: Vec<Disk>,
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        let options = DeserializeOptions::new()
+            .nested_key_separator('.')
+            .indexed_lists(true)
+            .comment_markers(["#"])
+            .trailing_comments(true);
+        let vm: Vm = from_key_values_with(
+            "net.route.0=10.0.0.0/24 net.route.1=192.168.0.0/16 net.mtu=1500 \
+             disks.0.path=/a disks.0.ro=true disks.1.path=/b # boot disk",
+            options.clone(),
+        )
+        .unwrap();
         assert_eq!(
-            err,
+            vm,
+            Vm {
+                net: Net {
+                    route: vec!["10.0.0.0/24".into(), "192.168.0.0/16".into()],
+                    mtu: 1500,
+                },
+                disks: vec![
+                    Disk {
+                        path: "/a".into(),
+                        ro: true,
+                    },
+                    Disk {
+                        path: "/b".into(),
+                        ro: false,
+                    },
+                ],
+                tags: Vec::new(),
+            }
+        );
+
+        // Sequences enclosed in brackets are still accepted.
+        let vm: Vm = from_key_values_with(
+            "tags=[a,b] net.mtu=1 net.route=[x] disks.0.path=/a",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(vm.tags, ["a", "b"]);
+        assert_eq!(vm.net.route, ["x"]);
+
+        // Elements must follow each other in order.
+        assert!(from_key_values_with::<Net>("route.1=a mtu=1", options.clone()).is_err());
+        assert!(from_key_values_with::<Net>("route.0=a mtu=1 route.1=b", options.clone()).is_err());
+        assert_eq!(
+            from_key_values_with::<Net>("route.0=a route.1 mtu=1", options).unwrap_err(),
             ParseError {
-                kind: ErrorKind::ExpectedString,
-                pos: 2
+                kind: ErrorKind::ExpectedEqual,
+                pos: 17,
             }
         );
+    }
 
-        // Quoted strings.
-        let kv = r#"m="John Doe""#;
-        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
-        assert_eq!(res.m, "John Doe".to_string());
-        let kv = r#"m='John Doe'"#;
-        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
-        assert_eq!(res.m, "John Doe".to_string());
+    #[cfg(feature = "json")]
+    #[test]
+    fn deserialize_embedded_json() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Mount {
+            path: String,
+            ro: bool,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Job {
+            name: String,
+            mounts: Vec<Mount>,
+            env: std::collections::BTreeMap<String, String>,
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        let options = DeserializeOptions::new().embedded_json(true);
+        let job: Job = from_key_values_with(
+            r#"name=b,mounts=[{"path":"/a]","ro":true},[path=/b,ro=false]],env={"A":"}\""}"#,
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            job,
+            Job {
+                name: "b".into(),
+                mounts: vec![
+                    Mount {
+                        path: "/a]".into(),
+                        ro: true
+                    },
+                    Mount {
+                        path: "/b".into(),
+                        ro: false
+                    },
+                ],
+                env: [("A".into(), "}\"".into())].into(),
+                tags: Vec::new(),
+            }
+        );
+
+        let err =
+            from_key_values_with::<Job>(r#"name=a,env={"A":1}"#, options.clone()).unwrap_err();
+        assert_eq!(err.pos, 11);
+        assert!(matches!(err.kind, ErrorKind::SerdeError(_)));
+        let err = from_key_values_with::<Job>(r#"name=a,env={"A":"x""#, options).unwrap_err();
+        assert_eq!(err.pos, 11);
+        assert!(from_key_values::<Job>(r#"name=a,env={"A":"x"}"#).is_err());
+    }
+
+    #[test]
+    fn deserialize_number_overflow() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Limits {
+            small: u8,
+            signed: i8,
+            large: u64,
+        }
+
+        let parse = |input, overflow| {
+            let options = DeserializeOptions::new().overflow(overflow);
+            from_key_values_with::<Limits>(input, options)
+        };
+        let input = "small=300,signed=-129,large=-1";
+        assert_eq!(
+            parse(input, Overflow::Saturate).unwrap(),
+            Limits {
+                small: u8::MAX,
+                signed: i8::MIN,
+                large: 0,
+            }
+        );
+        assert_eq!(
+            parse(input, Overflow::Wrap).unwrap(),
+            Limits {
+                small: 44,
+                signed: 127,
+                large: u64::MAX,
+            }
+        );
+        assert_eq!(
+            parse(input, Overflow::Error).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::OutOfRange {
+                    key: "small".into()
+                },
+                pos: 6,
+            }
+        );
+        assert_eq!(
+            parse("small=0x1ff,signed=0,large=0", Overflow::Saturate).unwrap(),
+            Limits {
+                small: u8::MAX,
+                signed: 0,
+                large: 0,
+            }
+        );
+        // Malformed numbers are rejected whatever the policy.
+        assert_eq!(
+            parse("small=1,signed=1x,large=0", Overflow::Saturate).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 15,
+            }
+        );
+
+        let options = DeserializeOptions::new().truncate_floats(true);
+        assert_eq!(
+            from_key_values_with::<Limits>("small=2.9,signed=-1.5,large=7", options.clone())
+                .unwrap(),
+            Limits {
+                small: 2,
+                signed: -1,
+                large: 7,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Limits>("small=255.5,signed=0,large=1e3", options.clone())
+                .unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 27,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Limits>("small=256.5,signed=0,large=0", options.clone())
+                .unwrap_err()
+                .kind,
+            ErrorKind::OutOfRange {
+                key: "small".into()
+            }
+        );
+        let options = options.overflow(Overflow::Saturate);
+        assert_eq!(
+            from_key_values_with::<Limits>("small=256.5,signed=-200.5,large=0", options).unwrap(),
+            Limits {
+                small: u8::MAX,
+                signed: i8::MIN,
+                large: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_cow_strings() {
+        #[derive(Deserialize)]
+        struct Borrowing<'a> {
+            #[serde(borrow)]
+            m: Cow<'a, str>,
+        }
+
+        let is_borrowed = |input, quoting| {
+            let options = DeserializeOptions::new().quoting(quoting);
+            match from_key_values_with::<Borrowing>(input, options).unwrap().m {
+                Cow::Borrowed(s) => (true, s.to_owned()),
+                Cow::Owned(s) => (false, s),
+            }
+        };
+
+        assert_eq!(is_borrowed("m=a", Quoting::WhenNeeded), (true, "a".into()));
+        assert_eq!(
+            is_borrowed(r#"m="a b""#, Quoting::WhenNeeded),
+            (true, "a b".into())
+        );
+        assert_eq!(
+            is_borrowed(r#"m="a\tb""#, Quoting::WhenNeeded),
+            (false, "a\tb".into())
+        );
+        assert_eq!(
+            is_borrowed(r#"m="a b""#, Quoting::Token),
+            (true, "a b".into())
+        );
+        assert_eq!(
+            is_borrowed(r#"m="a\"b""#, Quoting::Token),
+            (false, "a\"b".into())
+        );
+        assert_eq!(
+            is_borrowed(r#"m="a\b""#, Quoting::Doubled),
+            (true, r"a\b".into())
+        );
+        assert_eq!(
+            is_borrowed(r#"m="""a"" b""#, Quoting::Doubled),
+            (false, r#""a" b"#.into())
+        );
+        assert_eq!(is_borrowed("m='a b'", Quoting::Libpq), (true, "a b".into()));
+        assert_eq!(
+            is_borrowed(r"m='a\'b'", Quoting::Libpq),
+            (false, "a'b".into())
+        );
+        assert_eq!(
+            is_borrowed("m=a", Quoting::SingleQuoted),
+            (true, "a".into())
+        );
+        assert_eq!(
+            is_borrowed("m=a'b c'", Quoting::SingleQuoted),
+            (false, "ab c".into())
+        );
+        assert_eq!(
+            is_borrowed(r"m=a\ b", Quoting::Escaped),
+            (false, "a b".into())
+        );
+    }
+
+    #[test]
+    fn deserialize_string() {
+        let kv = "m=John";
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "John".to_string());
+
+        // Spaces are separators, so they end unquoted strings.
+        let kv = "m=John Doe";
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "John".to_string());
+
+        // Empty string is not valid if unquoted
+        let kv = "m=";
+        let err = from_key_values::<SingleStruct<String>>(kv).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: ErrorKind::ExpectedString,
+                pos: 2
+            }
+        );
+
+        // Quoted strings.
+        let kv = r#"m="John Doe""#;
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "John Doe".to_string());
+        let kv = r#"m='John Doe'"#;
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "John Doe".to_string());
+
+        // Double-quoted strings without escapes can be borrowed.
+        let kv = r#"m="John Doe""#;
+        let res = from_key_values::<SingleStruct<&str>>(kv).unwrap();
+        assert_eq!(res.m, "John Doe");
+        let kv = r#"m="John \"Doe\"""#;
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, r#"John "Doe""#);
 
         // Empty quoted strings.
         let kv = r#"m="""#;
@@ -1182,6 +3522,11 @@ mod tests {
         let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
         assert_eq!(res.m, "false".to_string());
 
+        // Braces are ordinary characters, e.g. in braced UUIDs.
+        let kv = "m={67e55044-10b1-426f-9247-bb680e5fe0c8}";
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "{67e55044-10b1-426f-9247-bb680e5fe0c8}".to_string());
+
         // Escaped quote.
         let kv = r#"m="Escaped \" quote""#;
         let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
@@ -1192,6 +3537,15 @@ mod tests {
         let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
         assert_eq!(res.m, r"Escaped slash\".to_string());
 
+        // Escaped control characters.
+        let kv = r#"m="a\nb\r\tc""#;
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "a\nb\r\tc".to_string());
+        let kv = r#"m="a\u0001\u00e9""#;
+        let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
+        assert_eq!(res.m, "a\u{1}\u{e9}".to_string());
+        assert!(from_key_values::<SingleStruct<String>>(r#"m="a\u01""#).is_err());
+
         // Characters within single quotes should not be escaped.
         let kv = r#"m='Escaped \" quote'"#;
         let res = from_key_values::<SingleStruct<String>>(kv).unwrap();
@@ -1201,6 +3555,87 @@ mod tests {
         assert_eq!(res.m, r"Escaped slash\\".to_string());
     }
 
+    #[test]
+    fn deserialize_network_addresses() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Net {
+            ip: IpAddr,
+            sock: SocketAddr,
+            subnet: String,
+        }
+
+        let expected = Net {
+            ip: "fe80::1".parse().unwrap(),
+            sock: "[fe80::1%2]:8080".parse().unwrap(),
+            subnet: "10.0.0.0/24".into(),
+        };
+        let kv = "ip=fe80::1,sock=[fe80::1%2]:8080,subnet=10.0.0.0/24";
+        assert_eq!(from_key_values::<Net>(kv).unwrap(), expected);
+
+        // Colons are part of the values even if they also delimit keys.
+        let kv = "ip:fe80::1,sock:[fe80::1%2]:8080,subnet:10.0.0.0/24";
+        let options = DeserializeOptions::new().key_value_delimiters([':']);
+        assert_eq!(from_key_values_with::<Net>(kv, options).unwrap(), expected);
+
+        // Brackets are only allowed around IPv6 addresses.
+        let kv = "m=[a]:80";
+        let err = from_key_values::<SingleStruct<String>>(kv).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: ErrorKind::ExpectedString,
+                pos: 2
+            }
+        );
+    }
+
+    #[cfg(feature = "ipnet")]
+    #[test]
+    fn ipnet_subnets() {
+        use serde::Serialize;
+
+        use crate::ipnet::IpNet;
+
+        #[derive(Deserialize, Serialize, PartialEq, Debug)]
+        struct Route {
+            subnet: IpNet,
+            via: IpAddr,
+        }
+
+        let route = Route {
+            subnet: "fd00::/64".parse().unwrap(),
+            via: "fd00::1".parse().unwrap(),
+        };
+        let mut serializer = crate::KeyValueSerializer::new();
+        route.serialize(&mut serializer).unwrap();
+        let output = serializer.into_output();
+        assert_eq!(output, "subnet=fd00::/64 via=fd00::1");
+        assert_eq!(from_key_values::<Route>(&output).unwrap(), route);
+
+        let kv = "subnet:10.0.0.0/24,via:10.0.0.1";
+        let options = DeserializeOptions::new().key_value_delimiters([':']);
+        let route = from_key_values_with::<Route>(kv, options).unwrap();
+        assert_eq!(route.subnet, "10.0.0.0/24".parse::<IpNet>().unwrap());
+        assert!(from_key_values::<Route>("subnet=10.0.0.0/33,via=10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn deserialize_uuids() {
+        let expected: uuid::Uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8".parse().unwrap();
+        for kv in [
+            "m=67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "m=67e5504410b1426f9247bb680e5fe0c8",
+            "m={67e55044-10b1-426f-9247-bb680e5fe0c8}",
+            "m=urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8",
+        ] {
+            let res = from_key_values::<SingleStruct<uuid::Uuid>>(kv).unwrap();
+            assert_eq!(res.m, expected, "{}", kv);
+        }
+
+        let res = from_key_values::<SingleStruct<uuid::Uuid>>("m=67e55044-10b1");
+        assert!(res.is_err());
+    }
+
     #[test]
     fn deserialize_unit() {
         from_key_values::<SingleStruct<()>>("m").unwrap();
@@ -1326,6 +3761,86 @@ mod tests {
         assert!(from_key_values::<TestStruct>(kv).is_err());
     }
 
+    #[test]
+    fn deserialize_empty_values() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct TestStruct {
+            name: String,
+            opt: Option<u32>,
+            label: Option<String>,
+            #[serde(default)]
+            flag: bool,
+        }
+
+        let with_empty = |values| DeserializeOptions::new().empty_values(values);
+
+        let kv = "name=,label=,opt=1,flag";
+        let res: TestStruct =
+            from_key_values_with(kv, with_empty(EmptyValues::EmptyString)).unwrap();
+        assert_eq!(
+            res,
+            TestStruct {
+                name: "".into(),
+                opt: Some(1),
+                label: Some("".into()),
+                flag: true,
+            }
+        );
+
+        let kv = "name=\"\",opt=,label=";
+        let res: TestStruct = from_key_values_with(kv, with_empty(EmptyValues::None)).unwrap();
+        assert_eq!(
+            res,
+            TestStruct {
+                name: "".into(),
+                opt: None,
+                label: None,
+                flag: false,
+            }
+        );
+        // Other empty values are still parsed.
+        assert_eq!(
+            from_key_values_with::<TestStruct>("name=", with_empty(EmptyValues::None)),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedString,
+                pos: 5,
+            })
+        );
+
+        // Bare keys are not empty values.
+        let kv = "name=a,opt=,flag";
+        assert_eq!(
+            from_key_values_with::<TestStruct>(kv, with_empty(EmptyValues::Error)),
+            Err(ParseError {
+                kind: ErrorKind::EmptyValue,
+                pos: 11,
+            })
+        );
+        let kv = "name=a,flag,label=";
+        assert_eq!(
+            from_key_values_with::<TestStruct>(kv, with_empty(EmptyValues::Error)),
+            Err(ParseError {
+                kind: ErrorKind::EmptyValue,
+                pos: 18,
+            })
+        );
+
+        // Without type information, empty values are no longer flags.
+        let kv = "a=,b";
+        let res: serde_json::Value =
+            from_key_values_with(kv, with_empty(EmptyValues::None)).unwrap();
+        assert_eq!(res, serde_json::json!({"a": null, "b": true}));
+        let res: serde_json::Value =
+            from_key_values_with(kv, with_empty(EmptyValues::EmptyString)).unwrap();
+        assert_eq!(res, serde_json::json!({"a": "", "b": true}));
+
+        // `None` values written with `NoneStyle::Empty` are read back as `None`.
+        let options = crate::SerializeOptions::new().none_style(crate::NoneStyle::Empty);
+        let res: TestStruct = from_key_values_with("name=a,opt=,label=", &options).unwrap();
+        assert_eq!(res.opt, None);
+        assert_eq!(res.label, None);
+    }
+
     #[test]
     fn deserialize_optional_struct_with_default() {
         #[derive(Deserialize, PartialEq, Debug)]
@@ -1821,6 +4336,77 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserialize_bare_list_of_structs() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Disk {
+            path: String,
+            #[serde(default)]
+            ro: bool,
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Vm {
+            disks: Vec<Disk>,
+            #[serde(default)]
+            mem: u32,
+        }
+
+        let disks = || {
+            vec![
+                Disk {
+                    path: "/a".into(),
+                    ro: true,
+                    tags: vec!["x".into(), "y".into()],
+                },
+                Disk {
+                    path: "/b".into(),
+                    ro: false,
+                    tags: vec![],
+                },
+            ]
+        };
+        for input in [
+            "disks=[path=/a ro=true tags=[x,y]],[path=/b],mem=2",
+            "disks=[path=/a,ro,tags=[x,y]],[path=/b] mem=2",
+            "mem=2,disks=[[path=/a,ro=true,tags=[x,y]],[path=/b]]",
+        ] {
+            assert_eq!(
+                from_key_values::<Vm>(input).unwrap(),
+                Vm {
+                    disks: disks(),
+                    mem: 2,
+                },
+                "{}",
+                input
+            );
+        }
+
+        let res: Vm = from_key_values("disks=[path=/a]").unwrap();
+        assert_eq!(res.disks[0].path, "/a");
+        assert_eq!(
+            from_key_values::<Vm>("disks=[path=/a],[path=/b").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 24,
+            }
+        );
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Cluster {
+            vms: Vec<Vm>,
+        }
+
+        let res: Cluster =
+            from_key_values("vms=[disks=[path=/a],[path=/b],mem=1],[disks=[]]").unwrap();
+        assert_eq!(
+            res.vms.iter().map(|vm| vm.disks.len()).collect::<Vec<_>>(),
+            [2, 0]
+        );
+    }
+
     #[test]
     fn deserialize_set() {
         #[derive(Deserialize, PartialEq, Eq, Debug, PartialOrd, Ord)]
@@ -2007,4 +4593,1537 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn deserialize_nested_keys() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Backend {
+            kind: String,
+            size: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Memory {
+            backend: Backend,
+            #[serde(default)]
+            share: bool,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Machine {
+            name: String,
+            memory: Memory,
+            #[serde(default)]
+            props: BTreeMap<String, u32>,
+        }
+
+        let options = DeserializeOptions::new().nested_key_separator('.');
+        let expected = Machine {
+            name: "q35".into(),
+            memory: Memory {
+                backend: Backend {
+                    kind: "ram".into(),
+                    size: 64,
+                },
+                share: true,
+            },
+            props: BTreeMap::from([("a".into(), 1), ("b".into(), 2)]),
+        };
+
+        let res: Machine = from_key_values_with(
+            "q35,memory.backend.kind=ram,memory.backend.size=64,memory.share,props.a=1,props.b=2",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(res, expected);
+
+        // Nested structures may still be enclosed in brackets.
+        let res: Machine = from_key_values_with(
+            "name=q35,memory.backend=[kind=ram,size=64],memory.share,props.a=1,props.b=2",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(res, expected);
+
+        // The pairs of a nested structure must be consecutive.
+        let res = from_key_values_with::<Machine>(
+            "q35,memory.backend.kind=ram,props.a=1,memory.backend.size=64",
+            options,
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn deserialize_selected_key_prefix() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Cache {
+            mode: String,
+            #[serde(default)]
+            direct: bool,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Disk {
+            path: String,
+            #[serde(default)]
+            ro: bool,
+            cache: Cache,
+        }
+
+        let expected = Disk {
+            path: "/dev/vda".into(),
+            ro: true,
+            cache: Cache {
+                mode: "wb".into(),
+                direct: true,
+            },
+        };
+
+        let options = DeserializeOptions::new().select_key_prefix("disk0.");
+        let res: Disk = from_key_values_with(
+            concat!(
+                r#"net0.mtu=9000,disk0.path=/dev/vda disk1.path="a, b","#,
+                "disk0.cache=[mode=wb,direct],disk1.ro,disk0.ro,boot=[1,2]",
+            ),
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(res, expected);
+
+        let res: BTreeMap<String, String> =
+            from_key_values_with("a=1,disk0.b=2,disk0.c=3", options.clone()).unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([("b".into(), "2".into()), ("c".into(), "3".into())])
+        );
+
+        // Dotted keys of nested structures may be interleaved with unselected pairs.
+        let res: Disk = from_key_values_with(
+            "disk0.cache.mode=wb,net0.mtu=1,disk0.cache.direct,disk0.path=/dev/vda,disk0.ro",
+            options.clone().nested_key_separator('.'),
+        )
+        .unwrap();
+        assert_eq!(res, expected);
+
+        assert_eq!(
+            from_key_values_with::<Disk>("net0.mtu=9000,disk0.ro=x", options.clone()).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedBoolean,
+                pos: 23,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Disk>(r#"net0.name="a"b"#, options).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidCharInString,
+                pos: 13,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_renamed_keys() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "kebab-case")]
+        enum Mode {
+            Old,
+            New,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            mode: Mode,
+            size: u32,
+        }
+
+        let options = DeserializeOptions::new()
+            .rename_keys([("kind", "mode")])
+            .rename_keys([("old", "new"), ("len", "size")]);
+
+        // Values are not renamed.
+        let res: Config = from_key_values_with("kind=old,len=3", options.clone()).unwrap();
+        assert_eq!(
+            res,
+            Config {
+                mode: Mode::Old,
+                size: 3,
+            }
+        );
+
+        let res: BTreeMap<String, u32> =
+            from_key_values_with("old=1,other=2", options.clone()).unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([("new".into(), 1), ("other".into(), 2)])
+        );
+
+        assert!(matches!(
+            from_key_values_with::<Config>("mode=new,kind=old,size=1", options),
+            Err(ParseError {
+                kind: ErrorKind::SerdeError(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn deserialize_key_case() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Pool {
+            max_conn: u32,
+            http_server: String,
+            ipv4_addr: String,
+        }
+
+        let options = DeserializeOptions::new().key_case(KeyCase::Snake);
+        for input in [
+            "max_conn=1,http_server=a,ipv4_addr=b",
+            "max-conn=1,http-server=a,ipv4-addr=b",
+            "maxConn=1,httpServer=a,ipv4Addr=b",
+            "MaxConn=1,HTTPServer=a,Ipv4Addr=b",
+            "MAX_CONN=1,HTTP__SERVER=a,ipv4-Addr=b",
+        ] {
+            let res: Pool = from_key_values_with(input, options.clone()).unwrap();
+            assert_eq!(
+                res,
+                Pool {
+                    max_conn: 1,
+                    http_server: "a".into(),
+                    ipv4_addr: "b".into(),
+                },
+                "{}",
+                input
+            );
+        }
+
+        let keys = ["max_conn", "maxConn", "db.idle-Timeout", "HTTPServer"];
+        let convert = |case: KeyCase| keys.map(|key| case.convert(key));
+        assert_eq!(
+            convert(KeyCase::Snake),
+            ["max_conn", "max_conn", "db.idle_timeout", "http_server"]
+        );
+        assert_eq!(
+            convert(KeyCase::Kebab),
+            ["max-conn", "max-conn", "db.idle-timeout", "http-server"]
+        );
+        assert_eq!(
+            convert(KeyCase::Camel),
+            ["maxConn", "maxConn", "db.idleTimeout", "httpServer"]
+        );
+
+        // Renamed keys are matched as written and not converted.
+        let options = options.rename_keys([("maxConnections", "maxConn")]);
+        let res: BTreeMap<String, u32> =
+            from_key_values_with("maxConnections=1,Idle=2", options).unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([("maxConn".into(), 1), ("idle".into(), 2)])
+        );
+    }
+
+    #[test]
+    fn deserialize_unsupported() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Bytes<'a> {
+            data: &'a [u8],
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        enum Mode {
+            Tap(String),
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Net {
+            mode: Mode,
+        }
+
+        assert_eq!(
+            from_key_values::<Bytes>("data=abc").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::Unsupported("bytes"),
+                pos: 5,
+            }
+        );
+        let err = from_key_values::<Net>("mode=Tap").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: ErrorKind::Unsupported("newtype variant"),
+                pos: 8,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "newtype variant cannot be deserialized from key-values at position 8"
+        );
+    }
+
+    #[test]
+    fn deserialize_validators() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Inner {
+            b: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[serde(default)]
+            ro: bool,
+            sizes: Vec<u32>,
+            inner: Inner,
+        }
+
+        fn no_spaces(key: &str, value: &str) -> std::result::Result<(), String> {
+            if value.contains(' ') {
+                Err(format!("{} contains a space", key))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn record(key: &str, value: &str) -> std::result::Result<(), String> {
+            match (key, value) {
+                ("name", "x,y") | ("ro", "") | ("sizes", "[1,2]") | ("b", "3") => Ok(()),
+                ("inner", "[b=3]") => Ok(()),
+                _ => Err(format!("unexpected pair {}={}", key, value)),
+            }
+        }
+
+        let options = DeserializeOptions::new().validator(record);
+        let res: Config =
+            from_key_values_with(r#"name="x,y",ro,sizes=[1,2],inner=[b=3]"#, options.clone())
+                .unwrap();
+        assert_eq!(res.name, "x,y");
+
+        let options = options.validator(no_spaces);
+        assert_eq!(
+            from_key_values_with::<Config>(r#"ro,name="x,y",sizes=[1,2],inner=[b=4]"#, options),
+            Err(ParseError {
+                kind: ErrorKind::InvalidValue {
+                    key: "b".into(),
+                    message: "unexpected pair b=4".into(),
+                },
+                pos: 35,
+            })
+        );
+
+        let options = DeserializeOptions::new()
+            .validator(no_spaces)
+            .nested_key_separator('.');
+        assert_eq!(
+            from_key_values_with::<Config>(r#"sizes=[],inner.b=1,name="a b""#, options),
+            Err(ParseError {
+                kind: ErrorKind::InvalidValue {
+                    key: "name".into(),
+                    message: "name contains a space".into(),
+                },
+                pos: 24,
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_resolvers() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        enum Mode {
+            Fast,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            path: String,
+            port: Option<u16>,
+            verbose: bool,
+            mode: Mode,
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        fn file(path: &str) -> std::result::Result<String, String> {
+            match path {
+                "/run/secrets/path" => Ok("/a b,[c]".into()),
+                "/run/secrets/port" => Ok("0x1F90".into()),
+                "/run/secrets/bad-port" => Ok("99999".into()),
+                "/run/secrets/verbose" => Ok("true".into()),
+                "/run/secrets/mode" => Ok("Fast".into()),
+                _ => Err("no such file".into()),
+            }
+        }
+
+        fn upper(value: &str) -> std::result::Result<String, String> {
+            Ok(value.to_uppercase())
+        }
+
+        let options = DeserializeOptions::new()
+            .resolver("file:", file)
+            .resolver("upper:", upper);
+        let config: Config = from_key_values_with(
+            "path=\"file:/run/secrets/path\",port=file:/run/secrets/port,\
+             verbose=file:/run/secrets/verbose,mode=file:/run/secrets/mode,tags=[upper:x]",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                path: "/a b,[c]".into(),
+                port: Some(8080),
+                verbose: true,
+                mode: Mode::Fast,
+                // Only the values of pairs are resolved.
+                tags: vec!["upper:x".into()],
+            }
+        );
+
+        let parse = |input| from_key_values_with::<Config>(input, options.clone());
+        assert_eq!(
+            parse("path=upper:a,port=file:/x,verbose,mode=Fast"),
+            Err(ParseError {
+                kind: ErrorKind::InvalidValue {
+                    key: "port".into(),
+                    message: "no such file".into(),
+                },
+                pos: 18,
+            })
+        );
+        assert_eq!(
+            parse("path=a,port=file:/run/secrets/bad-port,verbose,mode=Fast"),
+            Err(ParseError {
+                kind: ErrorKind::OutOfRange { key: "port".into() },
+                pos: 12,
+            })
+        );
+        assert!(parse("path=a,port=1,verbose=upper:x,mode=Fast").is_err());
+        // A resolved value is not resolved again, and schemes are only matched at the start.
+        assert_eq!(
+            parse("path=upper:file:x,verbose,mode=Fast").unwrap().path,
+            "FILE:X"
+        );
+        assert_eq!(
+            parse("path=x-upper:y,verbose,mode=Fast").unwrap().path,
+            "x-upper:y"
+        );
+    }
+
+    #[test]
+    fn deserialize_whitespace() {
+        use crate::Whitespace;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[serde(default)]
+            ro: bool,
+            sizes: Vec<u32>,
+            tags: Vec<String>,
+        }
+
+        let input = "  name=\"a b\"\t  ro ,sizes=[ 1 ,2 ]  # a comment, tags=x\t,  tags=y  ";
+        let options = DeserializeOptions::new()
+            .whitespace(Whitespace::Collapse)
+            .comment_chars(['#'])
+            .repeated_keys(true);
+        let res: Config = from_key_values_with(input, options.clone()).unwrap();
+        assert_eq!(
+            res,
+            Config {
+                name: "a b".into(),
+                ro: true,
+                sizes: vec![1, 2],
+                tags: vec!["x".into(), "y".into()],
+            }
+        );
+
+        // Newlines only separate pairs with `Whitespace::Lines`.
+        let input = "name=a\r\n\n  ro\nsizes=[]\ntags=[]\n";
+        assert_eq!(
+            from_key_values_with::<Config>(input, options.clone()),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedEqual,
+                pos: 13,
+            })
+        );
+        let options = options.whitespace(Whitespace::Lines);
+        let res: Config = from_key_values_with(input, options.clone()).unwrap();
+        assert_eq!(res.name, "a");
+        assert!(res.ro);
+
+        // Empty pairs are still rejected.
+        assert_eq!(
+            from_key_values_with::<Config>("name=a , ,ro", options),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 9,
+            })
+        );
+
+        // The default strict whitespace keeps spaces in values, and rejects the others.
+        let res: Config = from_key_values_with(
+            "sizes=[],tags=[],name=a b",
+            DeserializeOptions::new().pair_separators([',']),
+        )
+        .unwrap();
+        assert_eq!(res.name, "a b");
+        assert_eq!(
+            from_key_values_with::<Config>("name=a  ro", DeserializeOptions::new()),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 7,
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_comments() {
+        use crate::Preset;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Env {
+            #[serde(rename = "GREETING")]
+            greeting: String,
+            #[serde(rename = "QUOTED")]
+            quoted: String,
+            #[serde(rename = "SIZES")]
+            sizes: Vec<String>,
+            #[serde(rename = "COLOR")]
+            color: String,
+            #[serde(rename = "DEBUG")]
+            debug: bool,
+        }
+
+        let input = "# Header\nGREETING=hello world # said twice\nQUOTED=\"a # b\" # c\n\
+                     export SIZES=[1,\"# 2\"]\t#\r\nCOLOR=#fff\nDEBUG # no value";
+        let res: Env = from_key_values_with(input, Preset::Dotenv).unwrap();
+        assert_eq!(
+            res,
+            Env {
+                greeting: "hello world".into(),
+                quoted: "a # b".into(),
+                sizes: vec!["1".into(), "# 2".into()],
+                color: "#fff".into(),
+                debug: true,
+            }
+        );
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Inner {
+            a: u32,
+            b: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            inner: Inner,
+            tags: Vec<String>,
+            name: String,
+        }
+
+        // Comments end with separators other than spaces, and may follow dotted keys and
+        // repeated keys.
+        let options = DeserializeOptions::new()
+            .comment_markers(["//", ""])
+            .trailing_comments(true)
+            .nested_key_separator('.')
+            .repeated_keys(true);
+        let input = "// Header,inner.a=1 // One,inner.b=2 //,tags=x // x,tags=y,name=a // b";
+        let res: Config = from_key_values_with(input, options.clone()).unwrap();
+        assert_eq!(
+            res,
+            Config {
+                inner: Inner { a: 1, b: 2 },
+                tags: vec!["x".into(), "y".into()],
+                name: "a".into(),
+            }
+        );
+        let res: Config =
+            from_key_values_with("inner=[a=1 // c,b=2] // d,tags=[],name=a", options).unwrap();
+        assert_eq!(res.inner, Inner { a: 1, b: 2 });
+
+        // Trailing comments are part of values unless enabled.
+        let options = DeserializeOptions::new()
+            .pair_separators(['\n'])
+            .comment_chars([';']);
+        let res: BTreeMap<String, String> =
+            from_key_values_with("; Header\na=x ; y\nb=z", options.clone()).unwrap();
+        assert_eq!(res["a"], "x ; y");
+        let res: BTreeMap<String, String> =
+            from_key_values_with("a=x ; y\nb=z", options.trailing_comments(true)).unwrap();
+        assert_eq!(res["a"], "x");
+        assert_eq!(res["b"], "z");
+    }
+
+    #[test]
+    fn deserialize_line_continuations() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Inner {
+            a: u32,
+            b: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[serde(default)]
+            ro: bool,
+            sizes: Vec<u32>,
+            inner: Inner,
+        }
+
+        let input = "\\\nname=\"a b\" \\\n\t\\\n  ro sizes=[1,\\\r\n  2] inner=[\\\n a=1,\\\n b=2]";
+        let options = DeserializeOptions::new().line_continuations(true);
+        let res: Config = from_key_values_with(input, options.clone()).unwrap();
+        assert_eq!(
+            res,
+            Config {
+                name: "a b".into(),
+                ro: true,
+                sizes: vec![1, 2],
+                inner: Inner { a: 1, b: 2 },
+            }
+        );
+
+        // Unquoted values may be split across lines, but keys may not.
+        let res: Config = from_key_values_with(
+            "name=a\\\n   b\\\r\n\\\nc,sizes=[1],inner=[a=1,b=2]",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.name, "abc");
+        let res: BTreeMap<String, String> =
+            from_key_values_with("a=x\\\n  y\\z,b=\"q\\\\\n\"", options.clone()).unwrap();
+        assert_eq!(res["a"], "xy\\z");
+        assert_eq!(res["b"], "q\\\n");
+        assert_eq!(
+            from_key_values_with::<Config>("ro\\\nname=a", options),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedEqual,
+                pos: 2,
+            })
+        );
+        assert_eq!(
+            from_key_values_with::<Config>("ro,\\\nname=a", DeserializeOptions::new()),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_trimmed_values() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            quoted: String,
+            sizes: Vec<u32>,
+        }
+
+        let input = "name =  a b\t\nquoted= \" c \"  \nsizes = [1,2] \n";
+        let options = DeserializeOptions::new()
+            .pair_separators(['\n'])
+            .trim_values(true);
+        let res: Config = from_key_values_with(input, options.clone()).unwrap();
+        assert_eq!(
+            res,
+            Config {
+                name: "a b".into(),
+                quoted: " c ".into(),
+                sizes: vec![1, 2],
+            }
+        );
+        let res: BTreeMap<String, u32> =
+            from_key_values_with("a = 1  \r\nb=2 ", options.clone()).unwrap();
+        assert_eq!(res, BTreeMap::from([("a".into(), 1), ("b".into(), 2)]));
+
+        // Spaces inside values are kept.
+        assert_eq!(
+            from_key_values_with::<BTreeMap<String, u32>>("a = 1 2\n", options),
+            Err(ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 4,
+            })
+        );
+
+        // Values are not trimmed by default.
+        let options = DeserializeOptions::new().pair_separators(['\n']);
+        let res: BTreeMap<String, String> = from_key_values_with("a=1 \nb=2", options).unwrap();
+        assert_eq!(res["a"], "1 ");
+    }
+
+    #[test]
+    fn deserialize_crlf_and_bom() {
+        use crate::Preset;
+
+        let options = DeserializeOptions::new()
+            .pair_separators(['\n'])
+            .comment_chars(['#']);
+        let res: BTreeMap<String, u32> =
+            from_key_values_with("\u{feff}# c\r\na=1\r\nb=2\r\n", options.clone()).unwrap();
+        assert_eq!(res, BTreeMap::from([("a".into(), 1), ("b".into(), 2)]));
+        // A lone carriage return separates pairs as well.
+        let res: BTreeMap<String, u32> = from_key_values_with("a=1\rb=2", options.clone()).unwrap();
+        assert_eq!(res.len(), 2);
+        // Positions are relative to the start of the input, including its byte order mark.
+        assert_eq!(
+            from_key_values_with::<BTreeMap<String, u32>>("\u{feff}a=1\r\nb=x\r\n", options),
+            Err(ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 10,
+            })
+        );
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Server {
+            port: u16,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        let res: Config =
+            from_key_values_with("\u{feff}name = a\r\n[server]\r\nport = 80\r\n", Preset::Ini)
+                .unwrap();
+        assert_eq!(
+            res,
+            Config {
+                name: "a".into(),
+                server: Server { port: 80 },
+            }
+        );
+        assert_eq!(
+            crate::pairs_with("\u{feff}a=1\r\nb=2", Preset::KernelCmdline)
+                .map(|pair| pair.unwrap().0)
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn deserialize_presets() {
+        use crate::Preset;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Boot {
+            root: String,
+            #[serde(default)]
+            ro: bool,
+            #[serde(default)]
+            quiet: bool,
+            #[serde(default)]
+            splash: bool,
+        }
+
+        let res: Boot =
+            from_key_values_with("root=/dev/sda1 ro quiet splash=n", Preset::KernelCmdline)
+                .unwrap();
+        assert_eq!(
+            res,
+            Boot {
+                root: "/dev/sda1".into(),
+                ro: true,
+                quiet: true,
+                splash: false,
+            }
+        );
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Env {
+            name: String,
+            count: u32,
+        }
+
+        let res: Env = from_key_values_with("name=my app\ncount=3", Preset::Dotenv).unwrap();
+        assert_eq!(
+            res,
+            Env {
+                name: "my app".into(),
+                count: 3,
+            }
+        );
+
+        // Blank lines, comments, `export` prefixes and CRLF line endings in .env files.
+        let res: Env = from_key_values_with(
+            "# App settings\r\n\nexport name='my \"app\"'\r\n#count=2\ncount=3\n\n",
+            Preset::Dotenv,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Env {
+                name: r#"my "app""#.into(),
+                count: 3,
+            }
+        );
+
+        // Empty values and indented lines in .env files.
+        let res: BTreeMap<String, String> =
+            from_key_values_with("A=x\nD=\n  B=y\n\tC=\n", Preset::Dotenv).unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([
+                ("A".into(), "x".into()),
+                ("B".into(), "y".into()),
+                ("C".into(), "".into()),
+                ("D".into(), "".into()),
+            ])
+        );
+
+        // Java properties files with dotted keys, both delimiters, escapes and continuations.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Kafka {
+            #[serde(rename = "bootstrap.servers")]
+            servers: String,
+            #[serde(rename = "client.id")]
+            client_id: String,
+            #[serde(rename = "ssl.enabled")]
+            ssl: bool,
+            #[serde(rename = "sasl.jaas.config")]
+            jaas: String,
+        }
+
+        let res: Kafka = from_key_values_with(
+            "# Kafka\n! legacy\n\nbootstrap.servers = localhost:9092\r\nclient.id: my\\u0020\"app\"\n\
+             ssl.enabled=true\nsasl.jaas.config=first \\\n    second\\=\\[x\\]\n",
+            Preset::JavaProperties,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Kafka {
+                servers: "localhost:9092".into(),
+                client_id: r#"my "app""#.into(),
+                ssl: true,
+                jaas: "first second=[x]".into(),
+            }
+        );
+        // Keys may be delimited from their value by spaces alone, and values may be empty.
+        let res: BTreeMap<String, String> = from_key_values_with(
+            "key=\nb=1\nk key2\nc\t  x y\nd = \ne:",
+            Preset::JavaProperties,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([
+                ("b".into(), "1".into()),
+                ("c".into(), "x y".into()),
+                ("d".into(), "".into()),
+                ("e".into(), "".into()),
+                ("k".into(), "key2".into()),
+                ("key".into(), "".into()),
+            ])
+        );
+        assert_eq!(
+            from_key_values_with::<Kafka>("client.id=\\u12", Preset::JavaProperties)
+                .unwrap_err()
+                .kind,
+            ErrorKind::ExpectedString
+        );
+
+        // systemd environment files with comments, quotes, escapes and continuations.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Unit {
+            #[serde(rename = "OPTS")]
+            opts: String,
+            #[serde(rename = "MSG")]
+            msg: String,
+            #[serde(rename = "RAW")]
+            raw: String,
+            #[serde(rename = "PATH")]
+            path: String,
+        }
+
+        let res: Unit = from_key_values_with(
+            "; comment\n# other\n\nOPTS = -v --name=%i  \nMSG=\"say \\\"hi\\\"\n\\$HOME \\d\"\n\
+             RAW='a \\ b'\nPATH=/usr/\\\nbin\\ \n",
+            Preset::SystemdEnvironment,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Unit {
+                opts: "-v --name=%i".into(),
+                msg: "say \"hi\"\n$HOME \\d".into(),
+                raw: "a \\ b".into(),
+                path: "/usr/bin ".into(),
+            }
+        );
+
+        // Module parameters with comma-separated arrays and bare flags.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Module {
+            irq: Vec<u8>,
+            io: Vec<u16>,
+            debug: bool,
+            name: String,
+        }
+
+        let res: Module =
+            from_key_values_with("irq=5,7,9 io= debug name=\"eth 0\"", Preset::ModuleParams)
+                .unwrap();
+        assert_eq!(
+            res,
+            Module {
+                irq: vec![5, 7, 9],
+                io: vec![],
+                debug: true,
+                name: "eth 0".into(),
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Module>("irq=5,x io=1 debug=N name=a", Preset::ModuleParams)
+                .unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 6,
+            }
+        );
+
+        // mount(8) options mixing bare flags and pairs.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Mount {
+            #[serde(default)]
+            rw: bool,
+            #[serde(default)]
+            relatime: bool,
+            #[serde(default)]
+            noexec: bool,
+            size: String,
+            mode: String,
+            context: Option<String>,
+        }
+
+        let input =
+            r#"rw,relatime,size=10M,mode=755,context="system_u:object_r:tmp_t:s0:c127,c456""#;
+        let res: Mount = from_key_values_with(input, Preset::MountOptions).unwrap();
+        assert_eq!(
+            res,
+            Mount {
+                rw: true,
+                relatime: true,
+                noexec: false,
+                size: "10M".into(),
+                mode: "755".into(),
+                context: Some("system_u:object_r:tmp_t:s0:c127,c456".into()),
+            }
+        );
+
+        // Commas are not separators in logfmt.
+        let res: Env = from_key_values_with("name=a,b count=3", Preset::Logfmt).unwrap();
+        assert_eq!(
+            res,
+            Env {
+                name: "a,b".into(),
+                count: 3,
+            }
+        );
+        let err = from_key_values_with::<Env>("name=a&count=3", Preset::Logfmt).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::SerdeError("missing field `count`".into())
+        );
+
+        // Bare keys are empty strings in logfmt, and quoted strings may contain escaped newlines.
+        let res: Env = from_key_values_with(r#"count=3 name"#, Preset::Logfmt).unwrap();
+        assert_eq!(res.name, "");
+        let res: Env = from_key_values_with(r#"name="a\nb" count=3"#, Preset::Logfmt).unwrap();
+        assert_eq!(res.name, "a\nb");
+        assert_eq!(
+            from_key_values::<Env>("count=3 name").unwrap_err().kind,
+            ErrorKind::ExpectedString
+        );
+
+        // Empty values, and runs of spaces and tabs between pairs.
+        let res: BTreeMap<String, String> =
+            from_key_values_with("a= b=2  c=x\td=\"\"\t e=y ", Preset::Logfmt).unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([
+                ("a".into(), "".into()),
+                ("b".into(), "2".into()),
+                ("c".into(), "x".into()),
+                ("d".into(), "".into()),
+                ("e".into(), "y".into()),
+            ])
+        );
+
+        // Query strings are percent-encoded, and sequences are given as repeated keys.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Query {
+            tag: Vec<String>,
+            user: String,
+        }
+        let res: Query =
+            from_key_values_with("tag=a&tag=b%2Bc&user=bob+%22b%22", Preset::QueryString).unwrap();
+        assert_eq!(
+            res,
+            Query {
+                tag: vec!["a".into(), "b+c".into()],
+                user: r#"bob "b""#.into(),
+            }
+        );
+        let res: Query = from_key_values_with("user=&tag=[a,b]", Preset::QueryString).unwrap();
+        assert_eq!(res.tag, ["a", "b"]);
+        assert_eq!(res.user, "");
+        assert_eq!(
+            from_key_values_with::<Query>("tag=a&user=%4&tag=b", Preset::QueryString).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedString,
+                pos: 11,
+            }
+        );
+        // `+` is not a hexadecimal digit, even though `u8::from_str_radix` accepts it as a sign.
+        assert_eq!(
+            from_key_values_with::<Query>("tag=a&user=%+1", Preset::QueryString).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedString,
+                pos: 11,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Query>("tag=a&user=b&tag=c", Preset::QueryString)
+                .unwrap_err()
+                .kind,
+            ErrorKind::SerdeError("duplicate field `tag`".into())
+        );
+
+        // HTTP parameters may be surrounded by spaces, and backslashes escape any character in
+        // quoted strings.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct ContentType {
+            media_type: String,
+            charset: Option<String>,
+            #[serde(default)]
+            boundary: String,
+        }
+        let res: ContentType = from_key_values_with(
+            r#"multipart/form-data ;charset=utf-8;  boundary="a\b\"c" "#,
+            Preset::HttpParameters,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            ContentType {
+                media_type: "multipart/form-data".into(),
+                charset: Some("utf-8".into()),
+                boundary: r#"ab"c"#.into(),
+            }
+        );
+        let res: ContentType =
+            from_key_values_with("text/plain;boundary='x'", Preset::HttpParameters).unwrap();
+        assert_eq!(res.boundary, "'x'");
+
+        // Cookie attributes may be bare flags, and values may contain spaces and commas.
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "PascalCase")]
+        struct Cookie {
+            id: String,
+            expires: String,
+            #[serde(default)]
+            secure: bool,
+            #[serde(default)]
+            http_only: bool,
+        }
+        let res: Cookie = from_key_values_with(
+            "Id=a3fWa;  Secure;HttpOnly; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            Preset::Cookie,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Cookie {
+                id: "a3fWa".into(),
+                expires: "Wed, 21 Oct 2015 07:28:00 GMT".into(),
+                secure: true,
+                http_only: true,
+            }
+        );
+
+        // Baggage members may be surrounded by spaces, and their properties are ignored.
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Baggage {
+            user_id: String,
+            retries: u32,
+            tenant: String,
+        }
+        let res: Baggage = from_key_values_with(
+            "userId = a+b%20c ;ttl=60, retries=3 ,tenant=acme;x;y=1",
+            Preset::Baggage,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Baggage {
+                user_id: "a+b c".into(),
+                retries: 3,
+                tenant: "acme".into(),
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Baggage>("userId=%FF,retries=3,tenant=x", Preset::Baggage)
+                .unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedString,
+                pos: 7,
+            }
+        );
+
+        // InfluxDB tags escape commas, equals signs and spaces with a backslash.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Tags {
+            host: String,
+            path: String,
+            cpu: u32,
+        }
+        let res: Tags =
+            from_key_values_with(r"host=server\ 01,path=C:\a\=b\,c,cpu=3", Preset::InfluxTags)
+                .unwrap();
+        assert_eq!(
+            res,
+            Tags {
+                host: "server 01".into(),
+                path: r"C:\a=b,c".into(),
+                cpu: 3,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Tags>("host=a b,path=x,cpu=3", Preset::InfluxTags).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidCharInString,
+                pos: 6,
+            }
+        );
+
+        // libpq connection strings accept whitespace around `=` and escapes in unquoted values.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Dsn {
+            host: String,
+            port: u16,
+            dbname: String,
+            sslcompression: bool,
+        }
+        let res: Dsn = from_key_values_with(
+            " host = db\tport=5432\n dbname='my \\'db\\'' sslcompression=on ",
+            Preset::PostgresDsn,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Dsn {
+                host: "db".into(),
+                port: 5432,
+                dbname: "my 'db'".into(),
+                sslcompression: true,
+            }
+        );
+        let res: Dsn = from_key_values_with(
+            r"host=a\ b port=1 dbname=x sslcompression=0",
+            Preset::PostgresDsn,
+        )
+        .unwrap();
+        assert_eq!(res.host, "a b");
+        assert!(!res.sslcompression);
+
+        // ODBC values may be enclosed in braces, and a trailing separator is accepted.
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "PascalCase")]
+        struct Odbc {
+            driver: String,
+            #[serde(rename = "Data Source")]
+            data_source: String,
+            #[serde(rename = "Trusted_Connection")]
+            trusted_connection: bool,
+        }
+        let res: Odbc = from_key_values_with(
+            "Driver={SQL Server};Data Source={a}};b};Trusted_Connection=Yes;",
+            Preset::OdbcConnectionString,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Odbc {
+                driver: "SQL Server".into(),
+                data_source: "a};b".into(),
+                trusted_connection: true,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Odbc>("Driver={SQL Server", Preset::OdbcConnectionString)
+                .unwrap_err()
+                .pos,
+            7
+        );
+
+        // INI sections are nested structs or maps, which may be empty.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Remote {
+            url: String,
+            #[serde(default)]
+            prune: bool,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Ini {
+            #[serde(default)]
+            name: String,
+            core: BTreeMap<String, String>,
+            #[serde(rename = "remote origin")]
+            origin: Remote,
+        }
+        let res: Ini = from_key_values_with(
+            "; Config\n[core]\n[ remote origin ]\r\n  url = https://a/b.git\n\n  prune=true\n",
+            Preset::Ini,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Ini {
+                name: "".into(),
+                core: BTreeMap::new(),
+                origin: Remote {
+                    url: "https://a/b.git".into(),
+                    prune: true,
+                },
+            }
+        );
+        let res: BTreeMap<String, BTreeMap<String, u32>> =
+            from_key_values_with("[a]\nx=1\n[b]\ny=2", Preset::Ini).unwrap();
+        assert_eq!(res["a"]["x"], 1);
+        assert_eq!(res["b"]["y"], 2);
+        assert_eq!(
+            from_key_values_with::<Ini>("[core\nx=1", Preset::Ini).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 6,
+            }
+        );
+
+        // Shell words may mix quoted and unquoted parts, and the `export ` prefix is optional.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Shell {
+            #[serde(rename = "NAME")]
+            name: String,
+            #[serde(rename = "DEBUG")]
+            debug: bool,
+        }
+        let res: Shell = from_key_values_with(
+            "# Generated\nexport NAME='it'\\''s'\\ a' b'\nDEBUG=true\n",
+            Preset::ShellExport,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Shell {
+                name: "it's a b".into(),
+                debug: true,
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Shell>("NAME='a\nDEBUG=true", Preset::ShellExport)
+                .unwrap_err()
+                .kind,
+            ErrorKind::ExpectedString
+        );
+
+        // Header values may be folded over several lines.
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "PascalCase")]
+        struct Headers {
+            subject: String,
+            #[serde(rename = "Content-Length")]
+            content_length: u32,
+            to: String,
+        }
+        let res: Headers = from_key_values_with(
+            "Subject: a long\r\n\tsubject  \r\nContent-Length:42\nTo: a@b.org,\n  c@d.org\n",
+            Preset::Headers,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Headers {
+                subject: "a long\tsubject".into(),
+                content_length: 42,
+                to: "a@b.org,  c@d.org".into(),
+            }
+        );
+
+        // sysctl.conf files may contain comments, indentation and keys prefixed with `-`.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Sysctl {
+            #[serde(rename = "net.ipv4.ip_forward")]
+            ip_forward: u8,
+            #[serde(rename = "kernel.sysrq")]
+            sysrq: u32,
+            #[serde(rename = "vm.swappiness")]
+            swappiness: u8,
+        }
+        let res: Sysctl = from_key_values_with(
+            "# Routing\n  net.ipv4.ip_forward=1\r\n; Debug\n\n\
+             -kernel/sysrq = 176\nvm.swappiness =10\n",
+            Preset::Sysctl,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Sysctl {
+                ip_forward: 1,
+                sysrq: 176,
+                swappiness: 10,
+            }
+        );
+
+        // `/` separated keys are read as dotted keys, with dots within names becoming slashes.
+        let res: BTreeMap<String, u8> = from_key_values_with(
+            "net/ipv4/conf/eth0.100/forwarding = 1\nnet.ipv4.conf.eth1/200.forwarding = 0\n\
+             vm/swappiness = 10\n",
+            Preset::Sysctl,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            BTreeMap::from([
+                ("net.ipv4.conf.eth0/100.forwarding".into(), 1),
+                ("net.ipv4.conf.eth1/200.forwarding".into(), 0),
+                ("vm.swappiness".into(), 10),
+            ])
+        );
+
+        // GStreamer elements start with their factory name, and properties may span lines.
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "kebab-case")]
+        struct Element {
+            factory: String,
+            location: String,
+            #[serde(default)]
+            sync: bool,
+        }
+        let res: Element = from_key_values_with(
+            "filesink\n  location=\"/tmp/a b.mp4\"  sync=false ",
+            Preset::GstElement,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Element {
+                factory: "filesink".into(),
+                location: "/tmp/a b.mp4".into(),
+                sync: false,
+            }
+        );
+
+        // FFmpeg options may be single-quoted, and trailing unescaped spaces are dropped.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Scale {
+            w: i32,
+            flags: String,
+            force_original_aspect_ratio: String,
+            eval: bool,
+        }
+        let res: Scale = from_key_values_with(
+            r"w=-2:flags='a:b':force_original_aspect_ratio=decrease\:x :eval=true",
+            Preset::FfmpegOptions,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Scale {
+                w: -2,
+                flags: "a:b".into(),
+                force_original_aspect_ratio: "decrease:x".into(),
+                eval: true,
+            }
+        );
+
+        // Distinguished names accept hexadecimal escapes and attribute types given as OIDs.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Dn {
+            #[serde(rename = "CN")]
+            cn: String,
+            #[serde(rename = "2.5.4.10")]
+            o: String,
+        }
+        let res: Dn = from_key_values_with(
+            r"CN=Jos\C3\A9\2C Jr,2.5.4.10=Acme",
+            Preset::DistinguishedName,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Dn {
+                cn: "José, Jr".into(),
+                o: "Acme".into(),
+            }
+        );
+        assert_eq!(
+            from_key_values_with::<Dn>("CN=a+UID=b,2.5.4.10=x", Preset::DistinguishedName)
+                .unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidCharInString,
+                pos: 4,
+            }
+        );
+
+        // Spaces are not separators in the crosvm syntax, and the first key may be omitted.
+        let res: Env = from_key_values_with("my name,count=3", Preset::Crosvm).unwrap();
+        assert_eq!(
+            res,
+            Env {
+                name: "my name".into(),
+                count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_with_options() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Range(u16, u16);
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct TestStruct {
+            name: String,
+            ports: (u16, u16),
+            range: Range,
+            enabled: bool,
+        }
+
+        let options = DeserializeOptions::new()
+            .pair_separators([';'])
+            .key_value_delimiter(':')
+            .tuple_separator('-')
+            .quoting(Quoting::Never)
+            .true_values(["yes"])
+            .false_values(["no"]);
+
+        let res: TestStruct = from_key_values_with(
+            r#"name:"web" server;ports:80-88;range:[1,2];enabled:yes"#,
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            TestStruct {
+                name: r#""web" server"#.into(),
+                ports: (80, 88),
+                range: Range(1, 2),
+                enabled: true,
+            }
+        );
+
+        // Missing tuple element.
+        let err =
+            from_key_values_with::<TestStruct>("name:a;ports:80;range:1-2;enabled:no", options)
+                .unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: ErrorKind::ExpectedTupleSeparator,
+                pos: 15,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_spaced_delimiter() {
+        use serde::Serialize;
+
+        #[derive(Deserialize, Serialize, PartialEq, Debug)]
+        struct Sysctl {
+            swappiness: u8,
+            hostname: String,
+            forwarding: bool,
+        }
+
+        let sysctl = Sysctl {
+            swappiness: 10,
+            hostname: "build server".into(),
+            forwarding: false,
+        };
+        let options = crate::SerializeOptions::new()
+            .pair_separator("\n")
+            .delimiter_style(DelimiterStyle::Spaced)
+            .quoting(Quoting::WhenNeeded);
+
+        let mut serializer = crate::KeyValueSerializer::with_options(options.clone());
+        sysctl.serialize(&mut serializer).unwrap();
+        let output = serializer.into_output();
+        assert_eq!(
+            output,
+            "swappiness = 10\nhostname = \"build server\"\nforwarding = False"
+        );
+        assert_eq!(
+            from_key_values_with::<Sysctl>(&output, DeserializeOptions::from(&options)).unwrap(),
+            sysctl
+        );
+
+        // Any spacing is accepted, and the delimiter is still required.
+        let options = DeserializeOptions::new().delimiter_style(DelimiterStyle::Spaced);
+        let res: Sysctl = from_key_values_with(
+            "swappiness=10 hostname  =\tlocal forwarding",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            Sysctl {
+                swappiness: 10,
+                hostname: "local".into(),
+                forwarding: true,
+            }
+        );
+        let err = from_key_values_with::<Sysctl>("swappiness 10", options).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+    }
+
+    #[test]
+    fn transcode_to_json() {
+        let mut deserializer =
+            KeyValueDeserializer::from(r#"mtu=1500,name="tap 0" vhost,queues=[1,2]"#);
+        let mut output = Vec::new();
+        serde_transcode::transcode(
+            &mut deserializer,
+            &mut serde_json::Serializer::new(&mut output),
+        )
+        .unwrap();
+        deserializer.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"{"mtu":1500,"name":"tap 0","vhost":true,"queues":[1,2]}"#
+        );
+    }
+
+    #[test]
+    fn get_single_key() {
+        let input = r#"a=1,name="x y",a=-2,list=[1,2],flag,bad=1x"#;
+
+        assert_eq!(get::<i32>(input, "a"), Ok(Some(-2)));
+        assert_eq!(get::<String>(input, "name"), Ok(Some("x y".into())));
+        assert_eq!(get::<Vec<u8>>(input, "list"), Ok(Some(vec![1, 2])));
+        assert_eq!(get::<bool>(input, "flag"), Ok(Some(true)));
+        assert_eq!(get::<u8>(input, "missing"), Ok(None));
+        assert_eq!(
+            get::<u8>(input, "bad"),
+            Err(ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 40,
+            })
+        );
+        assert_eq!(
+            get::<u8>("a=1,=2", "a"),
+            Err(ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            })
+        );
+    }
 }
@@ -0,0 +1,218 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Conversions between serializable structures and the key-values of records logged through the
+//! [log](https://docs.rs/log) facade.
+
+use log::kv::Key;
+use log::kv::Source;
+use log::kv::VisitSource;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::KeyValueSerializer;
+use crate::KvBuilder;
+use crate::Map;
+use crate::Preset;
+use crate::SerializeError;
+use crate::SerializeOptions;
+use crate::Value;
+
+/// Key-values of a serializable structure, as a `log::kv::Source` that can be attached to a
+/// record.
+///
+/// The structure is written as a logfmt string and parsed back, so the values given to the
+/// logger are those a reader of that string would see: a key without a value is a `true` boolean,
+/// numbers are integers, and lists and nested structures are given as `serde` values.
+///
+/// ```
+/// use log::kv::Source;
+/// use serde::Serialize;
+/// use serde_keyvalue::KvSource;
+///
+/// #[derive(Serialize)]
+/// struct Disk {
+///     path: String,
+///     ro: bool,
+/// }
+///
+/// let source = KvSource::new(&Disk { path: "/dev/vda".into(), ro: true }).unwrap();
+/// assert_eq!(source.count(), 2);
+/// assert_eq!(source.get(log::kv::Key::from("ro")).unwrap().to_bool(), Some(true));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvSource(Map);
+
+impl KvSource {
+    /// Creates the key-values of `value`, which is expected to serialize as a structure or a map.
+    pub fn new<T: Serialize + ?Sized>(value: &T) -> Result<Self, SerializeError> {
+        let mut serializer = KeyValueSerializer::with_options(Preset::Logfmt);
+        value.serialize(&mut serializer)?;
+        from_key_values_with(&serializer.into_output(), Preset::Logfmt)
+            .map(Self)
+            .map_err(|e| SerializeError::Custom(e.to_string()))
+    }
+}
+
+impl Source for KvSource {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), log::kv::Error> {
+        for (key, value) in &self.0 {
+            let value = match value {
+                Value::Bool(b) => log::kv::Value::from(*b),
+                Value::Int(i) => log::kv::Value::from(*i),
+                Value::UInt(u) => log::kv::Value::from(*u),
+                Value::Float(f) => log::kv::Value::from(*f),
+                Value::String(s) => log::kv::Value::from(s.as_str()),
+                Value::List(_) | Value::Map(_) => log::kv::Value::from_serde(value),
+            };
+            visitor.visit_pair(Key::from_str(key), value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the key-values of `source`, e.g. those of a logged record, as a key-values string.
+///
+/// ```
+/// use serde_keyvalue::to_string_from_kv_source;
+///
+/// let pairs = [("path", "/dev/vda"), ("mode", "ro")];
+/// assert_eq!(to_string_from_kv_source(&pairs).unwrap(), "path=/dev/vda mode=ro");
+/// ```
+pub fn to_string_from_kv_source<S: Source + ?Sized>(source: &S) -> Result<String, SerializeError> {
+    to_string_from_kv_source_with(source, SerializeOptions::default())
+}
+
+/// Writes the key-values of `source` as a key-values string according to `options`. See
+/// [`to_string_from_kv_source`] for details.
+pub fn to_string_from_kv_source_with<S: Source + ?Sized>(
+    source: &S,
+    options: impl Into<SerializeOptions>,
+) -> Result<String, SerializeError> {
+    let mut pairs = Pairs(Some(KvBuilder::with_options(options)));
+    source
+        .visit(&mut pairs)
+        .map_err(|e| SerializeError::Custom(e.to_string()))?;
+    pairs.0.unwrap_or_default().finish()
+}
+
+/// Adds the key-values of a source to a builder.
+struct Pairs(Option<KvBuilder>);
+
+impl<'kvs> VisitSource<'kvs> for Pairs {
+    fn visit_pair(
+        &mut self,
+        key: Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0 = self
+            .0
+            .take()
+            .map(|builder| builder.field(key.as_str(), value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use log::kv::VisitValue;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Disk {
+        path: String,
+        ro: bool,
+        size: u64,
+        queues: Vec<u32>,
+    }
+
+    /// Records the values of a source as their debug representation.
+    #[derive(Default)]
+    struct Collect(BTreeMap<String, String>);
+
+    impl<'kvs> VisitSource<'kvs> for Collect {
+        fn visit_pair(
+            &mut self,
+            key: Key<'kvs>,
+            value: log::kv::Value<'kvs>,
+        ) -> Result<(), log::kv::Error> {
+            struct Kind<'a>(&'a mut String);
+
+            impl<'v> VisitValue<'v> for Kind<'_> {
+                fn visit_any(&mut self, value: log::kv::Value<'_>) -> Result<(), log::kv::Error> {
+                    *self.0 = format!("any:{}", value);
+                    Ok(())
+                }
+
+                fn visit_bool(&mut self, value: bool) -> Result<(), log::kv::Error> {
+                    *self.0 = format!("bool:{}", value);
+                    Ok(())
+                }
+
+                fn visit_i64(&mut self, value: i64) -> Result<(), log::kv::Error> {
+                    *self.0 = format!("i64:{}", value);
+                    Ok(())
+                }
+
+                fn visit_borrowed_str(&mut self, value: &'v str) -> Result<(), log::kv::Error> {
+                    *self.0 = format!("str:{}", value);
+                    Ok(())
+                }
+            }
+
+            let mut kind = String::new();
+            value.visit(Kind(&mut kind))?;
+            self.0.insert(key.to_string(), kind);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn source_of_struct() {
+        let disk = Disk {
+            path: "/dev/vda 1".into(),
+            ro: true,
+            size: 1024,
+            queues: vec![1, 2],
+        };
+        let mut collect = Collect::default();
+        KvSource::new(&disk).unwrap().visit(&mut collect).unwrap();
+
+        assert_eq!(
+            collect.0,
+            BTreeMap::from([
+                ("path".into(), "str:/dev/vda 1".into()),
+                ("queues".into(), "any:[1, 2]".into()),
+                ("ro".into(), "bool:true".into()),
+                ("size".into(), "i64:1024".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn string_from_source() {
+        let disk = Disk {
+            path: "/dev/vda 1".into(),
+            ro: false,
+            size: 1024,
+            queues: vec![1, 2],
+        };
+        let source = KvSource::new(&disk).unwrap();
+        let output = to_string_from_kv_source_with(&source, Preset::Logfmt).unwrap();
+        assert_eq!(
+            from_key_values_with::<Disk>(&output, Preset::Logfmt).unwrap(),
+            disk
+        );
+
+        let pairs = [("msg", "a \"b\"")];
+        assert_eq!(
+            to_string_from_kv_source_with(&pairs, Preset::Logfmt).unwrap(),
+            r#"msg="a \"b\"""#
+        );
+    }
+}
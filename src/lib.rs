@@ -17,11 +17,145 @@
 //! the same keys.
 //!
 //! Integration with the [argh](https://github.com/google/argh) command-line parser is also
-//! provided via the `argh_derive` feature.
+//! provided via the `argh_derive` feature. With [clap](https://docs.rs/clap), the `clap` feature
+//! provides `KeyValueParser`, the value parser of arguments given as key-values strings, e.g.
+//! `#[arg(long, value_parser = KeyValueParser::<Disk>::new())]`. Parsing errors are then reported
+//! by clap along with the name of the argument, as shown by the `clap` example of the repository.
+//!
+//! Key-values strings and files can also be one of the layered sources of a
+//! [config-rs](https://docs.rs/config) configuration with the `KeyValueSource` provided by the
+//! `config` feature, as shown by the `config` example of the repository.
+//!
+//! Similarly, with [figment](https://docs.rs/figment), the `figment` feature provides
+//! `KeyValueProvider`, which merges a key-values string or file into the configuration with its
+//! dotted keys nested, as shown by the `figment` example of the repository.
+//!
+//! Structures can also be serialized into key-values strings using [KeyValueSerializer]. The
+//! `derive` feature provides the `keyvalue` attribute macro, which allows the formatting of
+//! individual fields to be controlled with attributes like `#[keyvalue(quote)]` or
+//! `#[keyvalue(format = "hex")]`, the `KeyValueHelp` derive macro, which generates a usage
+//! text describing the keys accepted by a struct, the `KeyValueStr` derive macro, which implements
+//! `FromStr` and `Display` with this crate, and the `Partial` derive macro, which generates
+//! a copy of a struct with optional fields, so defaults, configuration files and command-line
+//! overrides can be merged in order:
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use serde::Deserialize;
+//! use serde_keyvalue::{from_key_values, Partial};
+//!
+//! #[derive(Debug, PartialEq, Deserialize, Partial)]
+//! struct Net {
+//!     tap: String,
+//!     mtu: u16,
+//! }
+//!
+//! let defaults = Net { tap: "tap0".into(), mtu: 1500 };
+//! let file: NetPartial = from_key_values("mtu=9000").unwrap();
+//! let cli: NetPartial = from_key_values("tap=tap1").unwrap();
+//!
+//! let net = defaults.merge(file.merge(cli));
+//! assert_eq!(net, Net { tap: "tap1".into(), mtu: 9000 });
+//! # }
+//! ```
+//!
+//! The `derive` feature also provides the `key_values!` macro, which parses a string literal at
+//! compile time into a static table of its pairs, so a malformed string fails the build rather
+//! than the program.
+//!
+//! Logs can be written with the same escaping rules as serialized structures by building their
+//! lines with [KvBuilder] and [Preset::Logfmt]. With
+//! [tracing-subscriber](https://docs.rs/tracing-subscriber), the `tracing` feature provides
+//! `LogfmtFormat`, which formats events along with the fields of their spans that way when set
+//! with both `event_format` and `fmt_fields`, as shown by the `tracing` example of the repository.
+//! With the [log](https://docs.rs/log) facade, the `log` feature provides `KvSource`, the
+//! key-values of a serialized structure attached to a record, and `to_string_from_kv_source`,
+//! which renders the key-values of a record, as shown by the `log` example.
+//!
+//! The serializer and the deserializer work on complete strings, so async services using
+//! [tokio](https://docs.rs/tokio) can stream records without blocking adapters. The `tokio`
+//! feature provides `to_async_writer`, which writes each serialized record at once, and
+//! `RecordStream`, which deserializes records as soon as their line has been read, as shown by the
+//! `tokio` example of the repository.
+//!
+//! Records framed with another [record separator](DeserializeOptions::record_separator) of a
+//! single byte, e.g. `\0`, can be read with `AsyncBufReadExt::read_until` instead, and split with
+//! [records] if their values may contain the separator within quotes.
+//!
+//! Key-values strings whose keys are not known in advance can be deserialized into a [Map] of
+//! [Value]s, and serialized back. Enabling the `preserve_order` feature keeps the pairs of a [Map]
+//! in their original order instead of sorting them by key.
+//!
+//! With [OpenTelemetry](https://docs.rs/opentelemetry), the `opentelemetry` feature provides
+//! `to_otel_attributes`, which turns key-values strings into `KeyValue` attributes, e.g. the
+//! resource attributes of the `OTEL_RESOURCE_ATTRIBUTES` variable, which follow the W3C baggage
+//! syntax of [`Preset::Baggage`], and `from_otel_attributes`, which writes span attributes back
+//! into a key-values string, as shown by the `opentelemetry` example of the repository.
+//!
+//! Both the serializer and the deserializer can be used with
+//! [serde_transcode](https://docs.rs/serde-transcode) to convert key-values strings from and to
+//! other formats without going through a typed structure. The `json` feature provides
+//! `to_json` and `to_string_from_json` helpers doing so for JSON values, and
+//! `Schema::to_json_schema`, which describes the keys of a [Schema] as a JSON Schema, e.g. one
+//! built from a struct with `Schema::of`. It also enables the `embedded_json` options, with which
+//! nested structs and maps are written and read as compact JSON, e.g.
+//! `limits={"cpu":2,"mem":"4G"}`. The `schemars` feature provides `Schema::of_json_schema`, which
+//! builds a [Schema] from the `JsonSchema` implementation of a struct generated by
+//! [schemars](https://docs.rs/schemars), including the fields of its flattened structs. The
+//! `duration` feature
+//! provides the `duration` module, which reads and writes `Duration` fields as `30s` or `1h30m`,
+//! and the `size` feature provides the `ByteSize` type for sizes in bytes written as `4GiB`.
+//! The `chrono` feature provides the `datetime` module, which reads and writes
+//! [chrono](https://docs.rs/chrono) `DateTime` fields as RFC 3339 timestamps and can accept
+//! timestamps in another format as well. The `arbitrary` feature implements the `Arbitrary` trait
+//! of [arbitrary](https://docs.rs/arbitrary) for [Value] and `testing::Document`, so the parser
+//! and the serializer can be fuzzed with structured inputs. The `parallel` feature provides
+//! `par_from_lines`, which deserializes the records of a batch on one thread per available core,
+//! or on the [rayon](https://docs.rs/rayon) thread pool with the `rayon` feature.
+//! The `cli` feature builds the `skv` binary, which converts key-values strings read from the
+//! standard input to and from JSON, canonicalizes them, looks up keys and validates them, in any
+//! of the [Preset] dialects. The `wasm` feature exports `fromStr`, `toString` and `canonicalize`
+//! functions to JavaScript with [wasm-bindgen](https://docs.rs/wasm-bindgen), so a web frontend
+//! can validate and normalize key-values strings with the same parser as its backend.
+//!
+//! The crate only requires `core` and `alloc` when its default `std` feature is disabled, so the
+//! serializer and the deserializer can be used in `no_std` environments. `expand_argfiles`,
+//! `apply_env`, `from_command_env`, `from_docker_args_env`, `from_environ_bytes`, `group_by_key`,
+//! `interpolate_env`, `read_with_includes`, `set_env_vars`, `to_env_vars`,
+//! `with_serializer`, `Interner`, `RecordWriter` and the `json`, `schemars`, `argh_derive`,
+//! `clap`, `config`, `figment`, `log`, `opentelemetry`, `tokio`, `tracing`, `preserve_order`,
+//! `cli`, `wasm`, `arbitrary`, `parallel` and `rayon` features need `std`.
 //!
 //! The deserializer supports parsing signed and unsigned integers, booleans, strings (quoted or
 //! not), paths, and enums inside a top-level struct. The order in which the fields appear in the
-//! string is not important.
+//! string is not important. Network addresses, and identifiers or CIDR blocks like those of
+//! [uuid](https://docs.rs/uuid) and [ipnet](https://docs.rs/ipnet) are supported through their
+//! serde implementations, which use strings that do not need to be quoted, e.g. UUIDs are written
+//! hyphenated and read in their hyphenated, simple, braced or URN forms. The `uuid` and `ipnet`
+//! features re-export uuid and ipnet with their serde implementations enabled, so `Uuid` and
+//! `IpNet` fields, e.g. `subnet=10.0.0.0/24`, can be used without depending on them separately:
+//!
+//! ```
+//! # #[cfg(feature = "uuid")]
+//! # {
+//! use serde::Deserialize;
+//! use serde_keyvalue::from_key_values;
+//! use serde_keyvalue::uuid::Uuid;
+//!
+//! #[derive(Deserialize)]
+//! struct Request {
+//!     id: Uuid,
+//! }
+//!
+//! let request: Request = from_key_values("id={67e55044-10b1-426f-9247-bb680e5fe0c8}").unwrap();
+//! assert_eq!(request.id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+//! # }
+//! ```
+//!
+//! Decimal numbers, e.g. those of [rust_decimal](https://docs.rs/rust_decimal), round-trip as
+//! strings: they are written as strings, and numbers with a fractional part are read as strings
+//! too, so their scale is kept.
 //!
 //! Simple example:
 //!
@@ -123,8 +257,10 @@
 //! ```
 //!
 //! Strings can be quoted, which is useful if they need to include a comma or a bracket, which are
-//! considered separators for unquoted strings. Quoted strings can also contain escaped characters,
-//! where any character after a `\` is repeated as-is:
+//! considered separators for unquoted strings. Double-quoted strings can also contain escaped
+//! characters: `\"` and `\\` stand for a quote and a backslash, `\n`, `\r` and `\t` for a
+//! newline, a carriage return and a tab, and `\uXXXX` for the character with the hexadecimal
+//! code point `XXXX`, which is how other control characters are written:
 //!
 //! ```
 //! # use serde_keyvalue::from_key_values;
@@ -136,6 +272,50 @@
 //!
 //! let config: Config = from_key_values(r#"path="/some/\"strange\"/pa,th""#).unwrap();
 //! assert_eq!(config, Config { path: r#"/some/"strange"/pa,th"#.into() });
+//!
+//! let config: Config = from_key_values(r#"path="two\nlines\u0007""#).unwrap();
+//! assert_eq!(config, Config { path: "two\nlines\u{7}".into() });
+//! ```
+//!
+//! Strings without escaped characters are borrowed from the input, so fields of type `Cow<str>`
+//! with the `#[serde(borrow)]` attribute only allocate when a string had to be unescaped:
+//!
+//! ```
+//! # use std::borrow::Cow;
+//! # use serde_keyvalue::from_key_values;
+//! # use serde::Deserialize;
+//! #[derive(Deserialize)]
+//! struct Event<'a> {
+//!     #[serde(borrow)]
+//!     msg: Cow<'a, str>,
+//! }
+//!
+//! let event: Event = from_key_values(r#"msg="disk full""#).unwrap();
+//! assert!(matches!(event.msg, Cow::Borrowed("disk full")));
+//!
+//! let event: Event = from_key_values(r#"msg="disk \"vda\" full""#).unwrap();
+//! assert!(matches!(event.msg, Cow::Owned(_)));
+//! ```
+//!
+//! With the `bumpalo` feature, `pairs_in` returns the pairs of a string in a vector allocated
+//! in a [bumpalo](https://docs.rs/bumpalo) `Bump`, borrowing from the input the strings that did
+//! not have to be unescaped and copying the others into the arena, so parsing large batches of
+//! short-lived records does not go through the global allocator:
+//!
+//! ```
+//! # #[cfg(feature = "bumpalo")]
+//! # {
+//! use bumpalo::Bump;
+//! use serde_keyvalue::{pairs_in, DeserializeOptions};
+//!
+//! let mut bump = Bump::new();
+//! for line in ["msg=\"disk \\\"vda\\\" full\"", "msg=retry"] {
+//!     let pairs = pairs_in(line, DeserializeOptions::default(), &bump).unwrap();
+//!     assert_eq!(pairs[0].0, "msg");
+//!     drop(pairs);
+//!     bump.reset();
+//! }
+//! # }
 //! ```
 //!
 //! Tuples and vectors are allowed and must be specified between `[` and `]`:
@@ -153,6 +333,30 @@
 //! assert_eq!(layout, Layout { resolution: (320, 200), scanlines: vec![0, 64, 128] });
 //! ```
 //!
+//! Structs nested in a sequence are enclosed in brackets too, and the brackets of the sequence
+//! itself may then be omitted, so its elements are separated by commas. The serializer writes this
+//! form with the `bare_struct_lists` option:
+//!
+//! ```
+//! # use serde_keyvalue::from_key_values;
+//! # use serde::Deserialize;
+//! #[derive(Debug, PartialEq, Deserialize)]
+//! struct Disk {
+//!     path: String,
+//!     #[serde(default)]
+//!     ro: bool,
+//! }
+//!
+//! #[derive(Debug, PartialEq, Deserialize)]
+//! struct Vm {
+//!     disks: Vec<Disk>,
+//! }
+//!
+//! let vm: Vm = from_key_values("disks=[path=/a ro=true],[path=/b]").unwrap();
+//! assert_eq!(vm.disks[1], Disk { path: "/b".into(), ro: false });
+//! assert_eq!(vm, from_key_values("disks=[[path=/a ro=true],[path=/b]]").unwrap());
+//! ```
+//!
 //! Enums can be directly specified by name. It is recommended to use the `rename_all` serde
 //! container attribute to make them parseable using snake or kebab case representation. Serde's
 //! `rename` and `alias` field attributes can also be used to provide shorter values:
@@ -343,17 +547,274 @@
 //! Another limitation of using `flatten` that is inherent to serde is that it won't allow
 //! `deny_unknown_fields` to be used in either the embedding or the flattened struct.
 #![deny(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "bumpalo")]
+mod arena;
+#[cfg(feature = "std")]
+mod argfile;
+mod args;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod aws;
+mod builder;
+mod canonical;
+mod cmdline;
+#[cfg(feature = "config")]
+mod config_source;
+#[cfg(feature = "chrono")]
+pub mod datetime;
+mod diff;
+mod document;
+#[cfg(feature = "duration")]
+pub mod duration;
+#[cfg(feature = "std")]
+mod env;
+mod ext;
+#[cfg(feature = "figment")]
+mod figment_provider;
+mod formatter;
+mod help;
+pub mod helpers;
+#[cfg(feature = "std")]
+mod include;
+#[cfg(feature = "std")]
+mod intern;
+mod interpolate;
+mod journal;
+#[cfg(feature = "json")]
+mod json;
 mod key_values;
+#[cfg(feature = "log")]
+mod kv_source;
+#[cfg(feature = "tracing")]
+mod logfmt;
+mod merge;
+mod mount;
+mod options;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod partial;
+#[cfg(feature = "std")]
+mod pool;
+mod prometheus;
+mod push;
+mod raw;
+mod records;
+mod scanner;
+mod schema;
+mod search;
+mod selector;
 mod serializer;
+#[cfg(feature = "size")]
+mod size;
+mod syslog;
+pub mod testing;
+mod token;
+mod trace;
+mod value;
+#[cfg(feature = "clap")]
+mod value_parser;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod writer;
 
+#[cfg(feature = "bumpalo")]
+pub use arena::pairs_in;
+#[cfg(feature = "std")]
+pub use argfile::expand_argfiles;
 #[cfg(feature = "argh_derive")]
 pub use argh;
+pub use args::from_args;
+pub use args::from_docker_args;
+#[cfg(feature = "std")]
+pub use args::from_docker_args_env;
+pub use args::to_docker_args;
+#[cfg(feature = "tokio")]
+pub use async_io::to_async_writer;
+#[cfg(feature = "tokio")]
+pub use async_io::to_async_writer_with;
+#[cfg(feature = "tokio")]
+pub use async_io::RecordStream;
+pub use aws::from_shorthand;
+pub use aws::from_shorthand_args;
+pub use aws::to_shorthand;
+pub use aws::to_shorthand_args;
+pub use builder::KvBuilder;
+pub use canonical::canonicalize;
+pub use canonical::stable_hash;
+pub use canonical::stable_hash_of;
+pub use cmdline::from_cmdline;
+pub use cmdline::to_cmdline;
+#[cfg(feature = "config")]
+pub use config_source::KeyValueSource;
+pub use diff::diff;
+pub use diff::diff_with;
+pub use diff::is_subset;
+pub use diff::is_subset_with;
+pub use diff::matches;
+pub use diff::matches_with;
+pub use diff::Change;
+pub use diff::KvDiff;
+pub use document::KvDocument;
+#[cfg(feature = "std")]
+pub use env::apply_env;
+#[cfg(feature = "std")]
+pub use env::from_command_env;
+#[cfg(feature = "std")]
+pub use env::from_environ_bytes;
+#[cfg(feature = "std")]
+pub use env::set_env_vars;
+#[cfg(feature = "std")]
+pub use env::to_env_vars;
+#[cfg(feature = "std")]
+pub use env::EnvCase;
+#[cfg(feature = "std")]
+pub use env::EnvOptions;
+pub use ext::FromKeyValue;
+pub use ext::ToKeyValue;
+#[cfg(feature = "figment")]
+pub use figment_provider::KeyValueProvider;
+pub use formatter::DefaultFormatter;
+pub use formatter::ValueFormatter;
+pub use help::KeyValueHelp;
+#[cfg(feature = "std")]
+pub use include::read_with_includes;
+#[cfg(feature = "std")]
+pub use intern::InternedValue;
+#[cfg(feature = "std")]
+pub use intern::Interner;
+pub use interpolate::interpolate;
+#[cfg(feature = "std")]
+pub use interpolate::interpolate_env;
+#[cfg(feature = "ipnet")]
+pub use ipnet;
+pub use journal::to_journal_export;
+#[cfg(feature = "json")]
+pub use json::to_json;
+#[cfg(feature = "json")]
+pub use json::to_json_with;
+#[cfg(feature = "json")]
+pub use json::to_string_from_json;
+#[cfg(feature = "json")]
+pub use json::to_string_from_json_with;
 pub use key_values::from_key_values;
+pub use key_values::from_key_values_with;
+pub use key_values::get;
+pub use key_values::get_with;
 pub use key_values::ErrorKind;
 pub use key_values::KeyValueDeserializer;
 pub use key_values::ParseError;
+#[cfg(feature = "log")]
+pub use kv_source::to_string_from_kv_source;
+#[cfg(feature = "log")]
+pub use kv_source::to_string_from_kv_source_with;
+#[cfg(feature = "log")]
+pub use kv_source::KvSource;
+#[cfg(feature = "tracing")]
+pub use logfmt::LogfmtFormat;
+pub use merge::from_key_values_with_defaults;
+pub use merge::merge;
+pub use merge::merge_into;
+pub use merge::merge_with;
+pub use merge::Defaults;
+pub use merge::ListPolicy;
+pub use mount::fstab_entries;
+pub use mount::mountinfo_entries;
+pub use mount::FstabEntry;
+pub use mount::MountInfo;
+#[cfg(feature = "derive")]
+pub use serde_keyvalue_derive::key_values;
+#[cfg(feature = "derive")]
+pub use serde_keyvalue_derive::keyvalue;
 #[cfg(feature = "argh_derive")]
 pub use serde_keyvalue_derive::FromKeyValues;
+#[cfg(feature = "derive")]
+pub use serde_keyvalue_derive::KeyValueHelp;
+#[cfg(feature = "derive")]
+pub use serde_keyvalue_derive::KeyValueStr;
+#[cfg(feature = "derive")]
+pub use serde_keyvalue_derive::Partial;
 
+pub use options::BoolStyle;
+pub use options::DelimiterStyle;
+pub use options::DeserializeOptions;
+pub use options::EmptyValues;
+pub use options::KeyCase;
+pub use options::NewlineStyle;
+pub use options::NoneStyle;
+pub use options::Overflow;
+pub use options::Preset;
+pub use options::Quoting;
+pub use options::SerializeOptions;
+pub use options::Whitespace;
+#[cfg(feature = "opentelemetry")]
+pub use otel::from_otel_attributes;
+#[cfg(feature = "opentelemetry")]
+pub use otel::from_otel_attributes_with;
+#[cfg(feature = "opentelemetry")]
+pub use otel::to_otel_attributes;
+#[cfg(feature = "opentelemetry")]
+pub use otel::to_otel_attributes_with;
+#[cfg(feature = "parallel")]
+pub use parallel::par_from_lines;
+#[cfg(feature = "parallel")]
+pub use parallel::par_from_lines_with;
+pub use partial::from_key_values_partial;
+#[cfg(feature = "std")]
+pub use pool::with_serializer;
+pub use prometheus::from_labels;
+pub use prometheus::to_labels;
+pub use push::PushParser;
+pub use raw::RawValue;
+#[cfg(feature = "std")]
+pub use records::group_by_key;
+#[cfg(feature = "std")]
+pub use records::group_by_key_with;
+pub use records::records;
+#[cfg(feature = "std")]
+pub use records::RecordWriter;
+pub use scanner::pairs;
+pub use scanner::pairs_with;
+pub use scanner::validate;
+pub use schema::Schema;
+pub use schema::ValueType;
+pub use schema::Violation;
+pub use selector::LabelOperator;
+pub use selector::LabelRequirement;
+pub use selector::LabelSelector;
+pub use serializer::BudgetExceeded;
 pub use serializer::KeyValueSerializer;
+pub use serializer::SerializeError;
+pub use serializer::Unsupported;
+#[cfg(feature = "size")]
+pub use size::ByteSize;
+pub use syslog::from_sd_element;
+pub use syslog::sd_elements;
+pub use syslog::to_sd_element;
+pub use token::tokenize;
+pub use token::tokenize_with;
+pub use token::Token;
+#[cfg(feature = "uuid")]
+pub use uuid;
+pub use value::Map;
+pub use value::Value;
+#[cfg(feature = "clap")]
+pub use value_parser::KeyValueParser;
+pub use writer::to_slice;
+pub use writer::to_writer;
+pub use writer::KeyValueDisplay;
+
+/// Implementation details used by the code generated by the `serde_keyvalue_derive` macros.
+#[doc(hidden)]
+pub mod __private {
+    pub extern crate alloc;
+
+    pub use serde;
+
+    pub use crate::serializer::serialize_formatted;
+}
@@ -0,0 +1,196 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Formatting of [tracing](https://docs.rs/tracing) events as logfmt lines with
+//! [tracing-subscriber](https://docs.rs/tracing-subscriber).
+
+use alloc::string::String;
+use core::fmt;
+
+use serde::Serialize;
+use tracing_core::field::Field;
+use tracing_core::field::Visit;
+use tracing_core::Event;
+use tracing_core::Subscriber;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::fmt::FormatEvent;
+use tracing_subscriber::fmt::FormatFields;
+use tracing_subscriber::fmt::FormattedFields;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::options::Preset;
+use crate::KvBuilder;
+
+/// Formatter of tracing events and span fields writing them as logfmt lines, with the escaping
+/// rules of [`Preset::Logfmt`].
+///
+/// Each event is written on its own line as its level, the fields of the spans it is in, from the
+/// outermost one, and its own fields, e.g. `level=INFO vm=1 message="disk attached" ro=true`.
+/// Span fields are only written that way if `LogfmtFormat` also formats the fields of the
+/// subscriber, as set with `fmt_fields`:
+///
+/// ```
+/// use serde_keyvalue::LogfmtFormat;
+///
+/// let subscriber = tracing_subscriber::fmt()
+///     .event_format(LogfmtFormat)
+///     .fmt_fields(LogfmtFormat)
+///     .finish();
+///
+/// tracing::subscriber::with_default(subscriber, || {
+///     let _vm = tracing::info_span!("vm", id = 1).entered();
+///     tracing::info!(path = "/dev/vda 1", ro = true, "disk attached");
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogfmtFormat;
+
+impl<S, N> FormatEvent<S, N> for LogfmtFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let level = event.metadata().level().as_str();
+        writer.write_str(
+            &KvBuilder::with_options(Preset::Logfmt)
+                .field("level", level)
+                .finish()?,
+        )?;
+
+        for span in ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+        {
+            let extensions = span.extensions();
+            if let Some(fields) = extensions.get::<FormattedFields<N>>() {
+                if !fields.is_empty() {
+                    write!(writer, " {}", fields)?;
+                }
+            }
+        }
+
+        let fields = record_fields(event)?;
+        if !fields.is_empty() {
+            write!(writer, " {}", fields)?;
+        }
+        writeln!(writer)
+    }
+}
+
+impl<'writer> FormatFields<'writer> for LogfmtFormat {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        writer.write_str(&record_fields(fields)?)
+    }
+}
+
+/// Returns `fields` written as key-value pairs.
+fn record_fields<R: RecordFields>(fields: R) -> Result<String, fmt::Error> {
+    let mut visitor = Fields(Some(KvBuilder::with_options(Preset::Logfmt)));
+    fields.record(&mut visitor);
+    Ok(visitor.0.unwrap_or_default().finish()?)
+}
+
+/// Adds the fields of an event or a span to a builder.
+struct Fields(Option<KvBuilder>);
+
+impl Fields {
+    fn add<T: Serialize>(&mut self, field: &Field, value: T) {
+        self.0 = self
+            .0
+            .take()
+            .map(|builder| builder.field(field.name(), value));
+    }
+}
+
+impl Visit for Fields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.add(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.add(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.add(field, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.add(field, value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.add(field, alloc::format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Writer appending to a shared buffer.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn format_events() {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(LogfmtFormat)
+            .fmt_fields(LogfmtFormat)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("no span");
+            let _vm = tracing::info_span!("vm", id = 1, name = "a b").entered();
+            let _disk = tracing::info_span!("disk", index = 0u8).entered();
+            tracing::info!(
+                path = "/dev/vda 1",
+                ro = true,
+                ratio = 0.5,
+                "disk \"attached\""
+            );
+        });
+
+        assert_eq!(
+            String::from_utf8(output.0.lock().unwrap().clone()).unwrap(),
+            "level=WARN message=\"no span\"\n\
+             level=INFO id=1 name=\"a b\" index=0 message=\"disk \\\"attached\\\"\" \
+             path=\"/dev/vda 1\" ro=true ratio=0.5\n"
+        );
+    }
+}
@@ -0,0 +1,385 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Layering of key-values strings.
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::document::KvDocument;
+use crate::from_key_values_with;
+use crate::options::DeserializeOptions;
+use crate::options::SerializeOptions;
+use crate::scanner::ScannedPair;
+use crate::scanner::ScannedValue;
+use crate::scanner::Scanner;
+use crate::trace::trace_fields;
+use crate::ErrorKind;
+use crate::KeyValueSerializer;
+use crate::Map;
+use crate::ParseError;
+
+/// How values enclosed in brackets, i.e. lists, are merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListPolicy {
+    /// The list of the overlay replaces the list of the base.
+    #[default]
+    Replace,
+    /// The elements of the list of the overlay are appended to the list of the base.
+    Append,
+}
+
+/// Merges the key-values string `overlay` on top of `base`, using the default options of the
+/// deserializer.
+///
+/// The pairs of `overlay` replace the pairs of `base` with the same key, and the pairs of `overlay`
+/// whose key is not in `base` are appended. The text of the pairs of `base` that are not
+/// overridden is kept unchanged.
+///
+/// ```
+/// use serde_keyvalue::merge;
+///
+/// let defaults = "mtu=1500,tap=tap0,queues=[1,2]";
+/// let cli = "mtu=9000,queues=[3] vhost";
+///
+/// assert_eq!(merge(defaults, cli).unwrap(), "mtu=9000,tap=tap0,queues=[3] vhost");
+/// ```
+pub fn merge(base: &str, overlay: &str) -> Result<String, ParseError> {
+    let mut document = KvDocument::parse(base)?;
+    overlay_document(&mut document, overlay, ListPolicy::default())?;
+
+    Ok(document.into_string())
+}
+
+/// Merges the key-values string `overlay` on top of `base` like [`merge`], using the dialect
+/// described by `options` and merging lists according to `lists`.
+///
+/// ```
+/// use serde_keyvalue::{merge_with, ListPolicy, Preset};
+///
+/// let merged = merge_with(
+///     "level=info tags=[a,b]",
+///     r#"tags=[c] msg="hello world""#,
+///     Preset::Logfmt,
+///     ListPolicy::Append,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(merged, r#"level=info tags=[a,b,c] msg="hello world""#);
+/// ```
+pub fn merge_with<O>(
+    base: &str,
+    overlay: &str,
+    options: O,
+    lists: ListPolicy,
+) -> Result<String, ParseError>
+where
+    O: Into<SerializeOptions> + Into<DeserializeOptions> + Clone,
+{
+    let mut document = KvDocument::parse_with(base, options)?;
+    overlay_document(&mut document, overlay, lists)?;
+
+    Ok(document.into_string())
+}
+
+/// Merges the key-values strings of `layers` in order, each one taking precedence over the previous
+/// ones, and deserializes the result into `T`.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::merge_into;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Net {
+///     mtu: u16,
+///     tap: String,
+/// }
+///
+/// let net: Net = merge_into(["mtu=1500,tap=tap0", "mtu=9000", ""]).unwrap();
+/// assert_eq!(net, Net { mtu: 9000, tap: "tap0".into() });
+/// ```
+pub fn merge_into<'a, T: DeserializeOwned>(
+    layers: impl IntoIterator<Item = &'a str>,
+) -> Result<T, ParseError> {
+    let mut document = KvDocument::parse("")?;
+    for layer in layers {
+        overlay_document(&mut document, layer, ListPolicy::default())?;
+    }
+
+    from_key_values_with(document.as_str(), document.deserialize_options().clone())
+}
+
+/// Source of the values of the keys missing from a key-values string, consulted by
+/// [`from_key_values_with_defaults`].
+///
+/// It is implemented for key-values strings, e.g. the contents of a system-wide defaults file
+/// written in the same dialect as the input, for [`Map`]s, and for closures returning the
+/// unquoted value of a key, or `None` if it has no default.
+pub trait Defaults {
+    /// Sets the default value of `key` in `document`, which does not contain it, if there is one.
+    fn set_default(&self, key: &str, document: &mut KvDocument) -> Result<(), ParseError>;
+}
+
+impl Defaults for str {
+    fn set_default(&self, key: &str, document: &mut KvDocument) -> Result<(), ParseError> {
+        let mut default = None;
+        for pair in Scanner::new(self, document.deserialize_options().clone()) {
+            let pair = pair?;
+            if pair.key == key {
+                default = Some(pair);
+            }
+        }
+
+        if let Some(pair) = default {
+            apply_pair(document, pair, ListPolicy::Replace);
+        }
+        Ok(())
+    }
+}
+
+impl Defaults for Map {
+    fn set_default(&self, key: &str, document: &mut KvDocument) -> Result<(), ParseError> {
+        let Some(value) = self.get(key) else {
+            return Ok(());
+        };
+
+        let mut serializer = KeyValueSerializer::with_options(document.serialize_options().clone());
+        [(key, value)]
+            .serialize(&mut serializer)
+            .map_err(|_| ParseError {
+                kind: ErrorKind::InvalidValue {
+                    key: key.to_owned(),
+                    message: "default value cannot be written in the dialect of the input".into(),
+                },
+                pos: 0,
+            })?;
+        overlay_document(document, &serializer.into_output(), ListPolicy::Replace)
+    }
+}
+
+impl<F: Fn(&str) -> Option<String>> Defaults for F {
+    fn set_default(&self, key: &str, document: &mut KvDocument) -> Result<(), ParseError> {
+        if let Some(value) = self(key) {
+            document.set(key, &value);
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes `input`, written in the dialect described by `options`, into `T`, taking the
+/// values of the fields of `T` missing from `input` from `defaults` before falling back to their
+/// `#[serde(default)]` value, e.g. to apply a user-provided string on top of a system-wide
+/// defaults file with a single call.
+///
+/// Only the fields of `T` itself are looked up, under their serialized name, so types without a
+/// fixed set of fields, like maps or structs with flattened fields, get no defaults. The values
+/// of `defaults` are appended to `input`, so the positions of the errors met in `input` are
+/// unchanged, and the ones of the errors met in default values are past its end.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::{from_key_values_with_defaults, Map, Preset, Value};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Net {
+///     mtu: u16,
+///     tap: String,
+///     #[serde(default)]
+///     vhost: bool,
+/// }
+///
+/// let system = "mtu=1500 tap=tap0";
+/// let net: Net = from_key_values_with_defaults("mtu=9000", system, Preset::Logfmt).unwrap();
+/// assert_eq!(net, Net { mtu: 9000, tap: "tap0".into(), vhost: false });
+///
+/// let defaults = Map::from_iter([("tap", Value::String("tap 1".into()))]);
+/// let net: Net = from_key_values_with_defaults("mtu=1500", &defaults, Preset::Logfmt).unwrap();
+/// assert_eq!(net.tap, "tap 1");
+///
+/// let env = |key: &str| (key == "tap").then(|| "tap2".to_string());
+/// let net: Net = from_key_values_with_defaults("mtu=1500 vhost", &env, Preset::Logfmt).unwrap();
+/// assert_eq!(net, Net { mtu: 1500, tap: "tap2".into(), vhost: true });
+/// ```
+pub fn from_key_values_with_defaults<T, D, O>(
+    input: &str,
+    defaults: &D,
+    options: O,
+) -> Result<T, ParseError>
+where
+    T: DeserializeOwned,
+    D: Defaults + ?Sized,
+    O: Into<SerializeOptions> + Into<DeserializeOptions> + Clone,
+{
+    let mut document = KvDocument::parse_with(input, options)?;
+    for (field, _) in trace_fields::<T>().unwrap_or_default() {
+        if !document.contains_key(field) {
+            defaults.set_default(field, &mut document)?;
+        }
+    }
+
+    from_key_values_with(document.as_str(), document.deserialize_options().clone())
+}
+
+/// Applies the pairs of `overlay` to `document`.
+fn overlay_document(
+    document: &mut KvDocument,
+    overlay: &str,
+    lists: ListPolicy,
+) -> Result<(), ParseError> {
+    for pair in Scanner::new(overlay, document.deserialize_options().clone()) {
+        apply_pair(document, pair?, lists);
+    }
+
+    Ok(())
+}
+
+/// Sets `pair` in `document`.
+fn apply_pair(document: &mut KvDocument, pair: ScannedPair, lists: ListPolicy) {
+    match pair.value {
+        ScannedValue::None => document.set_flag(pair.key),
+        ScannedValue::String(value) => document.set(pair.key, &value),
+        ScannedValue::Bracketed(list) => {
+            let base_list = match lists {
+                ListPolicy::Replace => None,
+                ListPolicy::Append => document
+                    .pairs()
+                    .filter(|base| base.key == pair.key)
+                    .last()
+                    .and_then(|base| match base.value {
+                        ScannedValue::Bracketed(base_list) => Some(base_list.to_owned()),
+                        _ => None,
+                    }),
+            };
+
+            match base_list {
+                Some(base_list) => {
+                    let list = append_list(&base_list, list);
+                    document.set_verbatim(pair.key, &list);
+                }
+                None => document.set_verbatim(pair.key, list),
+            }
+        }
+    }
+}
+
+/// Returns the list containing the elements of the bracketed list `base` followed by the ones of
+/// `other`.
+fn append_list(base: &str, other: &str) -> String {
+    let base = &base[1..base.len() - 1];
+    let other = &other[1..other.len() - 1];
+
+    match (base.is_empty(), other.is_empty()) {
+        (_, true) => format!("[{}]", base),
+        (true, false) => format!("[{}]", other),
+        (false, false) => format!("[{},{}]", base, other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn merge_pairs() {
+        assert_eq!(merge("a=1 b=2", "b=3 c").unwrap(), "a=1 b=3 c");
+        assert_eq!(merge("", "a=1").unwrap(), "a=1");
+        assert_eq!(merge("a=1", "").unwrap(), "a=1");
+        assert_eq!(merge("a=1", "a=[x]").unwrap(), "a=[x]");
+        assert_eq!(merge(r#"a="x y""#, "a='z w'").unwrap(), r#"a="z w""#);
+    }
+
+    #[test]
+    fn merge_lists() {
+        let append = |base, overlay| {
+            merge_with(
+                base,
+                overlay,
+                SerializeOptions::default(),
+                ListPolicy::Append,
+            )
+        };
+
+        assert_eq!(append("l=[1,2]", "l=[3]").unwrap(), "l=[1,2,3]");
+        assert_eq!(append("l=[]", "l=[3]").unwrap(), "l=[3]");
+        assert_eq!(append("l=[1]", "l=[]").unwrap(), "l=[1]");
+        assert_eq!(append("l=1", "l=[2]").unwrap(), "l=[2]");
+        assert_eq!(append("", "l=[2]").unwrap(), "l=[2]");
+    }
+
+    #[test]
+    fn merge_defaults() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Disk {
+            path: String,
+            #[serde(default)]
+            ro: bool,
+            queues: Vec<u8>,
+            #[serde(default)]
+            id: Option<String>,
+        }
+
+        let options = SerializeOptions::new().quoting(crate::Quoting::WhenNeeded);
+        let system = "ro queues=[1,2] path=/dev/vda id=x unknown=1";
+        let disk: Disk =
+            from_key_values_with_defaults("path=/dev/vdb", system, options.clone()).unwrap();
+        assert_eq!(
+            disk,
+            Disk {
+                path: "/dev/vdb".into(),
+                ro: true,
+                queues: vec![1, 2],
+                id: Some("x".into()),
+            }
+        );
+
+        let defaults = Map::from_iter([
+            ("queues", crate::Value::List(vec![crate::Value::Int(4)])),
+            ("id", crate::Value::String("a b".into())),
+        ]);
+        let disk: Disk =
+            from_key_values_with_defaults("path=p", &defaults, options.clone()).unwrap();
+        assert_eq!(disk.queues, [4]);
+        assert_eq!(disk.id.as_deref(), Some("a b"));
+
+        let none = |_: &str| None;
+        let disk: Disk =
+            from_key_values_with_defaults("path=p queues=[]", &none, options.clone()).unwrap();
+        assert_eq!(disk.id, None);
+
+        // Errors in the input keep their position, errors in defaults are past its end.
+        let err =
+            from_key_values_with_defaults::<Disk, _, _>("path=p queues=[x]", "", options.clone())
+                .unwrap_err();
+        assert_eq!(err.pos, 15);
+        let err =
+            from_key_values_with_defaults::<Disk, _, _>("path=p", "queues=[x]", options.clone())
+                .unwrap_err();
+        assert_eq!(err.pos, 15);
+        assert_eq!(
+            from_key_values_with_defaults::<Disk, _, _>("path=p", "a=[", options.clone())
+                .unwrap_err()
+                .kind,
+            ErrorKind::ExpectedCloseBracket
+        );
+    }
+
+    #[test]
+    fn merge_errors() {
+        assert_eq!(
+            merge("a=1", "b=[1").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 4,
+            }
+        );
+        assert_eq!(
+            merge_into::<(u8, u8)>(["a=1"]).unwrap_err().kind,
+            ErrorKind::ExpectedOpenBracket
+        );
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Records of `/etc/fstab` and `/proc/self/mountinfo`.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::from_key_values_with;
+use crate::key_values::BYTE_ORDER_MARK;
+use crate::options::Preset;
+use crate::ErrorKind;
+use crate::Map;
+use crate::ParseError;
+
+/// An entry of `/etc/fstab`, whose mount options are deserialized into `T`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FstabEntry<T = Map> {
+    /// Block device or remote filesystem to be mounted, e.g. `/dev/sda1` or `UUID=...`.
+    pub spec: String,
+    /// Mount point of the filesystem.
+    pub file: String,
+    /// Type of the filesystem.
+    pub vfstype: String,
+    /// Mount options.
+    pub options: T,
+    /// Whether the filesystem needs to be dumped. Defaults to `0`.
+    pub freq: u32,
+    /// Order in which filesystems are checked at boot time. Defaults to `0`.
+    pub passno: u32,
+}
+
+/// An entry of `/proc/<pid>/mountinfo`, whose per-mount options are deserialized into `M` and
+/// per-superblock options into `S`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MountInfo<M = Map, S = Map> {
+    /// Unique identifier of the mount.
+    pub mount_id: u32,
+    /// Identifier of the parent mount.
+    pub parent_id: u32,
+    /// Major number of the device of the filesystem.
+    pub major: u32,
+    /// Minor number of the device of the filesystem.
+    pub minor: u32,
+    /// Directory of the filesystem forming the root of the mount.
+    pub root: String,
+    /// Mount point, relative to the root directory of the process.
+    pub mount_point: String,
+    /// Per-mount options.
+    pub mount_options: M,
+    /// Optional fields, e.g. `shared:1` or `master:2`.
+    pub optional_fields: Vec<String>,
+    /// Type of the filesystem.
+    pub fs_type: String,
+    /// Filesystem-specific source, or `none`.
+    pub source: String,
+    /// Per-superblock options.
+    pub super_options: S,
+}
+
+/// Returns an iterator over the entries of the fstab file `input`.
+///
+/// Blank lines and `#` comment lines are skipped. Octal escapes such as `\040` for spaces are
+/// decoded in the text fields, and the options are deserialized with [`Preset::MountOptions`].
+/// Positions of errors are relative to the start of `input`.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::fstab_entries;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Options {
+///     #[serde(default)]
+///     ro: bool,
+///     size: Option<String>,
+/// }
+///
+/// let fstab = "# <spec> <file> <type> <options> <dump> <pass>\n\
+///              /dev/sda1  /          ext4   ro             0 1\n\
+///              tmpfs      /my\\040tmp tmpfs  size=10M,mode=1777\n";
+/// let entries = fstab_entries::<Options>(fstab).collect::<Result<Vec<_>, _>>().unwrap();
+///
+/// assert_eq!(entries[0].options, Options { ro: true, size: None });
+/// assert_eq!(entries[0].passno, 1);
+/// assert_eq!(entries[1].file, "/my tmp");
+/// assert_eq!(entries[1].options, Options { ro: false, size: Some("10M".into()) });
+/// ```
+pub fn fstab_entries<'a, T: Deserialize<'a>>(
+    input: &'a str,
+) -> impl Iterator<Item = Result<FstabEntry<T>, ParseError>> + 'a {
+    lines(input)
+        .filter(|(_, line)| !line.trim_start().starts_with('#'))
+        .map(|(pos, line)| {
+            let mut fields = Fields::new(pos, line);
+            let entry = FstabEntry {
+                spec: fields.next_string()?,
+                file: fields.next_string()?,
+                vfstype: fields.next_string()?,
+                options: fields.next_options()?,
+                freq: fields.next_optional_number()?.unwrap_or(0),
+                passno: fields.next_optional_number()?.unwrap_or(0),
+            };
+            fields.finish()?;
+
+            Ok(entry)
+        })
+}
+
+/// Returns an iterator over the entries of the mountinfo file `input`, as described in
+/// `proc_pid_mountinfo(5)`.
+///
+/// Octal escapes such as `\040` for spaces are decoded in the text fields, and the options are
+/// deserialized with [`Preset::MountOptions`]. Positions of errors are relative to the start of
+/// `input`.
+///
+/// ```
+/// use serde_keyvalue::{mountinfo_entries, Map, MountInfo, Value};
+///
+/// let mountinfo =
+///     "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue\n";
+/// let entry: MountInfo = mountinfo_entries(mountinfo).next().unwrap().unwrap();
+///
+/// assert_eq!((entry.major, entry.minor), (98, 0));
+/// assert_eq!(entry.mount_options.get("noatime"), Some(&Value::Bool(true)));
+/// assert_eq!(entry.optional_fields, ["master:1"]);
+/// assert_eq!(entry.super_options.get("errors"), Some(&Value::String("continue".into())));
+/// ```
+pub fn mountinfo_entries<'a, M, S>(
+    input: &'a str,
+) -> impl Iterator<Item = Result<MountInfo<M, S>, ParseError>> + 'a
+where
+    M: Deserialize<'a>,
+    S: Deserialize<'a>,
+{
+    lines(input).map(|(pos, line)| {
+        let mut fields = Fields::new(pos, line);
+        let mount_id = fields.next_number()?;
+        let parent_id = fields.next_number()?;
+        let (device_pos, device) = fields.next_field()?;
+        let (major, minor) = device.split_once(':').ok_or(ParseError {
+            kind: ErrorKind::InvalidNumber,
+            pos: device_pos,
+        })?;
+        let root = fields.next_string()?;
+        let mount_point = fields.next_string()?;
+        let mount_options = fields.next_options()?;
+        let mut optional_fields = Vec::new();
+        loop {
+            match fields.next_field()? {
+                (_, "-") => break,
+                (_, field) => optional_fields.push(field.to_owned()),
+            }
+        }
+
+        let entry = MountInfo {
+            mount_id,
+            parent_id,
+            major: parse_number(device_pos, major)?,
+            minor: parse_number(device_pos + major.len() + 1, minor)?,
+            root,
+            mount_point,
+            mount_options,
+            optional_fields,
+            fs_type: fields.next_string()?,
+            source: fields.next_string()?,
+            super_options: fields.next_options()?,
+        };
+        fields.finish()?;
+
+        Ok(entry)
+    })
+}
+
+/// Returns the non-blank lines of `input` along with their position, skipping the byte order mark
+/// starting `input` if any.
+fn lines(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    let start = if input.starts_with(BYTE_ORDER_MARK) {
+        BYTE_ORDER_MARK.len_utf8()
+    } else {
+        0
+    };
+
+    input[start..]
+        .split('\n')
+        .scan(start, |pos, line| {
+            let start = *pos;
+            *pos += line.len() + 1;
+            Some((start, line))
+        })
+        .filter(|(_, line)| !line.trim().is_empty())
+}
+
+/// Parses the number `s` found at position `pos`.
+fn parse_number<T: FromStr>(pos: usize, s: &str) -> Result<T, ParseError> {
+    s.parse().map_err(|_| ParseError {
+        kind: ErrorKind::InvalidNumber,
+        pos,
+    })
+}
+
+/// Decodes the octal escapes of `s`, e.g. `\040` for a space.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let c = rest
+            .get(1..4)
+            .and_then(|code| u8::from_str_radix(code, 8).ok())
+            .map(char::from);
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+/// The whitespace-separated fields of a line.
+struct Fields<'a> {
+    /// Position of the line in the input.
+    pos: usize,
+    line: &'a str,
+    /// Remaining part of the line.
+    rest: &'a str,
+}
+
+impl<'a> Fields<'a> {
+    fn new(pos: usize, line: &'a str) -> Self {
+        Self {
+            pos,
+            line,
+            rest: line,
+        }
+    }
+
+    /// Returns the position in the input of the remaining part of the line.
+    fn rest_pos(&self) -> usize {
+        self.pos + self.line.len() - self.rest.len()
+    }
+
+    fn next_field_opt(&mut self) -> Option<(usize, &'a str)> {
+        self.rest = self.rest.trim_start();
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let pos = self.rest_pos();
+        let end = self
+            .rest
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest.len());
+        let (field, rest) = self.rest.split_at(end);
+        self.rest = rest;
+
+        Some((pos, field))
+    }
+
+    fn next_field(&mut self) -> Result<(usize, &'a str), ParseError> {
+        let pos = self.rest_pos();
+        self.next_field_opt().ok_or(ParseError {
+            kind: ErrorKind::Eof,
+            pos,
+        })
+    }
+
+    fn next_string(&mut self) -> Result<String, ParseError> {
+        self.next_field().map(|(_, field)| unescape(field))
+    }
+
+    fn next_number<T: FromStr>(&mut self) -> Result<T, ParseError> {
+        let (pos, field) = self.next_field()?;
+        parse_number(pos, field)
+    }
+
+    fn next_optional_number<T: FromStr>(&mut self) -> Result<Option<T>, ParseError> {
+        self.next_field_opt()
+            .map(|(pos, field)| parse_number(pos, field))
+            .transpose()
+    }
+
+    fn next_options<T: Deserialize<'a>>(&mut self) -> Result<T, ParseError> {
+        let (pos, field) = self.next_field()?;
+        from_key_values_with(field, Preset::MountOptions).map_err(|e| ParseError {
+            kind: e.kind,
+            pos: pos + e.pos,
+        })
+    }
+
+    /// Checks that all the fields of the line have been consumed.
+    fn finish(mut self) -> Result<(), ParseError> {
+        match self.next_field_opt() {
+            None => Ok(()),
+            Some((pos, _)) => Err(ParseError {
+                kind: ErrorKind::TrailingCharacters,
+                pos,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn parse_fstab() {
+        let fstab =
+            "\n  # comment\nLABEL=root / ext4 defaults,noatime 0 1\r\nproc /proc proc defaults\n";
+        let entries: Vec<FstabEntry> = fstab_entries(fstab).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].spec, "LABEL=root");
+        assert_eq!(entries[0].options.get("noatime"), Some(&Value::Bool(true)));
+        assert_eq!((entries[0].freq, entries[0].passno), (0, 1));
+        assert_eq!(entries[1].vfstype, "proc");
+        assert_eq!((entries[1].freq, entries[1].passno), (0, 0));
+
+        let mut entries = fstab_entries::<Map>("\u{feff}/dev/sda1 / ext4 ro x\r\n");
+        assert_eq!(entries.next().unwrap().unwrap_err().pos, 23);
+    }
+
+    #[test]
+    fn parse_mountinfo() {
+        let mountinfo = "22 1 0:21 / /sys rw,nosuid shared:7 - sysfs sysfs rw\n\
+                         35 22 8:1 / /media/a\\040b rw - vfat /dev/sdb1 rw,fmask=0022\n";
+        let entries: Vec<MountInfo> = mountinfo_entries(mountinfo)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(entries[0].parent_id, 1);
+        assert_eq!(entries[0].optional_fields, ["shared:7"]);
+        assert_eq!(entries[1].mount_point, "/media/a b");
+        assert!(entries[1].optional_fields.is_empty());
+        assert_eq!(entries[1].source, "/dev/sdb1");
+        assert_eq!(entries[1].super_options.get("fmask"), Some(&Value::Int(22)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut entries = fstab_entries::<Map>("/dev/sda1 / ext4 ro 0 x\n/dev/sdb1 /b\n");
+        assert_eq!(
+            entries.next().unwrap().unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 22,
+            }
+        );
+        assert_eq!(
+            entries.next().unwrap().unwrap_err(),
+            ParseError {
+                kind: ErrorKind::Eof,
+                pos: 36,
+            }
+        );
+
+        assert_eq!(
+            mountinfo_entries::<Map, Map>("1 2 3 / / rw - x y rw")
+                .next()
+                .unwrap()
+                .unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 4,
+            }
+        );
+        assert_eq!(
+            mountinfo_entries::<Map, Map>("1 2 0:3 / / rw=[ - x y rw")
+                .next()
+                .unwrap()
+                .unwrap_err()
+                .pos,
+            16
+        );
+    }
+}
@@ -0,0 +1,2000 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Configuration of the key-values serializer and deserializer.
+
+use crate::KeyValueSerializer;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// How string values are quoted by the serializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quoting {
+    /// Strings are written as-is, without any quoting or escaping. When deserializing, quotes are
+    /// ordinary characters and strings may be empty.
+    #[default]
+    Never,
+    /// Strings are double-quoted only if they are empty or contain characters that would
+    /// otherwise be interpreted as separators, delimiters, quotes or brackets.
+    WhenNeeded,
+    /// All strings are double-quoted.
+    Always,
+    /// Strings are not quoted, but backslashes, control characters, brackets and separators are
+    /// escaped with a backslash, as in Java properties files. When deserializing, quotes are
+    /// ordinary characters and backslash escapes are processed in all strings, including `\uXXXX`
+    /// code points and line continuations.
+    Escaped,
+    /// Strings are double-quoted when needed, following the rules of shell scripts and systemd
+    /// environment files: `"`, `\`, `` ` `` and `$` are escaped with a backslash, and other
+    /// characters, including newlines, are kept as-is. When deserializing, unquoted strings may
+    /// escape any character with a backslash and lose their trailing whitespace, and a backslash at
+    /// the end of a line continues the string on the next line.
+    Shell,
+    /// Strings are percent-encoded as in `application/x-www-form-urlencoded` data: characters
+    /// other than alphanumerics, `-`, `.`, `_` and `~` are written as `%XX` escapes of their UTF-8
+    /// bytes, and spaces as `+`. When deserializing, quotes are ordinary characters, `%XX` escapes
+    /// and `+` are decoded in all strings, and strings may be empty.
+    Percent,
+    /// Strings are percent-encoded like with [`Quoting::Percent`], except that spaces are written
+    /// as `%20` and `+` is an ordinary character, as in URI components or W3C baggage values.
+    Uri,
+    /// Strings are written as-is if they are tokens as defined by RFC 9110, and double-quoted
+    /// otherwise with `"` and `\` escaped with a backslash, as in the parameters of HTTP headers.
+    /// When deserializing, a backslash escapes any character in double-quoted strings, and single
+    /// quotes are ordinary characters.
+    Token,
+    /// All values, including numbers and booleans, are double-quoted, with `"`, `\` and newlines
+    /// escaped with a backslash and other characters kept as-is, as in Prometheus label values.
+    /// When deserializing, strings must be double-quoted, and numbers and booleans may be.
+    Prometheus,
+    /// All values, including numbers and booleans, are double-quoted, with `"`, `\` and `]`
+    /// escaped with a backslash and other characters kept as-is, as in the parameters of the
+    /// structured data of syslog messages defined by RFC 5424. When deserializing, strings must be
+    /// double-quoted, numbers and booleans may be, and backslashes that do not escape one of these
+    /// characters are kept.
+    Syslog,
+    /// Values, and keys when serializing, are written unquoted with commas, equals signs and
+    /// spaces escaped with a backslash, as in the tag sets of the InfluxDB line protocol, as well
+    /// as backslashes that would otherwise escape the next character, e.g. a trailing one. Other
+    /// backslashes and quotes are ordinary characters. Empty strings cannot be serialized.
+    Influx,
+    /// Values are single-quoted when empty or containing whitespace, `'`, `\` or brackets, with
+    /// `'` and `\` escaped with a backslash, as in libpq connection strings. When deserializing,
+    /// a backslash followed by any character stands for that character, in quoted and unquoted
+    /// values alike.
+    Libpq,
+    /// Values are enclosed in braces when empty, starting or ending with whitespace, or containing
+    /// `;`, braces or brackets, with `}` doubled, as in ODBC connection strings. Quotes are
+    /// ordinary characters.
+    Braces,
+    /// Values are written unquoted with the special characters of RFC 4514 escaped with a
+    /// backslash, i.e. `"`, `+`, `,`, `;`, `<`, `>` and `\`, as well as leading spaces and `#`
+    /// and trailing spaces. When deserializing, `\XX` hexadecimal escapes of UTF-8 bytes are also
+    /// accepted.
+    DistinguishedName,
+    /// Values are written verbatim, with line breaks followed by a space to fold them as in mail
+    /// headers. When deserializing, line breaks followed by a space or tab are removed, and
+    /// trailing spaces and tabs are dropped.
+    Folded,
+    /// String values are always single-quoted, with `'` written as `'\''`, so POSIX shells take
+    /// them literally. When deserializing, values are shell words made of single-quoted strings,
+    /// backslash-escaped characters and unquoted characters, ending with the next unquoted
+    /// separator, space or tab.
+    SingleQuoted,
+    /// Values are written unquoted with `'`, `\`, separators, brackets, and leading and trailing
+    /// whitespace escaped with a backslash, as in the options of FFmpeg filters, and empty values
+    /// written as `''`. When deserializing, a backslash followed by any character stands for that
+    /// character, values may be single-quoted, and trailing unescaped whitespace is dropped.
+    Ffmpeg,
+    /// Strings are double-quoted when needed, like with [`Quoting::WhenNeeded`], with `"` written
+    /// as `""` and other characters, including backslashes and newlines, kept as-is, as in CSV
+    /// files or SQL. When deserializing, `""` stands for `"` in double-quoted strings, and single
+    /// quotes and backslashes are ordinary characters.
+    Doubled,
+    /// Strings are written as-is, except that the pair separator is doubled, e.g. `a,,b` for
+    /// `a,b`, as in QEMU option strings. Strings containing brackets cannot be serialized. When
+    /// deserializing, a doubled pair separator stands for the separator itself, quotes are
+    /// ordinary characters and strings may be empty.
+    Qemu,
+    /// Strings are written as-is, as in `Cookie` and `Set-Cookie` headers, and strings that would
+    /// break the header cannot be serialized: those containing `;` or control characters, and
+    /// those containing `,` unless they are the value of an `Expires` attribute, whose dates
+    /// contain commas. Strings containing brackets cannot be serialized either. When
+    /// deserializing, quotes are ordinary characters and strings may be empty.
+    Cookie,
+    /// Strings are written as-is and must be valid Kubernetes label values: empty, or at most 63
+    /// alphanumeric ASCII characters, `-`, `_` and `.`, starting and ending with an alphanumeric
+    /// character. Other strings cannot be serialized, and are rejected when deserializing.
+    Label,
+    /// Strings are written as-is, as in files with one pair per line such as `sysctl.conf`, and
+    /// strings containing line breaks or brackets cannot be serialized. When deserializing, quotes
+    /// are ordinary characters and strings may be empty.
+    SingleLine,
+    /// Strings are double-quoted when empty or containing whitespace or brackets, as on the Linux
+    /// kernel command line, which has no escapes, so strings containing `"` cannot be serialized.
+    /// When deserializing, backslashes and single quotes are ordinary characters.
+    Kernel,
+}
+
+impl Quoting {
+    /// Returns the quote characters recognized when deserializing.
+    pub(crate) fn quotes(self) -> &'static [char] {
+        match self {
+            Quoting::WhenNeeded | Quoting::Always | Quoting::Shell => &['"', '\''],
+            Quoting::Token
+            | Quoting::Prometheus
+            | Quoting::Syslog
+            | Quoting::Doubled
+            | Quoting::Kernel => &['"'],
+            Quoting::Libpq | Quoting::Ffmpeg | Quoting::SingleQuoted => &['\''],
+            Quoting::Never
+            | Quoting::Escaped
+            | Quoting::Percent
+            | Quoting::Uri
+            | Quoting::Influx
+            | Quoting::Braces
+            | Quoting::DistinguishedName
+            | Quoting::Folded
+            | Quoting::Qemu
+            | Quoting::Cookie
+            | Quoting::Label
+            | Quoting::SingleLine => &[],
+        }
+    }
+}
+
+/// How booleans are written by the serializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoolStyle {
+    /// `True` or `False`.
+    #[default]
+    TitleCase,
+    /// `true` or `false`.
+    Lowercase,
+    /// `on` or `off`.
+    OnOff,
+    /// `yes` or `no`.
+    YesNo,
+    /// `1` or `0`.
+    Numeric,
+    /// A `true` value is written as a bare key without any value, and a `false` value is omitted
+    /// altogether.
+    Flag,
+}
+
+impl BoolStyle {
+    /// Returns the words used for `true` and `false` values in this style. [`BoolStyle::Flag`]
+    /// falls back to `true` and `false` where a bare key cannot be used.
+    pub(crate) fn words(self) -> (&'static str, &'static str) {
+        match self {
+            BoolStyle::TitleCase => ("True", "False"),
+            BoolStyle::Lowercase | BoolStyle::Flag => ("true", "false"),
+            BoolStyle::OnOff => ("on", "off"),
+            BoolStyle::YesNo => ("yes", "no"),
+            BoolStyle::Numeric => ("1", "0"),
+        }
+    }
+}
+
+/// Spacing around the delimiter between keys and values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DelimiterStyle {
+    /// No spaces around the delimiter, e.g. `key=value`.
+    #[default]
+    Compact,
+    /// A space on each side of the delimiter, e.g. `key = value`, as found in `sysctl.conf` or
+    /// Java properties files. When deserializing, any number of spaces and tabs is accepted on
+    /// either side of the delimiter, including none.
+    Spaced,
+    /// A space after the delimiter only, e.g. `key: value`, as found in mail headers. When
+    /// deserializing, any number of spaces and tabs is accepted after the delimiter, including
+    /// none.
+    SpaceAfter,
+}
+
+/// Whitespace accepted between pairs by the deserializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Whitespace {
+    /// Pairs are separated by exactly one pair separator, as written by the serializer. Other
+    /// spaces are part of unquoted values, or rejected.
+    #[default]
+    Strict,
+    /// Runs of spaces and tabs separate pairs, and are accepted around the other pair separators
+    /// and at both ends of the input, e.g. `  a=1   b=2 , c=3 `. Unquoted values end with a space
+    /// or a tab.
+    Collapse,
+    /// Like [`Whitespace::Collapse`], with newlines also separating pairs, so that a record may
+    /// span several lines.
+    Lines,
+}
+
+impl Whitespace {
+    /// Returns the whitespace characters separating pairs.
+    pub(crate) fn blanks(self) -> &'static [char] {
+        match self {
+            Whitespace::Strict => &[],
+            Whitespace::Collapse => &[' ', '\t'],
+            Whitespace::Lines => &[' ', '\t', '\n', '\r'],
+        }
+    }
+}
+
+/// How `None` values are written by the serializer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoneStyle {
+    /// The key-value pair is omitted from the output.
+    #[default]
+    Skip,
+    /// The key is written with an empty value, e.g. `key=`.
+    Empty,
+    /// Serialization fails.
+    Error,
+}
+
+/// How line breaks in double-quoted values are written by the serializer, with
+/// [`Quoting::WhenNeeded`] or [`Quoting::Always`]. The deserializer accepts both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewlineStyle {
+    /// Line breaks are escaped, e.g. `trace="line 1\nline 2"`, so each pair stays on one line.
+    #[default]
+    Escaped,
+    /// Line breaks are written verbatim between the quotes, e.g. to carry a certificate or a stack
+    /// trace as it reads. The closing quote still ends the value, so pairs separated by newlines
+    /// remain unambiguous.
+    Literal,
+}
+
+/// How empty values, e.g. `key=`, are deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyValues {
+    /// Empty values are parsed like any other value: they are empty strings if the quoting accepts
+    /// unquoted empty strings, e.g. [`Quoting::Never`], and rejected otherwise.
+    #[default]
+    Parsed,
+    /// Empty values are empty strings, whatever the quoting, and `Some("")` for `Option` fields.
+    EmptyString,
+    /// Empty values of `Option` fields are `None`, as written with [`NoneStyle::Empty`]. Other
+    /// empty values are parsed as with [`EmptyValues::Parsed`].
+    None,
+    /// Empty values are rejected with an [`ErrorKind::EmptyValue`](crate::ErrorKind::EmptyValue)
+    /// error, whatever their type.
+    Error,
+}
+
+/// How integers that do not fit the type they are deserialized into are handled, e.g. `300` for a
+/// `u8` or `-1` for a `u32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// The value is rejected with an [`ErrorKind::OutOfRange`](crate::ErrorKind::OutOfRange)
+    /// error naming its key.
+    #[default]
+    Error,
+    /// The value is clamped to the nearest bound of the type, e.g. `255` for `300` and a `u8`.
+    Saturate,
+    /// The value wraps around the bounds of the type, e.g. `44` for `300` and a `u8`, as done by
+    /// an `as` cast.
+    Wrap,
+}
+
+/// Naming convention to which keys are converted by the deserializer before being matched to the
+/// names of fields, see [`DeserializeOptions::key_case`].
+///
+/// Keys are split into words at `-` and `_` characters and where an uppercase letter follows a
+/// lowercase letter or a digit, e.g. `maxConn`, or starts a word after an acronym, e.g.
+/// `HTTPServer`. Other characters, such as the `.` of nested keys, are kept and start a new key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    /// Lowercase words separated by underscores, e.g. `max_conn`, the convention of Rust fields.
+    Snake,
+    /// Lowercase words separated by hyphens, e.g. `max-conn`.
+    Kebab,
+    /// Words written together, each but the first starting with an uppercase letter, e.g.
+    /// `maxConn`.
+    Camel,
+}
+
+impl KeyCase {
+    /// Returns `key` written in this convention.
+    pub(crate) fn convert(self, key: &str) -> String {
+        let mut converted = String::with_capacity(key.len() + 4);
+        // Whether the next word is the first one of the key, and whether a word ends before the
+        // next letter or digit.
+        let mut first_word = true;
+        let mut word_break = false;
+        let mut previous: Option<char> = None;
+
+        let mut chars = key.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '-' || c == '_' {
+                word_break = !first_word;
+                previous = None;
+                continue;
+            }
+            if !c.is_alphanumeric() {
+                converted.push(c);
+                first_word = true;
+                word_break = false;
+                previous = None;
+                continue;
+            }
+
+            let starts_word = word_break
+                || c.is_uppercase()
+                    && previous.is_some_and(|p| {
+                        p.is_lowercase()
+                            || p.is_numeric()
+                            || p.is_uppercase() && chars.peek().is_some_and(|n| n.is_lowercase())
+                    });
+            match self {
+                KeyCase::Snake if starts_word && !first_word => converted.push('_'),
+                KeyCase::Kebab if starts_word && !first_word => converted.push('-'),
+                _ => (),
+            }
+            if self == KeyCase::Camel && starts_word && !first_word {
+                converted.extend(c.to_uppercase());
+            } else {
+                converted.extend(c.to_lowercase());
+            }
+
+            first_word = false;
+            word_break = false;
+            previous = Some(c);
+        }
+
+        converted
+    }
+}
+
+/// Options controlling the output of [`KeyValueSerializer`].
+///
+/// Options are built by chaining setters on the default options:
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::{BoolStyle, KeyValueSerializer, Quoting, SerializeOptions};
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     name: String,
+///     active: bool,
+///     threads: Option<u8>,
+/// }
+///
+/// let options = SerializeOptions::new()
+///     .pair_separator(",")
+///     .quoting(Quoting::WhenNeeded)
+///     .bool_style(BoolStyle::Lowercase);
+///
+/// let config = Config { name: "my disk".into(), active: true, threads: None };
+/// let mut serializer = KeyValueSerializer::with_options(options);
+/// config.serialize(&mut serializer).unwrap();
+///
+/// assert_eq!(serializer.into_output(), r#"name="my disk",active=true"#);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializeOptions {
+    pub(crate) pair_separator: String,
+    pub(crate) key_value_delimiter: char,
+    pub(crate) delimiter_style: DelimiterStyle,
+    pub(crate) tuple_separator: char,
+    pub(crate) bracketed_tuples: bool,
+    pub(crate) braced_structs: bool,
+    pub(crate) bare_struct_lists: bool,
+    #[cfg(feature = "json")]
+    pub(crate) embedded_json: bool,
+    pub(crate) list_separator: Option<char>,
+    pub(crate) repeated_keys: bool,
+    pub(crate) indexed_lists: bool,
+    pub(crate) quoting: Quoting,
+    pub(crate) bare_empty_strings: bool,
+    pub(crate) bool_style: BoolStyle,
+    pub(crate) none_style: NoneStyle,
+    pub(crate) newline_style: NewlineStyle,
+    pub(crate) positional_first_field: bool,
+    pub(crate) nested_key_separator: Option<char>,
+    pub(crate) key_prefix: String,
+    pub(crate) sections: bool,
+    pub(crate) max_len: Option<usize>,
+    pub(crate) byte_budget: Option<usize>,
+    pub(crate) record_separator: String,
+    pub(crate) include_keys: Option<Vec<String>>,
+    pub(crate) exclude_keys: Vec<String>,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        Self {
+            pair_separator: " ".into(),
+            key_value_delimiter: '=',
+            delimiter_style: DelimiterStyle::default(),
+            tuple_separator: ':',
+            bracketed_tuples: false,
+            braced_structs: false,
+            bare_struct_lists: false,
+            #[cfg(feature = "json")]
+            embedded_json: false,
+            list_separator: None,
+            repeated_keys: false,
+            indexed_lists: false,
+            quoting: Quoting::default(),
+            bare_empty_strings: false,
+            bool_style: BoolStyle::default(),
+            none_style: NoneStyle::default(),
+            newline_style: NewlineStyle::default(),
+            positional_first_field: false,
+            nested_key_separator: None,
+            key_prefix: String::new(),
+            sections: false,
+            max_len: None,
+            byte_budget: None,
+            record_separator: "\n".into(),
+            include_keys: None,
+            exclude_keys: Vec::new(),
+        }
+    }
+}
+
+impl SerializeOptions {
+    /// Returns the default options: pairs separated by a space, keys and values delimited by `=`,
+    /// tuple elements separated by `:`, unquoted strings and `True`/`False` booleans.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the string written between two key-value pairs.
+    pub fn pair_separator(mut self, separator: impl Into<String>) -> Self {
+        self.pair_separator = separator.into();
+        self
+    }
+
+    /// Sets the character written between a key and its value.
+    pub fn key_value_delimiter(mut self, delimiter: char) -> Self {
+        self.key_value_delimiter = delimiter;
+        self
+    }
+
+    /// Sets the spacing written around the key-value delimiter.
+    pub fn delimiter_style(mut self, style: DelimiterStyle) -> Self {
+        self.delimiter_style = style;
+        self
+    }
+
+    /// Sets the character used to separate the elements of tuples and tuple structs.
+    pub fn tuple_separator(mut self, separator: char) -> Self {
+        self.tuple_separator = separator;
+        self
+    }
+
+    /// Sets whether tuples and tuple structs are written like sequences, enclosed in brackets with
+    /// their elements separated by commas, e.g. `size=[320,200]`, instead of being separated by the
+    /// tuple separator.
+    pub fn bracketed_tuples(mut self, bracketed: bool) -> Self {
+        self.bracketed_tuples = bracketed;
+        self
+    }
+
+    /// Sets whether nested structs are enclosed in braces rather than brackets, e.g.
+    /// `Ebs={VolumeSize=20}`, as in the shorthand syntax of the AWS CLI. Strings containing braces
+    /// are then quoted like those containing brackets.
+    pub fn braced_structs(mut self, braced: bool) -> Self {
+        self.braced_structs = braced;
+        self
+    }
+
+    /// Sets whether sequences of structs are written without brackets of their own, as their
+    /// elements enclosed in brackets and separated by commas, e.g. `disks=[path=/a],[path=/b]`
+    /// instead of `disks=[[path=/a],[path=/b]]`. The deserializer accepts both forms.
+    ///
+    /// Sequences whose first element is a struct without any field keep their brackets, as do
+    /// sequences holding other values.
+    pub fn bare_struct_lists(mut self, bare: bool) -> Self {
+        self.bare_struct_lists = bare;
+        self
+    }
+
+    /// Sets whether the values of pairs that are structs or maps, or sequences holding some, are
+    /// written as compact JSON, e.g. `limits={"cpu":2,"mem":"4G"}`, while other values keep the
+    /// key-values syntax. [`DeserializeOptions::embedded_json`] reads them back.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::{KeyValueSerializer, SerializeOptions};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Limits {
+    ///     cpu: u32,
+    ///     mem: String,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Job {
+    ///     name: String,
+    ///     limits: Limits,
+    /// }
+    ///
+    /// let job = Job { name: "build".into(), limits: Limits { cpu: 2, mem: "4G".into() } };
+    /// let mut serializer =
+    ///     KeyValueSerializer::with_options(SerializeOptions::new().embedded_json(true));
+    /// job.serialize(&mut serializer).unwrap();
+    /// assert_eq!(serializer.into_output(), r#"name=build limits={"cpu":2,"mem":"4G"}"#);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn embedded_json(mut self, embedded: bool) -> Self {
+        self.embedded_json = embedded;
+        self
+    }
+
+    /// Writes sequences without brackets, as their elements separated by `separator`, e.g.
+    /// `irq=5,7,9`. By default, sequences are enclosed in brackets, e.g. `irq=[5,7,9]`.
+    pub fn list_separator(mut self, separator: char) -> Self {
+        self.list_separator = Some(separator);
+        self
+    }
+
+    /// Sets whether sequences are written as one pair per element, all with the key of the
+    /// sequence, e.g. `tag=a&tag=b`. Empty sequences are then omitted.
+    pub fn repeated_keys(mut self, repeated: bool) -> Self {
+        self.repeated_keys = repeated;
+        self
+    }
+
+    /// Sets whether sequences are written as one pair per element, keyed by the key of the
+    /// sequence, the nested key separator and the index of the element, e.g.
+    /// `route.0=10.0.0.0/24 route.1=192.168.0.0/16`, so values never hold commas or brackets.
+    /// Empty sequences are then omitted. This has no effect without a nested key separator.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::{KeyValueSerializer, SerializeOptions};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Net {
+    ///     route: Vec<&'static str>,
+    ///     mtu: u16,
+    /// }
+    ///
+    /// let net = Net { route: vec!["10.0.0.0/24", "192.168.0.0/16"], mtu: 1500 };
+    /// let options = SerializeOptions::new().nested_key_separator('.').indexed_lists(true);
+    /// let mut serializer = KeyValueSerializer::with_options(options);
+    /// net.serialize(&mut serializer).unwrap();
+    /// assert_eq!(
+    ///     serializer.into_output(),
+    ///     "route.0=10.0.0.0/24 route.1=192.168.0.0/16 mtu=1500"
+    /// );
+    /// ```
+    pub fn indexed_lists(mut self, indexed: bool) -> Self {
+        self.indexed_lists = indexed;
+        self
+    }
+
+    /// Sets how string values are quoted.
+    pub fn quoting(mut self, quoting: Quoting) -> Self {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Sets whether empty strings are written as nothing, e.g. `key=`, even if the quoting would
+    /// quote them. Deserializers accept them with [`EmptyValues::EmptyString`]. Defaults to
+    /// `false`.
+    pub fn bare_empty_strings(mut self, bare: bool) -> Self {
+        self.bare_empty_strings = bare;
+        self
+    }
+
+    /// Sets how booleans are written.
+    pub fn bool_style(mut self, style: BoolStyle) -> Self {
+        self.bool_style = style;
+        self
+    }
+
+    /// Sets how `None` values are written.
+    pub fn none_style(mut self, style: NoneStyle) -> Self {
+        self.none_style = style;
+        self
+    }
+
+    /// Sets how line breaks are written in double-quoted values.
+    pub fn newline_style(mut self, style: NewlineStyle) -> Self {
+        self.newline_style = style;
+        self
+    }
+
+    /// Sets whether the first field of the top-level struct is written as its value alone, without
+    /// its key, e.g. `virtio-blk,drive=disk0`. The deserializer always accepts a value without a
+    /// key for the first field. Values that would not be read back as such, i.e. empty values and
+    /// unquoted values starting with a key and a delimiter, e.g. `file=a`, cannot be serialized.
+    pub fn positional_first_field(mut self, positional: bool) -> Self {
+        self.positional_first_field = positional;
+        self
+    }
+
+    /// Writes nested structs and maps as their pairs, with keys prefixed by the key of the nested
+    /// value and `separator`, e.g. `cache.direct=on`. By default, nested structs are written as
+    /// their pairs enclosed in brackets, e.g. `cache=[direct=on]`, and nested maps are rejected.
+    pub fn nested_key_separator(mut self, separator: char) -> Self {
+        self.nested_key_separator = Some(separator);
+        self
+    }
+
+    /// Writes `prefix` before each key, e.g. the `export ` prefix of shell variables.
+    pub fn key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = prefix.into();
+        self
+    }
+
+    /// Writes the structs and maps nested in the top-level value as INI sections, i.e. a
+    /// `[key]` header followed by their pairs, after the other pairs and separated from them by
+    /// an empty pair. The pairs of sections are not prefixed.
+    pub fn sections(mut self, enabled: bool) -> Self {
+        self.sections = enabled;
+        self
+    }
+
+    /// Limits the output to `max_len` bytes, e.g. to stay within the line length limit of a log
+    /// shipper. Longer outputs are cut after the last pair that fits and end with a
+    /// `…truncated=N` pair instead, `N` being the number of pairs dropped, so values are never
+    /// cut in the middle. Sections are kept or dropped as a whole, and count as one pair. The
+    /// marker is written even if no pair fits before it.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::SerializeOptions;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Request {
+    ///     method: String,
+    ///     path: String,
+    ///     status: u16,
+    /// }
+    ///
+    /// let request = Request { method: "GET".into(), path: "/index.html".into(), status: 200 };
+    /// let mut serializer = SerializeOptions::new().max_len(32).build();
+    /// request.serialize(&mut serializer).unwrap();
+    ///
+    /// assert_eq!(serializer.into_output(), "method=GET …truncated=2");
+    /// ```
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Makes serialization fail as soon as the output exceeds `budget` bytes, e.g. for protocols
+    /// rejecting larger records. The key of the pair whose value exceeded the budget can then be
+    /// retrieved with [`KeyValueSerializer::budget_exceeded`]. Unlike [`max_len`](Self::max_len),
+    /// which is applied once serialization is complete, the budget applies to the whole output.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::SerializeOptions;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Record {
+    ///     id: u32,
+    ///     payload: String,
+    /// }
+    ///
+    /// let record = Record { id: 1, payload: "x".repeat(64) };
+    /// let mut serializer = SerializeOptions::new().byte_budget(32).build();
+    /// assert!(record.serialize(&mut serializer).is_err());
+    ///
+    /// let exceeded = serializer.budget_exceeded().unwrap();
+    /// assert_eq!(exceeded.key, "payload");
+    /// assert_eq!(
+    ///     exceeded.to_string(),
+    ///     "value of `payload` exceeds the output budget of 32 bytes"
+    /// );
+    /// ```
+    pub fn byte_budget(mut self, budget: usize) -> Self {
+        self.byte_budget = Some(budget);
+        self
+    }
+
+    /// Sets the string written after each record by [`RecordWriter`](crate::RecordWriter), e.g.
+    /// `"\0"` for environ blobs. Defaults to `"\n"`.
+    pub fn record_separator(mut self, separator: impl Into<String>) -> Self {
+        self.record_separator = separator.into();
+        self
+    }
+
+    /// Writes only the pairs whose key is one of `keys`, e.g. to log a view of a large struct
+    /// without defining another type for it. With a [nested key
+    /// separator](Self::nested_key_separator), `keys` may name nested pairs, e.g. `net.mtu`, and
+    /// naming a nested struct or map keeps all its pairs. Otherwise, nested values are kept or
+    /// dropped as a whole.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::SerializeOptions;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Net {
+    ///     mtu: u32,
+    ///     mac: String,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Vm {
+    ///     name: String,
+    ///     mem: u64,
+    ///     net: Net,
+    /// }
+    ///
+    /// let vm = Vm { name: "vm0".into(), mem: 1024, net: Net { mtu: 1500, mac: "x".into() } };
+    /// let options = SerializeOptions::new().nested_key_separator('.');
+    ///
+    /// let mut serializer = options.clone().include_keys(&["name", "net.mtu"]).build();
+    /// vm.serialize(&mut serializer).unwrap();
+    /// assert_eq!(serializer.into_output(), "name=vm0 net.mtu=1500");
+    ///
+    /// let mut serializer = options.exclude_keys(&["net"]).build();
+    /// vm.serialize(&mut serializer).unwrap();
+    /// assert_eq!(serializer.into_output(), "name=vm0 mem=1024");
+    /// ```
+    pub fn include_keys(mut self, keys: &[&str]) -> Self {
+        self.include_keys = Some(keys.iter().map(|&key| key.into()).collect());
+        self
+    }
+
+    /// Omits the pairs whose key is one of `keys`, along with the pairs nested in them. See
+    /// [`include_keys`](Self::include_keys) for how keys are matched. Exclusions take precedence
+    /// over inclusions.
+    pub fn exclude_keys(mut self, keys: &[&str]) -> Self {
+        self.exclude_keys = keys.iter().map(|&key| key.into()).collect();
+        self
+    }
+
+    /// Creates a serializer using these options.
+    pub fn build(self) -> KeyValueSerializer {
+        KeyValueSerializer::with_options(self)
+    }
+}
+
+impl From<Preset> for SerializeOptions {
+    fn from(preset: Preset) -> Self {
+        preset.serialize_options()
+    }
+}
+
+/// Function checking the value of a pair given its key, see [`DeserializeOptions::validator`].
+pub(crate) type Validator = fn(&str, &str) -> Result<(), String>;
+
+/// Function returning the value referenced by a value given the part of it following its scheme,
+/// see [`DeserializeOptions::resolver`].
+pub(crate) type Resolver = fn(&str) -> Result<String, String>;
+
+/// Options controlling the syntax accepted by
+/// [`KeyValueDeserializer`](crate::KeyValueDeserializer).
+///
+/// The options mirror [`SerializeOptions`], and can be obtained from them so both directions are
+/// guaranteed to agree on the syntax:
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_keyvalue::{
+///     from_key_values_with, BoolStyle, DeserializeOptions, KeyValueSerializer, Quoting,
+///     SerializeOptions,
+/// };
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Port {
+///     name: String,
+///     range: (u16, u16),
+///     enabled: bool,
+/// }
+///
+/// let options = SerializeOptions::new()
+///     .pair_separator(";")
+///     .quoting(Quoting::WhenNeeded)
+///     .bool_style(BoolStyle::OnOff);
+///
+/// let port = Port { name: "web server".into(), range: (80, 88), enabled: true };
+/// let mut serializer = KeyValueSerializer::with_options(options.clone());
+/// port.serialize(&mut serializer).unwrap();
+/// let output = serializer.into_output();
+/// assert_eq!(output, r#"name="web server";range=80:88;enabled=on"#);
+///
+/// let parsed: Port = from_key_values_with(&output, DeserializeOptions::from(&options)).unwrap();
+/// assert_eq!(parsed, port);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializeOptions {
+    pub(crate) pair_separators: Vec<char>,
+    pub(crate) key_value_delimiters: Vec<char>,
+    pub(crate) key_chars: Vec<char>,
+    pub(crate) delimiter_style: DelimiterStyle,
+    pub(crate) tuple_separator: Option<char>,
+    pub(crate) list_separator: Option<char>,
+    pub(crate) repeated_keys: bool,
+    pub(crate) indexed_lists: bool,
+    pub(crate) quoting: Quoting,
+    pub(crate) true_values: Vec<String>,
+    pub(crate) false_values: Vec<String>,
+    pub(crate) bare_keys: bool,
+    pub(crate) empty_pairs: bool,
+    pub(crate) leading_spaces: bool,
+    pub(crate) property_separator: Option<char>,
+    pub(crate) comment_markers: Vec<String>,
+    pub(crate) trailing_comments: bool,
+    pub(crate) line_continuations: bool,
+    pub(crate) trim_values: bool,
+    pub(crate) empty_values: EmptyValues,
+    pub(crate) overflow: Overflow,
+    pub(crate) truncate_floats: bool,
+    #[cfg(feature = "json")]
+    pub(crate) embedded_json: bool,
+    pub(crate) key_prefix: Option<String>,
+    pub(crate) selected_key_prefix: Option<String>,
+    pub(crate) key_renames: Vec<(String, String)>,
+    pub(crate) key_case: Option<KeyCase>,
+    pub(crate) slash_separated_keys: bool,
+    pub(crate) validators: Vec<Validator>,
+    pub(crate) resolvers: Vec<(String, Resolver)>,
+    pub(crate) nested_key_separator: Option<char>,
+    pub(crate) sections: bool,
+    pub(crate) whitespace: Whitespace,
+    pub(crate) record_separator: String,
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> Self {
+        Self {
+            pair_separators: vec![' ', ','],
+            key_value_delimiters: vec!['='],
+            key_chars: Vec::new(),
+            delimiter_style: DelimiterStyle::default(),
+            tuple_separator: None,
+            list_separator: None,
+            repeated_keys: false,
+            indexed_lists: false,
+            quoting: Quoting::WhenNeeded,
+            true_values: vec!["true".into(), "True".into()],
+            false_values: vec!["false".into(), "False".into()],
+            bare_keys: false,
+            empty_pairs: false,
+            leading_spaces: false,
+            property_separator: None,
+            comment_markers: Vec::new(),
+            trailing_comments: false,
+            line_continuations: false,
+            trim_values: false,
+            empty_values: EmptyValues::default(),
+            overflow: Overflow::default(),
+            truncate_floats: false,
+            #[cfg(feature = "json")]
+            embedded_json: false,
+            key_prefix: None,
+            selected_key_prefix: None,
+            key_renames: Vec::new(),
+            key_case: None,
+            slash_separated_keys: false,
+            validators: Vec::new(),
+            resolvers: Vec::new(),
+            nested_key_separator: None,
+            sections: false,
+            whitespace: Whitespace::default(),
+            record_separator: "\n".into(),
+        }
+    }
+}
+
+impl DeserializeOptions {
+    /// Returns the default options: pairs separated by spaces or commas, keys and values delimited
+    /// by `=`, tuples enclosed in brackets, quoted strings recognized and `true`/`false`
+    /// booleans.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the characters accepted between two key-value pairs.
+    pub fn pair_separators(mut self, separators: impl IntoIterator<Item = char>) -> Self {
+        self.pair_separators = separators.into_iter().collect();
+        self
+    }
+
+    /// Sets the character expected between a key and its value.
+    pub fn key_value_delimiter(mut self, delimiter: char) -> Self {
+        self.key_value_delimiters = vec![delimiter];
+        self
+    }
+
+    /// Sets the characters accepted between a key and its value.
+    pub fn key_value_delimiters(mut self, delimiters: impl IntoIterator<Item = char>) -> Self {
+        self.key_value_delimiters = delimiters.into_iter().collect();
+        self
+    }
+
+    /// Sets additional characters accepted in keys after their first character, e.g. `.` for
+    /// dotted keys. Keys otherwise contain alphanumeric characters, `_` and `-`.
+    pub fn key_chars(mut self, chars: impl IntoIterator<Item = char>) -> Self {
+        self.key_chars = chars.into_iter().collect();
+        self
+    }
+
+    /// Sets the spacing accepted around the key-value delimiter.
+    pub fn delimiter_style(mut self, style: DelimiterStyle) -> Self {
+        self.delimiter_style = style;
+        self
+    }
+
+    /// Allows tuples and tuple structs to be written without brackets, as their elements separated
+    /// by `separator`, e.g. `range=1024:2048`.
+    pub fn tuple_separator(mut self, separator: char) -> Self {
+        self.tuple_separator = Some(separator);
+        self
+    }
+
+    /// Allows sequences to be written without brackets, as their elements separated by
+    /// `separator`, e.g. `irq=5,7,9`.
+    pub fn list_separator(mut self, separator: char) -> Self {
+        self.list_separator = Some(separator);
+        self
+    }
+
+    /// Sets whether sequences may be written as consecutive pairs with the same key, one per
+    /// element, e.g. `tag=a&tag=b`. Sequences enclosed in brackets are still accepted.
+    pub fn repeated_keys(mut self, repeated: bool) -> Self {
+        self.repeated_keys = repeated;
+        self
+    }
+
+    /// Sets whether sequences may be written as consecutive pairs keyed by the key of the sequence,
+    /// the nested key separator and the index of the element, e.g.
+    /// `route.0=10.0.0.0/24 route.1=192.168.0.0/16`. Indexes must start at 0 and follow each other
+    /// in order. Elements that are structs have their fields nested in the indexed keys, e.g.
+    /// `disk.0.path=/a`. Sequences enclosed in brackets are still accepted. This has no effect
+    /// without a nested key separator.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Net {
+    ///     route: Vec<String>,
+    ///     mtu: u16,
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().nested_key_separator('.').indexed_lists(true);
+    /// let net: Net =
+    ///     from_key_values_with("route.0=10.0.0.0/24 route.1=192.168.0.0/16 mtu=1500", options)
+    ///         .unwrap();
+    /// assert_eq!(net.route, ["10.0.0.0/24", "192.168.0.0/16"]);
+    /// ```
+    pub fn indexed_lists(mut self, indexed: bool) -> Self {
+        self.indexed_lists = indexed;
+        self
+    }
+
+    /// Sets whether quoted strings are recognized. With [`Quoting::Never`], quotes are ordinary
+    /// characters of unquoted strings. [`Quoting::WhenNeeded`] and [`Quoting::Always`] both
+    /// accept quoted and unquoted strings. With [`Quoting::Escaped`], quotes are ordinary
+    /// characters and backslash escapes are processed. With [`Quoting::Percent`] and
+    /// [`Quoting::Uri`], percent escapes are decoded instead. With [`Quoting::Token`], only double
+    /// quotes are recognized, and with [`Quoting::Doubled`], they are escaped by doubling them.
+    pub fn quoting(mut self, quoting: Quoting) -> Self {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Sets the words parsed as a `true` boolean.
+    pub fn true_values<S: Into<String>>(mut self, values: impl IntoIterator<Item = S>) -> Self {
+        self.true_values = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the words parsed as a `false` boolean.
+    pub fn false_values<S: Into<String>>(mut self, values: impl IntoIterator<Item = S>) -> Self {
+        self.false_values = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether keys given without a value are accepted for string fields, which then get an
+    /// empty string. Keys without a value are always accepted for booleans, which are then `true`.
+    pub fn bare_keys(mut self, allowed: bool) -> Self {
+        self.bare_keys = allowed;
+        self
+    }
+
+    /// Sets whether consecutive pair separators are accepted, e.g. blank lines when pairs are
+    /// separated by newlines.
+    pub fn allow_empty_pairs(mut self, allowed: bool) -> Self {
+        self.empty_pairs = allowed;
+        self
+    }
+
+    /// Sets whether spaces and tabs are accepted before keys, e.g. after the `; ` separators of
+    /// cookies. Unlike spaces used as pair separators, this keeps spaces in unquoted values, e.g.
+    /// `Expires=Wed, 21 Oct 2015 07:28:00 GMT`.
+    pub fn allow_leading_spaces(mut self, allowed: bool) -> Self {
+        self.leading_spaces = allowed;
+        self
+    }
+
+    /// Sets whether INI section headers are accepted, e.g. `[server]`. The pairs following a
+    /// header, up to the next one, are the fields of the nested struct or map named after the
+    /// section. Pairs preceding the first header belong to the top-level value.
+    pub fn allow_sections(mut self, allowed: bool) -> Self {
+        self.sections = allowed;
+        self
+    }
+
+    /// Sets the whitespace accepted between pairs, e.g. [`Whitespace::Lines`] for records whose
+    /// pairs are aligned over several lines.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions, Whitespace};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Disk {
+    ///     path: String,
+    ///     ro: bool,
+    ///     size: u64,
+    /// }
+    ///
+    /// let input = "path=/dev/vda ,  ro\r\n\tsize=1024\n";
+    /// let options = DeserializeOptions::new().whitespace(Whitespace::Lines);
+    /// let disk: Disk = from_key_values_with(input, options).unwrap();
+    /// assert_eq!(disk, Disk { path: "/dev/vda".into(), ro: true, size: 1024 });
+    /// ```
+    pub fn whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Sets the string separating records in multi-record input, as split by
+    /// [`records`](crate::records), e.g. `"\0"` for environ blobs. Separators within quoted
+    /// strings or escaped with a backslash do not end a record. Defaults to `"\n"`, in which case
+    /// `\r\n` line endings are accepted as well.
+    pub fn record_separator(mut self, separator: impl Into<String>) -> Self {
+        self.record_separator = separator.into();
+        self
+    }
+
+    /// Returns the options with the whitespace accepted between pairs added to the pair
+    /// separators, as expected by the parsers, as well as `\r` if newlines separate pairs so
+    /// `\r\n` line endings are accepted.
+    pub(crate) fn with_implied_separators(mut self) -> Self {
+        let carriage_return: &[char] = if self.pair_separators.contains(&'\n') {
+            &['\r']
+        } else {
+            &[]
+        };
+        for &c in self.whitespace.blanks().iter().chain(carriage_return) {
+            if !self.pair_separators.contains(&c) {
+                self.pair_separators.push(c);
+            }
+        }
+        self
+    }
+
+    /// Ignores the properties following the value of pairs after `separator`, up to the next pair
+    /// separator, e.g. `;ttl=60` in the `key=value;ttl=60` members of W3C baggage. Values end
+    /// with `separator` or with a space or tab, and spaces and tabs are accepted before
+    /// `separator` or the next pair separator.
+    pub fn property_separator(mut self, separator: char) -> Self {
+        self.property_separator = Some(separator);
+        self
+    }
+
+    /// Ignores the pairs starting with one of `chars` up to the next pair separator, e.g. `#`
+    /// comment lines when pairs are separated by newlines.
+    pub fn comment_chars(mut self, chars: impl IntoIterator<Item = char>) -> Self {
+        self.comment_markers = chars.into_iter().map(String::from).collect();
+        self
+    }
+
+    /// Like [`comment_chars`](Self::comment_chars), with comments starting with one of `markers`,
+    /// which may be longer than a character, e.g. `//`. Empty markers are ignored.
+    pub fn comment_markers<S: Into<String>>(
+        mut self,
+        markers: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.comment_markers = markers
+            .into_iter()
+            .map(Into::into)
+            .filter(|marker: &String| !marker.is_empty())
+            .collect();
+        self
+    }
+
+    /// Sets whether comments are also accepted after pairs when a space or a tab precedes them,
+    /// e.g. `port=8080  # HTTP`. Comments then extend up to the next pair separator other than a
+    /// space or a tab, and unquoted values end before the spaces preceding trailing comments.
+    /// Comment markers in quoted strings do not start comments.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Listen {
+    ///     host: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// let input = "// Public listener\nhost=web server  // Quoted if needed\nport=8080 // HTTP";
+    /// let options = DeserializeOptions::new()
+    ///     .pair_separators(['\n'])
+    ///     .comment_markers(["//"])
+    ///     .trailing_comments(true);
+    /// let listen: Listen = from_key_values_with(input, options).unwrap();
+    /// assert_eq!(listen, Listen { host: "web server".into(), port: 8080 });
+    /// ```
+    pub fn trailing_comments(mut self, allowed: bool) -> Self {
+        self.trailing_comments = allowed;
+        self
+    }
+
+    /// Sets whether the spaces and tabs around the key-value delimiter and following the values of
+    /// pairs are ignored, e.g. `port = 80  ` in files edited by hand. Otherwise, they are only
+    /// accepted around the delimiter as allowed by the [`DelimiterStyle`], and are part of
+    /// unquoted values when spaces do not separate pairs. Quoted strings always keep their spaces,
+    /// e.g. `name = " a "`.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Listen {
+    ///     host: String,
+    ///     port: u16,
+    ///     banner: String,
+    /// }
+    ///
+    /// let input = "host =  web server \t;port= 80 ;banner = ' hi '  ";
+    /// let options = DeserializeOptions::new().pair_separators([';']).trim_values(true);
+    /// let listen: Listen = from_key_values_with(input, options).unwrap();
+    /// assert_eq!(listen.host, "web server");
+    /// assert_eq!(listen.port, 80);
+    /// assert_eq!(listen.banner, " hi ");
+    /// ```
+    pub fn trim_values(mut self, trim: bool) -> Self {
+        self.trim_values = trim;
+        self
+    }
+
+    /// Sets how empty values, e.g. `key=`, are deserialized, so the meaning given to them by the
+    /// producer of the input can be matched.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions, EmptyValues};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Mount {
+    ///     label: String,
+    ///     owner: Option<String>,
+    /// }
+    ///
+    /// let empty = |values| DeserializeOptions::new().empty_values(values);
+    /// let mount: Mount = from_key_values_with("label=,owner=", empty(EmptyValues::EmptyString))
+    ///     .unwrap();
+    /// assert_eq!(mount, Mount { label: "".into(), owner: Some("".into()) });
+    /// let mount: Mount = from_key_values_with("label=\"\",owner=", empty(EmptyValues::None))
+    ///     .unwrap();
+    /// assert_eq!(mount, Mount { label: "".into(), owner: None });
+    /// assert!(from_key_values_with::<Mount>("label=\"\",owner=", empty(EmptyValues::Error))
+    ///     .is_err());
+    /// ```
+    pub fn empty_values(mut self, values: EmptyValues) -> Self {
+        self.empty_values = values;
+        self
+    }
+
+    /// Sets how integers that do not fit the type of their field are handled. Defaults to
+    /// [`Overflow::Error`].
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions, ErrorKind, Overflow};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Led {
+    ///     brightness: u8,
+    /// }
+    ///
+    /// let parse = |overflow| {
+    ///     let options = DeserializeOptions::new().overflow(overflow);
+    ///     from_key_values_with::<Led>("brightness=300", options)
+    /// };
+    /// assert_eq!(parse(Overflow::Saturate).unwrap(), Led { brightness: 255 });
+    /// assert_eq!(parse(Overflow::Wrap).unwrap(), Led { brightness: 44 });
+    /// let err = parse(Overflow::Error).unwrap_err();
+    /// assert_eq!(err.kind, ErrorKind::OutOfRange { key: "brightness".into() });
+    /// ```
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets whether numbers with a fractional part, e.g. `2.5`, are accepted for integer fields,
+    /// in which case they are truncated toward zero. Values that do not fit the type of their
+    /// field once truncated are handled according to [`DeserializeOptions::overflow`]. Disabled by
+    /// default, as the fractional part is lost.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Timer {
+    ///     secs: u32,
+    /// }
+    ///
+    /// assert!(from_key_values_with::<Timer>("secs=2.9", DeserializeOptions::new()).is_err());
+    /// let options = DeserializeOptions::new().truncate_floats(true);
+    /// let timer: Timer = from_key_values_with("secs=2.9", options).unwrap();
+    /// assert_eq!(timer, Timer { secs: 2 });
+    /// ```
+    pub fn truncate_floats(mut self, truncate: bool) -> Self {
+        self.truncate_floats = truncate;
+        self
+    }
+
+    /// Sets whether the values of nested structs and maps may be JSON objects, e.g.
+    /// `limits={"cpu":2,"mem":"4G"}`, which are then deserialized by `serde_json`. Values enclosed
+    /// in brackets are still accepted. Errors met within a JSON value are reported at its start.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Limits {
+    ///     cpu: u32,
+    ///     mem: String,
+    /// }
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Job {
+    ///     name: String,
+    ///     limits: Limits,
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().embedded_json(true);
+    /// let job: Job = from_key_values_with(r#"name=build,limits={"cpu":2,"mem":"4G"}"#, options)
+    ///     .unwrap();
+    /// assert_eq!(job.limits, Limits { cpu: 2, mem: "4G".into() });
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn embedded_json(mut self, embedded: bool) -> Self {
+        self.embedded_json = embedded;
+        self
+    }
+
+    /// Sets whether a backslash at the end of a line is accepted after a pair separator or within
+    /// an unquoted value, so a long list of pairs or a long value can be split across several
+    /// lines, as in shell scripts. The backslash, the line ending and the spaces and tabs indenting
+    /// the next line are then ignored.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Disk {
+    ///     path: String,
+    ///     ro: bool,
+    ///     size: u64,
+    /// }
+    ///
+    /// let input = "path=/dev/disk/by-id/\\\n    virtio-root,\\\n    ro,\\\r\n    size=1024";
+    /// let options = DeserializeOptions::new().line_continuations(true);
+    /// let disk: Disk = from_key_values_with(input, options).unwrap();
+    /// assert_eq!(disk, Disk { path: "/dev/disk/by-id/virtio-root".into(), ro: true, size: 1024 });
+    /// ```
+    pub fn line_continuations(mut self, allowed: bool) -> Self {
+        self.line_continuations = allowed;
+        self
+    }
+
+    /// Ignores `prefix` if it precedes a key, e.g. the `export ` prefix of shell variables.
+    pub fn ignored_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Accepts nested structs and maps written as their pairs, with keys prefixed by the key of
+    /// the nested value and `separator`, e.g. `cache.direct=on,cache.no-flush=off`. The pairs of a
+    /// nested value must be consecutive. Nested values enclosed in brackets are still accepted.
+    pub fn nested_key_separator(mut self, separator: char) -> Self {
+        self.nested_key_separator = Some(separator);
+        self
+    }
+
+    /// Only deserializes the pairs whose key starts with `prefix`, which is removed before the key
+    /// is matched, and skips the other pairs, so the settings of several components can share a
+    /// string, e.g. `disk0.path=/dev/vda,net0.mtu=9000,disk0.ro`.
+    ///
+    /// The keys of nested structures enclosed in brackets are not filtered, and the name of the
+    /// first field of a struct cannot be omitted.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Disk {
+    ///     path: String,
+    ///     #[serde(default)]
+    ///     ro: bool,
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().select_key_prefix("disk0.");
+    /// let disk: Disk =
+    ///     from_key_values_with("disk0.path=/dev/vda,net0.mtu=9000,disk0.ro", options).unwrap();
+    /// assert_eq!(disk, Disk { path: "/dev/vda".into(), ro: true });
+    /// ```
+    pub fn select_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.selected_key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Deserializes the keys of `renames` as the names they are mapped to, e.g. to accept the
+    /// deprecated names of fields without adding `#[serde(alias)]` attributes to them. Can be
+    /// called several times to add more renames.
+    ///
+    /// Renamed keys are not borrowed from the input, so they cannot be deserialized into `&str`.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Net {
+    ///     tap: String,
+    ///     mtu: u32,
+    /// }
+    ///
+    /// let legacy = HashMap::from([("ifname", "tap"), ("max_frame", "mtu")]);
+    /// let options = DeserializeOptions::new().rename_keys(legacy);
+    /// let net: Net = from_key_values_with("ifname=tap0,max_frame=1500", options).unwrap();
+    /// assert_eq!(net, Net { tap: "tap0".into(), mtu: 1500 });
+    /// ```
+    pub fn rename_keys<K, N>(mut self, renames: impl IntoIterator<Item = (K, N)>) -> Self
+    where
+        K: Into<String>,
+        N: Into<String>,
+    {
+        self.key_renames.extend(
+            renames
+                .into_iter()
+                .map(|(key, name)| (key.into(), name.into())),
+        );
+        self
+    }
+
+    /// Converts keys to the `case` convention before matching them to the names of fields, so
+    /// that keys written in other conventions are accepted interchangeably, e.g. `max-conn`,
+    /// `max_conn` and `maxConn` for a `max_conn` field with [`KeyCase::Snake`]. Keys renamed by
+    /// [`DeserializeOptions::rename_keys`] are not converted, while the keys of maps are converted
+    /// like the names of fields.
+    ///
+    /// Converted keys are not borrowed from the input, so they cannot be deserialized into `&str`.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions, KeyCase};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Pool {
+    ///     max_conn: u32,
+    ///     idle_timeout: u32,
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().key_case(KeyCase::Snake);
+    /// let pool: Pool = from_key_values_with("max-conn=8,idleTimeout=30", options).unwrap();
+    /// assert_eq!(pool, Pool { max_conn: 8, idle_timeout: 30 });
+    /// ```
+    pub fn key_case(mut self, case: KeyCase) -> Self {
+        self.key_case = Some(case);
+        self
+    }
+
+    /// Sets whether keys whose first separator is a `/` are converted to dotted keys before being
+    /// matched to the names of fields, as by `sysctl`: their slashes become dots and their dots
+    /// become slashes, e.g. `net/ipv4/conf/eth0.100/forwarding` is read as
+    /// `net.ipv4.conf.eth0/100.forwarding`. Defaults to `false`.
+    ///
+    /// Converted keys are not borrowed from the input, so they cannot be deserialized into `&str`.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Net {
+    ///     #[serde(rename = "net.ipv4.ip_forward")]
+    ///     ip_forward: u8,
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().key_chars(['.', '/']).slash_separated_keys(true);
+    /// let net: Net = from_key_values_with("net/ipv4/ip_forward=1", options).unwrap();
+    /// assert_eq!(net, Net { ip_forward: 1 });
+    /// ```
+    pub fn slash_separated_keys(mut self, convert: bool) -> Self {
+        self.slash_separated_keys = convert;
+        self
+    }
+
+    /// Adds a function called with the key, as written in the input, and the value of each pair
+    /// once it has been deserialized, so rules that the types of the fields cannot express are
+    /// checked while parsing. An error returned by the function is reported as an
+    /// [`ErrorKind::InvalidValue`](crate::ErrorKind::InvalidValue) error at the position of the
+    /// value.
+    ///
+    /// Quoted strings are passed unquoted, and other values as written, e.g. `[1,2]` for a list.
+    /// Flags have an empty value.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Listen {
+    ///     host: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// fn check_port(key: &str, value: &str) -> Result<(), String> {
+    ///     match (key, value.parse::<u16>()) {
+    ///         ("port", Ok(port)) if port <= 1024 => Err("must be above 1024".into()),
+    ///         _ => Ok(()),
+    ///     }
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().validator(check_port);
+    /// let err = from_key_values_with::<Listen>("host=::1,port=80", options).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "invalid value for key `port`: must be above 1024 at position 14"
+    /// );
+    /// ```
+    pub fn validator(mut self, validator: fn(&str, &str) -> Result<(), String>) -> Self {
+        self.validators.push(validator);
+        self
+    }
+
+    /// Registers `resolver` for the values starting with `scheme`, e.g. `secret://` or `file:`,
+    /// which are replaced while deserializing with the value `resolver` returns for the rest of
+    /// them, e.g. `prod/db-pass` for `secret://prod/db-pass`. Resolved values are deserialized
+    /// into strings verbatim, and parsed into numbers and booleans. The first resolver whose
+    /// scheme matches is used.
+    ///
+    /// Only the values of pairs are resolved, and quoted values are matched unquoted. Validators
+    /// check the resolved values. An error returned by `resolver` is reported as an
+    /// [`ErrorKind::InvalidValue`](crate::ErrorKind::InvalidValue) at the position of the value,
+    /// where the errors met when parsing the resolved value are reported as well.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{from_key_values_with, DeserializeOptions};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Db {
+    ///     host: String,
+    ///     port: u16,
+    ///     password: String,
+    /// }
+    ///
+    /// fn secret(path: &str) -> Result<String, String> {
+    ///     match path {
+    ///         "prod/db-pass" => Ok("s3cr3t, really".into()),
+    ///         "prod/db-port" => Ok("5432".into()),
+    ///         _ => Err(format!("no secret at {}", path)),
+    ///     }
+    /// }
+    ///
+    /// let options = DeserializeOptions::new().resolver("secret://", secret);
+    /// let db: Db = from_key_values_with(
+    ///     "host=db,port=secret://prod/db-port,password=secret://prod/db-pass",
+    ///     options.clone(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(db.port, 5432);
+    /// assert_eq!(db.password, "s3cr3t, really");
+    ///
+    /// let err = from_key_values_with::<Db>("host=db,port=1,password=secret://x", options);
+    /// assert_eq!(
+    ///     err.unwrap_err().to_string(),
+    ///     "invalid value for key `password`: no secret at x at position 24"
+    /// );
+    /// ```
+    pub fn resolver(
+        mut self,
+        scheme: impl Into<String>,
+        resolver: fn(&str) -> Result<String, String>,
+    ) -> Self {
+        self.resolvers.push((scheme.into(), resolver));
+        self
+    }
+}
+
+impl From<&SerializeOptions> for DeserializeOptions {
+    /// Returns options accepting the output produced with `options`.
+    fn from(options: &SerializeOptions) -> Self {
+        let (true_value, false_value) = options.bool_style.words();
+        let mut pair_separators: Vec<char> = options.pair_separator.chars().collect();
+        pair_separators.dedup();
+
+        Self {
+            pair_separators,
+            key_value_delimiters: vec![options.key_value_delimiter],
+            key_chars: Vec::new(),
+            delimiter_style: options.delimiter_style,
+            tuple_separator: Some(options.tuple_separator).filter(|_| !options.bracketed_tuples),
+            list_separator: options.list_separator,
+            repeated_keys: options.repeated_keys,
+            indexed_lists: options.indexed_lists,
+            quoting: options.quoting,
+            true_values: vec![true_value.into()],
+            false_values: vec![false_value.into()],
+            bare_keys: false,
+            empty_pairs: false,
+            leading_spaces: false,
+            property_separator: None,
+            comment_markers: Vec::new(),
+            trailing_comments: false,
+            line_continuations: false,
+            trim_values: false,
+            empty_values: if options.none_style == NoneStyle::Empty {
+                EmptyValues::None
+            } else if options.bare_empty_strings {
+                EmptyValues::EmptyString
+            } else {
+                EmptyValues::default()
+            },
+            overflow: Overflow::default(),
+            truncate_floats: false,
+            #[cfg(feature = "json")]
+            embedded_json: options.embedded_json,
+            key_prefix: Some(options.key_prefix.clone()).filter(|prefix| !prefix.is_empty()),
+            selected_key_prefix: None,
+            key_renames: Vec::new(),
+            key_case: None,
+            slash_separated_keys: false,
+            validators: Vec::new(),
+            resolvers: Vec::new(),
+            nested_key_separator: options.nested_key_separator,
+            sections: options.sections,
+            whitespace: Whitespace::default(),
+            record_separator: options.record_separator.clone(),
+        }
+    }
+}
+
+impl From<SerializeOptions> for DeserializeOptions {
+    fn from(options: SerializeOptions) -> Self {
+        Self::from(&options)
+    }
+}
+
+impl From<Preset> for DeserializeOptions {
+    fn from(preset: Preset) -> Self {
+        preset.deserialize_options()
+    }
+}
+
+/// Bundles of options implementing commonly used key-value dialects.
+///
+/// A preset can be used anywhere options are expected by the serializer or the deserializer:
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_keyvalue::{from_key_values_with, KeyValueSerializer, Preset};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Event {
+///     level: String,
+///     msg: String,
+///     retry: bool,
+/// }
+///
+/// let event = Event { level: "info".into(), msg: "disk attached".into(), retry: false };
+///
+/// let mut serializer = KeyValueSerializer::with_options(Preset::Logfmt);
+/// event.serialize(&mut serializer).unwrap();
+/// let output = serializer.into_output();
+/// assert_eq!(output, r#"level=info msg="disk attached" retry=false"#);
+///
+/// let parsed: Event = from_key_values_with(&output, Preset::Logfmt).unwrap();
+/// assert_eq!(parsed, event);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// The [logfmt](https://brandur.org/logfmt) format used by many logging systems: space
+    /// separated pairs with values quoted when needed, e.g. `level=info msg="hello world"`, and
+    /// control characters escaped in quoted values, e.g. `\u0001`. Booleans are lowercase. When
+    /// deserializing, pairs may be separated by any number of spaces and tabs, and keys without a
+    /// value or with an empty one, e.g. `a=`, are accepted for strings, which are then empty.
+    Logfmt,
+    /// The Linux kernel command line: space separated pairs where booleans are bare flags, e.g.
+    /// `root=/dev/sda1 ro quiet`, and values are double-quoted without escapes, see
+    /// [`Quoting::Kernel`]. When deserializing, pairs may be separated by any number of spaces,
+    /// tabs or newlines, keys may contain dots, e.g. `module.param=1`, and bare flags are also
+    /// accepted for strings, which are then empty.
+    KernelCmdline,
+    /// Linux kernel module parameters, as given to `modprobe` or `insmod`: space separated pairs
+    /// where arrays are written as their elements separated by commas, e.g. `irq=5,7,9 debug=1`.
+    /// Booleans are written as `1` or `0`, and may also be given as bare flags when deserializing.
+    ModuleParams,
+    /// mount(8) options: comma separated pairs where booleans are bare flags, e.g.
+    /// `rw,relatime,size=10M,mode=755`. `false` booleans are omitted, and values are double-quoted
+    /// when needed, e.g. SELinux contexts containing commas.
+    MountOptions,
+    /// `.env` files: one `KEY=value` pair per line, with values quoted when needed and empty
+    /// strings written as `KEY=`. When deserializing, blank lines, indentation, `#` comment lines,
+    /// `#` comments preceded by a space after pairs, `\r\n` line endings and the `export ` prefix
+    /// of keys are accepted.
+    Dotenv,
+    /// URL query strings and `application/x-www-form-urlencoded` data: pairs separated by `&`
+    /// with percent-encoded values, e.g. `user=alice&q=a+b%26c`. Sequences are written as repeated
+    /// keys, e.g. `tag=a&tag=b`.
+    QueryString,
+    /// systemd `EnvironmentFile=` files: one `KEY=value` pair per line, with values double-quoted
+    /// when needed following shell rules. `%` specifiers are not expanded in these files, so they
+    /// are kept untouched. When deserializing, blank lines, `#` or `;` comment lines, spaces around
+    /// `=`, single quotes and backslash line continuations are accepted.
+    SystemdEnvironment,
+    /// Java `.properties` files: one `key=value` pair per line, with special characters escaped
+    /// with a backslash. When deserializing, keys may contain dots and be delimited from their
+    /// value by `=` or `:` with optional spaces, or by spaces alone, values may be empty or span
+    /// several lines with backslash line continuations, and blank lines and `#` or `!` comment
+    /// lines are accepted.
+    JavaProperties,
+    /// QEMU `-device`, `-drive` or `-netdev` option strings: comma separated pairs where the first
+    /// field, e.g. the driver name, is written without its key, booleans are `on` or `off`, and
+    /// nested structs are written with dotted keys, e.g. `virtio-blk,drive=disk0,cache.direct=on`.
+    /// Values are written verbatim with commas doubled, as QEMU does not recognize quotes, e.g.
+    /// `file=a,,b` for `a,b`. When deserializing, the `yes`/`no`, `true`/`false` and `y`/`n`
+    /// booleans are also accepted.
+    Qemu,
+    /// The syntax of crosvm's `serde_keyvalue` crate: comma separated pairs where booleans may be
+    /// bare flags and tuples are enclosed in brackets, e.g. `/dev/vda,ro,size=[320,200]`. When
+    /// deserializing, the key of the first field may be omitted, as with all the presets.
+    Crosvm,
+    /// Parameters of HTTP headers as defined by RFC 9110, e.g. the
+    /// `attachment; filename="a b.txt"` value of a `Content-Disposition` header: pairs separated
+    /// by `; ` where the first field, e.g. the disposition type or the media type, is written
+    /// without its key, and values are double-quoted unless they are tokens. When deserializing,
+    /// any number of spaces and tabs is accepted around the `;` separators.
+    HttpParameters,
+    /// `Cookie` and `Set-Cookie` headers: pairs separated by `; ` where booleans are bare flags,
+    /// e.g. `id=a3fWa; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Secure; HttpOnly`. Values are
+    /// written verbatim and may contain spaces, and commas in the date of `Expires`. Values
+    /// containing `;`, control characters or other commas cannot be serialized, see
+    /// [`Quoting::Cookie`].
+    Cookie,
+    /// The W3C `baggage` header: comma separated pairs with percent-encoded values, e.g.
+    /// `userId=alice,serverNode=DF%2028`. When deserializing, spaces are accepted around pairs and
+    /// delimiters, and the `;` separated properties of the pairs are ignored, e.g. `;ttl=60` in
+    /// `userId=alice;ttl=60`.
+    Baggage,
+    /// Prometheus label sets without their enclosing braces, e.g. `job="api",code="200"`: comma
+    /// separated pairs where all values are double-quoted. When deserializing, a trailing comma is
+    /// accepted.
+    PrometheusLabels,
+    /// Tag sets of the InfluxDB line protocol, e.g. `host=server\ 01,region=us\,west`: comma
+    /// separated pairs where commas, equals signs and spaces are escaped with a backslash in keys
+    /// and values. When deserializing, keys must be identifiers.
+    InfluxTags,
+    /// PostgreSQL connection strings as parsed by libpq, e.g.
+    /// `host=db port=5432 options='-c geqo=off'`: pairs separated by spaces where values are
+    /// single-quoted when needed. When deserializing, any whitespace is accepted around pairs and
+    /// `=`, and booleans may also be given as `on`/`off`, `yes`/`no` or `1`/`0`.
+    PostgresDsn,
+    /// ODBC and ADO.NET connection strings, e.g.
+    /// `Driver={SQL Server};Server=x;Trusted_Connection=yes`: pairs separated by `;` where values
+    /// are enclosed in braces when needed and booleans are written as `yes` or `no`. Keys may
+    /// contain spaces, e.g. `Initial Catalog`. When deserializing, a trailing `;` is accepted and
+    /// booleans may also be given as `true`/`false` or `1`/`0`.
+    OdbcConnectionString,
+    /// X.509 and LDAP distinguished names as defined by RFC 4514, e.g.
+    /// `CN=Alice,OU=Eng,OU=R\+D,O=Acme,C=US`: comma separated attributes with backslash escapes.
+    /// Sequences are written as repeated attributes. Multi-valued RDNs joined with `+` are not
+    /// supported.
+    DistinguishedName,
+    /// Options of FFmpeg filters, muxers and codecs, e.g. `text=a\:b:fontsize=24:box=1`: pairs
+    /// separated by `:` where special characters are escaped with a backslash and booleans are
+    /// written as `1` or `0`. When deserializing, booleans may also be given as `true`/`false`.
+    FfmpegOptions,
+    /// GStreamer elements as written in `gst-launch-1.0` pipelines, e.g.
+    /// `filesrc name=src location="/tmp/a b.mp4"`: pairs separated by spaces where the first
+    /// field, i.e. the element factory name, is written without its key, and values are
+    /// double-quoted when needed. When deserializing, any whitespace is accepted between pairs.
+    GstElement,
+    /// `sysctl.conf` files: one `net.ipv4.ip_forward = 1` pair per line, with values written
+    /// verbatim, booleans written as `1` or `0`, and tuples separated by spaces, e.g.
+    /// `net.ipv4.ip_local_port_range = 32768 60999`. Keys are dotted paths and are not nested,
+    /// and values cannot contain line breaks, see [`Quoting::SingleLine`]. When deserializing,
+    /// blank lines, indentation, `#` or `;` comment lines and the `-` prefix of keys whose errors
+    /// are ignored are accepted, and `/` separated keys are converted to dotted keys, see
+    /// [`DeserializeOptions::slash_separated_keys`].
+    Sysctl,
+    /// RFC 822 style header blocks and git commit trailers: one `Key: value` pair per line, e.g.
+    /// `Signed-off-by: Alice <alice@example.com>`, with values written verbatim. Sequences are
+    /// written as repeated keys, and line breaks in values are folded. When deserializing, folded
+    /// lines are unfolded and blank lines are accepted.
+    Headers,
+    /// Shell scripts setting environment variables, e.g. `export NAME='my disk'`: one pair per
+    /// line with keys prefixed by `export ` and string values single-quoted so that the output can
+    /// be `source`d safely. When deserializing, blank lines, `#` comment lines and keys without
+    /// the `export ` prefix are accepted.
+    ShellExport,
+    /// Kubernetes label sets, e.g. `app.kubernetes.io/name=web,tier=frontend`: comma separated
+    /// pairs with values written verbatim and lowercase booleans. Values must be valid label
+    /// values, see [`Quoting::Label`]. When deserializing, keys may contain dots and slashes.
+    /// Label selectors are parsed by [`LabelSelector`](crate::LabelSelector).
+    KubernetesLabels,
+    /// The shorthand syntax of AWS CLI parameters, e.g. `Name=tag:env,Values=prod,staging`: comma
+    /// separated pairs where sequences are enclosed in brackets and nested structs in braces, e.g.
+    /// `Ebs={VolumeSize=20,DeleteOnTermination=true}`, and values are double-quoted when needed.
+    /// When deserializing, sequences may also be written as their elements separated by commas,
+    /// up to the next pair. Braces are only recognized by
+    /// [`from_shorthand`](crate::from_shorthand), and lists of structs passed as separate
+    /// arguments, e.g. `ParameterKey=A,ParameterValue=1 ParameterKey=B,ParameterValue=2`, are
+    /// handled by [`to_shorthand_args`](crate::to_shorthand_args) and
+    /// [`from_shorthand_args`](crate::from_shorthand_args).
+    AwsShorthand,
+    /// The parameters of the SD-ELEMENTs of syslog messages as defined by RFC 5424, without the
+    /// enclosing brackets and SD-ID, e.g. `iut="3" eventSource="App"`: space separated pairs where
+    /// all values are double-quoted with `"`, `\` and `]` escaped. When deserializing, keys may
+    /// contain dots, `@` and `:`. Whole elements are handled by
+    /// [`from_sd_element`](crate::from_sd_element) and [`to_sd_element`](crate::to_sd_element).
+    SyslogStructuredData,
+    /// INI files: one `key=value` pair per line, with values quoted when needed and nested
+    /// structs and maps written as `[section]` headers followed by their pairs. When
+    /// deserializing, blank lines, indentation, `#` or `;` comment lines and spaces around `=` are
+    /// accepted.
+    Ini,
+}
+
+impl Preset {
+    /// Returns the serializer options implementing this preset.
+    pub fn serialize_options(self) -> SerializeOptions {
+        let options = SerializeOptions::new();
+
+        match self {
+            Preset::Logfmt => options
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::KernelCmdline => options
+                .quoting(Quoting::Kernel)
+                .bool_style(BoolStyle::Flag)
+                .tuple_separator(','),
+            Preset::ModuleParams => options
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Numeric)
+                .list_separator(','),
+            Preset::MountOptions => options
+                .pair_separator(",")
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Flag),
+            Preset::Dotenv => options
+                .pair_separator("\n")
+                .quoting(Quoting::WhenNeeded)
+                .bare_empty_strings(true)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::QueryString => options
+                .pair_separator("&")
+                .quoting(Quoting::Percent)
+                .bool_style(BoolStyle::Lowercase)
+                .repeated_keys(true),
+            Preset::SystemdEnvironment => options
+                .pair_separator("\n")
+                .quoting(Quoting::Shell)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::JavaProperties => options
+                .pair_separator("\n")
+                .quoting(Quoting::Escaped)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::Qemu => options
+                .pair_separator(",")
+                .quoting(Quoting::Qemu)
+                .bool_style(BoolStyle::OnOff)
+                .positional_first_field(true)
+                .nested_key_separator('.'),
+            Preset::Crosvm => options
+                .pair_separator(",")
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Lowercase)
+                .bracketed_tuples(true),
+            Preset::HttpParameters => options
+                .pair_separator("; ")
+                .quoting(Quoting::Token)
+                .bool_style(BoolStyle::Lowercase)
+                .positional_first_field(true),
+            Preset::Cookie => options
+                .pair_separator("; ")
+                .quoting(Quoting::Cookie)
+                .bool_style(BoolStyle::Flag),
+            Preset::Baggage => options
+                .pair_separator(",")
+                .quoting(Quoting::Uri)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::KubernetesLabels => options
+                .pair_separator(",")
+                .quoting(Quoting::Label)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::AwsShorthand => options
+                .pair_separator(",")
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Lowercase)
+                .braced_structs(true),
+            Preset::SyslogStructuredData => options
+                .quoting(Quoting::Syslog)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::PrometheusLabels => options
+                .pair_separator(",")
+                .quoting(Quoting::Prometheus)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::InfluxTags => options
+                .pair_separator(",")
+                .quoting(Quoting::Influx)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::PostgresDsn => options
+                .pair_separator(" ")
+                .quoting(Quoting::Libpq)
+                .bool_style(BoolStyle::Lowercase),
+            Preset::OdbcConnectionString => options
+                .pair_separator(";")
+                .quoting(Quoting::Braces)
+                .bool_style(BoolStyle::YesNo),
+            Preset::Ini => options
+                .pair_separator("\n")
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Lowercase)
+                .sections(true),
+            Preset::ShellExport => options
+                .pair_separator("\n")
+                .quoting(Quoting::SingleQuoted)
+                .bool_style(BoolStyle::Lowercase)
+                .key_prefix("export "),
+            Preset::Headers => options
+                .pair_separator("\n")
+                .key_value_delimiter(':')
+                .delimiter_style(DelimiterStyle::SpaceAfter)
+                .quoting(Quoting::Folded)
+                .bool_style(BoolStyle::Lowercase)
+                .repeated_keys(true),
+            Preset::Sysctl => options
+                .pair_separator("\n")
+                .delimiter_style(DelimiterStyle::Spaced)
+                .quoting(Quoting::SingleLine)
+                .tuple_separator(' ')
+                .bool_style(BoolStyle::Numeric),
+            Preset::GstElement => options
+                .pair_separator(" ")
+                .quoting(Quoting::WhenNeeded)
+                .bool_style(BoolStyle::Lowercase)
+                .positional_first_field(true),
+            Preset::FfmpegOptions => options
+                .pair_separator(":")
+                .quoting(Quoting::Ffmpeg)
+                .bool_style(BoolStyle::Numeric),
+            Preset::DistinguishedName => options
+                .pair_separator(",")
+                .quoting(Quoting::DistinguishedName)
+                .bool_style(BoolStyle::Lowercase)
+                .repeated_keys(true),
+        }
+    }
+
+    /// Returns the deserializer options implementing this preset.
+    pub fn deserialize_options(self) -> DeserializeOptions {
+        let options = DeserializeOptions::from(self.serialize_options());
+
+        match self {
+            // Values accepted by the kernel's `kstrtobool`.
+            Preset::ModuleParams => options
+                .true_values(["1", "y", "Y", "on", "true"])
+                .false_values(["0", "n", "N", "off", "false"]),
+            Preset::KernelCmdline => options
+                .quoting(Quoting::Kernel)
+                .pair_separators([' ', '\t', '\n'])
+                .allow_empty_pairs(true)
+                .bare_keys(true)
+                .key_chars(['.'])
+                .true_values(["1", "y", "Y", "on", "true"])
+                .false_values(["0", "n", "N", "off", "false"]),
+            Preset::Logfmt => options
+                .bare_keys(true)
+                .whitespace(Whitespace::Collapse)
+                .empty_values(EmptyValues::EmptyString),
+            Preset::MountOptions => options,
+            Preset::Dotenv => options
+                .pair_separators(['\n', '\r'])
+                .allow_empty_pairs(true)
+                .allow_leading_spaces(true)
+                .empty_values(EmptyValues::EmptyString)
+                .comment_chars(['#'])
+                .trailing_comments(true)
+                .ignored_key_prefix("export "),
+            Preset::QueryString => options,
+            Preset::SystemdEnvironment => options
+                .pair_separators(['\n', '\r'])
+                .delimiter_style(DelimiterStyle::Spaced)
+                .allow_empty_pairs(true)
+                .comment_chars(['#', ';']),
+            Preset::JavaProperties => options
+                .pair_separators(['\n', '\r'])
+                .key_value_delimiters(['=', ':', ' ', '\t'])
+                .delimiter_style(DelimiterStyle::Spaced)
+                .key_chars(['.'])
+                .allow_empty_pairs(true)
+                .empty_values(EmptyValues::EmptyString)
+                .comment_chars(['#', '!']),
+            // Values accepted by QEMU's `qapi_bool_parse`.
+            Preset::Qemu => options
+                .true_values(["on", "yes", "true", "y"])
+                .false_values(["off", "no", "false", "n"]),
+            Preset::Crosvm => options,
+            Preset::HttpParameters => options
+                .pair_separators([';', ' ', '\t'])
+                .allow_empty_pairs(true),
+            Preset::Cookie => options.pair_separators([';']).allow_leading_spaces(true),
+            // Keys are tokens as defined by RFC 9110.
+            Preset::Baggage => options
+                .allow_leading_spaces(true)
+                .delimiter_style(DelimiterStyle::Spaced)
+                .key_chars([
+                    '!', '#', '$', '%', '&', '\'', '*', '+', '.', '^', '`', '|', '~',
+                ])
+                .property_separator(';'),
+            Preset::PrometheusLabels => options.allow_empty_pairs(true),
+            Preset::KubernetesLabels => options.key_chars(['.', '/']),
+            Preset::AwsShorthand => options.list_separator(','),
+            Preset::SyslogStructuredData => options.key_chars(['.', '@', ':']),
+            Preset::InfluxTags => options,
+            Preset::PostgresDsn => options
+                .pair_separators([' ', '\t', '\n', '\r'])
+                .delimiter_style(DelimiterStyle::Spaced)
+                .allow_empty_pairs(true)
+                .true_values(["true", "on", "yes", "1"])
+                .false_values(["false", "off", "no", "0"]),
+            Preset::OdbcConnectionString => options
+                .allow_empty_pairs(true)
+                .key_chars([' ', '.'])
+                .true_values(["yes", "Yes", "true", "True", "1"])
+                .false_values(["no", "No", "false", "False", "0"]),
+            Preset::Ini => options
+                .pair_separators(['\n', '\r'])
+                .delimiter_style(DelimiterStyle::Spaced)
+                .allow_empty_pairs(true)
+                .allow_leading_spaces(true)
+                .comment_chars(['#', ';']),
+            Preset::ShellExport => options
+                .pair_separators(['\n', '\r'])
+                .allow_empty_pairs(true)
+                .comment_chars(['#']),
+            Preset::Headers => options
+                .pair_separators(['\n', '\r'])
+                .allow_empty_pairs(true),
+            Preset::Sysctl => options
+                .pair_separators(['\n', '\r'])
+                .allow_empty_pairs(true)
+                .allow_leading_spaces(true)
+                .comment_chars(['#', ';'])
+                .ignored_key_prefix("-")
+                .key_chars(['.', '/'])
+                .slash_separated_keys(true),
+            Preset::GstElement => options
+                .pair_separators([' ', '\t', '\n', '\r'])
+                .allow_empty_pairs(true),
+            Preset::FfmpegOptions => options
+                .true_values(["1", "true"])
+                .false_values(["0", "false"]),
+            // Attribute types may be given as OIDs, and LDAP booleans are uppercase.
+            Preset::DistinguishedName => options
+                .key_chars(['.'])
+                .true_values(["true", "TRUE"])
+                .false_values(["false", "FALSE"]),
+        }
+    }
+}
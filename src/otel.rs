@@ -0,0 +1,301 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Conversions between key-values strings and [OpenTelemetry](https://docs.rs/opentelemetry)
+//! attributes.
+
+use opentelemetry::Array;
+use opentelemetry::KeyValue;
+use opentelemetry::Value as OtelValue;
+use serde::Serializer;
+
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+use crate::KeyValueSerializer;
+use crate::Map;
+use crate::ParseError;
+use crate::SerializeError;
+use crate::SerializeOptions;
+use crate::Value;
+
+/// Parses the key-values string `input` into OpenTelemetry attributes, e.g. the resource
+/// attributes given on the command line.
+///
+/// Keys containing dots are accepted, as in `service.name=api`. As for a [`Map`], the type of
+/// values is guessed from their content: a key without a value is a `true` boolean, numbers are
+/// integers, which become strings if they are too large for an `I64` value, and everything else is
+/// a string. Lists become arrays if their values all have the same type, and are omitted
+/// otherwise, as attribute arrays must be homogeneous.
+///
+/// ```
+/// use opentelemetry::KeyValue;
+/// use serde_keyvalue::to_otel_attributes;
+///
+/// let attributes = to_otel_attributes("replicas=3,service.name=api").unwrap();
+/// assert_eq!(
+///     attributes,
+///     [KeyValue::new("replicas", 3), KeyValue::new("service.name", "api")]
+/// );
+/// ```
+pub fn to_otel_attributes(input: &str) -> Result<Vec<KeyValue>, ParseError> {
+    to_otel_attributes_with(input, DeserializeOptions::new().nested_key_separator('.'))
+}
+
+/// Parses the key-values string `input` into OpenTelemetry attributes, using the syntax described
+/// by `options`, e.g. [`Preset::Baggage`](crate::Preset::Baggage) for the
+/// `OTEL_RESOURCE_ATTRIBUTES` variable. See [`to_otel_attributes`] for details.
+///
+/// Nested values, such as those of dotted keys when `options` has a
+/// [nested key separator](DeserializeOptions::nested_key_separator), are flattened back into
+/// attributes whose keys are joined with dots.
+pub fn to_otel_attributes_with(
+    input: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<Vec<KeyValue>, ParseError> {
+    let map: Map = from_key_values_with(input, options)?;
+    let mut attributes = Vec::new();
+    add_attributes(&mut attributes, "", &map);
+    Ok(attributes)
+}
+
+/// Adds the pairs of `map` to `attributes`, with their keys prefixed by `prefix`.
+fn add_attributes(attributes: &mut Vec<KeyValue>, prefix: &str, map: &Map) {
+    for (key, value) in map {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Map(map) => add_attributes(attributes, &key, map),
+            value => {
+                if let Some(value) = attribute_value(value) {
+                    attributes.push(KeyValue::new(key, value));
+                }
+            }
+        }
+    }
+}
+
+/// Returns the attribute value of a scalar or list value.
+fn attribute_value(value: &Value) -> Option<OtelValue> {
+    Some(match value {
+        Value::Bool(b) => OtelValue::Bool(*b),
+        Value::Int(i) => OtelValue::I64(*i),
+        Value::UInt(u) => match i64::try_from(*u) {
+            Ok(i) => OtelValue::I64(i),
+            Err(_) => OtelValue::String(u.to_string().into()),
+        },
+        Value::Float(f) => OtelValue::F64(*f),
+        Value::String(s) => OtelValue::String(s.clone().into()),
+        Value::List(list) => OtelValue::Array(attribute_array(list)?),
+        Value::Map(_) => return None,
+    })
+}
+
+/// Returns the attribute array of the values of `list`, which must all have the same type.
+fn attribute_array(list: &[Value]) -> Option<Array> {
+    let values: Vec<OtelValue> = list
+        .iter()
+        .map(|value| match value {
+            Value::List(_) | Value::Map(_) => None,
+            value => attribute_value(value),
+        })
+        .collect::<Option<_>>()?;
+
+    let all = |f: fn(&OtelValue) -> bool| values.iter().all(f);
+    Some(if all(|v| matches!(v, OtelValue::String(_))) {
+        Array::String(
+            values
+                .into_iter()
+                .filter_map(|v| match v {
+                    OtelValue::String(s) => Some(s),
+                    _ => None,
+                })
+                .collect(),
+        )
+    } else if all(|v| matches!(v, OtelValue::Bool(_))) {
+        Array::Bool(
+            values
+                .iter()
+                .filter_map(|v| match v {
+                    OtelValue::Bool(b) => Some(*b),
+                    _ => None,
+                })
+                .collect(),
+        )
+    } else if all(|v| matches!(v, OtelValue::I64(_))) {
+        Array::I64(
+            values
+                .iter()
+                .filter_map(|v| match v {
+                    OtelValue::I64(i) => Some(*i),
+                    _ => None,
+                })
+                .collect(),
+        )
+    } else if all(|v| matches!(v, OtelValue::I64(_) | OtelValue::F64(_))) {
+        Array::F64(
+            values
+                .iter()
+                .filter_map(|v| match v {
+                    OtelValue::I64(i) => Some(*i as f64),
+                    OtelValue::F64(f) => Some(*f),
+                    _ => None,
+                })
+                .collect(),
+        )
+    } else {
+        return None;
+    })
+}
+
+/// Writes OpenTelemetry attributes, e.g. those of a span, as a key-values string.
+///
+/// Array attributes are written as lists.
+///
+/// ```
+/// use opentelemetry::KeyValue;
+/// use serde_keyvalue::from_otel_attributes;
+///
+/// let attributes = [KeyValue::new("service.name", "api"), KeyValue::new("replicas", 3)];
+/// assert_eq!(
+///     from_otel_attributes(&attributes).unwrap(),
+///     "service.name=api replicas=3"
+/// );
+/// ```
+pub fn from_otel_attributes(attributes: &[KeyValue]) -> Result<String, SerializeError> {
+    from_otel_attributes_with(attributes, SerializeOptions::default())
+}
+
+/// Writes OpenTelemetry attributes as a key-values string according to `options`. See
+/// [`from_otel_attributes`] for details.
+pub fn from_otel_attributes_with(
+    attributes: &[KeyValue],
+    options: impl Into<SerializeOptions>,
+) -> Result<String, SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(options);
+    (&mut serializer).collect_map(
+        attributes
+            .iter()
+            .map(|kv| (kv.key.as_str(), value_of_attribute(&kv.value))),
+    )?;
+    Ok(serializer.into_output())
+}
+
+/// Returns the value of an attribute.
+fn value_of_attribute(value: &OtelValue) -> Value {
+    match value {
+        OtelValue::Bool(b) => Value::Bool(*b),
+        OtelValue::I64(i) => Value::Int(*i),
+        OtelValue::F64(f) => Value::Float(*f),
+        OtelValue::String(s) => Value::String(s.as_str().into()),
+        OtelValue::Array(Array::Bool(values)) => {
+            Value::List(values.iter().map(|b| Value::Bool(*b)).collect())
+        }
+        OtelValue::Array(Array::I64(values)) => {
+            Value::List(values.iter().map(|i| Value::Int(*i)).collect())
+        }
+        OtelValue::Array(Array::F64(values)) => {
+            Value::List(values.iter().map(|f| Value::Float(*f)).collect())
+        }
+        OtelValue::Array(Array::String(values)) => Value::List(
+            values
+                .iter()
+                .map(|s| Value::String(s.as_str().into()))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Preset;
+
+    /// Returns `attributes` sorted by key, as they are in the order of their `Map` with the
+    /// `preserve_order` feature.
+    fn sorted(mut attributes: Vec<KeyValue>) -> Vec<KeyValue> {
+        attributes.sort_by(|a, b| a.key.cmp(&b.key));
+        attributes
+    }
+
+    #[test]
+    fn attributes_from_string() {
+        let attributes =
+            to_otel_attributes("service.name=api,service.version=1.2,sampled,ratio=0.5").unwrap();
+        assert_eq!(
+            sorted(attributes),
+            [
+                KeyValue::new("ratio", "0.5"),
+                KeyValue::new("sampled", true),
+                KeyValue::new("service.name", "api"),
+                KeyValue::new("service.version", "1.2"),
+            ]
+        );
+
+        let attributes = to_otel_attributes_with(
+            "big=18446744073709551615,ports=[80,443],mixed=[1,a],flags=[true]",
+            DeserializeOptions::new().list_separator(','),
+        )
+        .unwrap();
+        assert_eq!(
+            sorted(attributes),
+            [
+                KeyValue::new("big", "18446744073709551615"),
+                KeyValue::new("flags", OtelValue::Array(Array::Bool(vec![true]))),
+                KeyValue::new("ports", OtelValue::Array(Array::I64(vec![80, 443]))),
+            ]
+        );
+
+        let attributes =
+            to_otel_attributes_with("deployment.environment=prod,team=a%20b", Preset::Baggage)
+                .unwrap();
+        assert_eq!(
+            sorted(attributes),
+            [
+                KeyValue::new("deployment.environment", "prod"),
+                KeyValue::new("team", "a b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_from_map() {
+        let map: Map = [
+            ("small", Value::UInt(7)),
+            ("big", Value::UInt(u64::MAX)),
+            (
+                "ratios",
+                Value::List(vec![Value::Int(1), Value::Float(0.5)]),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut attributes = Vec::new();
+        add_attributes(&mut attributes, "", &map);
+        assert_eq!(
+            sorted(attributes),
+            [
+                KeyValue::new("big", u64::MAX.to_string()),
+                KeyValue::new("ratios", OtelValue::Array(Array::F64(vec![1.0, 0.5]))),
+                KeyValue::new("small", 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_from_attributes() {
+        let attributes = [
+            KeyValue::new("service.name", "my api"),
+            KeyValue::new("replicas", 3),
+            KeyValue::new("ports", OtelValue::Array(Array::I64(vec![80, 443]))),
+        ];
+        let output = from_otel_attributes_with(&attributes, Preset::Logfmt).unwrap();
+        assert_eq!(output, r#"service.name="my api" replicas=3 ports=[80,443]"#);
+
+        let read = to_otel_attributes(&from_otel_attributes(&attributes[1..]).unwrap()).unwrap();
+        assert_eq!(sorted(read), [attributes[2].clone(), attributes[1].clone()]);
+    }
+}
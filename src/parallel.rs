@@ -0,0 +1,224 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Deserialization of records, one per line, on several threads.
+//!
+//! By default, the lines are split between scoped [`std::thread`]s, one per available core, so
+//! the `parallel` feature providing this module adds no dependency. With the `rayon` feature, they
+//! are parsed on the global [rayon](https://docs.rs/rayon) thread pool instead, which balances the
+//! work between its threads and shares them with the rest of the program.
+
+use std::thread;
+
+use serde::Deserialize;
+
+use crate::from_key_values_with;
+use crate::records;
+use crate::DeserializeOptions;
+use crate::ParseError;
+
+/// Deserializes each non-empty line of `input` as a `T`, splitting the lines between as many
+/// threads as there are available cores, or between the threads of the global rayon thread pool
+/// with the `rayon` feature.
+///
+/// The results are returned in the order of the lines, and the positions of errors are offsets in
+/// `input`, whatever the thread that parsed their line. Lines ending with `\r\n` are supported.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::par_from_lines;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Event {
+///     level: String,
+///     code: u32,
+/// }
+///
+/// let events = par_from_lines::<Event>("level=info code=1\n\nlevel=warn code=x\n");
+/// assert_eq!(events.len(), 2);
+/// assert_eq!(events[0], Ok(Event { level: "info".into(), code: 1 }));
+/// assert_eq!(events[1].as_ref().unwrap_err().pos, 35);
+/// ```
+pub fn par_from_lines<'a, T>(input: &'a str) -> Vec<Result<T, ParseError>>
+where
+    T: Deserialize<'a> + Send,
+{
+    par_from_lines_with(input, DeserializeOptions::default())
+}
+
+/// Deserializes each non-empty record of `input` as a `T` on several threads, using the syntax
+/// described by `options`. See [`par_from_lines`] for details.
+///
+/// Records are split as by [`records`], i.e. on the record separator of `options`, one per line
+/// by default, except within quoted strings. Since records are split before being parsed,
+/// `options` should not otherwise describe a dialect whose pairs span several records.
+pub fn par_from_lines_with<'a, T>(
+    input: &'a str,
+    options: impl Into<DeserializeOptions>,
+) -> Vec<Result<T, ParseError>>
+where
+    T: Deserialize<'a> + Send,
+{
+    #[cfg(feature = "rayon")]
+    {
+        parse_on_rayon(input, options.into())
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        parse_on_threads(input, options.into(), threads)
+    }
+}
+
+/// Deserializes the record `line` of `input`, reporting errors at their position in `input`.
+fn parse_record<'a, T: Deserialize<'a>>(
+    input: &str,
+    line: &'a str,
+    options: &DeserializeOptions,
+) -> Result<T, ParseError> {
+    // Records are slices of `input`.
+    let start = line.as_ptr() as usize - input.as_ptr() as usize;
+    from_key_values_with(line, options.clone()).map_err(|e| ParseError {
+        pos: start + e.pos,
+        ..e
+    })
+}
+
+/// Deserializes the records of `input` on the global rayon thread pool.
+#[cfg(feature = "rayon")]
+fn parse_on_rayon<'a, T>(input: &'a str, options: DeserializeOptions) -> Vec<Result<T, ParseError>>
+where
+    T: Deserialize<'a> + Send,
+{
+    use rayon::prelude::*;
+
+    let lines: Vec<&str> = records(input, options.clone()).collect();
+    lines
+        .par_iter()
+        .map(|line| parse_record(input, line, &options))
+        .collect()
+}
+
+/// Deserializes the records of `input` split between `threads` threads.
+#[cfg_attr(feature = "rayon", allow(dead_code))]
+fn parse_on_threads<'a, T>(
+    input: &'a str,
+    options: DeserializeOptions,
+    threads: usize,
+) -> Vec<Result<T, ParseError>>
+where
+    T: Deserialize<'a> + Send,
+{
+    let lines: Vec<&str> = records(input, options.clone()).collect();
+    let chunk_len = lines.len().div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = lines
+            .chunks(chunk_len)
+            .map(|chunk| {
+                let options = &options;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|line| parse_record(input, line, options))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+    use crate::Preset;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record<'a> {
+        id: u32,
+        name: &'a str,
+    }
+
+    #[test]
+    fn par_from_lines_order() {
+        let input: String = (0..1000)
+            .map(|id| format!("id={} name=n{}\r\n", id, id))
+            .collect();
+        let names: Vec<String> = (0..1000).map(|id| format!("n{}", id)).collect();
+
+        let records = par_from_lines::<Record>(&input);
+        assert_eq!(records.len(), 1000);
+        for (id, record) in records.into_iter().enumerate() {
+            assert_eq!(
+                record.unwrap(),
+                Record {
+                    id: id as u32,
+                    name: &names[id],
+                }
+            );
+        }
+
+        assert!(par_from_lines::<Record>("").is_empty());
+    }
+
+    #[test]
+    fn par_from_lines_errors() {
+        let records =
+            par_from_lines_with::<Record>("id=1 name=a\nid=2 nom=b\nid=x name=c", Preset::Logfmt);
+        assert_eq!(records[0], Ok(Record { id: 1, name: "a" }));
+        assert!(matches!(
+            &records[1],
+            Err(ParseError {
+                kind: ErrorKind::SerdeError(_),
+                ..
+            })
+        ));
+        assert_eq!(
+            records[2],
+            Err(ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 26,
+            })
+        );
+    }
+
+    #[test]
+    fn par_from_lines_absolute_positions() {
+        let input: String = (0..100)
+            .map(|id| {
+                if id % 7 == 3 {
+                    format!("id={}x name=n\n", id)
+                } else {
+                    format!("id={} name=n\r\n", id)
+                }
+            })
+            .collect();
+        let expected: Vec<usize> = input.match_indices("x name").map(|(i, _)| i).collect();
+
+        for threads in [1, 2, 3, 8, 200] {
+            let positions: Vec<usize> =
+                parse_on_threads::<Record>(&input, Preset::Logfmt.into(), threads)
+                    .into_iter()
+                    .filter_map(|record| record.err())
+                    .map(|e| e.pos)
+                    .collect();
+            assert_eq!(positions.len(), 14);
+            for (pos, x) in positions.iter().zip(&expected) {
+                // Each error points at the start of its invalid number.
+                let number_start = input[..*x].rfind('=').unwrap() + 1;
+                assert_eq!(*pos, number_start, "{} threads", threads);
+            }
+        }
+        assert_eq!(
+            par_from_lines_with::<Record>(&input, Preset::Logfmt),
+            parse_on_threads(&input, Preset::Logfmt.into(), 1)
+        );
+    }
+}
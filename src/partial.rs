@@ -0,0 +1,251 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Best-effort deserialization of key-values strings.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::DeserializeOptions;
+use crate::options::SerializeOptions;
+use crate::scanner::scan_all;
+use crate::scanner::ScannedPair;
+use crate::scanner::Scanner;
+use crate::trace::trace_fields;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+
+/// Deserializes `input`, written in the dialect described by `options`, into `T` as far as
+/// possible, and returns it along with all the errors met, e.g. so an interactive tool can show a
+/// preview of a configuration alongside its problems rather than nothing at all.
+///
+/// The pairs that are malformed or whose value cannot be deserialized are skipped, and the
+/// fields they would set, as well as the required fields missing from `input`, get their value in
+/// `T::default()`. The errors met in `input` are returned in order, followed by one error for
+/// each missing field, positioned at the end of `input`. If the valid pairs cannot be
+/// deserialized together, e.g. because a key is repeated, the error is returned last along with
+/// `T::default()`.
+///
+/// Each pair is deserialized on its own along with the default values of the other fields, so
+/// this is slower than [`from_key_values_with`] and suited to inputs written by people.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_keyvalue::{from_key_values_partial, Preset};
+///
+/// #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+/// struct Net {
+///     mtu: u16,
+///     tap: String,
+///     vhost: bool,
+/// }
+///
+/// let (net, errors): (Net, _) =
+///     from_key_values_partial(r#"mtu=huge tap=tap0 vhost="#, Preset::Logfmt);
+/// assert_eq!(net, Net { mtu: 0, tap: "tap0".into(), vhost: false });
+/// let errors: Vec<_> = errors.into_iter().map(|e| e.to_string()).collect();
+/// assert_eq!(
+///     errors,
+///     [
+///         "invalid characters for number or number does not fit into its destination type \
+///          at position 4",
+///         "expected a boolean at position 24",
+///     ]
+/// );
+/// ```
+pub fn from_key_values_partial<T, O>(input: &str, options: O) -> (T, Vec<ParseError>)
+where
+    T: DeserializeOwned + Serialize + Default,
+    O: Into<SerializeOptions> + Into<DeserializeOptions> + Clone,
+{
+    let serialize_options: SerializeOptions = options.clone().into();
+    let deserialize_options: DeserializeOptions = options.into();
+    let (pairs, mut errors) = scan_all(input, deserialize_options.clone());
+
+    // The pairs of the default value, whose keys are expected to be the ones of the fields of `T`.
+    let mut serializer = KeyValueSerializer::with_options(serialize_options.clone());
+    let defaults = match T::default().serialize(&mut serializer) {
+        Ok(()) => serializer.into_output(),
+        Err(_) => String::new(),
+    };
+    let default_pairs: Vec<ScannedPair> = Scanner::new(&defaults, deserialize_options.clone())
+        .filter_map(Result::ok)
+        .collect();
+    let layout = Layout {
+        input,
+        defaults: &defaults,
+        default_pairs: &default_pairs,
+        separator: &serialize_options.pair_separator,
+    };
+    let parse = |text: &str| from_key_values_with::<T>(text, deserialize_options.clone());
+
+    let mut valid = Vec::new();
+    for pair in &pairs {
+        let (text, _) = layout.join(&[pair], None);
+        match parse(&text) {
+            Ok(_) => valid.push(pair),
+            // The pair starts the text, so positions within it are the same as in `input`.
+            Err(e) => errors.push(ParseError {
+                pos: pair.span.start + if e.pos <= pair.span.len() { e.pos } else { 0 },
+                ..e
+            }),
+        }
+    }
+    errors.sort_by_key(|e| e.pos);
+
+    for (field, _) in trace_fields::<T>().unwrap_or_default() {
+        if pairs.iter().any(|pair| pair.key == field) {
+            continue;
+        }
+        let (text, _) = layout.join(&[], Some(field));
+        if let Err(e) = parse(&text) {
+            errors.push(ParseError {
+                pos: input.len(),
+                ..e
+            });
+        }
+    }
+
+    let (text, offsets) = layout.join(&valid, None);
+    match parse(&text) {
+        Ok(value) => (value, errors),
+        Err(e) => {
+            let pos = valid
+                .iter()
+                .zip(offsets)
+                .find(|(pair, offset)| (*offset..=offset + pair.span.len()).contains(&e.pos))
+                .map_or(input.len(), |(pair, offset)| {
+                    pair.span.start + e.pos - offset
+                });
+            errors.push(ParseError { pos, ..e });
+            (T::default(), errors)
+        }
+    }
+}
+
+/// Joins pairs of the input with the pairs of the default value.
+struct Layout<'a> {
+    input: &'a str,
+    defaults: &'a str,
+    default_pairs: &'a [ScannedPair<'a>],
+    separator: &'a str,
+}
+
+impl Layout<'_> {
+    /// Returns the text made of `pairs` followed by the default pairs whose key is neither the key
+    /// of one of `pairs` nor `omitted`, along with the position of each of `pairs` in the text.
+    fn join(&self, pairs: &[&ScannedPair], omitted: Option<&str>) -> (String, Vec<usize>) {
+        let mut text = String::new();
+        let mut offsets = Vec::with_capacity(pairs.len());
+        let defaults = self.default_pairs.iter().filter(|default| {
+            Some(default.key) != omitted && pairs.iter().all(|pair| pair.key != default.key)
+        });
+
+        for (i, pair) in pairs.iter().enumerate() {
+            if i > 0 {
+                text.push_str(self.separator);
+            }
+            offsets.push(text.len());
+            text.push_str(&self.input[pair.span.clone()]);
+        }
+        for default in defaults {
+            if !text.is_empty() {
+                text.push_str(self.separator);
+            }
+            text.push_str(&self.defaults[default.span.clone()]);
+        }
+
+        (text, offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::ErrorKind;
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    struct Inner {
+        size: u32,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    struct Disk {
+        path: String,
+        #[serde(default)]
+        ro: bool,
+        queues: Vec<u8>,
+        inner: Inner,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    }
+
+    fn partial(input: &str) -> (Disk, Vec<(ErrorKind, usize)>) {
+        let (disk, errors) = from_key_values_partial(input, SerializeOptions::new());
+        (disk, errors.into_iter().map(|e| (e.kind, e.pos)).collect())
+    }
+
+    #[test]
+    fn partial_values() {
+        let (disk, errors) = partial("path=/a queues=[1,x] ro=yes id=d inner=[size=2]");
+        assert_eq!(
+            disk,
+            Disk {
+                path: "/a".into(),
+                ro: false,
+                queues: Vec::new(),
+                inner: Inner { size: 2 },
+                id: Some("d".into()),
+            }
+        );
+        assert_eq!(
+            errors,
+            [
+                (ErrorKind::InvalidNumber, 18),
+                (ErrorKind::ExpectedBoolean, 24),
+            ]
+        );
+
+        let (disk, errors) = partial("path=/a queues=[] inner=[size=1] ro id=x");
+        assert_eq!(disk.id.as_deref(), Some("x"));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn partial_syntax_and_missing_fields() {
+        let (disk, errors) = partial("=1 path=/b unknown=1 inner=[size=3]");
+        assert_eq!(disk.path, "/b");
+        assert_eq!(disk.inner, Inner { size: 3 });
+        assert_eq!(
+            errors,
+            [
+                (ErrorKind::ExpectedIdentifier, 0),
+                (
+                    ErrorKind::SerdeError(
+                        "unknown field `unknown`, expected one of `path`, `ro`, `queues`, \
+                         `inner`, `id`"
+                            .into()
+                    ),
+                    11
+                ),
+                (ErrorKind::SerdeError("missing field `queues`".into()), 35),
+            ]
+        );
+
+        // Repeated keys cannot be deserialized together.
+        let (disk, errors) = partial("path=/a path=/b queues=[] inner=[size=1]");
+        assert_eq!(disk, Disk::default());
+        assert_eq!(
+            errors,
+            [(ErrorKind::SerdeError("duplicate field `path`".into()), 0)]
+        );
+    }
+}
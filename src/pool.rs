@@ -0,0 +1,90 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Serializers reused across calls on the same thread.
+
+use std::cell::RefCell;
+
+use crate::KeyValueSerializer;
+
+thread_local! {
+    static SERIALIZER: RefCell<Option<KeyValueSerializer>> = const { RefCell::new(None) };
+}
+
+/// Calls `f` with a cleared serializer using the default options, which is kept by the current
+/// thread for the next calls, so serializing values frequently does not allocate new buffers each
+/// time.
+///
+/// Calls nested in `f` use a new serializer. Serializers using other options can be reused with
+/// [`KeyValueSerializer::clear`].
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::with_serializer;
+///
+/// #[derive(Serialize)]
+/// struct Event<'a> {
+///     level: &'a str,
+///     code: u32,
+/// }
+///
+/// let mut log = String::new();
+/// for code in [1, 2] {
+///     with_serializer(|serializer| {
+///         Event { level: "info", code }.serialize(&mut *serializer).unwrap();
+///         log.push_str(serializer.output());
+///         log.push('\n');
+///     });
+/// }
+///
+/// assert_eq!(log, "level=info code=1\nlevel=info code=2\n");
+/// ```
+pub fn with_serializer<R>(f: impl FnOnce(&mut KeyValueSerializer) -> R) -> R {
+    SERIALIZER.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut serializer) => {
+            let serializer = serializer.get_or_insert_with(KeyValueSerializer::new);
+            serializer.clear();
+            f(serializer)
+        }
+        Err(_) => f(&mut KeyValueSerializer::new()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+
+    #[test]
+    fn with_serializer_reused() {
+        let map = BTreeMap::from([("a", 1), ("b", 2)]);
+
+        let first = with_serializer(|s| {
+            map.serialize(&mut *s).unwrap();
+            (s.output().to_string(), s.output().as_ptr())
+        });
+        let second = with_serializer(|s| {
+            map.serialize(&mut *s).unwrap();
+            (s.output().to_string(), s.output().as_ptr())
+        });
+        assert_eq!(first, second);
+        assert_eq!(first.0, "a=1 b=2");
+    }
+
+    #[test]
+    fn with_serializer_nested() {
+        let output = with_serializer(|outer| {
+            BTreeMap::from([("a", 1)]).serialize(&mut *outer).unwrap();
+            let inner = with_serializer(|inner| {
+                BTreeMap::from([("b", 2)]).serialize(&mut *inner).unwrap();
+                inner.output().to_string()
+            });
+            format!("{} {}", outer.output(), inner)
+        });
+        assert_eq!(output, "a=1 b=2");
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Prometheus label sets.
+
+use alloc::format;
+use alloc::string::String;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::Preset;
+use crate::ErrorKind;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Deserializes `T` from a Prometheus label set, e.g. `{job="api",code="200"}`.
+///
+/// The enclosing braces are optional, values must be double-quoted, and `"`, `\` and newlines are
+/// unescaped. Numbers and booleans are parsed from the quoted label values. See
+/// [`Preset::PrometheusLabels`] for details.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_labels;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Labels {
+///     job: String,
+///     code: u16,
+/// }
+///
+/// let labels: Labels = from_labels(r#"{job="api",code="200"}"#).unwrap();
+/// assert_eq!(labels, Labels { job: "api".into(), code: 200 });
+/// ```
+pub fn from_labels<'a, T: Deserialize<'a>>(input: &'a str) -> Result<T, ParseError> {
+    let labels = match input.strip_prefix('{') {
+        Some(labels) => labels.strip_suffix('}').ok_or(ParseError {
+            kind: ErrorKind::ExpectedCloseBracket,
+            pos: input.len(),
+        })?,
+        None => return from_key_values_with(input, Preset::PrometheusLabels),
+    };
+
+    // Report errors relative to the start of `input`, i.e. past the opening brace.
+    from_key_values_with(labels, Preset::PrometheusLabels).map_err(|e| ParseError {
+        pos: e.pos + 1,
+        ..e
+    })
+}
+
+/// Serializes `value` as a Prometheus label set enclosed in braces, which can be parsed back with
+/// [`from_labels`].
+///
+/// All values, including numbers and booleans, are double-quoted, and `"`, `\` and newlines are
+/// escaped. An empty label set is written as an empty string.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_labels;
+///
+/// #[derive(Serialize)]
+/// struct Labels {
+///     job: String,
+///     code: u16,
+/// }
+///
+/// let labels = Labels { job: "a \"b\"".into(), code: 200 };
+/// assert_eq!(to_labels(&labels).unwrap(), r#"{job="a \"b\"",code="200"}"#);
+/// ```
+pub fn to_labels<T: Serialize + ?Sized>(value: &T) -> Result<String, SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(Preset::PrometheusLabels);
+    value.serialize(&mut serializer)?;
+
+    let labels = serializer.into_output();
+    if labels.is_empty() {
+        Ok(labels)
+    } else {
+        Ok(format!("{{{}}}", labels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Labels {
+        path: String,
+        code: u16,
+        cached: bool,
+    }
+
+    #[test]
+    fn labels_roundtrip() {
+        let labels = Labels {
+            path: "C:\\dir\n\"x\"".into(),
+            code: 404,
+            cached: false,
+        };
+        let text = to_labels(&labels).unwrap();
+
+        assert_eq!(text, r#"{path="C:\\dir\n\"x\"",code="404",cached="false"}"#);
+        assert_eq!(from_labels::<Labels>(&text).unwrap(), labels);
+    }
+
+    #[test]
+    fn labels_syntax() {
+        let labels: BTreeMap<String, String> = from_labels(r#"{a="1",b="",}"#).unwrap();
+        assert_eq!(
+            labels,
+            BTreeMap::from([("a".into(), "1".into()), ("b".into(), "".into())])
+        );
+        assert_eq!(
+            from_labels::<BTreeMap<String, String>>(r#"a="x""#).unwrap(),
+            BTreeMap::from([("a".into(), "x".into())])
+        );
+        assert_eq!(
+            from_labels::<Labels>(r#"path="/",code=200,cached=true"#).unwrap(),
+            Labels {
+                path: "/".into(),
+                code: 200,
+                cached: true,
+            }
+        );
+        assert_eq!(to_labels(&BTreeMap::<String, String>::new()).unwrap(), "");
+    }
+
+    #[test]
+    fn labels_errors() {
+        assert_eq!(
+            from_labels::<BTreeMap<String, String>>(r#"{a="1""#).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 6,
+            }
+        );
+        assert_eq!(
+            from_labels::<BTreeMap<String, String>>("{a=x}")
+                .unwrap_err()
+                .pos,
+            3
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Parsing of records, one per line, from input received in chunks.
+
+use alloc::vec::Vec;
+use core::str;
+
+use memchr::memchr;
+use serde::de::DeserializeOwned;
+
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+use crate::ErrorKind;
+use crate::ParseError;
+
+/// Parser of records, one per line, fed with chunks of input as they are received, e.g. from a
+/// network connection.
+///
+/// A record is returned by [`next_record`](Self::next_record) once its line is complete, so
+/// records may span several chunks, and chunks may end in the middle of a UTF-8 character. Empty
+/// lines are skipped, and lines may end with `\r\n`. After [`finish`](Self::finish) has been
+/// called, the last line is returned even if it does not end with a newline.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::PushParser;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Event {
+///     level: String,
+///     code: u32,
+/// }
+///
+/// let mut parser = PushParser::new();
+/// parser.feed(b"level=info code=1\nlevel=wa");
+/// assert_eq!(parser.next_record(), Some(Ok(Event { level: "info".into(), code: 1 })));
+/// assert_eq!(parser.next_record::<Event>(), None);
+///
+/// parser.feed(b"rn code=2");
+/// parser.finish();
+/// assert_eq!(parser.next_record(), Some(Ok(Event { level: "warn".into(), code: 2 })));
+/// assert_eq!(parser.next_record::<Event>(), None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PushParser {
+    buffer: Vec<u8>,
+    /// Length of the start of `buffer` made of records already returned.
+    consumed: usize,
+    finished: bool,
+    options: DeserializeOptions,
+}
+
+impl PushParser {
+    /// Creates a `PushParser` accepting the default syntax.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a `PushParser` accepting the syntax described by `options`, which can also be a
+    /// [`Preset`](crate::Preset). Since lines are split before being parsed, `options` should not
+    /// describe a dialect whose pairs or values span several lines.
+    pub fn with_options(options: impl Into<DeserializeOptions>) -> Self {
+        PushParser {
+            options: options.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Appends `chunk` to the input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.drain(..self.consumed);
+        self.consumed = 0;
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Marks the end of the input, so the last line is returned as a record even if it does not
+    /// end with a newline.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Deserializes the next complete record, or returns `None` if there is none yet.
+    ///
+    /// The positions of errors are relative to the line of the record. Lines that are not valid
+    /// UTF-8 are reported as [`ErrorKind::InvalidUtf8`] errors.
+    pub fn next_record<T: DeserializeOwned>(&mut self) -> Option<Result<T, ParseError>> {
+        loop {
+            let rest = &self.buffer[self.consumed..];
+            let line = match memchr(b'\n', rest) {
+                Some(end) => {
+                    self.consumed += end + 1;
+                    &rest[..end]
+                }
+                None if self.finished && !rest.is_empty() => {
+                    self.consumed = self.buffer.len();
+                    rest
+                }
+                None => return None,
+            };
+
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+
+            return Some(match str::from_utf8(line) {
+                Ok(line) => from_key_values_with(line, self.options.clone()),
+                Err(e) => Err(ParseError {
+                    kind: ErrorKind::InvalidUtf8,
+                    pos: e.valid_up_to(),
+                }),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::Preset;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record {
+        id: u32,
+        name: String,
+    }
+
+    fn record(id: u32, name: &str) -> Option<Result<Record, ParseError>> {
+        Some(Ok(Record {
+            id,
+            name: name.into(),
+        }))
+    }
+
+    #[test]
+    fn push_parser_chunks() {
+        let input = "id=1 name=\"é t\"\r\n\nid=2 name=b\nid=3 name=c";
+        let mut parser = PushParser::with_options(Preset::Logfmt);
+
+        // Feed the input one byte at a time, splitting the multibyte character.
+        let mut records = Vec::new();
+        for b in input.bytes() {
+            parser.feed(&[b]);
+            while let Some(record) = parser.next_record() {
+                records.push(record);
+            }
+        }
+        assert_eq!(
+            records,
+            [record(1, "é t").unwrap(), record(2, "b").unwrap()]
+        );
+
+        assert_eq!(parser.next_record::<Record>(), None);
+        parser.finish();
+        assert_eq!(parser.next_record(), record(3, "c"));
+        assert_eq!(parser.next_record::<Record>(), None);
+    }
+
+    #[test]
+    fn push_parser_errors() {
+        let mut parser = PushParser::new();
+        parser.feed(b"id=x name=a\nid=1 name=\xff\nid=2 name=b\n");
+
+        assert_eq!(
+            parser.next_record::<Record>(),
+            Some(Err(ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 3,
+            }))
+        );
+        assert_eq!(
+            parser.next_record::<Record>(),
+            Some(Err(ParseError {
+                kind: ErrorKind::InvalidUtf8,
+                pos: 10,
+            }))
+        );
+        assert_eq!(parser.next_record(), record(2, "b"));
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Values captured without being parsed.
+
+use core::fmt;
+
+use serde::de;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::from_key_values_with;
+use crate::options::DeserializeOptions;
+use crate::ParseError;
+
+/// Name of the newtype struct used to serialize and deserialize a [`RawValue`].
+///
+/// It is also a per-field format name, so [`KeyValueSerializer`](crate::KeyValueSerializer) writes
+/// the value verbatim.
+pub(crate) const RAW_VALUE: &str = "$serde_keyvalue::private::FieldFormat:raw";
+
+/// The unparsed value of a key, borrowed from the input.
+///
+/// A `RawValue` field captures its value verbatim, including any quotes or brackets, so it can be
+/// parsed later, e.g. when its syntax depends on the value of another field. Serializing a
+/// `RawValue` writes it back unchanged.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::{from_key_values, RawValue};
+///
+/// #[derive(Deserialize)]
+/// struct Device<'a> {
+///     driver: String,
+///     #[serde(borrow)]
+///     driver_opts: RawValue<'a>,
+/// }
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct NetOptions {
+///     mtu: u16,
+///     tap: String,
+/// }
+///
+/// let device: Device = from_key_values("driver=net,driver_opts=[mtu=1500,tap=tap0]").unwrap();
+/// assert_eq!(device.driver_opts.get(), "[mtu=1500,tap=tap0]");
+///
+/// let opts: NetOptions = device.driver_opts.parse().unwrap();
+/// assert_eq!(opts, NetOptions { mtu: 1500, tap: "tap0".into() });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawValue<'a> {
+    raw: &'a str,
+}
+
+impl<'a> RawValue<'a> {
+    /// Creates a raw value from text that is already valid as a value.
+    pub fn new(raw: &'a str) -> Self {
+        Self { raw }
+    }
+
+    /// Returns the text of the value.
+    pub fn get(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Deserializes the value as a key-values string.
+    ///
+    /// If the value is enclosed in brackets, only their content is deserialized. Positions of
+    /// errors are relative to the start of the deserialized text.
+    pub fn parse<T: Deserialize<'a>>(&self) -> Result<T, ParseError> {
+        self.parse_with(DeserializeOptions::default())
+    }
+
+    /// Deserializes the value as a key-values string using the syntax described by `options`.
+    ///
+    /// If the value is enclosed in brackets, only their content is deserialized.
+    pub fn parse_with<T: Deserialize<'a>>(
+        &self,
+        options: impl Into<DeserializeOptions>,
+    ) -> Result<T, ParseError> {
+        let input = self
+            .raw
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(self.raw);
+
+        from_key_values_with(input, options)
+    }
+}
+
+impl fmt::Display for RawValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.raw)
+    }
+}
+
+impl Serialize for RawValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_VALUE, self.raw)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValue<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(RAW_VALUE, RawValueVisitor)
+    }
+}
+
+struct RawValueVisitor;
+
+impl<'de> Visitor<'de> for RawValueVisitor {
+    type Value = RawValue<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a borrowed raw value")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(RawValue::new(v))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        <&str>::deserialize(deserializer).map(RawValue::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_key_values;
+    use crate::ErrorKind;
+    use crate::KeyValueSerializer;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Device<'a> {
+        #[serde(borrow)]
+        opts: RawValue<'a>,
+        id: u32,
+    }
+
+    #[test]
+    fn deserialize_raw_value() {
+        let device: Device = from_key_values(r#"opts="a b",id=1"#).unwrap();
+        assert_eq!(device.opts.get(), r#""a b""#);
+
+        let device: Device = from_key_values("opts=[x=[1,2],y=\"]\"] id=2").unwrap();
+        assert_eq!(device.opts.get(), "[x=[1,2],y=\"]\"]");
+
+        // The raw value can be the first field without a key.
+        let device: Device = from_key_values("simple,id=3").unwrap();
+        assert_eq!(device.opts.get(), "simple");
+
+        let err = from_key_values::<Device>("opts=[a,b,id=3").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 14,
+            }
+        );
+    }
+
+    #[test]
+    fn serialize_raw_value() {
+        let device = Device {
+            opts: RawValue::new("[x=1,y=2]"),
+            id: 4,
+        };
+
+        let mut serializer = KeyValueSerializer::new();
+        device.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_output(), "opts=[x=1,y=2] id=4");
+    }
+}
@@ -0,0 +1,401 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Framing of multi-record input and output, one record per line by default.
+
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
+use serde::Deserialize;
+#[cfg(feature = "std")]
+use serde::Serialize;
+
+#[cfg(feature = "std")]
+use crate::from_key_values_with;
+use crate::options::Quoting;
+#[cfg(feature = "std")]
+use crate::pairs_with;
+use crate::DeserializeOptions;
+#[cfg(feature = "std")]
+use crate::ErrorKind;
+#[cfg(feature = "std")]
+use crate::KeyValueSerializer;
+#[cfg(feature = "std")]
+use crate::ParseError;
+#[cfg(feature = "std")]
+use crate::SerializeOptions;
+
+/// Returns an iterator over the records of `input`, separated by the record separator of
+/// `options`, i.e. one per line by default, e.g. to read back the output of a [`RecordWriter`]
+/// with a custom framing. Empty records are skipped.
+///
+/// Separators within strings quoted as recognized by the quoting of `options`, or escaped with a
+/// backslash where it escapes characters, do not end a record, so values may contain them, e.g.
+/// the line breaks of multi-line quoted values.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::{from_key_values_with, records, DeserializeOptions};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Event {
+///     level: String,
+///     msg: String,
+/// }
+///
+/// let options = DeserializeOptions::new().record_separator("\0");
+/// let input = "level=info msg=\"a\0b\"\0\0level=warn msg=c\0";
+/// let events: Vec<Event> = records(input, options.clone())
+///     .map(|record| from_key_values_with(record, options.clone()).unwrap())
+///     .collect();
+///
+/// assert_eq!(events[0], Event { level: "info".into(), msg: "a\0b".into() });
+/// assert_eq!(events[1], Event { level: "warn".into(), msg: "c".into() });
+/// ```
+pub fn records(input: &str, options: impl Into<DeserializeOptions>) -> impl Iterator<Item = &str> {
+    let options = options.into();
+    let mut rest = Some(input);
+
+    core::iter::from_fn(move || loop {
+        let s = rest?;
+        let len = record_len(s, &options);
+        let separator = options.record_separator.as_str();
+        rest = s[len..]
+            .strip_prefix(separator)
+            .filter(|_| !separator.is_empty());
+
+        let mut record = &s[..len];
+        if options.record_separator == "\n" {
+            record = record.strip_suffix('\r').unwrap_or(record);
+        }
+        if !record.is_empty() {
+            return Some(record);
+        }
+    })
+}
+
+/// Deserializes each non-empty line of `input` as a `T` and groups the records by the value of
+/// their `key` pair, e.g. to bucket log records by host in one call.
+///
+/// The records of each group are in the order of `input`. If `key` is repeated within a record,
+/// its last value is used. Records without a `key` pair are rejected, and the positions of errors
+/// are relative to the start of `input`.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::group_by_key;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Event {
+///     host: String,
+///     code: u32,
+/// }
+///
+/// let input = "host=a code=1\nhost=b code=2\nhost=a code=3\n";
+/// let groups = group_by_key::<Event>(input, "host").unwrap();
+/// assert_eq!(groups.len(), 2);
+/// assert_eq!(groups["a"].iter().map(|e| e.code).collect::<Vec<_>>(), [1, 3]);
+/// assert_eq!(groups["b"][0], Event { host: "b".into(), code: 2 });
+/// ```
+#[cfg(feature = "std")]
+pub fn group_by_key<'a, T: Deserialize<'a>>(
+    input: &'a str,
+    key: &str,
+) -> Result<HashMap<String, Vec<T>>, ParseError> {
+    group_by_key_with(input, key, DeserializeOptions::default())
+}
+
+/// Deserializes each non-empty record of `input` as a `T` using the syntax described by `options`,
+/// and groups the records by the value of their `key` pair. See [`group_by_key`] for details.
+///
+/// Records are split as by [`records`], on the record separator of `options`.
+#[cfg(feature = "std")]
+pub fn group_by_key_with<'a, T: Deserialize<'a>>(
+    input: &'a str,
+    key: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<HashMap<String, Vec<T>>, ParseError> {
+    let options = options.into();
+    let mut groups: HashMap<String, Vec<T>> = HashMap::new();
+
+    for record in records(input, options.clone()) {
+        // Records are slices of `input`.
+        let offset = record.as_ptr() as usize - input.as_ptr() as usize;
+        let relocate = |e: ParseError| ParseError {
+            pos: e.pos + offset,
+            ..e
+        };
+
+        // As when deserializing, the last value of a repeated key wins.
+        let mut group = None;
+        for pair in pairs_with(record, options.clone()) {
+            let (pair_key, value) = pair.map_err(relocate)?;
+            if pair_key == key {
+                group = Some(value.into_owned());
+            }
+        }
+        let group = group.ok_or_else(|| ParseError {
+            kind: ErrorKind::SerdeError(format!("missing field `{}`", key)),
+            pos: offset + record.len(),
+        })?;
+
+        let value = from_key_values_with(record, options.clone()).map_err(relocate)?;
+        groups.entry(group).or_default().push(value);
+    }
+
+    Ok(groups)
+}
+
+/// Returns the length of the record at the start of `s`, up to its first record separator that is
+/// neither quoted nor escaped.
+fn record_len(s: &str, options: &DeserializeOptions) -> usize {
+    let separator = options.record_separator.as_str();
+    let quotes = options.quoting.quotes();
+    let unquoted_escapes = matches!(
+        options.quoting,
+        Quoting::Escaped
+            | Quoting::Shell
+            | Quoting::Influx
+            | Quoting::Libpq
+            | Quoting::DistinguishedName
+            | Quoting::SingleQuoted
+            | Quoting::Ffmpeg
+    );
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            // Single quotes only escape characters in libpq connection strings.
+            (Some(q), '\\')
+                if (q == '"' && !matches!(options.quoting, Quoting::Doubled | Quoting::Kernel))
+                    || options.quoting == Quoting::Libpq =>
+            {
+                escaped = true
+            }
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\\') if unquoted_escapes => escaped = true,
+            (None, c) if quotes.contains(&c) => quote = Some(c),
+            (None, _) if !separator.is_empty() && s[i..].starts_with(separator) => return i,
+            _ => (),
+        }
+    }
+
+    s.len()
+}
+
+/// Writes records to an I/O stream as they are serialized, one per line, so the memory used does
+/// not grow with the number of records.
+///
+/// Each record is written to the stream once it is complete, so streams with costly writes such
+/// as files should be wrapped in a [`BufWriter`](std::io::BufWriter). The lines can be read back
+/// with [`records`], which also splits the records framed with another separator set with
+/// [`SerializeOptions::record_separator`], or with `par_from_lines` of the `parallel` feature.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::RecordWriter;
+///
+/// #[derive(Serialize)]
+/// struct Row {
+///     id: u32,
+///     name: String,
+/// }
+///
+/// let mut writer = RecordWriter::new(Vec::new());
+/// for id in 0..3 {
+///     writer.write(&Row { id, name: format!("row{}", id) }).unwrap();
+/// }
+///
+/// let output = String::from_utf8(writer.into_inner()).unwrap();
+/// assert_eq!(output, "id=0 name=row0\nid=1 name=row1\nid=2 name=row2\n");
+/// ```
+#[cfg(feature = "std")]
+pub struct RecordWriter<W> {
+    writer: W,
+    serializer: KeyValueSerializer,
+    separator: String,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> RecordWriter<W> {
+    /// Creates a `RecordWriter` writing records to `writer` with the default options.
+    pub fn new(writer: W) -> Self {
+        Self::with_options(writer, SerializeOptions::default())
+    }
+
+    /// Creates a `RecordWriter` writing records to `writer` according to `options`, which can
+    /// also be a [`Preset`](crate::Preset).
+    pub fn with_options(writer: W, options: impl Into<SerializeOptions>) -> Self {
+        let options = options.into();
+        RecordWriter {
+            writer,
+            separator: options.record_separator.clone(),
+            serializer: KeyValueSerializer::with_options(options),
+        }
+    }
+
+    /// Serializes `record` and writes it to the stream, followed by the record separator.
+    ///
+    /// Records that cannot be serialized are reported as [`io::ErrorKind::InvalidData`] errors,
+    /// and nothing is written for them.
+    pub fn write<T: ?Sized + Serialize>(&mut self, record: &T) -> io::Result<()> {
+        self.serializer.clear();
+        record
+            .serialize(&mut self.serializer)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "cannot serialize record"))?;
+
+        self.writer.write_all(self.serializer.output().as_bytes())?;
+        self.writer.write_all(self.separator.as_bytes())
+    }
+
+    /// Flushes the underlying stream.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Consumes the `RecordWriter` and returns the underlying stream.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::from_key_values_with;
+    use crate::Preset;
+
+    fn read_back(
+        output: &str,
+        options: impl Into<DeserializeOptions>,
+    ) -> Vec<BTreeMap<String, String>> {
+        let options = options.into();
+        records(output, options.clone())
+            .map(|record| from_key_values_with(record, options.clone()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn record_writer_roundtrip() {
+        let records: Vec<BTreeMap<String, String>> = (0..100)
+            .map(|i| BTreeMap::from([("msg".to_string(), format!("hello {}", i))]))
+            .collect();
+
+        let mut writer = RecordWriter::with_options(Vec::new(), Preset::Logfmt);
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(output.starts_with("msg=\"hello 0\"\nmsg=\"hello 1\"\n"));
+
+        assert_eq!(read_back(&output, Preset::Logfmt), records);
+    }
+
+    #[test]
+    fn record_writer_separator() {
+        let options = SerializeOptions::from(Preset::Logfmt).record_separator("\0");
+        let mut writer = RecordWriter::with_options(Vec::new(), options.clone());
+        writer.write(&BTreeMap::from([("msg", "a\0b")])).unwrap();
+        writer.write(&BTreeMap::from([("msg", "c")])).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(output, "msg=\"a\\u0000b\"\0msg=c\0");
+
+        let parsed = read_back(&output, &options);
+        assert_eq!(parsed[0]["msg"], "a\0b");
+        assert_eq!(parsed[1]["msg"], "c");
+    }
+
+    #[test]
+    fn records_split() {
+        let split =
+            |input, options: DeserializeOptions| records(input, options).collect::<Vec<_>>();
+
+        assert_eq!(
+            split("a=1\r\n\nb=\"x\ny\" c='\n'\n", DeserializeOptions::new()),
+            ["a=1", "b=\"x\ny\" c='\n'"]
+        );
+        assert_eq!(
+            split("a=\"\\\"\n\"\nb=2", DeserializeOptions::new()),
+            ["a=\"\\\"\n\"", "b=2"]
+        );
+        assert_eq!(
+            split("a=x\\\nb=y\nc=z", Preset::JavaProperties.into()),
+            ["a=x\\\nb=y", "c=z"]
+        );
+        assert_eq!(
+            split(
+                r#"a="x""--"--b=2--"#,
+                DeserializeOptions::new()
+                    .quoting(Quoting::Doubled)
+                    .record_separator("--")
+            ),
+            [r#"a="x""--""#, "b=2"]
+        );
+        assert_eq!(
+            split(
+                "a='b\0c'\0d=1",
+                DeserializeOptions::new()
+                    .quoting(Quoting::Never)
+                    .record_separator("\0")
+            ),
+            ["a='b", "c'", "d=1"]
+        );
+        assert_eq!(
+            split("a=1\nb=2", DeserializeOptions::new().record_separator("")),
+            ["a=1\nb=2"]
+        );
+        assert!(split("\n\n", DeserializeOptions::new()).is_empty());
+    }
+
+    #[test]
+    fn record_writer_errors() {
+        let mut writer = RecordWriter::new(Vec::new());
+        writer.write(&BTreeMap::from([("a", 1)])).unwrap();
+        let err = writer
+            .write(&BTreeMap::from([("b", BTreeMap::from([("c", 2)]))]))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(writer.into_inner(), b"a=1\n");
+    }
+
+    #[test]
+    fn group_records_by_key() {
+        let input = "name=a n=1\n\nn=2 name=b name=c\nname=a n=3";
+        let groups = group_by_key::<BTreeMap<String, String>>(input, "name").unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["a"].len(), 2);
+        assert_eq!(groups["a"][1]["n"], "3");
+        assert_eq!(groups["c"][0]["n"], "2");
+
+        let options = DeserializeOptions::from(Preset::Logfmt).record_separator("\0");
+        let groups: HashMap<String, Vec<BTreeMap<String, u32>>> =
+            group_by_key_with("id=1 n=2\0id=1 n=3", "id", options).unwrap();
+        assert_eq!(groups["1"][1]["n"], 3);
+
+        let error = |input| group_by_key::<BTreeMap<String, u32>>(input, "id").unwrap_err();
+        assert_eq!(
+            error("id=1\nn=2"),
+            ParseError {
+                kind: ErrorKind::SerdeError("missing field `id`".into()),
+                pos: 8,
+            }
+        );
+        assert_eq!(
+            error("id=1\nid=x"),
+            ParseError {
+                kind: ErrorKind::InvalidNumber,
+                pos: 8,
+            }
+        );
+        assert_eq!(error("id=1\nid=\"x").pos, 8);
+    }
+}
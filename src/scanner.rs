@@ -0,0 +1,476 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Scanning of key-value pairs without deserializing them into a type.
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use nom::Finish;
+
+use crate::key_values::any_identifier;
+use crate::key_values::any_string;
+use crate::key_values::find_trailing_trivia;
+use crate::key_values::is_separator;
+use crate::key_values::skip_trivia;
+use crate::key_values::starts_with_doubled_separator;
+use crate::key_values::strip_delimiter;
+use crate::key_values::strip_separator;
+use crate::key_values::BYTE_ORDER_MARK;
+use crate::options::DeserializeOptions;
+use crate::options::Quoting;
+use crate::ErrorKind;
+use crate::ParseError;
+
+/// Value of a scanned key-value pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ScannedValue<'a> {
+    /// The key was not followed by a value, i.e. it is a flag.
+    None,
+    /// A string value, with its quotes removed and escaped characters unescaped.
+    String(Cow<'a, str>),
+    /// A value enclosed in brackets, kept verbatim including the brackets.
+    Bracketed(&'a str),
+}
+
+impl<'a> ScannedValue<'a> {
+    /// Returns the text of the value. Flags have an empty value.
+    pub fn into_text(self) -> Cow<'a, str> {
+        match self {
+            ScannedValue::None => Cow::Borrowed(""),
+            ScannedValue::String(s) => s,
+            ScannedValue::Bracketed(s) => Cow::Borrowed(s),
+        }
+    }
+}
+
+/// A key-value pair scanned from an input string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ScannedPair<'a> {
+    pub key: &'a str,
+    pub value: ScannedValue<'a>,
+    /// Range of the input covered by the pair, excluding the separator that follows it.
+    pub span: Range<usize>,
+}
+
+/// Iterator over the key-value pairs of an input string.
+///
+/// The iteration stops after the first error.
+pub(crate) struct Scanner<'a> {
+    /// Full input originally received for scanning.
+    original_input: &'a str,
+    /// Input currently remaining to scan.
+    input: &'a str,
+    options: DeserializeOptions,
+    /// Set once an error has been returned.
+    failed: bool,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(input: &'a str, options: impl Into<DeserializeOptions>) -> Self {
+        Self::at(input, 0, options)
+    }
+
+    /// Creates a scanner starting at position `pos` of `input`, so the positions of errors are
+    /// relative to the start of `input`. A byte order mark starting `input` is skipped.
+    pub fn at(input: &'a str, pos: usize, options: impl Into<DeserializeOptions>) -> Self {
+        let pos = if input.starts_with(BYTE_ORDER_MARK) {
+            pos.max(BYTE_ORDER_MARK.len_utf8())
+        } else {
+            pos
+        };
+
+        Self {
+            original_input: input,
+            input: &input[pos..],
+            options: options.into().with_implied_separators(),
+            failed: false,
+        }
+    }
+
+    /// Returns the position of the scanner in the input.
+    pub fn pos(&self) -> usize {
+        self.original_input.len() - self.input.len()
+    }
+
+    fn error_here(&self, kind: ErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            pos: self.pos(),
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input.chars().next()
+    }
+
+    /// Scans a value enclosed in brackets, including nested brackets and quoted strings.
+    fn scan_bracketed(&mut self) -> Result<&'a str, ParseError> {
+        let mut depth = 0;
+        let mut quote = None;
+        let mut escaped = false;
+
+        for (i, c) in self.input.char_indices() {
+            match quote {
+                Some(_) if escaped => escaped = false,
+                // Single quotes only escape characters in libpq connection strings.
+                Some(q)
+                    if c == '\\'
+                        && ((q == '"'
+                            && !matches!(
+                                self.options.quoting,
+                                Quoting::Doubled | Quoting::Kernel
+                            ))
+                            || self.options.quoting == Quoting::Libpq) =>
+                {
+                    escaped = true
+                }
+                Some(q) if c == q => quote = None,
+                Some(_) => (),
+                None => match c {
+                    '[' => depth += 1,
+                    ']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            let (value, remainder) = self.input.split_at(i + 1);
+                            self.input = remainder;
+                            return Ok(value);
+                        }
+                    }
+                    c if self.options.quoting.quotes().contains(&c) => quote = Some(c),
+                    _ => (),
+                },
+            }
+        }
+
+        self.input = "";
+        Err(self.error_here(ErrorKind::ExpectedCloseBracket))
+    }
+
+    /// Scans the value following a key-value delimiter.
+    fn scan_value(&mut self) -> Result<ScannedValue<'a>, ParseError> {
+        match self.peek_char() {
+            Some('[') => return self.scan_bracketed().map(ScannedValue::Bracketed),
+            // An empty value.
+            c if is_separator(c, &self.options)
+                && !starts_with_doubled_separator(self.input, &self.options) =>
+            {
+                return Ok(ScannedValue::String(Cow::Borrowed("")))
+            }
+            _ => (),
+        }
+
+        let (remainder, value) = any_string(self.input, &self.options)
+            .finish()
+            .map_err(|_| self.error_here(ErrorKind::ExpectedString))?;
+        self.input = remainder;
+
+        if is_separator(self.peek_char(), &self.options) {
+            Ok(ScannedValue::String(value))
+        } else {
+            Err(self.error_here(ErrorKind::InvalidCharInString))
+        }
+    }
+
+    /// Scans the value following a key-value delimiter and returns its text verbatim, including
+    /// quotes and brackets.
+    pub fn scan_raw_value(&mut self) -> Result<&'a str, ParseError> {
+        let input = self.input;
+        self.scan_value()?;
+
+        Ok(&input[..input.len() - self.input.len()])
+    }
+
+    fn scan_pair(&mut self) -> Result<ScannedPair<'a>, ParseError> {
+        let start = self.pos();
+
+        // The trailing comment or spaces of the pair are cut from the input while the pair is
+        // scanned.
+        let original_input = self.original_input;
+        let trivia_end = find_trailing_trivia(self.input, &self.options).map(|(start, end)| {
+            let rest = &self.input[end..];
+            let len = self.input.len() - start;
+            self.original_input = &self.original_input[..self.original_input.len() - len];
+            self.input = &self.input[..start];
+            rest
+        });
+
+        let (remainder, key) = any_identifier(self.input, &self.options)
+            .finish()
+            .map_err(|_| self.error_here(ErrorKind::ExpectedIdentifier))?;
+        self.input = remainder;
+
+        let value = if let Some(remainder) = strip_delimiter(self.input, &self.options) {
+            self.input = remainder;
+            self.scan_value()?
+        } else {
+            match self.peek_char() {
+                Some(']') => return Err(self.error_here(ErrorKind::TrailingCharacters)),
+                c if is_separator(c, &self.options) => ScannedValue::None,
+                _ => return Err(self.error_here(ErrorKind::ExpectedEqual)),
+            }
+        };
+
+        let span = start..self.pos();
+        if let Some(rest) = trivia_end.filter(|_| self.input.is_empty()) {
+            self.original_input = original_input;
+            self.input = rest;
+        }
+
+        match strip_separator(self.input, &self.options) {
+            Some(remainder) if !remainder.starts_with(']') => self.input = remainder,
+            _ => return Err(self.error_here(ErrorKind::TrailingCharacters)),
+        }
+
+        Ok(ScannedPair { key, value, span })
+    }
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<ScannedPair<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.input = skip_trivia(self.input, &self.options);
+        if self.failed || self.input.is_empty() {
+            return None;
+        }
+
+        let pair = self.scan_pair();
+        self.failed = pair.is_err();
+
+        Some(pair)
+    }
+}
+
+/// Returns an iterator over the key-value pairs of `input`, parsed with the default options of the
+/// deserializer.
+///
+/// Pairs are parsed on demand, so looking for a single key or counting pairs does not require
+/// deserializing the whole input. Values are unquoted and unescaped, values enclosed in brackets
+/// are returned verbatim, and keys given without a value, i.e. flags, have an empty value. The
+/// iteration stops after the first error.
+///
+/// ```
+/// use serde_keyvalue::pairs;
+///
+/// let mut pairs = pairs(r#"path=/dev/vda,ro,name="root disk""#);
+///
+/// let (key, value) = pairs.next().unwrap().unwrap();
+/// assert_eq!((&*key, &*value), ("path", "/dev/vda"));
+/// let (key, value) = pairs.next().unwrap().unwrap();
+/// assert_eq!((&*key, &*value), ("ro", ""));
+/// let (key, value) = pairs.next().unwrap().unwrap();
+/// assert_eq!((&*key, &*value), ("name", "root disk"));
+/// assert!(pairs.next().is_none());
+/// ```
+pub fn pairs(
+    input: &str,
+) -> impl Iterator<Item = Result<(Cow<'_, str>, Cow<'_, str>), ParseError>> {
+    pairs_with(input, DeserializeOptions::default())
+}
+
+/// Returns an iterator over the key-value pairs of `input`, parsed with the syntax described by
+/// `options`. See [`pairs`] for details.
+pub fn pairs_with(
+    input: &str,
+    options: impl Into<DeserializeOptions>,
+) -> impl Iterator<Item = Result<(Cow<'_, str>, Cow<'_, str>), ParseError>> {
+    Scanner::new(input, options)
+        .map(|pair| pair.map(|pair| (Cow::Borrowed(pair.key), pair.value.into_text())))
+}
+
+/// Checks that `input` is well-formed according to `options` without deserializing it into a type,
+/// e.g. to reject a malformed configuration before it is stored or passed to another program.
+///
+/// Quotes must be terminated, escape sequences valid for the quoting set in `options`, brackets
+/// balanced, and pairs made of a key optionally followed by a delimiter and a value, as
+/// separated by the pair separators. Values are not checked further, as their expected type is not
+/// known. Scanning resumes after the next pair separator following an error, so all the errors
+/// found are returned in the order in which they appear in the input.
+///
+/// ```
+/// use serde_keyvalue::{validate, DeserializeOptions, ErrorKind};
+///
+/// assert!(validate(r#"path=/dev/vda,ro,name="root disk""#, DeserializeOptions::new()).is_ok());
+///
+/// let errors = validate(r#"path=/dev/"vda",=1,ro"#, DeserializeOptions::new()).unwrap_err();
+/// let kinds: Vec<_> = errors.into_iter().map(|e| (e.kind, e.pos)).collect();
+/// assert_eq!(
+///     kinds,
+///     [(ErrorKind::InvalidCharInString, 10), (ErrorKind::ExpectedIdentifier, 16)]
+/// );
+/// ```
+pub fn validate(
+    input: &str,
+    options: impl Into<DeserializeOptions>,
+) -> Result<(), Vec<ParseError>> {
+    let (_, errors) = scan_all(input, options.into());
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Scans all the pairs of `input`, resuming after the next pair separator following each error,
+/// and returns the well-formed pairs along with the errors, both in the order of the input.
+pub(crate) fn scan_all(
+    input: &str,
+    options: DeserializeOptions,
+) -> (Vec<ScannedPair<'_>>, Vec<ParseError>) {
+    let options = options.with_implied_separators();
+    let mut pairs = Vec::new();
+    let mut errors = Vec::new();
+    let mut scanner = Scanner::new(input, options.clone());
+
+    while let Some(pair) = scanner.next() {
+        match pair {
+            Ok(pair) => pairs.push(pair),
+            Err(e) => {
+                let resume = input[e.pos..]
+                    .find(options.pair_separators.as_slice())
+                    .map_or(input.len(), |len| {
+                        let pos = e.pos + len;
+                        pos + input[pos..].chars().next().map_or(0, char::len_utf8)
+                    });
+                errors.push(e);
+                scanner = Scanner::at(input, resume, options.clone());
+            }
+        }
+    }
+
+    (pairs, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Preset;
+
+    fn scan(input: &str) -> Result<Vec<ScannedPair<'_>>, ParseError> {
+        Scanner::new(input, DeserializeOptions::default()).collect()
+    }
+
+    #[test]
+    fn scan_pairs() {
+        assert_eq!(
+            scan(r#"path="/a b",ro,sizes=[1,"2]"],empty="#).unwrap(),
+            vec![
+                ScannedPair {
+                    key: "path",
+                    value: ScannedValue::String(Cow::Owned("/a b".into())),
+                    span: 0..11,
+                },
+                ScannedPair {
+                    key: "ro",
+                    value: ScannedValue::None,
+                    span: 12..14,
+                },
+                ScannedPair {
+                    key: "sizes",
+                    value: ScannedValue::Bracketed(r#"[1,"2]"]"#),
+                    span: 15..29,
+                },
+                ScannedPair {
+                    key: "empty",
+                    value: ScannedValue::String(Cow::Borrowed("")),
+                    span: 30..36,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_with_options() {
+        let options = DeserializeOptions::new().pair_separators(['&']);
+
+        assert_eq!(pairs_with("a=1&b&c=x y", options.clone()).count(), 3);
+        assert_eq!(
+            pairs_with("a=1&b&c=x y", options.clone())
+                .find_map(|pair| pair.ok().filter(|(key, _)| key == "c"))
+                .map(|(_, value)| value),
+            Some(Cow::Borrowed("x y"))
+        );
+
+        let dotenv: Vec<_> = pairs_with("# c\nA=1 # d\n\nexport B=2\n", Preset::Dotenv)
+            .map(|pair| pair.unwrap())
+            .collect();
+        assert_eq!(dotenv, [("A".into(), "1".into()), ("B".into(), "2".into())]);
+
+        let res: Vec<_> = pairs_with("a=1&=2&c=3", options).collect();
+        assert_eq!(res.len(), 2);
+        assert_eq!(
+            res[1],
+            Err(ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn scan_errors() {
+        assert_eq!(
+            scan("a=1,=2").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 4,
+            }
+        );
+        assert_eq!(
+            scan("a=[1,2").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 6,
+            }
+        );
+        assert_eq!(
+            scan("a=x'y'").unwrap_err(),
+            ParseError {
+                kind: ErrorKind::InvalidCharInString,
+                pos: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn validate_errors() {
+        let errors = |input, options: DeserializeOptions| {
+            validate(input, options)
+                .unwrap_err()
+                .into_iter()
+                .map(|e| (e.kind, e.pos))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            validate("a=1,b,c=[1,'2]']", DeserializeOptions::new()),
+            Ok(())
+        );
+        assert_eq!(
+            errors("a=x'y',b=\"\\q\",=1,d,e=[1,f=2", DeserializeOptions::new()),
+            [
+                (ErrorKind::InvalidCharInString, 3),
+                (ErrorKind::ExpectedString, 9),
+                (ErrorKind::ExpectedIdentifier, 14),
+                (ErrorKind::ExpectedCloseBracket, 27),
+            ]
+        );
+
+        // Errors are found in lines with other separators.
+        assert_eq!(
+            validate("# c\nA=1 # d\nexport B='x'\n", Preset::Dotenv),
+            Ok(())
+        );
+        assert_eq!(
+            errors("A=1\n=2\nB=\"x\nC=3", Preset::Dotenv.into()),
+            [
+                (ErrorKind::ExpectedIdentifier, 4),
+                (ErrorKind::ExpectedString, 9)
+            ]
+        );
+    }
+}
@@ -0,0 +1,913 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Validation of key-values strings against a description of their keys.
+//!
+//! A [`Schema`] is this crate's own description of keys, not a JSON Schema document. It is built
+//! key by key, derived from the `Deserialize` implementation of a struct with [`Schema::of`], or,
+//! with the `schemars` feature, from the `JsonSchema` implementation of a struct generated by
+//! [schemars](https://docs.rs/schemars) with `Schema::of_json_schema`. The `json` feature
+//! converts it to a JSON Schema with `Schema::to_json_schema`.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+use crate::key_values::strip_delimiter;
+use crate::options::DeserializeOptions;
+use crate::scanner::Scanner;
+use crate::trace::trace_fields;
+use crate::KeyValueDeserializer;
+use crate::ParseError;
+
+/// Type of the value expected for a key of a [`Schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// A boolean, which can also be given as a key without a value.
+    Bool,
+    /// A signed integer fitting in an `i64`.
+    Int,
+    /// An unsigned integer fitting in a `u64`.
+    UInt,
+    /// A floating point number.
+    Float,
+    /// A string.
+    String,
+    /// A list enclosed in brackets.
+    List,
+    /// Any value, including none.
+    Any,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::Bool => "a boolean",
+            ValueType::Int => "an integer",
+            ValueType::UInt => "an unsigned integer",
+            ValueType::Float => "a number",
+            ValueType::String => "a string",
+            ValueType::List => "a list",
+            ValueType::Any => "any value",
+        })
+    }
+}
+
+/// A reason for a key-values string not to conform to a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The string is not a well-formed key-values string. No further pairs are checked.
+    Syntax(ParseError),
+    /// A key is not described by the schema.
+    UnknownKey {
+        /// The key.
+        key: String,
+        /// Range of the input covered by the pair.
+        span: Range<usize>,
+    },
+    /// A required key is missing.
+    MissingKey {
+        /// The key.
+        key: String,
+    },
+    /// A key is given more than once.
+    DuplicateKey {
+        /// The key.
+        key: String,
+        /// Range of the input covered by the repeated pair.
+        span: Range<usize>,
+    },
+    /// The value of a key does not have the expected type.
+    InvalidValue {
+        /// The key.
+        key: String,
+        /// Type expected by the schema.
+        expected: ValueType,
+        /// Range of the input covered by the pair.
+        span: Range<usize>,
+    },
+    /// The value of a key is not one of its allowed values.
+    UnexpectedValue {
+        /// The key.
+        key: String,
+        /// The value.
+        value: String,
+        /// Values allowed by the schema.
+        allowed: Vec<String>,
+        /// Range of the input covered by the pair.
+        span: Range<usize>,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Syntax(e) => write!(f, "{}", e),
+            Violation::UnknownKey { key, .. } => write!(f, "unknown key `{}`", key),
+            Violation::MissingKey { key } => write!(f, "missing key `{}`", key),
+            Violation::DuplicateKey { key, .. } => write!(f, "duplicate key `{}`", key),
+            Violation::InvalidValue { key, expected, .. } => {
+                write!(f, "expected {} for key `{}`", expected, key)
+            }
+            Violation::UnexpectedValue {
+                key,
+                value,
+                allowed,
+                ..
+            } => {
+                write!(
+                    f,
+                    "unexpected value `{}` for key `{}`, expected ",
+                    value, key
+                )?;
+                for (i, expected) in allowed.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(if i + 1 == allowed.len() { " or " } else { ", " })?;
+                    }
+                    write!(f, "`{}`", expected)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl core::error::Error for Violation {}
+
+/// Description of a key of a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeySchema {
+    name: String,
+    value_type: ValueType,
+    required: bool,
+    /// Allowed values, any value of `value_type` being allowed if empty.
+    values: Vec<String>,
+}
+
+/// Description of the keys allowed in a key-values string, used to validate user input before
+/// deserializing it.
+///
+/// Only the keys of a string are described, with the type of their value, whether they are
+/// required and their allowed values. Nested structs, the items of lists and constraints such as
+/// ranges or patterns are not.
+///
+/// ```
+/// use serde_keyvalue::{Schema, ValueType, Violation};
+///
+/// let schema = Schema::new()
+///     .required("path", ValueType::String)
+///     .optional("ro", ValueType::Bool)
+///     .optional("size", ValueType::UInt);
+///
+/// assert!(schema.validate("path=/dev/vda,ro").is_ok());
+///
+/// let violations = schema.validate("size=-1,lun=0").unwrap_err();
+/// let messages: Vec<_> = violations.iter().map(Violation::to_string).collect();
+/// assert_eq!(
+///     messages,
+///     [
+///         "expected an unsigned integer for key `size`",
+///         "unknown key `lun`",
+///         "missing key `path`",
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Schema {
+    keys: Vec<KeySchema>,
+    allow_unknown_keys: bool,
+    options: DeserializeOptions,
+}
+
+impl Schema {
+    /// Creates a schema without any key, validating strings parsed with the default options of the
+    /// deserializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a schema without any key, validating strings parsed with the syntax described by
+    /// `options`.
+    pub fn with_options(options: impl Into<DeserializeOptions>) -> Self {
+        Self {
+            options: options.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Creates a schema describing the keys accepted by the struct `T`, validating strings parsed
+    /// with the default options of the deserializer.
+    ///
+    /// The keys and the types of their values are discovered by deserializing `T` from
+    /// placeholder values, so they follow its serde attributes, and the variants of enum fields
+    /// become the allowed values of their key. `Option` fields are optional and other fields are
+    /// required, including those with a default value, which can be made optional by adding them
+    /// again with [`optional`](Self::optional). Fields that `T` rejects placeholder values for,
+    /// and the ones following them, accept any value.
+    ///
+    /// Only the fields that `T` asks for by name can be discovered this way. If `T` is not
+    /// deserialized as a struct, e.g. because it has `#[serde(flatten)]` fields, none of its keys
+    /// are known and the schema accepts any key. `Schema::of_json_schema` describes such structs
+    /// from their `JsonSchema` implementation instead.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{Schema, ValueType};
+    ///
+    /// #[derive(Deserialize)]
+    /// #[serde(rename_all = "kebab-case")]
+    /// enum CacheMode {
+    ///     WriteBack,
+    ///     WriteThrough,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Disk {
+    ///     path: String,
+    ///     ro: Option<bool>,
+    ///     cache: CacheMode,
+    /// }
+    ///
+    /// let schema = Schema::of::<Disk>();
+    /// assert!(schema.validate("path=/dev/vda,cache=write-back").is_ok());
+    ///
+    /// let violations = schema.validate("path=/dev/vda,cache=none").unwrap_err();
+    /// assert_eq!(
+    ///     violations[0].to_string(),
+    ///     "unexpected value `none` for key `cache`, expected `write-back` or `write-through`"
+    /// );
+    /// ```
+    pub fn of<T: Deserialize<'static>>() -> Self {
+        let fields = match trace_fields::<T>() {
+            Some(fields) => fields,
+            None => return Self::new().allow_unknown_keys(true),
+        };
+
+        let mut schema = Self::new();
+        for (name, trace) in fields {
+            let (value_type, required) = match trace.value_type {
+                Some(value_type) => (value_type, !trace.optional),
+                None => (ValueType::Any, false),
+            };
+            schema = schema
+                .key(name.into(), value_type, required)
+                .allowed_values(name, trace.variants.iter().copied());
+        }
+        schema
+    }
+
+    /// Creates a schema describing the keys accepted by the struct `T`, as described by its
+    /// `JsonSchema` implementation, usually derived with [schemars](https://docs.rs/schemars),
+    /// and validating strings parsed with the default options of the deserializer.
+    ///
+    /// Unlike [`Schema::of`], this follows the fields of flattened structs, and fields with a
+    /// default value are optional. The allowed values of enum fields are the ones listed with
+    /// `enum` or `const` in the JSON Schema, and keys are added in the order of its properties,
+    /// i.e. sorted by name unless serde_json preserves their order. Keys that are not properties
+    /// of the JSON Schema are only accepted if it allows additional properties explicitly, e.g.
+    /// because `T` flattens a map. If `T` is not described as an object, the schema accepts any
+    /// key.
+    ///
+    /// ```
+    /// # #[cfg(feature = "schemars")]
+    /// # {
+    /// use schemars::JsonSchema;
+    /// use serde::Deserialize;
+    /// use serde_keyvalue::{Schema, ValueType};
+    ///
+    /// #[derive(Deserialize, JsonSchema)]
+    /// struct Cache {
+    ///     #[serde(default)]
+    ///     direct: bool,
+    /// }
+    ///
+    /// #[derive(Deserialize, JsonSchema)]
+    /// struct Disk {
+    ///     path: String,
+    ///     #[serde(flatten)]
+    ///     cache: Cache,
+    /// }
+    ///
+    /// let schema = Schema::of_json_schema::<Disk>();
+    /// assert_eq!(
+    ///     schema,
+    ///     Schema::new()
+    ///         .optional("direct", ValueType::Bool)
+    ///         .required("path", ValueType::String)
+    /// );
+    /// assert!(schema.validate("path=/dev/vda,direct").is_ok());
+    /// # }
+    /// ```
+    #[cfg(feature = "schemars")]
+    pub fn of_json_schema<T: schemars::JsonSchema>() -> Self {
+        let root = schemars::schema_for!(T);
+        let root = root.as_value();
+        let properties = match root["properties"].as_object() {
+            Some(properties) => properties,
+            None => return Self::new().allow_unknown_keys(true),
+        };
+
+        let mut schema = Self::new();
+        for (name, property) in properties {
+            let required = root["required"]
+                .as_array()
+                .is_some_and(|required| required.iter().any(|key| key == name));
+            let (value_type, values) = json_schema_type(root, property);
+            schema = schema
+                .key(name.clone(), value_type, required)
+                .allowed_values(name, values);
+        }
+        let additional = &root["additionalProperties"];
+        schema.allow_unknown_keys(!additional.is_null() && *additional != false)
+    }
+
+    /// Adds a key that must be present, with a value of type `value_type`.
+    pub fn required(self, key: impl Into<String>, value_type: ValueType) -> Self {
+        self.key(key.into(), value_type, true)
+    }
+
+    /// Adds a key that may be present, with a value of type `value_type`.
+    pub fn optional(self, key: impl Into<String>, value_type: ValueType) -> Self {
+        self.key(key.into(), value_type, false)
+    }
+
+    fn key(mut self, name: String, value_type: ValueType, required: bool) -> Self {
+        self.keys.retain(|key| key.name != name);
+        self.keys.push(KeySchema {
+            name,
+            value_type,
+            required,
+            values: Vec::new(),
+        });
+        self
+    }
+
+    /// Restricts the values of `key`, which must already have been added, to `values`.
+    pub fn allowed_values<S: Into<String>>(
+        mut self,
+        key: &str,
+        values: impl IntoIterator<Item = S>,
+    ) -> Self {
+        if let Some(key) = self.keys.iter_mut().find(|k| k.name == key) {
+            key.values = values.into_iter().map(Into::into).collect();
+        }
+        self
+    }
+
+    /// Sets whether keys that are not described by the schema are accepted. They are rejected by
+    /// default.
+    pub fn allow_unknown_keys(mut self, allow: bool) -> Self {
+        self.allow_unknown_keys = allow;
+        self
+    }
+
+    /// Returns a [JSON Schema](https://json-schema.org) describing the pairs accepted by the
+    /// schema as the properties of an object, so tools understanding JSON Schema can render forms
+    /// for key-values strings and validate them before they are submitted.
+    ///
+    /// ```
+    /// # #[cfg(feature = "json")]
+    /// # {
+    /// use serde_json::json;
+    /// use serde_keyvalue::{Schema, ValueType};
+    ///
+    /// let schema = Schema::new()
+    ///     .required("path", ValueType::String)
+    ///     .optional("cache", ValueType::String)
+    ///     .allowed_values("cache", ["write-back", "write-through"])
+    ///     .optional("size", ValueType::UInt);
+    ///
+    /// assert_eq!(
+    ///     schema.to_json_schema(),
+    ///     json!({
+    ///         "$schema": "https://json-schema.org/draft/2020-12/schema",
+    ///         "type": "object",
+    ///         "properties": {
+    ///             "path": { "type": "string" },
+    ///             "cache": { "type": "string", "enum": ["write-back", "write-through"] },
+    ///             "size": { "type": "integer", "minimum": 0 },
+    ///         },
+    ///         "required": ["path"],
+    ///         "additionalProperties": false,
+    ///     })
+    /// );
+    /// # }
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let properties: serde_json::Map<String, serde_json::Value> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let mut property = match key.value_type {
+                    ValueType::Bool => json!({ "type": "boolean" }),
+                    ValueType::Int => json!({ "type": "integer" }),
+                    ValueType::UInt => json!({ "type": "integer", "minimum": 0 }),
+                    ValueType::Float => json!({ "type": "number" }),
+                    ValueType::String => json!({ "type": "string" }),
+                    ValueType::List => json!({ "type": "array" }),
+                    ValueType::Any => json!({}),
+                };
+                if !key.values.is_empty() {
+                    property["enum"] = json!(key.values);
+                }
+                (key.name.clone(), property)
+            })
+            .collect();
+        let required: Vec<&str> = self
+            .keys
+            .iter()
+            .filter(|key| key.required)
+            .map(|key| key.name.as_str())
+            .collect();
+
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": self.allow_unknown_keys,
+        })
+    }
+
+    /// Checks that `input` conforms to the schema, returning all the violations found otherwise.
+    ///
+    /// Violations are reported in the order in which they appear in the input, followed by the
+    /// missing keys.
+    pub fn validate(&self, input: &str) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        let mut seen: Vec<&str> = Vec::new();
+
+        for pair in Scanner::new(input, self.options.clone()) {
+            let pair = match pair {
+                Ok(pair) => pair,
+                Err(e) => {
+                    violations.push(Violation::Syntax(e));
+                    break;
+                }
+            };
+
+            if seen.contains(&pair.key) {
+                violations.push(Violation::DuplicateKey {
+                    key: pair.key.to_owned(),
+                    span: pair.span,
+                });
+                continue;
+            }
+            seen.push(pair.key);
+
+            let key = match self.keys.iter().find(|key| key.name == pair.key) {
+                Some(key) => key,
+                None => {
+                    if !self.allow_unknown_keys {
+                        violations.push(Violation::UnknownKey {
+                            key: pair.key.to_owned(),
+                            span: pair.span,
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            let value = strip_delimiter(
+                &input[pair.span.start + pair.key.len()..pair.span.end],
+                &self.options,
+            );
+            if !self.check_value(value, key.value_type) {
+                violations.push(Violation::InvalidValue {
+                    key: pair.key.to_owned(),
+                    expected: key.value_type,
+                    span: pair.span,
+                });
+            } else if let Some(value) = self.unexpected_value(value, &key.values) {
+                violations.push(Violation::UnexpectedValue {
+                    key: pair.key.to_owned(),
+                    value,
+                    allowed: key.values.clone(),
+                    span: pair.span,
+                });
+            }
+        }
+
+        let has_syntax_error = matches!(violations.last(), Some(Violation::Syntax(_)));
+        if !has_syntax_error {
+            violations.extend(
+                self.keys
+                    .iter()
+                    .filter(|key| key.required && !seen.contains(&key.name.as_str()))
+                    .map(|key| Violation::MissingKey {
+                        key: key.name.clone(),
+                    }),
+            );
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Returns `true` if `value`, the text following the key-value delimiter or `None` for a flag,
+    /// is of type `value_type`.
+    fn check_value(&self, value: Option<&str>, value_type: ValueType) -> bool {
+        let value = match (value, value_type) {
+            (_, ValueType::Any) => return true,
+            (None, ValueType::Bool) => return true,
+            (None, _) => return false,
+            (Some(value), _) => value,
+        };
+
+        let mut deserializer =
+            KeyValueDeserializer::for_value(value, 0, true, self.options.clone());
+        let res = match value_type {
+            ValueType::Bool => bool::deserialize(&mut deserializer).map(drop),
+            ValueType::Int => i64::deserialize(&mut deserializer).map(drop),
+            ValueType::UInt => u64::deserialize(&mut deserializer).map(drop),
+            ValueType::Float => f64::deserialize(&mut deserializer).map(drop),
+            ValueType::String => String::deserialize(&mut deserializer).map(drop),
+            ValueType::List => Vec::<IgnoredAny>::deserialize(&mut deserializer).map(drop),
+            ValueType::Any => Ok(()),
+        };
+
+        res.and_then(|_| deserializer.finish()).is_ok()
+    }
+
+    /// Returns `value`, the text following the key-value delimiter or `None` for a flag, parsed
+    /// as a string if it is not one of `allowed`, which allows any value if empty.
+    fn unexpected_value(&self, value: Option<&str>, allowed: &[String]) -> Option<String> {
+        if allowed.is_empty() {
+            return None;
+        }
+
+        let value = match value {
+            Some(value) => {
+                let mut deserializer =
+                    KeyValueDeserializer::for_value(value, 0, true, self.options.clone());
+                String::deserialize(&mut deserializer).unwrap_or_else(|_| value.to_owned())
+            }
+            None => String::new(),
+        };
+        if allowed.contains(&value) {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+/// Returns the type of the values described by the JSON Schema `property` and their allowed
+/// values, following its references to the definitions of `root`.
+#[cfg(feature = "schemars")]
+fn json_schema_type<'a>(
+    root: &'a serde_json::Value,
+    mut property: &'a serde_json::Value,
+) -> (ValueType, Vec<String>) {
+    use serde_json::Value;
+
+    loop {
+        if let Some(name) = property["$ref"]
+            .as_str()
+            .and_then(|reference| reference.strip_prefix("#/$defs/"))
+        {
+            property = &root["$defs"][name];
+            continue;
+        }
+        // `Option`s are described as either their value or null.
+        if let Some(any_of) = property["anyOf"].as_array() {
+            let not_null: Vec<&Value> = any_of.iter().filter(|s| s["type"] != "null").collect();
+            if let [schema] = not_null[..] {
+                property = schema;
+                continue;
+            }
+        }
+        break;
+    }
+
+    // Variants with doc comments are described as constants.
+    let values: Vec<String> = match (property["enum"].as_array(), property["oneOf"].as_array()) {
+        (Some(values), _) => values
+            .iter()
+            .filter_map(Value::as_str)
+            .map(Into::into)
+            .collect(),
+        (None, Some(one_of)) => one_of
+            .iter()
+            .map(|s| s["const"].as_str().map(Into::into))
+            .collect::<Option<_>>()
+            .unwrap_or_default(),
+        (None, None) => Vec::new(),
+    };
+    let types: Vec<&str> = match &property["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|t| *t != "null")
+            .collect(),
+        _ => Vec::new(),
+    };
+    let value_type = match types[..] {
+        ["boolean"] => ValueType::Bool,
+        ["integer"] if property["minimum"].as_f64().is_some_and(|min| min >= 0.0) => {
+            ValueType::UInt
+        }
+        ["integer"] => ValueType::Int,
+        ["number"] => ValueType::Float,
+        ["string"] => ValueType::String,
+        ["array"] => ValueType::List,
+        [] if !values.is_empty() => ValueType::String,
+        _ => ValueType::Any,
+    };
+
+    (value_type, values)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::ErrorKind;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .required("name", ValueType::String)
+            .optional("count", ValueType::Int)
+            .optional("ratio", ValueType::Float)
+            .optional("sizes", ValueType::List)
+            .optional("ro", ValueType::Bool)
+            .optional("extra", ValueType::Any)
+    }
+
+    #[test]
+    fn validate_valid() {
+        let schema = schema();
+
+        assert_eq!(schema.validate("name=a"), Ok(()));
+        assert_eq!(
+            schema.validate(r#"name="a b",count=-3,ratio=0.5,sizes=[1,2],ro=false,extra"#),
+            Ok(())
+        );
+        assert_eq!(
+            schema.allow_unknown_keys(true).validate("name=a,other=1"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_violations() {
+        let schema = schema();
+
+        assert_eq!(
+            schema.validate("count=x,ro=maybe,sizes=1,name,name=b,foo"),
+            Err(vec![
+                Violation::InvalidValue {
+                    key: "count".into(),
+                    expected: ValueType::Int,
+                    span: 0..7,
+                },
+                Violation::InvalidValue {
+                    key: "ro".into(),
+                    expected: ValueType::Bool,
+                    span: 8..16,
+                },
+                Violation::InvalidValue {
+                    key: "sizes".into(),
+                    expected: ValueType::List,
+                    span: 17..24,
+                },
+                Violation::InvalidValue {
+                    key: "name".into(),
+                    expected: ValueType::String,
+                    span: 25..29,
+                },
+                Violation::DuplicateKey {
+                    key: "name".into(),
+                    span: 30..36,
+                },
+                Violation::UnknownKey {
+                    key: "foo".into(),
+                    span: 37..40,
+                },
+            ])
+        );
+
+        assert_eq!(
+            schema.validate("foo=1,=2"),
+            Err(vec![
+                Violation::UnknownKey {
+                    key: "foo".into(),
+                    span: 0..5,
+                },
+                Violation::Syntax(ParseError {
+                    kind: ErrorKind::ExpectedIdentifier,
+                    pos: 6,
+                }),
+            ])
+        );
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Mode {
+        #[serde(rename = "rw")]
+        ReadWrite,
+        #[serde(rename = "ro")]
+        ReadOnly,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Nested {
+        a: u8,
+    }
+
+    fn non_zero<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        match u32::deserialize(deserializer)? {
+            0 => Err(serde::de::Error::custom("zero")),
+            v => Ok(v),
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Device<'a> {
+        #[serde(rename = "dev-path")]
+        path: &'a str,
+        id: i16,
+        ratio: Option<f32>,
+        sizes: Vec<u64>,
+        pos: (u8, u8),
+        mode: Mode,
+        nested: Nested,
+        map: BTreeMap<String, u8>,
+        #[serde(skip)]
+        skipped: bool,
+        #[serde(deserialize_with = "non_zero")]
+        queues: u32,
+        after: bool,
+    }
+
+    #[test]
+    fn schema_of_struct() {
+        let schema = Schema::of::<Device>();
+        let expected = Schema::new()
+            .required("dev-path", ValueType::String)
+            .required("id", ValueType::Int)
+            .optional("ratio", ValueType::Float)
+            .required("sizes", ValueType::List)
+            .required("pos", ValueType::List)
+            .required("mode", ValueType::String)
+            .allowed_values("mode", ["rw", "ro"])
+            .required("nested", ValueType::Any)
+            .required("map", ValueType::Any)
+            .required("queues", ValueType::UInt)
+            .optional("after", ValueType::Any);
+        assert_eq!(schema, expected);
+
+        assert_eq!(
+            schema.validate(
+                "dev-path=/a,id=1,sizes=[1],pos=[1,2],mode=rw,nested=[a=1],map=[],queues=1"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            schema.validate("dev-path=/a,id=1,sizes=[],pos=[1,2],mode=rx,nested,map,queues=1"),
+            Err(vec![Violation::UnexpectedValue {
+                key: "mode".into(),
+                value: "rx".into(),
+                allowed: vec!["rw".into(), "ro".into()],
+                span: 36..43,
+            }])
+        );
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Flattened {
+        a: u8,
+        #[serde(flatten)]
+        rest: BTreeMap<String, u8>,
+    }
+
+    #[derive(Deserialize)]
+    #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+    #[allow(dead_code)]
+    struct Inner {
+        b: Option<u8>,
+        #[serde(default)]
+        c: i32,
+    }
+
+    #[derive(Deserialize)]
+    #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+    #[allow(dead_code)]
+    struct FlattenedStruct {
+        a: u8,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+
+    #[test]
+    fn schema_of_other_types() {
+        assert_eq!(
+            Schema::of::<Flattened>(),
+            Schema::new().allow_unknown_keys(true)
+        );
+        // The fields of flattened structs are not asked for by name.
+        assert_eq!(
+            Schema::of::<FlattenedStruct>(),
+            Schema::new().allow_unknown_keys(true)
+        );
+        assert_eq!(Schema::of::<u32>(), Schema::new().allow_unknown_keys(true));
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn schema_of_json_schema() {
+        #[derive(Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            /// Read-write.
+            #[serde(rename = "rw")]
+            ReadWrite,
+            /// Read-only.
+            #[serde(rename = "ro")]
+            ReadOnly,
+        }
+
+        #[derive(Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Device {
+            #[serde(rename = "dev-path")]
+            path: String,
+            id: i16,
+            ratio: Option<f32>,
+            sizes: Vec<u64>,
+            mode: Option<Mode>,
+            nested: Inner,
+            #[serde(flatten)]
+            flattened: FlattenedStruct,
+        }
+
+        let schema = Schema::of_json_schema::<Device>();
+        let expected = Schema::new()
+            .required("a", ValueType::UInt)
+            .optional("b", ValueType::UInt)
+            .optional("c", ValueType::Int)
+            .required("dev-path", ValueType::String)
+            .required("id", ValueType::Int)
+            .optional("mode", ValueType::String)
+            .allowed_values("mode", ["rw", "ro"])
+            .required("nested", ValueType::Any)
+            .optional("ratio", ValueType::Float)
+            .required("sizes", ValueType::List);
+        assert_eq!(schema, expected);
+        assert_eq!(
+            schema.validate("dev-path=/a,id=-1,sizes=[1],a=1,b=2,mode=ro,nested=[b=1]"),
+            Ok(())
+        );
+
+        #[derive(Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct FlattenedMap {
+            a: u8,
+            #[serde(flatten)]
+            rest: BTreeMap<String, u8>,
+        }
+
+        assert_eq!(
+            Schema::of_json_schema::<FlattenedMap>(),
+            Schema::new()
+                .required("a", ValueType::UInt)
+                .allow_unknown_keys(true)
+        );
+        assert_eq!(
+            Schema::of_json_schema::<u32>(),
+            Schema::new().allow_unknown_keys(true)
+        );
+    }
+
+    #[test]
+    fn unexpected_value() {
+        let schema = Schema::new()
+            .optional("mode", ValueType::String)
+            .allowed_values("mode", ["a", "b c", "d"]);
+
+        assert_eq!(schema.validate(r#"mode="b c""#), Ok(()));
+        let violations = schema.validate(r#"mode="b""#).unwrap_err();
+        assert_eq!(
+            violations[0].to_string(),
+            "unexpected value `b` for key `mode`, expected `a`, `b c` or `d`"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Searching for the characters ending tokens several bytes at a time.
+//!
+//! Since the bytes of multibyte UTF-8 characters are never ASCII, searching for ASCII characters
+//! byte by byte always stops at a character boundary.
+
+use memchr::memchr;
+use memchr::memchr2;
+use memchr::memchr3;
+
+/// Maximum number of ASCII characters searched for at once.
+const MAX_NEEDLES: usize = 8;
+
+const LO: u64 = 0x0101_0101_0101_0101;
+const HI: u64 = 0x8080_8080_8080_8080;
+
+/// Returns the index of the first character of `s` contained in one of `sets`, or the length of
+/// `s` if there is none.
+///
+/// Up to three ASCII characters are searched for with `memchr`, which uses SIMD instructions
+/// where available, and up to eight by comparing eight bytes at a time. Other sets of characters
+/// are compared one character at a time.
+pub(crate) fn find_any(s: &str, sets: &[&[char]]) -> usize {
+    let mut needles = [0u8; MAX_NEEDLES];
+    let mut len = 0;
+
+    for &c in sets.iter().flat_map(|set| set.iter()) {
+        if !c.is_ascii() || len == MAX_NEEDLES {
+            return s
+                .find(|c| sets.iter().any(|set| set.contains(&c)))
+                .unwrap_or(s.len());
+        }
+        needles[len] = c as u8;
+        len += 1;
+    }
+
+    let haystack = s.as_bytes();
+    let found = match needles[..len] {
+        [] => None,
+        [a] => memchr(a, haystack),
+        [a, b] => memchr2(a, b, haystack),
+        [a, b, c] => memchr3(a, b, c, haystack),
+        ref needles => find_bytes(needles, haystack),
+    };
+
+    found.unwrap_or(s.len())
+}
+
+/// Returns the index of the first byte of `haystack` contained in `needles`, comparing the bytes
+/// of 64-bit words with all needles at once.
+fn find_bytes(needles: &[u8], haystack: &[u8]) -> Option<usize> {
+    let mut chunks = haystack.chunks_exact(8);
+
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        // The highest bit of each byte equal to a needle is set. Bytes following a match may be
+        // flagged as well, but the lowest flagged byte is always the first match.
+        let matches = needles.iter().fold(0, |matches, &needle| {
+            let x = word ^ (LO * needle as u64);
+            matches | (x.wrapping_sub(LO) & !x & HI)
+        });
+        if matches != 0 {
+            return Some(i * 8 + matches.trailing_zeros() as usize / 8);
+        }
+    }
+
+    let rest = chunks.remainder();
+    rest.iter()
+        .position(|b| needles.contains(b))
+        .map(|pos| haystack.len() - rest.len() + pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_any_needles() {
+        assert_eq!(find_any("abc", &[]), 3);
+        assert_eq!(find_any("abc,d", &[&[',']]), 3);
+        assert_eq!(find_any("ab]c,d", &[&[','], &['[', ']']]), 2);
+        assert_eq!(find_any("abcdefgh", &[&[' ', ',', '[', ']', '"']]), 8);
+
+        // Matches in and after the first word, and in the remainder.
+        let set: &[char] = &[' ', ',', '[', ']', '"', '\''];
+        assert_eq!(find_any("ab\"c def", &[set]), 2);
+        assert_eq!(
+            find_any("\x01\x01\x01\x01\x01\x01\x01\x01\x01 x", &[set]),
+            9
+        );
+        assert_eq!(find_any("0123456789abcdef'", &[set]), 16);
+        assert_eq!(find_any("élan vital", &[set]), 5);
+
+        // Non-ASCII characters and large sets are searched for one character at a time.
+        assert_eq!(find_any("a→b", &[&['→']]), 1);
+        assert_eq!(find_any("abcdefghij", &[&['a'; 9][..]]), 0);
+    }
+}
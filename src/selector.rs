@@ -0,0 +1,463 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Kubernetes label selectors.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use serde::de::Error;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::ErrorKind;
+use crate::ParseError;
+
+/// Condition put by a [`LabelRequirement`] on the value of its label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelOperator {
+    /// `key=value` or `key==value`: the label is set to the value.
+    Equals(String),
+    /// `key!=value`: the label is not set, or is set to another value.
+    NotEquals(String),
+    /// `key in (a,b)`: the label is set to one of the values.
+    In(Vec<String>),
+    /// `key notin (a,b)`: the label is not set, or is set to none of the values.
+    NotIn(Vec<String>),
+    /// `key`: the label is set, whatever its value.
+    Exists,
+    /// `!key`: the label is not set.
+    DoesNotExist,
+}
+
+/// One of the comma separated requirements of a [`LabelSelector`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelRequirement {
+    /// Key of the label, e.g. `app.kubernetes.io/name`.
+    pub key: String,
+    /// Condition on the value of the label.
+    pub operator: LabelOperator,
+}
+
+impl LabelRequirement {
+    /// Returns `true` if `value`, the value of the label or `None` if it is not set, satisfies
+    /// the requirement.
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        let is_in =
+            |values: &[String]| value.is_some_and(|v| values.iter().any(|value| value == v));
+
+        match &self.operator {
+            LabelOperator::Equals(expected) => value == Some(expected.as_str()),
+            LabelOperator::NotEquals(expected) => value != Some(expected.as_str()),
+            LabelOperator::In(values) => is_in(values),
+            LabelOperator::NotIn(values) => !is_in(values),
+            LabelOperator::Exists => value.is_some(),
+            LabelOperator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl fmt::Display for LabelRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.operator {
+            LabelOperator::Equals(value) => write!(f, "{}={}", self.key, value),
+            LabelOperator::NotEquals(value) => write!(f, "{}!={}", self.key, value),
+            LabelOperator::In(values) => write!(f, "{} in ({})", self.key, values.join(",")),
+            LabelOperator::NotIn(values) => write!(f, "{} notin ({})", self.key, values.join(",")),
+            LabelOperator::Exists => f.write_str(&self.key),
+            LabelOperator::DoesNotExist => write!(f, "!{}", self.key),
+        }
+    }
+}
+
+/// A Kubernetes label selector, e.g. `env!=prod,tier in (frontend,backend),!canary`, as used by
+/// `kubectl -l` and the `labelSelector` parameter of the Kubernetes API.
+///
+/// A selector matches a set of labels if all its requirements are satisfied, so an empty selector
+/// matches any set of labels. The label sets themselves, e.g. `app=web,tier=frontend`, can be
+/// deserialized into typed structures with [`Preset::KubernetesLabels`](crate::Preset).
+///
+/// Selectors implement `Serialize` and `Deserialize` as strings, so they can be used as the
+/// values of fields.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use serde_keyvalue::{from_key_values_with, LabelSelector, Preset};
+///
+/// let labels: BTreeMap<String, String> =
+///     from_key_values_with("app=web,tier=frontend,env=staging", Preset::KubernetesLabels)
+///         .unwrap();
+///
+/// let selector: LabelSelector = "env!=prod,tier in (frontend, backend),!canary".parse().unwrap();
+/// assert!(selector.matches(&labels));
+///
+/// let selector = LabelSelector::parse("app=web,env").unwrap();
+/// assert!(selector.matches([("app", "web"), ("env", "")]));
+/// assert!(!selector.matches([("app", "web")]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<LabelRequirement>,
+}
+
+impl LabelSelector {
+    /// Parses a selector made of comma separated requirements, with optional spaces around them
+    /// and their operators.
+    ///
+    /// Keys are made of alphanumeric characters, `-`, `_`, `.` and `/`, and values of
+    /// alphanumeric characters, `-`, `_` and `.`. Values may be empty, except in the sets of the
+    /// `in` and `notin` operators.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut parser = SelectorParser { input, pos: 0 };
+        let mut requirements = Vec::new();
+
+        parser.skip_spaces();
+        if parser.rest().is_empty() {
+            return Ok(Self { requirements });
+        }
+        loop {
+            requirements.push(parser.requirement()?);
+            parser.skip_spaces();
+            if parser.rest().is_empty() {
+                return Ok(Self { requirements });
+            }
+            parser.expect(',', ErrorKind::ExpectedComma)?;
+        }
+    }
+
+    /// Returns the requirements of the selector, in the order in which they were written.
+    pub fn requirements(&self) -> &[LabelRequirement] {
+        &self.requirements
+    }
+
+    /// Returns `true` if the labels satisfy all the requirements of the selector. If a key appears
+    /// several times, its last value is used.
+    pub fn matches<K, V>(&self, labels: impl IntoIterator<Item = (K, V)>) -> bool
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let labels: Vec<(K, V)> = labels.into_iter().collect();
+
+        self.requirements.iter().all(|requirement| {
+            let value = labels
+                .iter()
+                .rev()
+                .find(|(key, _)| key.as_ref() == requirement.key)
+                .map(|(_, value)| value.as_ref());
+            requirement.matches(value)
+        })
+    }
+}
+
+impl FromIterator<LabelRequirement> for LabelSelector {
+    fn from_iter<I: IntoIterator<Item = LabelRequirement>>(iter: I) -> Self {
+        Self {
+            requirements: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, requirement) in self.requirements.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", requirement)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for LabelSelector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LabelSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LabelSelectorVisitor;
+
+        impl Visitor<'_> for LabelSelectorVisitor {
+            type Value = LabelSelector;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a label selector such as `app=web,env!=prod`")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                LabelSelector::parse(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(LabelSelectorVisitor)
+    }
+}
+
+fn is_value_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+fn is_key_char(c: char) -> bool {
+    is_value_char(c) || c == '/'
+}
+
+struct SelectorParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> SelectorParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error(&self, kind: ErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            pos: self.pos,
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `c`, after optional spaces, or returns an error of `kind`.
+    fn expect(&mut self, c: char, kind: ErrorKind) -> Result<(), ParseError> {
+        self.skip_spaces();
+        if !self.rest().starts_with(c) {
+            return Err(self.error(kind));
+        }
+        self.pos += c.len_utf8();
+        Ok(())
+    }
+
+    /// Consumes `prefix` if the input starts with it.
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.rest().starts_with(prefix);
+        if found {
+            self.pos += prefix.len();
+        }
+        found
+    }
+
+    /// Consumes the longest prefix made of characters accepted by `f`, after optional spaces.
+    fn take(&mut self, f: fn(char) -> bool) -> &'a str {
+        self.skip_spaces();
+        let rest = self.rest();
+        let len = rest.find(|c: char| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        match self.take(is_key_char) {
+            "" => Err(self.error(ErrorKind::ExpectedIdentifier)),
+            key => Ok(key.into()),
+        }
+    }
+
+    /// Consumes the `in` or `notin` word, which must be followed by a space or a parenthesis.
+    fn set_operator(&mut self, word: &str) -> bool {
+        let is_word = self
+            .rest()
+            .strip_prefix(word)
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '('));
+        is_word && self.eat(word)
+    }
+
+    /// Parses the parenthesized values of the `in` and `notin` operators.
+    fn values(&mut self) -> Result<Vec<String>, ParseError> {
+        self.expect('(', ErrorKind::ExpectedOpenBracket)?;
+        let mut values = Vec::new();
+        loop {
+            let value = self.take(is_value_char);
+            if value.is_empty() {
+                return Err(self.error(ErrorKind::EmptyValue));
+            }
+            values.push(value.into());
+
+            self.skip_spaces();
+            if !self.eat(",") {
+                self.expect(')', ErrorKind::ExpectedCloseBracket)?;
+                return Ok(values);
+            }
+        }
+    }
+
+    fn requirement(&mut self) -> Result<LabelRequirement, ParseError> {
+        self.skip_spaces();
+        if self.eat("!") {
+            return Ok(LabelRequirement {
+                key: self.key()?,
+                operator: LabelOperator::DoesNotExist,
+            });
+        }
+
+        let key = self.key()?;
+        self.skip_spaces();
+        let operator = if self.rest().is_empty() || self.rest().starts_with(',') {
+            LabelOperator::Exists
+        } else if self.eat("==") || self.eat("=") {
+            LabelOperator::Equals(self.take(is_value_char).into())
+        } else if self.eat("!=") {
+            LabelOperator::NotEquals(self.take(is_value_char).into())
+        } else if self.set_operator("in") {
+            LabelOperator::In(self.values()?)
+        } else if self.set_operator("notin") {
+            LabelOperator::NotIn(self.values()?)
+        } else {
+            return Err(self.error(ErrorKind::ExpectedEqual));
+        };
+
+        Ok(LabelRequirement { key, operator })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirement(key: &str, operator: LabelOperator) -> LabelRequirement {
+        LabelRequirement {
+            key: key.into(),
+            operator,
+        }
+    }
+
+    #[test]
+    fn selector_parse() {
+        let selector =
+            LabelSelector::parse(" env!=prod , tier in (a, b),x notin(c),app==web,!canary,gpu,e=")
+                .unwrap();
+        assert_eq!(
+            selector.requirements(),
+            [
+                requirement("env", LabelOperator::NotEquals("prod".into())),
+                requirement("tier", LabelOperator::In(vec!["a".into(), "b".into()])),
+                requirement("x", LabelOperator::NotIn(vec!["c".into()])),
+                requirement("app", LabelOperator::Equals("web".into())),
+                requirement("canary", LabelOperator::DoesNotExist),
+                requirement("gpu", LabelOperator::Exists),
+                requirement("e", LabelOperator::Equals("".into())),
+            ]
+        );
+        assert_eq!(
+            selector.to_string(),
+            "env!=prod,tier in (a,b),x notin (c),app=web,!canary,gpu,e="
+        );
+        assert_eq!(LabelSelector::parse(&selector.to_string()), Ok(selector));
+
+        let selector = LabelSelector::parse("app.kubernetes.io/name=web,index").unwrap();
+        assert_eq!(selector.requirements()[0].key, "app.kubernetes.io/name");
+        // `in` and `notin` are only operators when separated from the key.
+        assert_eq!(selector.requirements()[1].operator, LabelOperator::Exists);
+        assert_eq!(LabelSelector::parse("  "), Ok(LabelSelector::default()));
+    }
+
+    #[test]
+    fn selector_errors() {
+        for (input, kind, pos) in [
+            ("a=b,", ErrorKind::ExpectedIdentifier, 4),
+            ("a<b", ErrorKind::ExpectedEqual, 1),
+            ("a=b c", ErrorKind::ExpectedComma, 4),
+            ("a in b", ErrorKind::ExpectedOpenBracket, 5),
+            ("a in (b", ErrorKind::ExpectedCloseBracket, 7),
+            ("a in ()", ErrorKind::EmptyValue, 6),
+            ("a in (b,)", ErrorKind::EmptyValue, 8),
+            ("!", ErrorKind::ExpectedIdentifier, 1),
+        ] {
+            assert_eq!(
+                LabelSelector::parse(input),
+                Err(ParseError { kind, pos }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn kubernetes_labels_preset() {
+        use std::collections::BTreeMap;
+
+        use crate::from_key_values_with;
+        use crate::KeyValueSerializer;
+        use crate::Preset;
+
+        let input = "app.kubernetes.io/name=web,canary=true,tier=frontend";
+        let labels: BTreeMap<String, String> =
+            from_key_values_with(input, Preset::KubernetesLabels).unwrap();
+        assert_eq!(labels["app.kubernetes.io/name"], "web");
+        let mut serializer = KeyValueSerializer::with_options(Preset::KubernetesLabels);
+        labels.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_output(), input);
+
+        let selector = LabelSelector::parse("app.kubernetes.io/name in (web),canary").unwrap();
+        assert!(selector.matches(&labels));
+
+        // Values must be valid label values.
+        let long = "a".repeat(63);
+        for value in ["", "v1.2_b-c", long.as_str()] {
+            let labels = BTreeMap::from([("tier", value)]);
+            let mut serializer = KeyValueSerializer::with_options(Preset::KubernetesLabels);
+            labels.serialize(&mut serializer).unwrap();
+            let output = serializer.into_output();
+            let parsed: BTreeMap<String, String> =
+                from_key_values_with(&output, Preset::KubernetesLabels).unwrap();
+            assert_eq!(parsed["tier"], value);
+        }
+        let too_long = "a".repeat(64);
+        for value in ["a b", "-a", "a.", "a/b", "é", too_long.as_str()] {
+            let labels = BTreeMap::from([("tier", value)]);
+            let mut serializer = KeyValueSerializer::with_options(Preset::KubernetesLabels);
+            assert!(labels.serialize(&mut serializer).is_err(), "{}", value);
+            assert_eq!(
+                serializer.unsupported().unwrap().shape,
+                "string that is not a valid Kubernetes label value"
+            );
+
+            let input = format!("tier={}", value);
+            let err =
+                from_key_values_with::<BTreeMap<String, String>>(&input, Preset::KubernetesLabels)
+                    .unwrap_err();
+            assert_eq!(err.kind, crate::ErrorKind::ExpectedString, "{}", value);
+            assert_eq!(err.pos, 5);
+        }
+    }
+
+    #[test]
+    fn selector_matches() {
+        let labels = [("app", "web"), ("tier", "db"), ("tier", "frontend")];
+        let matches = |selector: &str| LabelSelector::parse(selector).unwrap().matches(labels);
+
+        assert!(matches(""));
+        assert!(matches("app=web,tier=frontend"));
+        assert!(!matches("tier=db"));
+        assert!(matches("env!=prod,tier!=db"));
+        assert!(matches("tier in (frontend,backend),env notin (prod)"));
+        assert!(!matches("env in (prod)"));
+        assert!(!matches("app notin (web)"));
+        assert!(matches("app,!env"));
+        assert!(!matches("env"));
+        assert!(!matches("!app"));
+    }
+}
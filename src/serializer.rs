@@ -1,25 +1,53 @@
-use serde::{ser::{Impossible, SerializeStruct}, Serializer};
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, string::ToString, vec::Vec};
+use core::fmt;
+
+use serde::{
+    ser::{
+        Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple,
+        SerializeTupleStruct,
+    },
+    Serializer,
+};
+
+use crate::formatter::{DefaultFormatter, ValueFormatter};
+use crate::key_values::{any_identifier, is_label_value, strip_delimiter};
+use crate::options::{
+    BoolStyle, DelimiterStyle, DeserializeOptions, NewlineStyle, NoneStyle, Quoting,
+    SerializeOptions,
+};
 
 /// A serializer that outputs key-value pairs in a string format.
 /// The output is a single string where each key-value pair is separated by spaces,
 /// and each key is followed by an equals sign and its corresponding value.
-/// 
+///
 /// For example: "key1=value1 key2=value2 key3=value3".
-/// 
+///
 /// This serializer is designed to be used with structs,
 /// where each field is serialized as a key-value pair.
-/// 
+///
+/// Tuples and tuple structs are serialized as a list of their elements separated by
+/// a colon, e.g. `range=1024:2048`. Other sequences are serialized as a list of their elements
+/// separated by commas and enclosed in brackets, e.g. `sizes=[1,2]`, as are the pairs of nested
+/// structs, e.g. `cache=[direct=True size=64]`. Unit values and unit structs are serialized as
+/// bare keys, e.g. `feature_x`. Maps, newtype structs wrapping a struct or map, and sequences or
+/// arrays of key-value pairs like `Vec<(String, u32)>` can be serialized instead of a top-level
+/// struct, e.g. for pairs only known at runtime.
+///
+/// The separators, quoting of strings and the representation of booleans and `None` values can
+/// be configured through [`SerializeOptions`], and the rendering of scalar values through a
+/// [`ValueFormatter`].
+///
 /// # Example
 /// ```
 /// use serde::Serialize;
 /// use serde_keyvalue::KeyValueSerializer;
-/// 
+///
 /// #[derive(Serialize)]
 /// enum Color {
 ///     Red,
 ///     Blue,
 /// }
-/// 
+///
 /// #[derive(Serialize)]
 /// struct MyStruct {
 ///     key1: String,
@@ -28,7 +56,7 @@ use serde::{ser::{Impossible, SerializeStruct}, Serializer};
 ///     key4: f64,
 ///     key5: Color,
 /// }
-/// 
+///
 /// let my_struct = MyStruct {
 ///     key1: "value1".to_string(),
 ///     key2: 42,
@@ -36,246 +64,3575 @@ use serde::{ser::{Impossible, SerializeStruct}, Serializer};
 ///     key4: 1.5,
 ///     key5: Color::Red,
 /// };
-/// 
+///
 /// let mut serializer = KeyValueSerializer::new();
 /// my_struct.serialize(&mut serializer).unwrap();
 /// let output = serializer.into_output();
-/// 
+///
 /// assert_eq!(output, "key1=value1 key2=42 key3=True key4=1.5 key5=Red");
 /// ```
 pub struct KeyValueSerializer {
     top_parsed: bool,
+    options: SerializeOptions,
+    formatter: Box<dyn ValueFormatter>,
+    /// Per-field formatting directives applying to the value being serialized.
+    format: FieldFormat,
+    /// Position of the key-value pair whose value is currently being serialized, if the value
+    /// has not been entered into a compound type yet.
+    pair: Option<PairPosition>,
+    /// Whether the next field of the top-level struct is written without its key.
+    positional_field: bool,
+    /// Prefix written before the keys of the nested struct or map being serialized.
+    key_prefix: String,
+    /// How the nested structs or maps being serialized are written, innermost last.
+    nesting: Vec<Nesting>,
+    /// Output of the top-level value while one of its sections is written to `output`.
+    parked_output: Option<String>,
+    /// Sections already serialized, written after the other pairs of the top-level value.
+    sections: String,
+    /// Ends of the sections in `sections`.
+    section_ends: Vec<usize>,
+    /// Ends of the pairs of the top-level value in the output, where it may be truncated.
+    pair_ends: Vec<usize>,
+    /// Set when the output has exceeded the byte budget set in the options.
+    budget_exceeded: Option<BudgetExceeded>,
+    /// Set when a value that cannot be written as key-values has been met.
+    unsupported: Option<Unsupported>,
+    /// Position in the output of the element of a sequence being serialized, and whether it has
+    /// turned out to be a struct enclosed in brackets.
+    element: Option<(usize, bool)>,
     output: String,
 }
 
+/// Error recorded by [`KeyValueSerializer`] when its output exceeds the budget set with
+/// [`SerializeOptions::byte_budget`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// Key of the pair whose value made the output exceed the budget, as written in the output.
+    /// Empty if the pair was written without its key.
+    pub key: String,
+    /// Maximum number of bytes of the output.
+    pub budget: usize,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "value of `{}` exceeds the output budget of {} bytes",
+            self.key, self.budget
+        )
+    }
+}
+
+impl core::error::Error for BudgetExceeded {}
+
+/// Error recorded by [`KeyValueSerializer`] when it meets a value that cannot be written as
+/// key-values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    /// Type of the value in the serde data model, e.g. `bytes` or `struct variant`, along with
+    /// the option or the dialect rejecting it if any.
+    pub shape: &'static str,
+    /// Key of the pair whose value cannot be serialized, as written in the output, or name of the
+    /// field written without its key. Empty if the value is not the value of a pair, e.g. a key.
+    pub key: String,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.key.is_empty() {
+            write!(f, "value of `{}`: ", self.key)?;
+        }
+        write!(f, "{} cannot be serialized as key-values", self.shape)
+    }
+}
+
+impl core::error::Error for Unsupported {}
+
+/// Error returned by [`KeyValueSerializer`] and by the functions serializing values as
+/// key-values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SerializeError {
+    /// The output exceeded the budget set with [`SerializeOptions::byte_budget`].
+    BudgetExceeded(BudgetExceeded),
+    /// Error reported by the `Serialize` implementation of a value.
+    Custom(String),
+    /// A value cannot be written as key-values.
+    Unsupported(Unsupported),
+    /// The output could not be written, e.g. because it does not fit in its buffer.
+    Write,
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeError::BudgetExceeded(e) => e.fmt(f),
+            SerializeError::Custom(s) => f.write_str(s),
+            SerializeError::Unsupported(e) => e.fmt(f),
+            SerializeError::Write => f.write_str("cannot write the output"),
+        }
+    }
+}
+
+impl core::error::Error for SerializeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            SerializeError::BudgetExceeded(e) => Some(e),
+            SerializeError::Unsupported(e) => Some(e),
+            SerializeError::Custom(_) | SerializeError::Write => None,
+        }
+    }
+}
+
+impl serde::ser::Error for SerializeError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        SerializeError::Custom(msg.to_string())
+    }
+}
+
+impl From<fmt::Error> for SerializeError {
+    fn from(_: fmt::Error) -> Self {
+        SerializeError::Write
+    }
+}
+
+impl From<SerializeError> for fmt::Error {
+    fn from(_: SerializeError) -> Self {
+        fmt::Error
+    }
+}
+
+/// How a struct or map nested in the top-level value is written.
+enum Nesting {
+    /// Its pairs are prefixed by its key, with `key_prefix` having this length before it.
+    Prefixed(usize),
+    /// Its pairs are enclosed in brackets, with `key_prefix` restored to this value after it.
+    Bracketed(String),
+}
+
+/// Positions in the output of a key-value pair being serialized.
+#[derive(Clone, Copy)]
+struct PairPosition {
+    /// Start of the pair, including the separator with the previous pair.
+    start: usize,
+    /// Start of the key, after the prefix of keys set in the options.
+    key_start: usize,
+    /// End of the key, i.e. position of the key-value delimiter.
+    key_end: usize,
+}
+
+/// Prefix of the newtype struct names used to pass per-field formatting directives, as set by the
+/// `#[keyvalue]` attribute macro, to the serializer.
+///
+/// The prefix is followed by a colon and a list of directives separated by semicolons, e.g.
+/// `quote;radix=16`. Serializers other than [`KeyValueSerializer`] see a regular newtype struct
+/// and serialize its content as usual.
+pub(crate) const FIELD_FORMAT: &str = "$serde_keyvalue::private::FieldFormat";
+
+/// Placeholder written instead of the value of redacted fields.
+const REDACTED: &str = "***";
+
+/// Key of the pair ending truncated outputs, whose value is the number of pairs dropped.
+const TRUNCATED: &str = "…truncated";
+
+/// Per-field formatting directives.
+#[derive(Clone, Copy, Default)]
+struct FieldFormat {
+    /// Quote strings even if the options do not require it.
+    quote: bool,
+    /// Replace the value with a placeholder.
+    redact: bool,
+    /// Write strings verbatim, without formatting nor quoting.
+    raw: bool,
+    /// Write booleans as bare flags, as with [`BoolStyle::Flag`].
+    flag: bool,
+    /// Radix in which integers are written.
+    radix: Option<u32>,
+    /// Separator of tuple elements, overriding the one from the options.
+    separator: Option<char>,
+}
+
+impl FieldFormat {
+    /// Parses directives as written after [`FIELD_FORMAT`]. Unknown directives are ignored.
+    fn parse(directives: &str) -> Self {
+        let mut format = Self::default();
+
+        for directive in directives.split(';') {
+            match directive.split_once('=') {
+                None if directive == "quote" => format.quote = true,
+                None if directive == "redact" => format.redact = true,
+                None if directive == "raw" => format.raw = true,
+                None if directive == "flag" => format.flag = true,
+                Some(("radix", radix)) => format.radix = radix.parse().ok(),
+                // The separator is given as a character code, so it can be any character.
+                Some(("separator", code)) => {
+                    format.separator = code.parse().ok().and_then(char::from_u32)
+                }
+                _ => (),
+            }
+        }
+
+        format
+    }
+}
+
 pub struct KeyValueSerializerCounted<'s>(&'s mut KeyValueSerializer, usize);
 
+/// Serializes the elements of a tuple, separating them with the tuple separator.
+///
+/// The elements of a top-level tuple, e.g. an array, are key-value pairs instead, the last field
+/// being the number of pairs already written.
+pub struct KeyValueSerializerTuple<'s>(&'s mut KeyValueSerializer, bool, Option<usize>);
+
+/// Serializes the elements of a sequence as a list enclosed in brackets, or as repeated pairs
+/// with the key of the pair whose value is the sequence.
+///
+/// The elements of a top-level sequence are key-value pairs instead, the fourth field being the
+/// number of pairs already written. The fifth field is the position of the opening bracket of a
+/// list that may be written without its brackets, as long as its elements are bracketed structs.
+/// The last field is the index of the next element of a sequence whose elements are written as
+/// pairs keyed by their index, the fourth field then being the number of pairs already written.
+pub struct KeyValueSerializerSeq<'s>(
+    &'s mut KeyValueSerializer,
+    bool,
+    Option<PairPosition>,
+    Option<usize>,
+    Option<usize>,
+    Option<usize>,
+);
+
+impl Default for KeyValueSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl KeyValueSerializer {
     /// Creates a new `KeyValueSerializer` instance with an empty output string.
     pub fn new() -> Self {
+        Self::with_options(SerializeOptions::default())
+    }
+
+    /// Creates a new `KeyValueSerializer` instance with an empty output string, producing its
+    /// output according to `options`, which can also be a [`Preset`](crate::Preset).
+    pub fn with_options(options: impl Into<SerializeOptions>) -> Self {
         KeyValueSerializer {
             top_parsed: false,
+            options: options.into(),
+            formatter: Box::new(DefaultFormatter),
+            format: FieldFormat::default(),
+            pair: None,
+            positional_field: false,
+            key_prefix: String::new(),
+            nesting: Vec::new(),
+            parked_output: None,
+            sections: String::new(),
+            section_ends: Vec::new(),
+            pair_ends: Vec::new(),
+            budget_exceeded: None,
+            unsupported: None,
+            element: None,
             output: String::new(),
         }
     }
 
+    /// Returns the default options, to be customized and turned into a serializer with
+    /// [`SerializeOptions::build`].
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::KeyValueSerializer;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Window {
+    ///     size: (u16, u16),
+    /// }
+    ///
+    /// let mut serializer = KeyValueSerializer::builder().tuple_separator('x').build();
+    /// Window { size: (640, 480) }.serialize(&mut serializer).unwrap();
+    ///
+    /// assert_eq!(serializer.into_output(), "size=640x480");
+    /// ```
+    pub fn builder() -> SerializeOptions {
+        SerializeOptions::default()
+    }
+
+    /// Renders the scalar values with `formatter` instead of [`DefaultFormatter`].
+    pub fn formatter(mut self, formatter: impl ValueFormatter + 'static) -> Self {
+        self.formatter = Box::new(formatter);
+        self
+    }
+
     /// Consumes the serializer and returns the serialized output as a string.
-    pub fn into_output(self) -> String {
+    pub fn into_output(mut self) -> String {
+        self.append_sections();
+        self.truncate();
         self.output
     }
-    
-    fn serialize_signed(&mut self, v: i64) -> Result<(), std::fmt::Error> {
-        self.output.push_str(&v.to_string());
-        Ok(())
+
+    /// Returns the serialized output without consuming the serializer, so its memory can be
+    /// reused after calling [`clear`](Self::clear).
+    pub fn output(&mut self) -> &str {
+        self.append_sections();
+        self.truncate();
+        &self.output
     }
 
-    fn serialize_unsigned(&mut self, v: u64) -> Result<(), std::fmt::Error> {
-        self.output.push_str(&v.to_string());
-        Ok(())
+    /// Clears the output and the state of the serializer, so another value can be serialized
+    /// without allocating the memory used by the previous one again. The options and formatter
+    /// are kept.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::KeyValueSerializer;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Event {
+    ///     id: u32,
+    /// }
+    ///
+    /// let mut serializer = KeyValueSerializer::new();
+    /// let mut lines = Vec::new();
+    /// for id in 0..2 {
+    ///     serializer.clear();
+    ///     Event { id }.serialize(&mut serializer).unwrap();
+    ///     lines.push(serializer.output().to_string());
+    /// }
+    ///
+    /// assert_eq!(lines, ["id=0", "id=1"]);
+    /// ```
+    pub fn clear(&mut self) {
+        self.top_parsed = false;
+        self.format = FieldFormat::default();
+        self.pair = None;
+        self.positional_field = false;
+        self.key_prefix.clear();
+        self.nesting.clear();
+        self.parked_output = None;
+        self.sections.clear();
+        self.section_ends.clear();
+        self.pair_ends.clear();
+        self.budget_exceeded = None;
+        self.unsupported = None;
+        self.element = None;
+        self.output.clear();
     }
-}
 
-impl SerializeStruct for KeyValueSerializerCounted<'_> {
-    type Ok = ();
-    type Error = std::fmt::Error;
+    /// Returns the reason why serialization failed if the output exceeded the budget set with
+    /// [`SerializeOptions::byte_budget`].
+    pub fn budget_exceeded(&self) -> Option<&BudgetExceeded> {
+        self.budget_exceeded.as_ref()
+    }
 
-    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
-    where
-        T: ?Sized + serde::Serialize {
-        self.0.output.push_str(key);
-        self.0.output.push('=');
-        value.serialize(&mut *self.0)?;
-        
-        if self.1 > 1 {
-            self.1 -= 1;
-            self.0.output.push(' ');
-        }
+    /// Returns the reason why serialization failed if a value that cannot be written as
+    /// key-values, like a byte array, was met. This is also returned by the failed serialization,
+    /// as [`SerializeError::Unsupported`].
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_keyvalue::{KeyValueSerializer, SerializeError};
+    ///
+    /// #[derive(Serialize)]
+    /// enum Shape {
+    ///     Rect { w: u32, h: u32 },
+    /// }
+    ///
+    /// let mut serializer = KeyValueSerializer::new();
+    /// let err = Shape::Rect { w: 1, h: 2 }.serialize(&mut serializer).unwrap_err();
+    /// assert_eq!(err.to_string(), "struct variant cannot be serialized as key-values");
+    /// assert_eq!(err, SerializeError::Unsupported(serializer.unsupported().unwrap().clone()));
+    /// ```
+    pub fn unsupported(&self) -> Option<&Unsupported> {
+        self.unsupported.as_ref()
+    }
 
-        Ok(())
+    /// Records that a value of type `shape` cannot be serialized, and returns the error to
+    /// return.
+    fn unsupported_error(&mut self, shape: &'static str) -> SerializeError {
+        let key = match self.pair {
+            Some(pair) => self.output[pair.key_start..pair.key_end].to_owned(),
+            None => String::new(),
+        };
+        let unsupported = Unsupported { shape, key };
+        self.unsupported = Some(unsupported.clone());
+        SerializeError::Unsupported(unsupported)
     }
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+    /// Checks that the value of the `field` written without its key from `start` in the output
+    /// would be read back as such, i.e. that it is not empty and does not start like a pair.
+    fn check_positional_value(&mut self, field: &str, start: usize) -> Result<(), SerializeError> {
+        let value = &self.output[start..];
+        let options = DeserializeOptions::from(&self.options);
+        let shape = if value.is_empty() {
+            "empty value without its key"
+        } else if any_identifier(value, &options)
+            .is_ok_and(|(rest, _)| strip_delimiter(rest, &options).is_some())
+        {
+            "value without its key starting like a pair"
+        } else {
+            return Ok(());
+        };
+
+        let unsupported = Unsupported {
+            shape,
+            key: field.to_owned(),
+        };
+        self.unsupported = Some(unsupported.clone());
+        Err(SerializeError::Unsupported(unsupported))
     }
-    
-    fn skip_field(&mut self, _: &'static str) -> Result<(), Self::Error> {
-        self.1 -= 1;
-        Ok(())
+
+    /// Returns `true` if the pair with `key`, prefixed by the keys of the values it is nested in,
+    /// passes the filters set with [`SerializeOptions::include_keys`] and
+    /// [`SerializeOptions::exclude_keys`].
+    fn keeps_key(&self, key: &str) -> bool {
+        let separator = self.options.nested_key_separator;
+        let included = self.options.include_keys.as_ref().is_none_or(|keys| {
+            keys.iter()
+                .any(|k| is_nested_key(key, k, separator) || is_nested_key(k, key, separator))
+        });
+        let excluded = self
+            .options
+            .exclude_keys
+            .iter()
+            .any(|k| is_nested_key(key, k, separator));
+
+        included && !excluded
     }
-}
 
-impl<'a> Serializer for &'a mut KeyValueSerializer {
-    type Ok = ();
-    type Error = std::fmt::Error;
-    type SerializeSeq = Impossible<Self::Ok, Self::Error>;
-    type SerializeTuple = Impossible<Self::Ok, Self::Error>;
-    type SerializeTupleStruct = Impossible<Self::Ok, Self::Error>;
-    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
-    type SerializeMap = Impossible<Self::Ok, Self::Error>;
-    type SerializeStruct = KeyValueSerializerCounted<'a>;
-    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+    /// Returns the length of the output if serialization stopped now, including the sections.
+    fn output_len(&self) -> usize {
+        let (main, section) = match &self.parked_output {
+            Some(main) => (main, Some(&self.output)),
+            None => (&self.output, None),
+        };
 
-    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(if v { "True" } else { "False" });
-        Ok(())
+        let mut len = main.len();
+        for part in [Some(&self.sections), section].into_iter().flatten() {
+            if !part.is_empty() {
+                if len > 0 {
+                    len += 2 * self.options.pair_separator.len();
+                }
+                len += part.len();
+            }
+        }
+        len
     }
 
-    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        self.serialize_signed(v as i64)
+    /// Appends the sections serialized so far to the output.
+    fn append_sections(&mut self) {
+        if !self.sections.is_empty() {
+            if !self.output.is_empty() {
+                self.output.push_str(&self.options.pair_separator);
+                self.output.push_str(&self.options.pair_separator);
+            }
+            let start = self.output.len();
+            self.output.push_str(&self.sections);
+            self.pair_ends
+                .extend(self.section_ends.drain(..).map(|end| start + end));
+            self.sections.clear();
+        }
     }
 
-    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        self.serialize_signed(v as i64)
+    /// Records the end of a pair of the top-level value.
+    fn end_pair(&mut self) {
+        let end = self.output.len();
+        let bracketed = self
+            .nesting
+            .iter()
+            .any(|nesting| matches!(nesting, Nesting::Bracketed(_)));
+        if self.parked_output.is_none() && !bracketed && self.pair_ends.last() < Some(&end) {
+            self.pair_ends.push(end);
+        }
     }
 
-    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        self.serialize_signed(v as i64)
+    /// Cuts the output after the last pair leaving enough room for the truncation marker if it
+    /// is longer than the maximum length set in the options, and appends the marker.
+    fn truncate(&mut self) {
+        let max_len = match self.options.max_len {
+            Some(max_len) if self.output.len() > max_len => max_len,
+            _ => return,
+        };
+
+        let mut marker = String::new();
+        for kept in (0..self.pair_ends.len()).rev() {
+            let end = kept.checked_sub(1).map_or(0, |last| self.pair_ends[last]);
+            marker.clear();
+            if end > 0 {
+                marker.push_str(&self.options.pair_separator);
+            }
+            marker.push_str(TRUNCATED);
+            write_delimiter(&mut marker, &self.options);
+            marker.push_str(&format!("{}", self.pair_ends.len() - kept));
+
+            if end + marker.len() <= max_len || kept == 0 {
+                self.output.truncate(end);
+                break;
+            }
+        }
+        self.output.push_str(&marker);
+        self.pair_ends.clear();
     }
 
-    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        self.serialize_signed(v)
+    /// Serializes a key-value pair after the `count` pairs already in the output, and increments
+    /// `count` if the pair has been written.
+    pub(crate) fn serialize_pair<T>(
+        &mut self,
+        count: &mut usize,
+        key: &str,
+        value: &T,
+    ) -> Result<(), SerializeError>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        // The value is not the top-level structure.
+        self.top_parsed = true;
+
+        let mut counted = KeyValueSerializerCounted(self, *count);
+        counted.serialize_key_str(key);
+        let ret = counted.serialize_pair_value(value);
+        *count = counted.1;
+
+        ret
     }
 
-    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        self.serialize_unsigned(v as u64)
+    /// Serializes an element of a top-level sequence, which must be a key-value pair, after the
+    /// `count` pairs already in the output, and increments `count` if the pair has been written.
+    fn serialize_pair_element<T>(
+        &mut self,
+        count: &mut usize,
+        value: &T,
+    ) -> Result<(), SerializeError>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let mut counted = KeyValueSerializerCounted(self, *count);
+        let ret = value.serialize(PairSerializer(&mut counted));
+        *count = counted.1;
+
+        ret
     }
 
-    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        self.serialize_unsigned(v as u64)
+    /// Writes a key without a value after the `count` pairs already in the output, and increments
+    /// `count`.
+    pub(crate) fn serialize_flag(&mut self, count: &mut usize, key: &str) {
+        if !self.keeps_key(key) {
+            return;
+        }
+        if *count > 0 {
+            self.output.push_str(&self.options.pair_separator);
+        }
+        self.output.push_str(key);
+        self.end_pair();
+        *count += 1;
     }
 
-    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        self.serialize_unsigned(v as u64)
+    /// Starts a struct or map nested as the value of the current pair. Its pairs replace the
+    /// current pair, with their keys prefixed by the key of the current pair. Without a nested
+    /// key separator, the pairs of a struct are written as the value of the current pair instead,
+    /// enclosed in brackets, or braces with `braced_structs`, if `bracketed` is set, and other
+    /// values are rejected.
+    fn begin_nested(
+        &mut self,
+        bracketed: bool,
+    ) -> Result<KeyValueSerializerCounted<'_>, SerializeError> {
+        if self.options.sections && self.parked_output.is_none() && self.nesting.is_empty() {
+            return self.begin_section();
+        }
+
+        let (separator, pair) = match (self.options.nested_key_separator, self.pair.take()) {
+            (Some(separator), Some(pair)) => (separator, pair),
+            // Structs that are elements of sequences have no pair of their own.
+            (None, _) if bracketed => {
+                if let Some((start, is_struct)) = &mut self.element {
+                    *is_struct |= *start == self.output.len();
+                }
+                let key_prefix = core::mem::take(&mut self.key_prefix);
+                self.nesting.push(Nesting::Bracketed(key_prefix));
+                let [open, _] = self.struct_brackets();
+                self.output.push(open);
+                return Ok(KeyValueSerializerCounted(self, 0));
+            }
+            (None, _) => {
+                return Err(self.unsupported_error("nested map without a nested key separator"))
+            }
+            (Some(_), None) => return Err(self.unsupported_error("map in a sequence")),
+        };
+
+        let preceded = self.begin_prefixed(pair, separator);
+        Ok(KeyValueSerializerCounted(self, preceded))
     }
 
-    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        self.serialize_unsigned(v)
+    /// Replaces `pair` with the pairs nested in it, whose keys are prefixed by its key followed by
+    /// `separator`, and returns the number of pairs that they follow, 0 or 1.
+    fn begin_prefixed(&mut self, pair: PairPosition, separator: char) -> usize {
+        self.nesting.push(Nesting::Prefixed(self.key_prefix.len()));
+        self.key_prefix = format!(
+            "{}{}",
+            &self.output[pair.key_start..pair.key_end],
+            separator
+        );
+        // Whether the current pair follows other pairs, in which case so will the nested pairs.
+        let preceded = pair.key_start - self.options.key_prefix.len() > pair.start;
+        self.output.truncate(pair.start);
+
+        preceded as usize
     }
 
-    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(&v.to_string());
-        Ok(())
+    /// Returns the characters enclosing the pairs of nested structs.
+    fn struct_brackets(&self) -> [char; 2] {
+        if self.options.braced_structs {
+            ['{', '}']
+        } else {
+            ['[', ']']
+        }
     }
 
-    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(&v.to_string());
-        Ok(())
+    /// Starts a section whose name is the key of the current pair. The section is written
+    /// separately from the pairs of the top-level value until `end_nested` is called.
+    fn begin_section(&mut self) -> Result<KeyValueSerializerCounted<'_>, SerializeError> {
+        let pair = self.pair.take().ok_or(fmt::Error)?;
+        let header = format!("[{}]", &self.output[pair.key_start..pair.key_end]);
+        self.output.truncate(pair.start);
+        self.parked_output = Some(core::mem::replace(&mut self.output, header));
+
+        // The header precedes the pairs of the section.
+        Ok(KeyValueSerializerCounted(self, 1))
     }
 
-    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        self.output.push(v);
+    /// Ends the nested struct or map being serialized, if any.
+    fn end_nested(&mut self) {
+        if let Some(nesting) = self.nesting.pop() {
+            match nesting {
+                Nesting::Prefixed(len) => self.key_prefix.truncate(len),
+                Nesting::Bracketed(key_prefix) => {
+                    self.key_prefix = key_prefix;
+                    let [_, close] = self.struct_brackets();
+                    self.output.push(close);
+                }
+            }
+        } else if let Some(output) = self.parked_output.take() {
+            let section = core::mem::replace(&mut self.output, output);
+            if !self.sections.is_empty() {
+                self.sections.push_str(&self.options.pair_separator);
+                self.sections.push_str(&self.options.pair_separator);
+            }
+            self.sections.push_str(&section);
+            self.section_ends.push(self.sections.len());
+        }
+    }
+
+    fn serialize_signed(&mut self, v: i64) -> Result<(), SerializeError> {
+        let number = if self.format.radix.is_none() {
+            self.formatter.format_i64(v)
+        } else if v < 0 {
+            format!("-{}", self.format_unsigned(v.unsigned_abs()))
+        } else {
+            self.format_unsigned(v as u64)
+        };
+        self.write_scalar(&number);
         Ok(())
     }
 
-    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(v);
+    fn serialize_unsigned(&mut self, v: u64) -> Result<(), SerializeError> {
+        let number = self.format_unsigned(v);
+        self.write_scalar(&number);
         Ok(())
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn format_unsigned(&self, v: u64) -> String {
+        // The prefixes are the ones recognized by the deserializer.
+        match self.format.radix {
+            Some(16) => format!("{:#x}", v),
+            Some(8) => format!("{:#o}", v),
+            Some(2) => format!("{:#b}", v),
+            _ => self.formatter.format_u64(v),
+        }
     }
 
-    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        unreachable!("None should have been skipped in serialization")
+    /// Writes a number or boolean, which is quoted like strings if `Quoting::Prometheus` or
+    /// `Quoting::Syslog` is used.
+    fn write_scalar(&mut self, s: &str) {
+        if matches!(self.options.quoting, Quoting::Prometheus | Quoting::Syslog) {
+            write_label_value(&mut self.output, s);
+        } else {
+            self.output.push_str(s);
+        }
     }
 
-    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    /// Writes `value` as compact JSON if it is a struct or a map, or a sequence holding some, and
+    /// returns whether it was written.
+    #[cfg(feature = "json")]
+    fn write_embedded_json<T>(&mut self, value: &T) -> Result<bool, SerializeError>
     where
-        T: ?Sized + serde::Serialize {
-        value.serialize(self)
+        T: ?Sized + serde::Serialize,
+    {
+        fn is_complex(value: &serde_json::Value) -> bool {
+            match value {
+                serde_json::Value::Object(_) => true,
+                serde_json::Value::Array(elements) => elements.iter().any(is_complex),
+                _ => false,
+            }
+        }
+
+        // Values JSON cannot represent, e.g. maps with non-string keys, keep the key-values syntax.
+        match serde_json::to_value(value) {
+            Ok(json) if is_complex(&json) => {
+                self.output.push_str(&json.to_string());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
     }
 
-    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+    fn serialize_string(&mut self, v: &str) -> Result<(), SerializeError> {
+        if self.format.raw {
+            self.output.push_str(v);
+            return Ok(());
+        }
+
+        let v = self.formatter.format_str(v);
+        if self.options.quoting == Quoting::Cookie && !self.format.quote {
+            let pair = self.pair.as_ref();
+            let key = pair.map(|pair| &self.output[pair.key_start..pair.key_end]);
+            if v.contains(|c: char| c == ';' || c.is_control()) {
+                return Err(self.unsupported_error(
+                    "string containing `;` or a control character in a cookie",
+                ));
+            }
+            if v.contains(',') && !key.is_some_and(|key| key.eq_ignore_ascii_case("Expires")) {
+                return Err(self.unsupported_error("string containing `,` in a cookie"));
+            }
+        }
+        if self.options.quoting == Quoting::Influx && !self.format.quote && v.is_empty() {
+            return Err(self.unsupported_error("empty string in an InfluxDB tag set"));
+        }
+        if self.options.quoting == Quoting::Kernel && !self.format.quote && v.contains('"') {
+            return Err(self.unsupported_error("string containing `\"` on a kernel command line"));
+        }
+        if self.options.quoting == Quoting::SingleLine
+            && !self.format.quote
+            && v.contains(['\n', '\r'])
+        {
+            return Err(self.unsupported_error("string containing a line break"));
+        }
+        // Brackets start or end lists when deserializing, and these dialects cannot quote them.
+        if matches!(
+            self.options.quoting,
+            Quoting::SingleLine | Quoting::Qemu | Quoting::Cookie
+        ) && !self.format.quote
+            && v.contains(['[', ']'])
+        {
+            return Err(self.unsupported_error("string containing a bracket without quotes"));
+        }
+        if self.options.quoting == Quoting::Label && !self.format.quote && !is_label_value(&v) {
+            return Err(self.unsupported_error("string that is not a valid Kubernetes label value"));
+        }
+        if self.format.quote {
+            write_quoted(&mut self.output, &v, self.options.newline_style);
+        } else {
+            write_string(&mut self.output, &v, &self.options);
+        }
         Ok(())
     }
+}
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        self.serialize_unit()
+/// Returns `true` if `key` is `ancestor` or the key of a pair nested in it.
+fn is_nested_key(key: &str, ancestor: &str, separator: Option<char>) -> bool {
+    match key.strip_prefix(ancestor) {
+        Some("") => true,
+        Some(rest) => separator.is_some_and(|separator| rest.starts_with(separator)),
+        None => false,
     }
+}
 
-    fn serialize_unit_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        variant: &'static str,
-    ) -> Result<Self::Ok, Self::Error> {
-        self.output.push_str(variant);
-        Ok(())
+/// Returns `true` if `s` cannot be written unquoted with `options` without being misinterpreted.
+fn needs_quoting(s: &str, options: &SerializeOptions) -> bool {
+    s.is_empty()
+        || s.contains(&options.pair_separator)
+        || (!options.record_separator.is_empty() && s.contains(&options.record_separator))
+        || s.chars().any(|c| {
+            c.is_whitespace()
+                || matches!(c, ',' | '"' | '\'' | '[' | ']')
+                || c == options.key_value_delimiter
+                || (options.braced_structs && matches!(c, '{' | '}'))
+        })
+}
+
+/// Returns `true` if `s` is a token as defined by RFC 9110, i.e. a non-empty string of
+/// alphanumeric ASCII characters and ``!#$%&'*+-.^_`|~``.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+/// Appends the key-value delimiter to `output`, spaced as required by `options`.
+pub(crate) fn write_delimiter(output: &mut String, options: &SerializeOptions) {
+    match options.delimiter_style {
+        DelimiterStyle::Compact => output.push(options.key_value_delimiter),
+        DelimiterStyle::SpaceAfter => {
+            output.push(options.key_value_delimiter);
+            output.push(' ');
+        }
+        DelimiterStyle::Spaced => {
+            output.push(' ');
+            output.push(options.key_value_delimiter);
+            output.push(' ');
+        }
     }
+}
 
-    fn serialize_newtype_struct<T>(
-        self,
-        _name: &'static str,
-        value: &T,
-    ) -> Result<Self::Ok, Self::Error>
-    where
-        T: ?Sized + serde::Serialize {
-        value.serialize(self)
+/// Appends the string value `v` to `output`, quoting and escaping it as required by `options`.
+pub(crate) fn write_string(output: &mut String, v: &str, options: &SerializeOptions) {
+    if v.is_empty() && options.bare_empty_strings {
+        return;
+    }
+    let quote = match options.quoting {
+        Quoting::Never | Quoting::Cookie | Quoting::Label | Quoting::SingleLine => false,
+        Quoting::WhenNeeded => needs_quoting(v, options),
+        Quoting::Always => true,
+        Quoting::Escaped => return write_escaped(output, v, options),
+        Quoting::Percent => return write_percent_encoded(output, v, true),
+        Quoting::Uri => return write_percent_encoded(output, v, false),
+        Quoting::Token => {
+            if is_token(v) {
+                output.push_str(v);
+            } else {
+                write_quoted_verbatim(output, v, '"', &['"', '\\']);
+            }
+            return;
+        }
+        Quoting::Prometheus => return write_label_value(output, v),
+        Quoting::Syslog => return write_quoted_verbatim(output, v, '"', &['"', '\\', ']']),
+        Quoting::Influx => return write_influx_escaped(output, v),
+        Quoting::DistinguishedName => return write_dn_escaped(output, v),
+        Quoting::Ffmpeg => return write_ffmpeg_escaped(output, v, options),
+        Quoting::SingleQuoted => {
+            output.push('\'');
+            output.push_str(&v.replace('\'', r"'\''"));
+            output.push('\'');
+            return;
+        }
+        Quoting::Folded => {
+            output.push_str(&v.replace("\r\n", "\n").replace('\n', "\n "));
+            return;
+        }
+        Quoting::Qemu => {
+            let separator = options.pair_separator.as_str();
+            if separator.is_empty() {
+                output.push_str(v);
+            } else {
+                output.push_str(&v.replace(separator, &separator.repeat(2)));
+            }
+            return;
+        }
+        Quoting::Braces => {
+            let braced = v.is_empty()
+                || v.starts_with(char::is_whitespace)
+                || v.ends_with(char::is_whitespace)
+                || v.contains([';', '{', '}', '[', ']']);
+            if braced {
+                output.push('{');
+                output.push_str(&v.replace('}', "}}"));
+                output.push('}');
+            } else {
+                output.push_str(v);
+            }
+            return;
+        }
+        Quoting::Libpq => {
+            if v.is_empty() || v.contains(|c: char| c.is_whitespace() || "'\\[]".contains(c)) {
+                write_quoted_verbatim(output, v, '\'', &['\'', '\\']);
+            } else {
+                output.push_str(v);
+            }
+            return;
+        }
+        Quoting::Doubled => {
+            if needs_quoting(v, options) {
+                output.push('"');
+                output.push_str(&v.replace('"', "\"\""));
+                output.push('"');
+            } else {
+                output.push_str(v);
+            }
+            return;
+        }
+        Quoting::Kernel => {
+            if v.is_empty() || v.contains(|c: char| c.is_whitespace() || matches!(c, '[' | ']')) {
+                output.push('"');
+                output.push_str(v);
+                output.push('"');
+            } else {
+                output.push_str(v);
+            }
+            return;
+        }
+        Quoting::Shell => {
+            if needs_quoting(v, options) || v.contains(['\\', '$', '`']) {
+                write_quoted_verbatim(output, v, '"', &['"', '\\', '`', '$']);
+            } else {
+                output.push_str(v);
+            }
+            return;
+        }
+    };
+
+    if quote {
+        write_quoted(output, v, options.newline_style);
+    } else {
+        output.push_str(v);
     }
+}
 
-    fn serialize_newtype_variant<T>(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        value: &T,
-    ) -> Result<Self::Ok, Self::Error>
-    where
-        T: ?Sized + serde::Serialize {
-        value.serialize(self)
+/// Appends the string value `v` to `output` between double quotes, escaping it as needed. Line
+/// breaks are kept as-is with [`NewlineStyle::Literal`].
+fn write_quoted(output: &mut String, v: &str, newline_style: NewlineStyle) {
+    output.push('"');
+    for c in v.chars() {
+        match c {
+            '"' | '\\' => {
+                output.push('\\');
+                output.push(c);
+            }
+            '\n' | '\r' if newline_style == NewlineStyle::Literal => output.push(c),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
     }
+    output.push('"');
+}
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        unimplemented!()
+/// Appends the string value `v` to `output` between `quote` characters, escaping the `special`
+/// characters with a backslash and keeping the others as-is, as in shell scripts or HTTP headers.
+fn write_quoted_verbatim(output: &mut String, v: &str, quote: char, special: &[char]) {
+    output.push(quote);
+    for c in v.chars() {
+        if special.contains(&c) {
+            output.push('\\');
+        }
+        output.push(c);
     }
+    output.push(quote);
+}
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        unimplemented!()
+/// Appends the string value `v` to `output` between double quotes, escaping only `"`, `\` and
+/// newlines as required by the Prometheus exposition format.
+fn write_label_value(output: &mut String, v: &str) {
+    output.push('"');
+    for c in v.chars() {
+        match c {
+            '"' | '\\' => {
+                output.push('\\');
+                output.push(c);
+            }
+            '\n' => output.push_str("\\n"),
+            c => output.push(c),
+        }
     }
+    output.push('"');
+}
 
-    fn serialize_tuple_struct(
-        self,
-        _name: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        unimplemented!()
+/// Appends the string `v` to `output` without quotes, escaping commas, equals signs and spaces
+/// with a backslash as in the InfluxDB line protocol.
+fn write_influx_escaped(output: &mut String, v: &str) {
+    let mut chars = v.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Backslashes would otherwise escape the next character, or the following separator.
+        let escaped = match c {
+            ',' | '=' | ' ' => true,
+            '\\' => matches!(chars.peek(), None | Some(',' | '=' | ' ' | '\\')),
+            _ => false,
+        };
+        if escaped {
+            output.push('\\');
+        }
+        output.push(c);
     }
+}
 
-    fn serialize_tuple_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        unimplemented!()
+/// Appends the string value `v` to `output` without quotes, escaping it with backslashes as
+/// expected by FFmpeg's option parser.
+fn write_ffmpeg_escaped(output: &mut String, v: &str, options: &SerializeOptions) {
+    // Empty values are only accepted quoted.
+    if v.is_empty() {
+        output.push_str("''");
+        return;
+    }
+    let last = v.chars().count().saturating_sub(1);
+    for (i, c) in v.chars().enumerate() {
+        // Leading and trailing whitespace would be trimmed.
+        if matches!(c, '\'' | '\\' | '[' | ']')
+            || options.pair_separator.contains(c)
+            || c.is_whitespace() && (i == 0 || i == last)
+        {
+            output.push('\\');
+        }
+        output.push(c);
     }
+}
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        unimplemented!()
+/// Appends the string value `v` to `output` without quotes, escaping it as an attribute value of
+/// a distinguished name following RFC 4514.
+fn write_dn_escaped(output: &mut String, v: &str) {
+    let last = v.chars().count().saturating_sub(1);
+    for (i, c) in v.chars().enumerate() {
+        match c {
+            '\0' => output.push_str("\\00"),
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => {
+                output.push('\\');
+                output.push(c);
+            }
+            ' ' | '#' if i == 0 => {
+                output.push('\\');
+                output.push(c);
+            }
+            ' ' if i == last => output.push_str("\\ "),
+            c => output.push(c),
+        }
     }
+}
 
-    fn serialize_struct(
-        self,
-        _name: &'static str,
-        len: usize,
-    ) -> Result<Self::SerializeStruct, Self::Error> {
-        if !self.top_parsed {
-            self.top_parsed = true;
-            Ok(KeyValueSerializerCounted(self, len))
+/// Appends the string value `v` to `output` without quotes, escaping its special characters with
+/// a backslash.
+fn write_escaped(output: &mut String, v: &str, options: &SerializeOptions) {
+    for (i, c) in v.chars().enumerate() {
+        match c {
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{c}' => output.push_str("\\f"),
+            // Leading spaces would be taken as part of the delimiter.
+            ' ' if i == 0 => output.push_str("\\ "),
+            c if matches!(c, '\\' | '[' | ']') || options.pair_separator.contains(c) => {
+                output.push('\\');
+                output.push(c);
+            }
+            c => output.push(c),
+        }
+    }
+}
+
+/// Appends the string value `v` to `output` percent-encoded, with spaces written as `+` if
+/// `plus_as_space` is set.
+fn write_percent_encoded(output: &mut String, v: &str, plus_as_space: bool) {
+    for b in v.bytes() {
+        match b {
+            b' ' if plus_as_space => output.push('+'),
+            b if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') => {
+                output.push(b as char)
+            }
+            b => output.push_str(&format!("%{:02X}", b)),
+        }
+    }
+}
+
+/// Serializes `value` with the per-field formatting `directives`, which are the full name of the
+/// newtype struct starting with [`FIELD_FORMAT`].
+pub fn serialize_formatted<T, S>(
+    directives: &'static str,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + serde::Serialize,
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(directives, value)
+}
+
+impl KeyValueSerializerCounted<'_> {
+    /// Writes `key` and the key-value delimiter, and records the position of the new pair.
+    fn serialize_key_str(&mut self, key: &str) {
+        let start = self.0.output.len();
+        if self.1 > 0 {
+            self.0.output.push_str(&self.0.options.pair_separator);
+        }
+        self.0.output.push_str(&self.0.options.key_prefix);
+        let key_start = self.0.output.len();
+        self.0.output.push_str(&self.0.key_prefix);
+        if self.0.options.quoting == Quoting::Influx {
+            write_influx_escaped(&mut self.0.output, key);
         } else {
-            Err(std::fmt::Error)
+            self.0.output.push_str(key);
         }
+        let key_end = self.0.output.len();
+        write_delimiter(&mut self.0.output, &self.0.options);
+
+        self.0.pair = Some(PairPosition {
+            start,
+            key_start,
+            key_end,
+        });
     }
 
-    fn serialize_struct_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
+    /// Writes the value of the pair started by `serialize_key_str`, or removes the pair if its key
+    /// is filtered out.
+    fn serialize_pair_value<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        // The pairs nested in brackets or sections are kept or dropped with their parent.
+        let filtered = self.0.parked_output.is_none()
+            && self
+                .0
+                .nesting
+                .iter()
+                .all(|nesting| matches!(nesting, Nesting::Prefixed(_)));
+        if let Some(pair) = self.0.pair.filter(|_| filtered) {
+            if !self
+                .0
+                .keeps_key(&self.0.output[pair.key_start..pair.key_end])
+            {
+                self.0.output.truncate(pair.start);
+                self.0.pair = None;
+                return Ok(());
+            }
+        }
+
+        let start = self.0.pair.map_or(self.0.output.len(), |pair| pair.start);
+        let key = match (self.0.options.byte_budget, self.0.pair) {
+            (Some(_), Some(pair)) => self.0.output[pair.key_start..pair.key_end].to_owned(),
+            _ => String::new(),
+        };
+        #[cfg(feature = "json")]
+        let embedded = self.0.options.embedded_json
+            && self.0.pair.is_some()
+            && self.0.write_embedded_json(value)?;
+        #[cfg(not(feature = "json"))]
+        let embedded = false;
+        if !embedded {
+            value.serialize(&mut *self.0)?;
+        }
+        self.0.pair = None;
+
+        if let Some(budget) = self.0.options.byte_budget {
+            if self.0.output_len() > budget {
+                let exceeded = BudgetExceeded { key, budget };
+                self.0.budget_exceeded = Some(exceeded.clone());
+                return Err(SerializeError::BudgetExceeded(exceeded));
+            }
+        }
+
+        // The value may have removed the whole pair from the output, e.g. a skipped `None`.
+        if self.0.output.len() > start {
+            self.0.end_pair();
+            self.1 += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl SerializeStruct for KeyValueSerializerCounted<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if core::mem::take(&mut self.0.positional_field) {
+            let start = self.0.output.len();
+            self.serialize_pair_value(value)?;
+            return self.0.check_positional_value(key, start);
+        }
+        self.serialize_key_str(key);
+        self.serialize_pair_value(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.0.end_nested();
+        Ok(())
+    }
+}
+
+impl SerializeTuple for KeyValueSerializerTuple<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if let Some(count) = &mut self.2 {
+            return self.0.serialize_pair_element(count, value);
+        }
+
+        if self.1 {
+            let separator = if self.0.options.bracketed_tuples {
+                ','
+            } else {
+                self.0
+                    .format
+                    .separator
+                    .unwrap_or(self.0.options.tuple_separator)
+            };
+            self.0.output.push(separator);
+        }
+        self.1 = true;
+
+        value.serialize(&mut *self.0)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.0.options.bracketed_tuples && self.2.is_none() {
+            self.0.output.push(']');
+        }
+        Ok(())
+    }
+}
+
+impl SerializeMap for KeyValueSerializerCounted<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        // Keys are written verbatim, without the formatting applying to values.
+        let mut key_serializer = KeyValueSerializer::new();
+        if let Err(e) = key.serialize(&mut key_serializer) {
+            self.0.unsupported = key_serializer.unsupported.take();
+            return Err(e);
+        }
+        self.serialize_key_str(&key_serializer.into_output());
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.serialize_pair_value(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.0.end_nested();
+        Ok(())
+    }
+}
+
+impl SerializeSeq for KeyValueSerializerSeq<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if let (Some(count), Some(index)) = (&mut self.3, &mut self.5) {
+            let mut counted = KeyValueSerializerCounted(&mut *self.0, *count);
+            counted.serialize_key_str(&index.to_string());
+            let ret = counted.serialize_pair_value(value);
+            *count = counted.1;
+            *index += 1;
+            return ret;
+        }
+
+        if let Some(count) = &mut self.3 {
+            return self.0.serialize_pair_element(count, value);
+        }
+
+        if self.1 {
+            match self.2 {
+                // Each element is written as a new pair with the same key.
+                Some(pair) => {
+                    self.0.end_pair();
+                    let key = self.0.output[pair.key_start..pair.key_end].to_owned();
+                    self.0.output.push_str(&self.0.options.pair_separator);
+                    self.0.output.push_str(&self.0.options.key_prefix);
+                    self.0.output.push_str(&key);
+                    write_delimiter(&mut self.0.output, &self.0.options);
+                }
+                None => self
+                    .0
+                    .output
+                    .push(self.0.options.list_separator.unwrap_or(',')),
+            }
+        }
+        self.1 = true;
+
+        if self.4.is_none() {
+            return value.serialize(&mut *self.0);
+        }
+
+        let start = self.0.output.len();
+        let element = self.0.element.replace((start, false));
+        let ret = value.serialize(&mut *self.0);
+        let is_struct = core::mem::replace(&mut self.0.element, element).is_some_and(|e| e.1);
+        // The first element must start with a pair for the sequence to be told apart from an empty
+        // one when deserializing.
+        let [_, close] = self.0.struct_brackets();
+        let empty = self.0.output[start..].chars().nth(1) == Some(close);
+        if !is_struct || (self.4 == Some(start - 1) && empty) {
+            self.4 = None;
+        }
+
+        ret
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        match self.2 {
+            _ if self.5.is_some() => self.0.end_nested(),
+            _ if self.3.is_some() => (),
+            // An empty sequence has no pair at all.
+            Some(pair) if !self.1 => self.0.output.truncate(pair.start),
+            Some(_) => (),
+            None => match self.4 {
+                Some(open) if self.1 => {
+                    self.0.output.remove(open);
+                }
+                _ if self.0.options.list_separator.is_none() => self.0.output.push(']'),
+                _ => (),
+            },
+        }
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for KeyValueSerializerTuple<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeTuple::end(self)
+    }
+}
+
+/// Serializes an element of a top-level sequence, which must be a key-value pair, i.e. a tuple
+/// of two elements, as a pair of the top-level value.
+struct PairSerializer<'a, 's>(&'a mut KeyValueSerializerCounted<'s>);
+
+impl PairSerializer<'_, '_> {
+    fn not_a_pair(self) -> SerializeError {
+        let KeyValueSerializerCounted(serializer, _) = self.0;
+        serializer.unsupported_error("sequence element other than a key-value pair")
+    }
+}
+
+/// Implements methods of `Serializer` rejecting values that are not key-value pairs.
+macro_rules! not_a_pair {
+    ($($method:ident($($arg:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ret, SerializeError> {
+                Err(self.not_a_pair())
+            }
+        )*
+    };
+}
+
+impl<'a, 's> Serializer for PairSerializer<'a, 's> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<(), SerializeError>;
+    type SerializeTuple = PairElements<'a, 's>;
+    type SerializeTupleStruct = PairElements<'a, 's>;
+    type SerializeTupleVariant = Impossible<(), SerializeError>;
+    type SerializeMap = Impossible<(), SerializeError>;
+    type SerializeStruct = Impossible<(), SerializeError>;
+    type SerializeStructVariant = Impossible<(), SerializeError>;
+
+    not_a_pair! {
+        serialize_bool(bool) -> ();
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_str(&str) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(&'static str) -> ();
+        serialize_unit_variant(&'static str, u32, &'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T: ?Sized + serde::Serialize>(self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + serde::Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + serde::Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerializeError> {
+        Err(self.not_a_pair())
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<PairElements<'a, 's>, SerializeError> {
+        if len != 2 {
+            return Err(self.not_a_pair());
+        }
+        Ok(PairElements(self.0, false))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<PairElements<'a, 's>, SerializeError> {
+        self.serialize_tuple(len)
+    }
+}
+
+/// Serializes the key and then the value of a key-value pair, the last field being set once
+/// the key has been written.
+struct PairElements<'a, 's>(&'a mut KeyValueSerializerCounted<'s>, bool);
+
+impl SerializeTuple for PairElements<'_, '_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if core::mem::replace(&mut self.1, true) {
+            SerializeMap::serialize_value(self.0, value)
+        } else {
+            SerializeMap::serialize_key(self.0, value)
+        }
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for PairElements<'_, '_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        SerializeTuple::end(self)
+    }
+}
+
+impl<'a> Serializer for &'a mut KeyValueSerializer {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = KeyValueSerializerSeq<'a>;
+    type SerializeTuple = KeyValueSerializerTuple<'a>;
+    type SerializeTupleStruct = KeyValueSerializerTuple<'a>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = KeyValueSerializerCounted<'a>;
+    type SerializeStruct = KeyValueSerializerCounted<'a>;
+    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        if self.options.bool_style == BoolStyle::Flag || self.format.flag {
+            // Flags are only meaningful as the direct value of a pair.
+            if let Some(pair) = self.pair.take() {
+                self.output
+                    .truncate(if v { pair.key_end } else { pair.start });
+                return Ok(());
+            }
+        }
+
+        let word = self.formatter.format_bool(v, self.options.bool_style);
+        self.write_scalar(&word);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_signed(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_signed(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_signed(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_signed(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unsigned(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unsigned(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unsigned(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unsigned(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        let number = self.formatter.format_f32(v);
+        self.write_scalar(&number);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        let number = self.formatter.format_f64(v);
+        self.write_scalar(&number);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_string(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_string(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(self.unsupported_error("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        match self.options.none_style {
+            NoneStyle::Skip => {
+                if let Some(pair) = self.pair.take() {
+                    self.output.truncate(pair.start);
+                }
+                Ok(())
+            }
+            NoneStyle::Empty => Ok(()),
+            NoneStyle::Error => Err(self.unsupported_error("`None` with `NoneStyle::Error`")),
+        }
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        // Unit values are written as bare flags, which is how they are parsed.
+        if let Some(pair) = self.pair.take() {
+            self.output.truncate(pair.key_end);
+        }
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.output.push_str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let directives = match name
+            .strip_prefix(FIELD_FORMAT)
+            .and_then(|d| d.strip_prefix(':'))
+        {
+            Some(directives) => directives,
+            None => return value.serialize(self),
+        };
+
+        let previous = core::mem::replace(&mut self.format, FieldFormat::parse(directives));
+        let ret = if self.format.redact {
+            self.serialize_string(REDACTED)
+        } else {
+            value.serialize(&mut *self)
+        };
+        self.format = previous;
+
+        ret
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        if !self.top_parsed {
+            self.top_parsed = true;
+            return Ok(KeyValueSerializerSeq(
+                self,
+                false,
+                None,
+                Some(0),
+                None,
+                None,
+            ));
+        }
+
+        let pair = self.pair.take();
+        if let (true, Some(separator), Some(pair)) = (
+            self.options.indexed_lists,
+            self.options.nested_key_separator,
+            pair,
+        ) {
+            let preceded = self.begin_prefixed(pair, separator);
+            return Ok(KeyValueSerializerSeq(
+                self,
+                false,
+                None,
+                Some(preceded),
+                None,
+                Some(0),
+            ));
+        }
+        if self.options.repeated_keys && pair.is_some() {
+            return Ok(KeyValueSerializerSeq(self, false, pair, None, None, None));
+        }
+
+        let open = self.output.len();
+        if self.options.list_separator.is_none() {
+            self.output.push('[');
+        }
+        let bare = self.options.bare_struct_lists && self.options.list_separator.is_none();
+        Ok(KeyValueSerializerSeq(
+            self,
+            false,
+            None,
+            None,
+            Some(open).filter(|_| bare),
+            None,
+        ))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        if !self.top_parsed {
+            self.top_parsed = true;
+            return Ok(KeyValueSerializerTuple(self, false, Some(0)));
+        }
+
+        self.pair = None;
+        if self.options.bracketed_tuples {
+            self.output.push('[');
+        }
+        Ok(KeyValueSerializerTuple(self, false, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(self.unsupported_error("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        if !self.top_parsed {
+            self.top_parsed = true;
+            Ok(KeyValueSerializerCounted(self, 0))
+        } else {
+            self.begin_nested(false)
+        }
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        if !self.top_parsed {
+            self.top_parsed = true;
+            self.positional_field = self.options.positional_first_field;
+            Ok(KeyValueSerializerCounted(self, 0))
+        } else {
+            self.begin_nested(true)
+        }
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        unimplemented!()
+        Err(self.unsupported_error("struct variant"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    fn to_output<T: Serialize>(value: &T, serializer: KeyValueSerializer) -> String {
+        let mut serializer = serializer;
+        value.serialize(&mut serializer).unwrap();
+        serializer.into_output()
+    }
+
+    #[test]
+    fn serialize_tuple() {
+        #[derive(Serialize)]
+        struct Ports {
+            range: (u16, u16),
+            origin: (i32, i32, i32),
+        }
+
+        let ports = Ports {
+            range: (1024, 2048),
+            origin: (0, -1, 2),
+        };
+
+        assert_eq!(
+            to_output(&ports, KeyValueSerializer::new()),
+            "range=1024:2048 origin=0:-1:2"
+        );
+        assert_eq!(
+            to_output(
+                &ports,
+                KeyValueSerializer::builder().tuple_separator('-').build()
+            ),
+            "range=1024-2048 origin=0--1-2"
+        );
+    }
+
+    #[test]
+    fn serialize_tuple_struct() {
+        #[derive(Serialize)]
+        struct Point(f64, f64);
+
+        #[derive(Serialize)]
+        struct Shape {
+            center: Point,
+            name: String,
+        }
+
+        let shape = Shape {
+            center: Point(1.5, 2.0),
+            name: "circle".into(),
+        };
+
+        assert_eq!(
+            to_output(&shape, KeyValueSerializer::new()),
+            "center=1.5:2 name=circle"
+        );
+    }
+
+    #[test]
+    fn serialize_logfmt() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Event {
+            msg: String,
+            empty: String,
+        }
+
+        let event = Event {
+            msg: "a\tb\u{1}\u{7f}".into(),
+            empty: "".into(),
+        };
+        let serializer = KeyValueSerializer::with_options(crate::Preset::Logfmt);
+        let output = to_output(&event, serializer);
+        assert_eq!(output, r#"msg="a\tb\u0001\u007f" empty="""#);
+        assert_eq!(
+            crate::from_key_values_with::<Event>(&output, crate::Preset::Logfmt).unwrap(),
+            event
+        );
+    }
+
+    #[test]
+    fn serialize_quoting() {
+        #[derive(Serialize)]
+        struct Names {
+            plain: String,
+            spaced: String,
+            quoted: String,
+            empty: String,
+        }
+
+        let names = Names {
+            plain: "disk".into(),
+            spaced: "my disk".into(),
+            quoted: "say \"hi\"\n".into(),
+            empty: "".into(),
+        };
+
+        assert_eq!(
+            to_output(&names, KeyValueSerializer::new()),
+            "plain=disk spaced=my disk quoted=say \"hi\"\n empty="
+        );
+        assert_eq!(
+            to_output(
+                &names,
+                SerializeOptions::new().quoting(Quoting::WhenNeeded).build()
+            ),
+            r#"plain=disk spaced="my disk" quoted="say \"hi\"\n" empty="""#
+        );
+        assert_eq!(
+            to_output(
+                &names,
+                SerializeOptions::new().quoting(Quoting::Always).build()
+            ),
+            r#"plain="disk" spaced="my disk" quoted="say \"hi\"\n" empty="""#
+        );
+    }
+
+    #[test]
+    fn serialize_escaped() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Props {
+            path: String,
+            text: String,
+            empty: String,
+        }
+
+        let props = Props {
+            path: r"C:\dir [1]".into(),
+            text: " two\nlines".into(),
+            empty: "".into(),
+        };
+
+        let output = to_output(
+            &props,
+            KeyValueSerializer::with_options(crate::Preset::JavaProperties),
+        );
+        assert_eq!(
+            output,
+            "path=C:\\\\dir \\[1\\]\ntext=\\ two\\nlines\nempty="
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Props>(&output, crate::Preset::JavaProperties).unwrap(),
+            props
+        );
+    }
+
+    #[test]
+    fn serialize_doubled_quotes() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Row {
+            path: String,
+            text: String,
+            tags: Vec<String>,
+        }
+
+        let row = Row {
+            path: r"C:\dir\".into(),
+            text: "say \"hi\"\nbye".into(),
+            tags: vec!["a \"b\"".into(), "\\".into()],
+        };
+        let options = SerializeOptions::new()
+            .pair_separator(",")
+            .quoting(Quoting::Doubled);
+
+        let output = to_output(&row, options.clone().build());
+        assert_eq!(
+            output,
+            "path=C:\\dir\\,text=\"say \"\"hi\"\"\nbye\",tags=[\"a \"\"b\"\"\",\\]"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Row>(&output, &options).unwrap(),
+            row
+        );
+    }
+
+    #[test]
+    fn serialize_shell_quoted() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Env {
+            plain: String,
+            spec: String,
+            text: String,
+        }
+
+        let env = Env {
+            plain: "/usr/bin".into(),
+            spec: "%i.service".into(),
+            text: "cost: $5\n\"ok\"".into(),
+        };
+
+        let output = to_output(
+            &env,
+            KeyValueSerializer::with_options(crate::Preset::SystemdEnvironment),
+        );
+        assert_eq!(
+            output,
+            "plain=/usr/bin\nspec=%i.service\ntext=\"cost: \\$5\n\\\"ok\\\"\""
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Env>(&output, crate::Preset::SystemdEnvironment).unwrap(),
+            env
+        );
+    }
+
+    #[test]
+    fn serialize_list_separator() {
+        #[derive(Serialize)]
+        struct Module {
+            irq: Vec<u8>,
+            io: Vec<u16>,
+            debug: bool,
+        }
+
+        let module = Module {
+            irq: vec![5, 7, 9],
+            io: vec![],
+            debug: true,
+        };
+
+        assert_eq!(
+            to_output(
+                &module,
+                KeyValueSerializer::with_options(crate::Preset::ModuleParams)
+            ),
+            "irq=5,7,9 io= debug=1"
+        );
+    }
+
+    #[test]
+    fn serialize_bare_struct_lists() {
+        #[derive(Serialize)]
+        struct Disk {
+            path: &'static str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            ro: Option<bool>,
+        }
+
+        #[derive(Serialize)]
+        struct Vm {
+            disks: Vec<Disk>,
+            tags: Vec<&'static str>,
+            groups: Vec<Vec<Disk>>,
+        }
+
+        let disk = |path, ro| Disk { path, ro };
+        let vm = Vm {
+            disks: vec![disk("/a", Some(true)), disk("/b", None)],
+            tags: vec!["x"],
+            groups: vec![vec![disk("/c", None)], vec![]],
+        };
+        let options = SerializeOptions::new().bare_struct_lists(true);
+        assert_eq!(
+            to_output(&vm, KeyValueSerializer::with_options(options.clone())),
+            "disks=[path=/a ro=True],[path=/b] tags=[x] groups=[[path=/c],[]]"
+        );
+        assert_eq!(
+            to_output(&vm, KeyValueSerializer::new()),
+            "disks=[[path=/a ro=True],[path=/b]] tags=[x] groups=[[[path=/c]],[]]"
+        );
+
+        #[derive(Serialize)]
+        struct Empty {}
+
+        assert_eq!(
+            to_output(
+                &[("e", vec![Empty {}, Empty {}])],
+                KeyValueSerializer::with_options(options)
+            ),
+            "e=[[],[]]"
+        );
+    }
+
+    #[test]
+    fn serialize_indexed_lists() {
+        #[derive(Serialize)]
+        struct Disk {
+            path: &'static str,
+            ro: bool,
+        }
+
+        #[derive(Serialize)]
+        struct Vm {
+            route: Vec<&'static str>,
+            disks: Vec<Disk>,
+            tags: Vec<&'static str>,
+            ports: Vec<(u16, u16)>,
+        }
+
+        let vm = Vm {
+            route: vec!["10.0.0.0/24", "192.168.0.0/16"],
+            disks: vec![Disk {
+                path: "/a",
+                ro: true,
+            }],
+            tags: Vec::new(),
+            ports: vec![(80, 8080)],
+        };
+        let options = SerializeOptions::new()
+            .nested_key_separator('_')
+            .indexed_lists(true);
+        assert_eq!(
+            to_output(&vm, KeyValueSerializer::with_options(options.clone())),
+            "route_0=10.0.0.0/24 route_1=192.168.0.0/16 disks_0_path=/a disks_0_ro=True \
+             ports_0=80:8080"
+        );
+        assert_eq!(
+            to_output(
+                &vm,
+                KeyValueSerializer::with_options(options.include_keys(&["disks_0_ro", "ports"]))
+            ),
+            "disks_0_ro=True ports_0=80:8080"
+        );
+        // Without a nested key separator, sequences keep their brackets.
+        assert_eq!(
+            to_output(
+                &[("a", vec![1, 2])],
+                KeyValueSerializer::with_options(SerializeOptions::new().indexed_lists(true))
+            ),
+            "a=[1,2]"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serialize_embedded_json() {
+        #[derive(Serialize)]
+        struct Mount {
+            path: &'static str,
+            ro: bool,
+        }
+
+        #[derive(Serialize)]
+        struct Job {
+            name: &'static str,
+            mounts: Vec<Mount>,
+            tags: Vec<&'static str>,
+            env: std::collections::BTreeMap<&'static str, &'static str>,
+        }
+
+        let job = Job {
+            name: "build",
+            mounts: vec![Mount {
+                path: "/a",
+                ro: true,
+            }],
+            tags: vec!["x", "y"],
+            env: [("HOME", "/root")].into(),
+        };
+        assert_eq!(
+            to_output(
+                &job,
+                KeyValueSerializer::with_options(SerializeOptions::new().embedded_json(true))
+            ),
+            r#"name=build mounts=[{"path":"/a","ro":true}] tags=[x,y] env={"HOME":"/root"}"#
+        );
+    }
+
+    #[test]
+    fn serialize_mount_options() {
+        #[derive(Serialize)]
+        struct Mount {
+            rw: bool,
+            noexec: bool,
+            size: String,
+            context: String,
+        }
+
+        let mount = Mount {
+            rw: true,
+            noexec: false,
+            size: "10M".into(),
+            context: "system_u:object_r:tmp_t:s0:c127,c456".into(),
+        };
+
+        assert_eq!(
+            to_output(
+                &mount,
+                KeyValueSerializer::with_options(crate::Preset::MountOptions)
+            ),
+            r#"rw,size=10M,context="system_u:object_r:tmp_t:s0:c127,c456""#
+        );
+    }
+
+    #[test]
+    fn serialize_qemu() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Cache {
+            direct: bool,
+            #[serde(rename = "no-flush")]
+            no_flush: bool,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Drive {
+            driver: String,
+            #[serde(rename = "node-name")]
+            node_name: String,
+            cache: Cache,
+            #[serde(rename = "read-only")]
+            read_only: bool,
+        }
+
+        let drive = Drive {
+            driver: "qcow2".into(),
+            node_name: "disk0".into(),
+            cache: Cache {
+                direct: true,
+                no_flush: false,
+            },
+            read_only: false,
+        };
+
+        let output = to_output(
+            &drive,
+            KeyValueSerializer::with_options(crate::Preset::Qemu),
+        );
+        assert_eq!(
+            output,
+            "qcow2,node-name=disk0,cache.direct=on,cache.no-flush=off,read-only=off"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Drive>(&output, crate::Preset::Qemu).unwrap(),
+            drive
+        );
+
+        // Without nested keys, nested structs are enclosed in brackets.
+        let output = to_output(
+            &drive,
+            KeyValueSerializer::with_options(SerializeOptions::new().pair_separator(",")),
+        );
+        assert_eq!(
+            output,
+            "driver=qcow2,node-name=disk0,cache=[direct=True,no-flush=False],read-only=False"
+        );
+        assert_eq!(crate::from_key_values::<Drive>(&output).unwrap(), drive);
+
+        // Drivers that would be read as a pair, or not at all, are rejected.
+        for (driver, shape) in [
+            ("file=a", "value without its key starting like a pair"),
+            ("", "empty value without its key"),
+        ] {
+            let drive = Drive {
+                driver: driver.into(),
+                node_name: "disk0".into(),
+                cache: Cache {
+                    direct: true,
+                    no_flush: false,
+                },
+                read_only: false,
+            };
+            let mut serializer = KeyValueSerializer::with_options(crate::Preset::Qemu);
+            let err = drive.serialize(&mut serializer).unwrap_err();
+            let key = "driver".into();
+            assert_eq!(err, SerializeError::Unsupported(Unsupported { shape, key }));
+        }
+
+        // Quoted drivers are read back as such.
+        let drive = Drive {
+            driver: "file=a".into(),
+            ..drive
+        };
+        let options = SerializeOptions::from(crate::Preset::Qemu).quoting(Quoting::WhenNeeded);
+        let output = to_output(&drive, options.clone().build());
+        assert!(output.starts_with(r#""file=a",node-name=disk0"#));
+        assert_eq!(
+            crate::from_key_values_with::<Drive>(&output, &options).unwrap(),
+            drive
+        );
+    }
+
+    #[test]
+    fn qemu_doubled_commas() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Device {
+            driver: String,
+            file: String,
+            id: String,
+        }
+
+        let device = Device {
+            driver: "virtio".into(),
+            file: "a,b,,c".into(),
+            id: "".into(),
+        };
+        let output = to_output(
+            &device,
+            KeyValueSerializer::with_options(crate::Preset::Qemu),
+        );
+        assert_eq!(output, "virtio,file=a,,b,,,,c,id=");
+        assert_eq!(
+            crate::from_key_values_with::<Device>(&output, crate::Preset::Qemu).unwrap(),
+            device
+        );
+
+        let device: Device =
+            crate::from_key_values_with("virtio,file=a,,b,id=x,,", crate::Preset::Qemu).unwrap();
+        assert_eq!(device.file, "a,b");
+        assert_eq!(device.id, "x,");
+        let device: Device =
+            crate::from_key_values_with("virtio,file=,,a,id=", crate::Preset::Qemu).unwrap();
+        assert_eq!(device.file, ",a");
+        let pairs: Vec<_> = crate::pairs_with("file=,,a,id=b", crate::Preset::Qemu)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            pairs,
+            [("file".into(), ",a".into()), ("id".into(), "b".into())]
+        );
+    }
+
+    #[test]
+    fn serialize_crosvm() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Size(u32, u32);
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Display {
+            path: String,
+            size: Size,
+            origin: (i32, i32),
+            hidden: bool,
+        }
+
+        let display = Display {
+            path: "/tmp/my display,1".into(),
+            size: Size(320, 200),
+            origin: (0, -1),
+            hidden: false,
+        };
+
+        let output = to_output(
+            &display,
+            KeyValueSerializer::with_options(crate::Preset::Crosvm),
+        );
+        assert_eq!(
+            output,
+            r#"path="/tmp/my display,1",size=[320,200],origin=[0,-1],hidden=false"#
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Display>(&output, crate::Preset::Crosvm).unwrap(),
+            display
+        );
+        // The default options of the deserializer accept the same syntax.
+        assert_eq!(crate::from_key_values::<Display>(&output).unwrap(), display);
+    }
+
+    #[test]
+    fn serialize_query_string() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Search {
+            q: String,
+            tags: Vec<String>,
+            #[serde(default)]
+            exclude: Vec<u32>,
+            page: u32,
+        }
+
+        let search = Search {
+            q: "a b&c/é".into(),
+            tags: vec!["x".into(), "y z".into()],
+            exclude: Vec::new(),
+            page: 2,
+        };
+
+        let output = to_output(
+            &search,
+            KeyValueSerializer::with_options(crate::Preset::QueryString),
+        );
+        assert_eq!(output, "q=a+b%26c%2F%C3%A9&tags=x&tags=y+z&page=2");
+        assert_eq!(
+            crate::from_key_values_with::<Search>(&output, crate::Preset::QueryString).unwrap(),
+            search
+        );
+    }
+
+    #[test]
+    fn serialize_http_parameters() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Disposition {
+            kind: String,
+            name: String,
+            filename: String,
+            size: u64,
+        }
+
+        let disposition = Disposition {
+            kind: "form-data".into(),
+            name: "user's_file".into(),
+            filename: r#"C:\my "docs"\a b.txt"#.into(),
+            size: 42,
+        };
+
+        let output = to_output(
+            &disposition,
+            KeyValueSerializer::with_options(crate::Preset::HttpParameters),
+        );
+        assert_eq!(
+            output,
+            r#"form-data; name=user's_file; filename="C:\\my \"docs\"\\a b.txt"; size=42"#
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Disposition>(&output, crate::Preset::HttpParameters)
+                .unwrap(),
+            disposition
+        );
+    }
+
+    #[test]
+    fn serialize_cookie() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct SetCookie {
+            #[serde(rename = "session")]
+            session: String,
+            expires: Option<String>,
+            #[serde(rename = "Max-Age")]
+            max_age: Option<u64>,
+            path: String,
+            #[serde(default)]
+            secure: bool,
+            #[serde(default)]
+            http_only: bool,
+        }
+
+        let cookie = SetCookie {
+            session: "a3fWa==".into(),
+            expires: Some("Wed, 21 Oct 2015 07:28:00 GMT".into()),
+            max_age: None,
+            path: "/".into(),
+            secure: true,
+            http_only: false,
+        };
+
+        let output = to_output(
+            &cookie,
+            KeyValueSerializer::with_options(crate::Preset::Cookie),
+        );
+        assert_eq!(
+            output,
+            "session=a3fWa==; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Path=/; Secure"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<SetCookie>(&output, crate::Preset::Cookie).unwrap(),
+            cookie
+        );
+
+        // Values that would end the pair, or be split by legacy parsers, are rejected.
+        let semicolon = "string containing `;` or a control character in a cookie";
+        let comma = "string containing `,` in a cookie";
+        for (session, path, shape, key) in [
+            ("a;b", "/", semicolon, "session"),
+            ("a", "/\n", semicolon, "Path"),
+            ("a,b", "/", comma, "session"),
+            ("a", "/a,b", comma, "Path"),
+        ] {
+            let cookie = SetCookie {
+                session: session.into(),
+                path: path.into(),
+                expires: cookie.expires.clone(),
+                ..cookie
+            };
+            let mut serializer = KeyValueSerializer::with_options(crate::Preset::Cookie);
+            assert!(cookie.serialize(&mut serializer).is_err());
+            let key = key.into();
+            assert_eq!(serializer.unsupported(), Some(&Unsupported { shape, key }));
+        }
+    }
+
+    #[test]
+    fn serialize_baggage() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Baggage {
+            user_id: String,
+            server_node: String,
+            sampled: bool,
+        }
+
+        let baggage = Baggage {
+            user_id: "alice+bob@example.com".into(),
+            server_node: "DF 28".into(),
+            sampled: true,
+        };
+
+        let output = to_output(
+            &baggage,
+            KeyValueSerializer::with_options(crate::Preset::Baggage),
+        );
+        assert_eq!(
+            output,
+            "userId=alice%2Bbob%40example.com,serverNode=DF%2028,sampled=true"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Baggage>(&output, crate::Preset::Baggage).unwrap(),
+            baggage
+        );
+    }
+
+    #[test]
+    fn serialize_influx_tags() {
+        let mut tags = std::collections::BTreeMap::new();
+        tags.insert("host name", "server 01");
+        tags.insert("path", "C:\\a=b");
+        tags.insert("region", "us,west");
+
+        let serializer = KeyValueSerializer::with_options(crate::Preset::InfluxTags);
+        let output = to_output(&tags, serializer);
+        assert_eq!(
+            output,
+            r"host\ name=server\ 01,path=C:\a\=b,region=us\,west"
+        );
+
+        // Backslashes that would escape the next character are escaped themselves.
+        let tags =
+            std::collections::BTreeMap::from([("a", r"dir\"), ("b", r"x\\,y"), ("c", r"\d")]);
+        let serializer = KeyValueSerializer::with_options(crate::Preset::InfluxTags);
+        let output = to_output(&tags, serializer);
+        assert_eq!(output, r"a=dir\\,b=x\\\\\,y,c=\d");
+        let parsed: std::collections::BTreeMap<&str, String> =
+            crate::from_key_values_with(&output, crate::Preset::InfluxTags).unwrap();
+        assert_eq!(
+            parsed,
+            tags.into_iter().map(|(k, v)| (k, v.into())).collect()
+        );
+
+        // Line protocol has no empty tag values.
+        let tags = std::collections::BTreeMap::from([("host", "a"), ("region", "")]);
+        let mut serializer = KeyValueSerializer::with_options(crate::Preset::InfluxTags);
+        assert!(tags.serialize(&mut serializer).is_err());
+        assert_eq!(
+            serializer.unsupported().unwrap().to_string(),
+            "value of `region`: empty string in an InfluxDB tag set cannot be serialized as \
+             key-values"
+        );
+    }
+
+    #[test]
+    fn serialize_odbc_connection_string() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Connection {
+            driver: String,
+            server: String,
+            #[serde(rename = "Initial Catalog")]
+            initial_catalog: String,
+            pwd: String,
+            trusted_connection: bool,
+        }
+
+        let connection = Connection {
+            driver: "SQL Server".into(),
+            server: "db,1433".into(),
+            initial_catalog: "sales".into(),
+            pwd: "a;b}c".into(),
+            trusted_connection: true,
+        };
+
+        let output = to_output(
+            &connection,
+            KeyValueSerializer::with_options(crate::Preset::OdbcConnectionString),
+        );
+        assert_eq!(
+            output,
+            "Driver=SQL Server;Server=db,1433;Initial Catalog=sales;Pwd={a;b}}c};\
+             TrustedConnection=yes"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Connection>(&output, crate::Preset::OdbcConnectionString)
+                .unwrap(),
+            connection
+        );
+    }
+
+    #[test]
+    fn serialize_distinguished_name() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "UPPERCASE")]
+        struct Subject {
+            cn: String,
+            ou: Vec<String>,
+            o: String,
+            c: String,
+        }
+
+        let subject = Subject {
+            cn: " #Doe, John ".into(),
+            ou: vec!["Eng".into(), "R+D".into()],
+            o: "Acme; \"Inc\"".into(),
+            c: "US".into(),
+        };
+
+        let output = to_output(
+            &subject,
+            KeyValueSerializer::with_options(crate::Preset::DistinguishedName),
+        );
+        assert_eq!(
+            output,
+            r#"CN=\ #Doe\, John\ ,OU=Eng,OU=R\+D,O=Acme\; \"Inc\",C=US"#
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Subject>(&output, crate::Preset::DistinguishedName)
+                .unwrap(),
+            subject
+        );
+    }
+
+    #[test]
+    fn serialize_ffmpeg_options() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Drawtext {
+            text: String,
+            fontsize: u32,
+            r#box: bool,
+        }
+
+        let drawtext = Drawtext {
+            text: " it's 12:30 ".into(),
+            fontsize: 24,
+            r#box: true,
+        };
+
+        let output = to_output(
+            &drawtext,
+            KeyValueSerializer::with_options(crate::Preset::FfmpegOptions),
+        );
+        assert_eq!(output, r"text=\ it\'s 12\:30\ :fontsize=24:box=1");
+        assert_eq!(
+            crate::from_key_values_with::<Drawtext>(&output, crate::Preset::FfmpegOptions).unwrap(),
+            drawtext
+        );
+
+        // Empty strings are quoted.
+        let drawtext = Drawtext {
+            text: "".into(),
+            ..drawtext
+        };
+        let serializer = KeyValueSerializer::with_options(crate::Preset::FfmpegOptions);
+        let output = to_output(&drawtext, serializer);
+        assert_eq!(output, "text='':fontsize=24:box=1");
+        assert_eq!(
+            crate::from_key_values_with::<Drawtext>(&output, crate::Preset::FfmpegOptions).unwrap(),
+            drawtext
+        );
+    }
+
+    #[test]
+    fn serialize_gst_element() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Element {
+            factory: String,
+            name: String,
+            caps: String,
+            do_timestamp: bool,
+            blocksize: u32,
+        }
+
+        let element = Element {
+            factory: "appsrc".into(),
+            name: "src".into(),
+            caps: "video/x-raw,width=640".into(),
+            do_timestamp: true,
+            blocksize: 4096,
+        };
+
+        let output = to_output(
+            &element,
+            KeyValueSerializer::with_options(crate::Preset::GstElement),
+        );
+        assert_eq!(
+            output,
+            r#"appsrc name=src caps="video/x-raw,width=640" do-timestamp=true blocksize=4096"#
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Element>(&output, crate::Preset::GstElement).unwrap(),
+            element
+        );
+    }
+
+    #[test]
+    fn serialize_sysctl() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Sysctl {
+            #[serde(rename = "net.ipv4.ip_forward")]
+            ip_forward: bool,
+            #[serde(rename = "net.ipv4.ip_local_port_range")]
+            port_range: (u16, u16),
+            #[serde(rename = "kernel.core_pattern")]
+            core_pattern: String,
+        }
+
+        let sysctl = Sysctl {
+            ip_forward: true,
+            port_range: (32768, 60999),
+            core_pattern: "|/usr/lib/core %p %e".into(),
+        };
+
+        let output = to_output(
+            &sysctl,
+            KeyValueSerializer::with_options(crate::Preset::Sysctl),
+        );
+        assert_eq!(
+            output,
+            "net.ipv4.ip_forward = 1\n\
+             net.ipv4.ip_local_port_range = 32768 60999\n\
+             kernel.core_pattern = |/usr/lib/core %p %e"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Sysctl>(&output, crate::Preset::Sysctl).unwrap(),
+            sysctl
+        );
+
+        // Values spanning lines would be read back as several pairs.
+        for core_pattern in ["core\nkernel.panic = 1", "core\r"] {
+            let sysctl = Sysctl {
+                core_pattern: core_pattern.into(),
+                ..sysctl
+            };
+            let mut serializer = KeyValueSerializer::with_options(crate::Preset::Sysctl);
+            assert!(sysctl.serialize(&mut serializer).is_err());
+            assert_eq!(
+                serializer.unsupported(),
+                Some(&Unsupported {
+                    shape: "string containing a line break",
+                    key: "kernel.core_pattern".into(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn serialize_unquoted_brackets() {
+        // Dialects without quotes cannot write brackets, which would start a list.
+        let values = std::collections::BTreeMap::from([("path", "/dev/[a]")]);
+        for preset in [
+            crate::Preset::Sysctl,
+            crate::Preset::Qemu,
+            crate::Preset::Cookie,
+        ] {
+            let mut serializer = KeyValueSerializer::with_options(preset);
+            assert!(values.serialize(&mut serializer).is_err());
+            assert_eq!(
+                serializer.unsupported(),
+                Some(&Unsupported {
+                    shape: "string containing a bracket without quotes",
+                    key: "path".into(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn serialize_headers() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Trailers {
+            #[serde(rename = "Signed-off-by")]
+            signed_off_by: Vec<String>,
+            #[serde(rename = "Fixes")]
+            fixes: String,
+            #[serde(rename = "X-Note")]
+            note: String,
+        }
+
+        let trailers = Trailers {
+            signed_off_by: vec!["Alice <alice@example.com>".into(), "Bob <bob@b.org>".into()],
+            fixes: "[1234] crash".into(),
+            note: "first\nsecond".into(),
+        };
+
+        let output = to_output(
+            &trailers,
+            KeyValueSerializer::with_options(crate::Preset::Headers),
+        );
+        assert_eq!(
+            output,
+            "Signed-off-by: Alice <alice@example.com>\n\
+             Signed-off-by: Bob <bob@b.org>\n\
+             Fixes: [1234] crash\n\
+             X-Note: first\n second"
+        );
+        // Folded line breaks are not kept.
+        let parsed =
+            crate::from_key_values_with::<Trailers>(&output, crate::Preset::Headers).unwrap();
+        assert_eq!(parsed.signed_off_by, trailers.signed_off_by);
+        assert_eq!(parsed.fixes, trailers.fixes);
+        assert_eq!(parsed.note, "first second");
+    }
+
+    #[test]
+    fn serialize_shell_export() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct Env {
+            name: String,
+            command: String,
+            workers: u32,
+        }
+
+        let env = Env {
+            name: "it's mine".into(),
+            command: "$(rm -rf /); `id`".into(),
+            workers: 4,
+        };
+
+        let output = to_output(
+            &env,
+            KeyValueSerializer::with_options(crate::Preset::ShellExport),
+        );
+        assert_eq!(
+            output,
+            "export NAME='it'\\''s mine'\n\
+             export COMMAND='$(rm -rf /); `id`'\n\
+             export WORKERS=4"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Env>(&output, crate::Preset::ShellExport).unwrap(),
+            env
+        );
+    }
+
+    #[test]
+    fn serialize_ini_sections() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Config {
+            name: String,
+            server: Server,
+            debug: bool,
+            env: std::collections::BTreeMap<String, String>,
+        }
+
+        let config = Config {
+            name: "my app".into(),
+            server: Server {
+                host: "localhost".into(),
+                port: 8080,
+            },
+            debug: true,
+            env: [("LANG".to_string(), "C".to_string())].into(),
+        };
+
+        let output = to_output(
+            &config,
+            KeyValueSerializer::with_options(crate::Preset::Ini),
+        );
+        assert_eq!(
+            output,
+            "name=\"my app\"\n\
+             debug=true\n\
+             \n\
+             [server]\n\
+             host=localhost\n\
+             port=8080\n\
+             \n\
+             [env]\n\
+             LANG=C"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Config>(&output, crate::Preset::Ini).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn serialize_uuid() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Record {
+            id: uuid::Uuid,
+        }
+
+        let record = Record {
+            id: "{67E55044-10B1-426F-9247-BB680E5FE0C8}".parse().unwrap(),
+        };
+        let output = to_output(&record, KeyValueSerializer::new());
+        assert_eq!(output, "id=67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!(crate::from_key_values::<Record>(&output).unwrap(), record);
+    }
+
+    #[test]
+    fn serialize_postgres_dsn() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Dsn {
+            host: String,
+            port: u16,
+            password: String,
+            options: String,
+            application_name: String,
+        }
+
+        let dsn = Dsn {
+            host: "db".into(),
+            port: 5432,
+            password: r"it's a\b".into(),
+            options: "-c geqo=off".into(),
+            application_name: "".into(),
+        };
+
+        let output = to_output(
+            &dsn,
+            KeyValueSerializer::with_options(crate::Preset::PostgresDsn),
+        );
+        assert_eq!(
+            output,
+            r"host=db port=5432 password='it\'s a\\b' options='-c geqo=off' application_name=''"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Dsn>(&output, crate::Preset::PostgresDsn).unwrap(),
+            dsn
+        );
+    }
+
+    #[test]
+    fn serialize_bool_style() {
+        #[derive(Serialize)]
+        struct Flags {
+            ro: bool,
+            sparse: bool,
+            direct: bool,
+        }
+
+        let flags = Flags {
+            ro: true,
+            sparse: false,
+            direct: true,
+        };
+        let with_style = |style| SerializeOptions::new().bool_style(style).build();
+
+        assert_eq!(
+            to_output(&flags, KeyValueSerializer::new()),
+            "ro=True sparse=False direct=True"
+        );
+        assert_eq!(
+            to_output(&flags, with_style(BoolStyle::Lowercase)),
+            "ro=true sparse=false direct=true"
+        );
+        assert_eq!(
+            to_output(&flags, with_style(BoolStyle::OnOff)),
+            "ro=on sparse=off direct=on"
+        );
+        assert_eq!(
+            to_output(&flags, with_style(BoolStyle::YesNo)),
+            "ro=yes sparse=no direct=yes"
+        );
+        assert_eq!(
+            to_output(&flags, with_style(BoolStyle::Numeric)),
+            "ro=1 sparse=0 direct=1"
+        );
+        assert_eq!(to_output(&flags, with_style(BoolStyle::Flag)), "ro direct");
+    }
+
+    #[test]
+    fn serialize_unit_flags() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct FeatureX;
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Features {
+            feature_x: FeatureX,
+            legacy: (),
+            level: u8,
+        }
+
+        let features = Features {
+            feature_x: FeatureX,
+            legacy: (),
+            level: 2,
+        };
+        let output = to_output(&features, KeyValueSerializer::new());
+        assert_eq!(output, "feature_x legacy level=2");
+        let options = SerializeOptions::new();
+        assert_eq!(
+            crate::from_key_values_with::<Features>(&output, &options).unwrap(),
+            features
+        );
+
+        let map = std::collections::BTreeMap::from([("a", ()), ("b", ())]);
+        let output = to_output(&map, options.clone().pair_separator(",").build());
+        assert_eq!(output, "a,b");
+        assert_eq!(
+            crate::from_key_values::<std::collections::BTreeMap<&str, ()>>(&output).unwrap(),
+            map
+        );
+    }
+
+    #[test]
+    fn serialize_newline_style() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Event {
+            level: String,
+            trace: String,
+        }
+
+        let event = Event {
+            level: "error".into(),
+            trace: "panicked at \"x\"\r\n  at main\n".into(),
+        };
+        let options = SerializeOptions::from(crate::Preset::Dotenv);
+
+        let output = to_output(&event, options.clone().build());
+        assert_eq!(
+            output,
+            "level=error\ntrace=\"panicked at \\\"x\\\"\\r\\n  at main\\n\""
+        );
+
+        let options = options.newline_style(NewlineStyle::Literal);
+        let output = to_output(&event, options.clone().build());
+        assert_eq!(
+            output,
+            "level=error\ntrace=\"panicked at \\\"x\\\"\r\n  at main\n\""
+        );
+        for preset in [crate::Preset::Dotenv, crate::Preset::SystemdEnvironment] {
+            assert_eq!(
+                crate::from_key_values_with::<Event>(&output, preset).unwrap(),
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn serialize_dotenv() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Env {
+            #[serde(rename = "NAME")]
+            name: String,
+            #[serde(rename = "EMPTY")]
+            empty: String,
+        }
+
+        let env = Env {
+            name: "my app".into(),
+            empty: "".into(),
+        };
+        let serializer = KeyValueSerializer::with_options(crate::Preset::Dotenv);
+        let output = to_output(&env, serializer);
+        assert_eq!(output, "NAME=\"my app\"\nEMPTY=");
+        assert_eq!(
+            crate::from_key_values_with::<Env>(&output, crate::Preset::Dotenv).unwrap(),
+            env
+        );
+    }
+
+    #[test]
+    fn serialize_none_style() {
+        #[derive(Serialize)]
+        struct Disk {
+            path: Option<String>,
+            size: Option<u64>,
+            ro: bool,
+        }
+
+        let disk = Disk {
+            path: None,
+            size: Some(1024),
+            ro: false,
+        };
+        let with_style = |style| SerializeOptions::new().none_style(style).build();
+
+        assert_eq!(
+            to_output(&disk, KeyValueSerializer::new()),
+            "size=1024 ro=False"
+        );
+        assert_eq!(
+            to_output(&disk, with_style(NoneStyle::Empty)),
+            "path= size=1024 ro=False"
+        );
+
+        let mut serializer = with_style(NoneStyle::Error);
+        disk.serialize(&mut serializer).unwrap_err();
+    }
+
+    #[test]
+    fn serialize_adjacently_tagged_enum() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(tag = "type", content = "args", rename_all = "lowercase")]
+        enum Transport {
+            Tcp { host: String, port: u16 },
+            Unix(String),
+            Pair(u8, u8),
+            Stdio,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Config {
+            name: String,
+            transport: Transport,
+        }
+
+        let options = SerializeOptions::new()
+            .pair_separator(",")
+            .quoting(Quoting::WhenNeeded);
+        for (transport, output) in [
+            (
+                Transport::Tcp {
+                    host: "db 1".into(),
+                    port: 5432,
+                },
+                r#"type=tcp,args=[host="db 1",port=5432]"#,
+            ),
+            (Transport::Unix("/run/s".into()), "type=unix,args=/run/s"),
+            (Transport::Pair(1, 2), "type=pair,args=1:2"),
+            (Transport::Stdio, "type=stdio"),
+        ] {
+            assert_eq!(to_output(&transport, options.clone().build()), output);
+            assert_eq!(
+                crate::from_key_values_with::<Transport>(output, &options).unwrap(),
+                transport
+            );
+        }
+
+        // Nested in a struct, the pairs of the enum are enclosed in brackets as well.
+        let config = Config {
+            name: "app".into(),
+            transport: Transport::Tcp {
+                host: "db".into(),
+                port: 5432,
+            },
+        };
+        let output = to_output(&config, options.clone().build());
+        assert_eq!(
+            output,
+            "name=app,transport=[type=tcp,args=[host=db,port=5432]]"
+        );
+        assert_eq!(
+            crate::from_key_values_with::<Config>(&output, &options).unwrap(),
+            config
+        );
+
+        // The content may come first.
+        assert_eq!(
+            crate::from_key_values::<Transport>("args=[port=1,host=a],type=tcp").unwrap(),
+            Transport::Tcp {
+                host: "a".into(),
+                port: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn serialize_top_level() {
+        #[derive(Serialize)]
+        struct Disk {
+            path: &'static str,
+            ro: bool,
+        }
+
+        #[derive(Serialize)]
+        struct Wrapper<T>(T);
+
+        #[derive(Serialize)]
+        struct Label(&'static str, u32);
+
+        let map = std::collections::BTreeMap::from([("b", 2), ("a", 1)]);
+        assert_eq!(to_output(&map, KeyValueSerializer::new()), "a=1 b=2");
+        assert_eq!(
+            to_output(&Wrapper(&map), KeyValueSerializer::new()),
+            "a=1 b=2"
+        );
+        assert_eq!(
+            to_output(
+                &Wrapper(Disk {
+                    path: "/dev/vda",
+                    ro: true
+                }),
+                KeyValueSerializer::new()
+            ),
+            "path=/dev/vda ro=True"
+        );
+
+        let pairs = vec![("b", vec![1, 2]), ("a", vec![]), ("b", vec![3])];
+        assert_eq!(
+            to_output(&pairs, KeyValueSerializer::new()),
+            "b=[1,2] a=[] b=[3]"
+        );
+        assert_eq!(
+            to_output(
+                &Wrapper([Label("x", 1), Label("y", 2)]),
+                KeyValueSerializer::builder().pair_separator(",").build()
+            ),
+            "x=1,y=2"
+        );
+        assert_eq!(
+            to_output(&Vec::<(String, u32)>::new(), KeyValueSerializer::new()),
+            ""
+        );
+
+        let mut serializer = KeyValueSerializer::new();
+        assert!([("a", 1, 2)].serialize(&mut serializer).is_err());
+        assert_eq!(
+            serializer.unsupported().unwrap().shape,
+            "sequence element other than a key-value pair"
+        );
+        assert!([1, 2].serialize(&mut KeyValueSerializer::new()).is_err());
+    }
+
+    #[test]
+    fn serialize_key_filters() {
+        #[derive(Serialize)]
+        struct Cache {
+            direct: bool,
+            size: u32,
+        }
+
+        #[derive(Serialize)]
+        struct Disk {
+            path: &'static str,
+            cache: Cache,
+            ro: bool,
+            id: Option<u32>,
+        }
+
+        let disk = Disk {
+            path: "/dev/vda",
+            cache: Cache {
+                direct: true,
+                size: 64,
+            },
+            ro: false,
+            id: None,
+        };
+        let output = |options: SerializeOptions| to_output(&disk, options.build());
+
+        let options = SerializeOptions::new();
+        assert_eq!(
+            output(options.clone().include_keys(&["ro", "cache.size", "cache"])),
+            "cache=[direct=True size=64] ro=False"
+        );
+        assert_eq!(
+            output(options.clone().exclude_keys(&["path", "ro", "cache.size"])),
+            "cache=[direct=True size=64]"
+        );
+        assert_eq!(output(options.clone().include_keys(&[])), "");
+
+        let options = options.nested_key_separator('.');
+        assert_eq!(
+            output(options.clone().include_keys(&["cache.size", "path"])),
+            "path=/dev/vda cache.size=64"
+        );
+        assert_eq!(
+            output(
+                options
+                    .clone()
+                    .include_keys(&["cache"])
+                    .exclude_keys(&["cache.direct"])
+            ),
+            "cache.size=64"
+        );
+        assert_eq!(
+            output(options.clone().exclude_keys(&["cach", "path"])),
+            "cache.direct=True cache.size=64 ro=False"
+        );
+
+        let options = SerializeOptions::new().sections(true);
+        assert_eq!(
+            output(options.clone().include_keys(&["cache", "ro"])),
+            "ro=False  [cache] direct=True size=64"
+        );
+        assert_eq!(
+            output(options.exclude_keys(&["cache"])),
+            "path=/dev/vda ro=False"
+        );
+
+        let map = std::collections::BTreeMap::from([("a", 1), ("b", 2), ("c", 3)]);
+        assert_eq!(
+            to_output(
+                &map,
+                SerializeOptions::new().exclude_keys(&["a", "c"]).build()
+            ),
+            "b=2"
+        );
+    }
+
+    #[test]
+    fn serialize_unsupported() {
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        enum Shape {
+            Point(u32, u32),
+            Rect { w: u32, h: u32 },
+        }
+
+        #[derive(Serialize)]
+        struct Bytes<'a> {
+            #[serde(with = "serde_bytes_ref")]
+            data: &'a [u8],
+        }
+
+        mod serde_bytes_ref {
+            pub fn serialize<S: serde::Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+                s.serialize_bytes(v)
+            }
+        }
+
+        let mut serializer = KeyValueSerializer::new();
+        assert!(Shape::Point(1, 2).serialize(&mut serializer).is_err());
+        assert_eq!(
+            serializer.unsupported(),
+            Some(&Unsupported {
+                shape: "tuple variant",
+                key: String::new(),
+            })
+        );
+        serializer.clear();
+        assert_eq!(serializer.unsupported(), None);
+        assert!(Shape::Rect { w: 1, h: 2 }
+            .serialize(&mut serializer)
+            .is_err());
+        assert_eq!(serializer.unsupported().unwrap().shape, "struct variant");
+
+        let mut serializer = KeyValueSerializer::new();
+        assert!(Bytes { data: b"abc" }.serialize(&mut serializer).is_err());
+        assert_eq!(serializer.unsupported().unwrap().shape, "bytes");
+
+        // Unsupported keys are reported as well.
+        let mut serializer = KeyValueSerializer::new();
+        let map = std::collections::BTreeMap::from([(Shape::Point(1, 2), 1)]);
+        assert!(map.serialize(&mut serializer).is_err());
+        assert_eq!(serializer.unsupported().unwrap().shape, "tuple variant");
+
+        // Values rejected by the options are reported along with the option.
+        #[derive(Serialize)]
+        struct Labels {
+            name: Option<String>,
+            labels: std::collections::BTreeMap<&'static str, u32>,
+        }
+        let labels = Labels {
+            name: Some("a".into()),
+            labels: std::collections::BTreeMap::from([("x", 1)]),
+        };
+        let mut serializer = KeyValueSerializer::new();
+        assert!(labels.serialize(&mut serializer).is_err());
+        assert_eq!(
+            serializer.unsupported().unwrap().to_string(),
+            "nested map without a nested key separator cannot be serialized as key-values"
+        );
+        let options = SerializeOptions::new().none_style(NoneStyle::Error);
+        let mut serializer = KeyValueSerializer::with_options(options);
+        let labels = Labels {
+            name: None,
+            labels: Default::default(),
+        };
+        assert!(labels.serialize(&mut serializer).is_err());
+        assert_eq!(
+            serializer.unsupported().unwrap().shape,
+            "`None` with `NoneStyle::Error`"
+        );
+    }
+
+    #[test]
+    fn serialize_byte_budget() {
+        #[derive(Serialize)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Serialize)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        let config = Config {
+            name: "app".into(),
+            server: Server {
+                host: "localhost".into(),
+                port: 8080,
+            },
+        };
+        let serialize = |options: SerializeOptions| {
+            let mut serializer = options.build();
+            let res = config.serialize(&mut serializer);
+            (res, serializer.budget_exceeded().cloned())
+        };
+
+        // Nested pairs are checked one by one.
+        let options = SerializeOptions::new().nested_key_separator('.');
+        assert_eq!(serialize(options.clone().byte_budget(47)), (Ok(()), None));
+        assert_eq!(
+            serialize(options.byte_budget(46)),
+            (
+                Err(SerializeError::BudgetExceeded(BudgetExceeded {
+                    key: "server.port".into(),
+                    budget: 46,
+                })),
+                Some(BudgetExceeded {
+                    key: "server.port".into(),
+                    budget: 46,
+                })
+            )
+        );
+
+        // Sections count, along with the separators written before them.
+        let options = SerializeOptions::from(crate::Preset::Ini);
+        assert_eq!(serialize(options.clone().byte_budget(43)), (Ok(()), None));
+        let (res, exceeded) = serialize(options.byte_budget(42));
+        assert_eq!(
+            res,
+            Err(SerializeError::BudgetExceeded(exceeded.clone().unwrap()))
+        );
+        assert_eq!(exceeded.unwrap().key, "port");
+
+        // Clearing the serializer forgets the error.
+        let mut serializer = SerializeOptions::new().byte_budget(4).build();
+        config.serialize(&mut serializer).unwrap_err();
+        assert_eq!(serializer.budget_exceeded().unwrap().key, "name");
+        serializer.clear();
+        assert_eq!(serializer.budget_exceeded(), None);
+    }
+
+    #[test]
+    fn serialize_max_len() {
+        #[derive(Serialize)]
+        struct Cache {
+            direct: bool,
+            size: u32,
+        }
+
+        #[derive(Serialize)]
+        struct Drive {
+            file: String,
+            cache: Cache,
+            tags: Vec<&'static str>,
+        }
+
+        let drive = Drive {
+            file: "disk.img".into(),
+            cache: Cache {
+                direct: true,
+                size: 64,
+            },
+            tags: vec!["a", "b"],
+        };
+        let with_max_len = |max_len| {
+            SerializeOptions::new()
+                .pair_separator(",")
+                .nested_key_separator('.')
+                .repeated_keys(true)
+                .max_len(max_len)
+                .build()
+        };
+
+        // Nested and repeated pairs are truncated one by one.
+        let output = "file=disk.img,cache.direct=True,cache.size=64,tags=a,tags=b";
+        assert_eq!(to_output(&drive, with_max_len(output.len())), output);
+        assert_eq!(
+            to_output(&drive, with_max_len(output.len() - 1)),
+            "file=disk.img,cache.direct=True,…truncated=3"
+        );
+        assert_eq!(
+            to_output(&drive, with_max_len(45)),
+            "file=disk.img,…truncated=4"
+        );
+        // The marker is kept even if it does not fit.
+        assert_eq!(to_output(&drive, with_max_len(4)), "…truncated=5");
+
+        // Sections are dropped as a whole.
+        let options = SerializeOptions::from(crate::Preset::Ini).max_len(40);
+        let output = to_output(&drive, options.build());
+        assert_eq!(output, "file=disk.img\ntags=[a,b]\n…truncated=1");
+
+        // The serializer can be reused.
+        let mut serializer = with_max_len(20);
+        drive.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.output(), "…truncated=5");
+        serializer.clear();
+        Cache {
+            direct: false,
+            size: 1,
+        }
+        .serialize(&mut serializer)
+        .unwrap();
+        assert_eq!(serializer.output(), "direct=False,size=1");
+    }
+
+    #[test]
+    fn serialize_clear() {
+        struct Panics;
+
+        impl Serialize for Panics {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                panic!("cannot serialize");
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Item {
+            a: u32,
+        }
+
+        #[derive(Serialize)]
+        struct List<T> {
+            items: Vec<T>,
+        }
+
+        let options = SerializeOptions::new().bare_struct_lists(true);
+        let list = List {
+            items: vec![Item { a: 1 }, Item { a: 2 }],
+        };
+        let expected = to_output(&list, options.clone().build());
+
+        // A serializer interrupted within an element of a sequence is left in its middle.
+        let mut serializer = options.build();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            List {
+                items: vec![Panics],
+            }
+            .serialize(&mut serializer)
+        }));
+        assert!(res.is_err());
+        assert!(serializer.element.is_some());
+
+        serializer.clear();
+        assert_eq!(serializer.element, None);
+        list.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.output(), expected);
+    }
+
+    #[test]
+    fn serialize_separators() {
+        #[derive(Serialize)]
+        struct Window {
+            size: (u16, u16),
+            title: String,
+        }
+
+        let window = Window {
+            size: (640, 480),
+            title: "main".into(),
+        };
+        let serializer = SerializeOptions::new()
+            .pair_separator(", ")
+            .key_value_delimiter(':')
+            .tuple_separator('x')
+            .build();
+
+        assert_eq!(to_output(&window, serializer), "size:640x480, title:main");
+    }
+
+    #[test]
+    fn serialize_with_formatter() {
+        use std::borrow::Cow;
+
+        struct Custom;
+
+        impl ValueFormatter for Custom {
+            fn format_bool(&self, v: bool, _style: BoolStyle) -> Cow<'static, str> {
+                Cow::Borrowed(if v { "enabled" } else { "disabled" })
+            }
+
+            fn format_u64(&self, v: u64) -> String {
+                format!("{}K", v / 1024)
+            }
+
+            fn format_str<'a>(&self, v: &'a str) -> Cow<'a, str> {
+                Cow::Owned(v.to_uppercase())
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Disk {
+            name: String,
+            size: u64,
+            ro: bool,
+            offset: i32,
+        }
+
+        let disk = Disk {
+            name: "root disk".into(),
+            size: 4096,
+            ro: false,
+            offset: -2,
+        };
+
+        assert_eq!(
+            to_output(
+                &disk,
+                SerializeOptions::new()
+                    .quoting(Quoting::WhenNeeded)
+                    .build()
+                    .formatter(Custom)
+            ),
+            r#"name="ROOT DISK" size=4K ro=disabled offset=-2"#
+        );
+    }
+
+    #[test]
+    fn transcode_from_json() {
+        let mut deserializer = serde_json::Deserializer::from_str(
+            r#"{"mtu": 9000, "tap": "tap 0", "queues": [1, 2], "vhost": true}"#,
+        );
+        let mut serializer = SerializeOptions::new().quoting(Quoting::WhenNeeded).build();
+        serde_transcode::transcode(&mut deserializer, &mut serializer).unwrap();
+
+        assert_eq!(
+            serializer.into_output(),
+            r#"mtu=9000 tap="tap 0" queues=[1,2] vhost=True"#
+        );
     }
 }
@@ -0,0 +1,169 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Sizes in bytes with unit suffixes, such as `4GiB`.
+
+use core::fmt;
+
+use serde::de::Error;
+use serde::de::Unexpected;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+/// Binary units, from the largest to the smallest, with their size in bytes.
+const BINARY_UNITS: [(&str, u64); 6] = [
+    ("E", 1 << 60),
+    ("P", 1 << 50),
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+];
+
+/// A size in bytes, written with the largest binary unit that represents it exactly, e.g. `4GiB`.
+///
+/// When deserializing, the number of bytes may be followed by a unit: `B` for bytes, `K`, `M`,
+/// `G`, `T`, `P` or `E` optionally followed by `iB` for powers of 1024, or `KB`, `MB`, `GB`, `TB`,
+/// `PB` or `EB` for powers of 1000. Units are case-insensitive.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_keyvalue::{from_key_values, ByteSize, KeyValueSerializer};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Vm {
+///     mem: ByteSize,
+///     disk: ByteSize,
+/// }
+///
+/// let vm: Vm = from_key_values("mem=4G,disk=10GB").unwrap();
+/// assert_eq!(vm, Vm { mem: ByteSize(4 << 30), disk: ByteSize(10_000_000_000) });
+///
+/// let mut serializer = KeyValueSerializer::new();
+/// vm.serialize(&mut serializer).unwrap();
+/// assert_eq!(serializer.into_output(), "mem=4GiB disk=9765625KiB");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// Parses a size with an optional unit, or returns `None` if `s` is not valid.
+    fn parse(s: &str) -> Option<Self> {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let value: u64 = s[..digits].parse().ok()?;
+
+        let unit = s[digits..].to_ascii_uppercase();
+        let multiplier = match unit.as_str() {
+            "" | "B" => 1,
+            unit => {
+                let prefix = unit.get(..1)?;
+                let exponent =
+                    BINARY_UNITS.len() - BINARY_UNITS.iter().position(|(p, _)| *p == prefix)?;
+                match &unit[1..] {
+                    "" | "IB" => 1024u64.pow(exponent as u32),
+                    "B" => 1000u64.pow(exponent as u32),
+                    _ => return None,
+                }
+            }
+        };
+
+        value.checked_mul(multiplier).map(ByteSize)
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (prefix, size) in BINARY_UNITS {
+            if self.0 != 0 && self.0.is_multiple_of(size) {
+                return write!(f, "{}{}iB", self.0 / size, prefix);
+            }
+        }
+
+        write!(f, "{}B", self.0)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteSizeVisitor;
+
+        impl<'de> Visitor<'de> for ByteSizeVisitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a size such as `4096` or `4GiB`")
+            }
+
+            fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(ByteSize(v))
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                ByteSize::parse(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(ByteSizeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_key_values;
+    use crate::ErrorKind;
+
+    #[test]
+    fn byte_size_display() {
+        assert_eq!(ByteSize(0).to_string(), "0B");
+        assert_eq!(ByteSize(512).to_string(), "512B");
+        assert_eq!(ByteSize(1536).to_string(), "1536B");
+        assert_eq!(ByteSize(3 << 20).to_string(), "3MiB");
+        assert_eq!(ByteSize(1 << 62).to_string(), "4EiB");
+    }
+
+    #[test]
+    fn byte_size_parse() {
+        assert_eq!(ByteSize::parse("4096"), Some(ByteSize(4096)));
+        assert_eq!(ByteSize::parse("12b"), Some(ByteSize(12)));
+        assert_eq!(ByteSize::parse("4k"), Some(ByteSize(4096)));
+        assert_eq!(ByteSize::parse("4KiB"), Some(ByteSize(4096)));
+        assert_eq!(ByteSize::parse("4kB"), Some(ByteSize(4000)));
+        assert_eq!(ByteSize::parse("2T"), Some(ByteSize(2 << 40)));
+
+        assert_eq!(ByteSize::parse(""), None);
+        assert_eq!(ByteSize::parse("G"), None);
+        assert_eq!(ByteSize::parse("1.5G"), None);
+        assert_eq!(ByteSize::parse("4 G"), None);
+        assert_eq!(ByteSize::parse("4GiBs"), None);
+        assert_eq!(ByteSize::parse("4X"), None);
+        assert_eq!(ByteSize::parse("4µB"), None);
+        assert_eq!(ByteSize::parse("16E"), None);
+    }
+
+    #[test]
+    fn byte_size_errors() {
+        #[derive(Debug, Deserialize)]
+        struct Vm {
+            #[allow(dead_code)]
+            mem: ByteSize,
+        }
+
+        assert_eq!(
+            from_key_values::<Vm>("mem=4Q").unwrap_err().kind,
+            ErrorKind::SerdeError(
+                "invalid value: string \"4Q\", expected a size such as `4096` or `4GiB`".into()
+            )
+        );
+    }
+}
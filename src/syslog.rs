@@ -0,0 +1,254 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Structured data of syslog messages.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::from_key_values_with;
+use crate::options::Preset;
+use crate::ErrorKind;
+use crate::KeyValueSerializer;
+use crate::ParseError;
+use crate::SerializeError;
+
+/// Deserializes `T` from the parameters of an SD-ELEMENT of a syslog message as defined by
+/// RFC 5424, e.g. `[exampleSDID@32473 iut="3" eventSource="App"]`, and returns it along with the
+/// SD-ID of the element, e.g. `exampleSDID@32473`.
+///
+/// Values must be double-quoted, with `"`, `\` and `]` escaped with a backslash. Numbers and
+/// booleans are parsed from the quoted values. See [`Preset::SyslogStructuredData`] for details.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::from_sd_element;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct Example {
+///     iut: u32,
+///     event_source: String,
+/// }
+///
+/// let (id, example): (_, Example) =
+///     from_sd_element(r#"[exampleSDID@32473 iut="3" eventSource="App\]"]"#).unwrap();
+/// assert_eq!(id, "exampleSDID@32473");
+/// assert_eq!(example, Example { iut: 3, event_source: "App]".into() });
+/// ```
+pub fn from_sd_element<'a, T: Deserialize<'a>>(input: &'a str) -> Result<(&'a str, T), ParseError> {
+    let error = |kind, pos| ParseError { kind, pos };
+
+    let content = input
+        .strip_prefix('[')
+        .ok_or_else(|| error(ErrorKind::ExpectedOpenBracket, 0))?;
+    let len =
+        element_len(content).ok_or_else(|| error(ErrorKind::ExpectedCloseBracket, input.len()))?;
+    if len + 2 < input.len() {
+        return Err(error(ErrorKind::TrailingCharacters, len + 2));
+    }
+
+    let content = &content[..len];
+    let (id, params) = content.split_once(' ').unwrap_or((content, ""));
+    if id.is_empty() || id.contains(['=', '"']) {
+        return Err(error(ErrorKind::ExpectedIdentifier, 1));
+    }
+
+    // Report errors relative to the start of `input`, i.e. past the SD-ID and its space.
+    let offset = 1 + id.len() + 1;
+    let value =
+        from_key_values_with(params, Preset::SyslogStructuredData).map_err(|e| ParseError {
+            pos: e.pos + offset,
+            ..e
+        })?;
+
+    Ok((id, value))
+}
+
+/// Serializes `value` as an SD-ELEMENT of a syslog message whose SD-ID is `id`, e.g.
+/// `[exampleSDID@32473 iut="3" eventSource="App"]`, which can be parsed back with
+/// [`from_sd_element`].
+///
+/// All values, including numbers and booleans, are double-quoted, and `"`, `\` and `]` are
+/// escaped. `id` is written as-is and must not contain spaces, `=`, `]` or `"`.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_sd_element;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct Origin {
+///     ip: String,
+///     software: String,
+/// }
+///
+/// let origin = Origin { ip: "192.0.2.1".into(), software: "a\"b\"".into() };
+/// assert_eq!(
+///     to_sd_element("origin", &origin).unwrap(),
+///     r#"[origin ip="192.0.2.1" software="a\"b\""]"#
+/// );
+/// ```
+pub fn to_sd_element<T: Serialize + ?Sized>(id: &str, value: &T) -> Result<String, SerializeError> {
+    if id.is_empty() || id.contains([' ', '=', ']', '"']) {
+        return Err(SerializeError::Custom(format!("invalid SD-ID `{}`", id)));
+    }
+
+    let mut serializer = KeyValueSerializer::with_options(Preset::SyslogStructuredData);
+    value.serialize(&mut serializer)?;
+    let params = serializer.into_output();
+
+    let mut element = String::with_capacity(id.len() + params.len() + 3);
+    element.push('[');
+    element.push_str(id);
+    if !params.is_empty() {
+        element.push(' ');
+        element.push_str(&params);
+    }
+    element.push(']');
+    Ok(element)
+}
+
+/// Splits the STRUCTURED-DATA field of a syslog message into its SD-ELEMENTs, e.g.
+/// `[a x="1"][b y="]"]` into `[a x="1"]` and `[b y="]"]`, which can then be parsed with
+/// [`from_sd_element`]. The `-` NILVALUE stands for no element.
+///
+/// ```
+/// use serde_keyvalue::sd_elements;
+///
+/// let elements = sd_elements(r#"[timeQuality tzKnown="1"][origin ip="192.0.2.1"]"#).unwrap();
+/// assert_eq!(elements, [r#"[timeQuality tzKnown="1"]"#, r#"[origin ip="192.0.2.1"]"#]);
+/// assert!(sd_elements("-").unwrap().is_empty());
+/// ```
+pub fn sd_elements(input: &str) -> Result<Vec<&str>, ParseError> {
+    let mut elements = Vec::new();
+    if input == "-" {
+        return Ok(elements);
+    }
+
+    let mut pos = 0;
+    while pos < input.len() {
+        let content = input[pos..].strip_prefix('[').ok_or(ParseError {
+            kind: ErrorKind::ExpectedOpenBracket,
+            pos,
+        })?;
+        let len = element_len(content).ok_or(ParseError {
+            kind: ErrorKind::ExpectedCloseBracket,
+            pos: input.len(),
+        })?;
+        elements.push(&input[pos..pos + len + 2]);
+        pos += len + 2;
+    }
+
+    Ok(elements)
+}
+
+/// Returns the length of the content of the SD-ELEMENT starting at `s`, past its opening bracket,
+/// up to its first closing bracket that is not within a quoted value.
+fn element_len(s: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ']' if !quoted => return Some(i),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Meta {
+        #[serde(rename = "sequenceId")]
+        sequence_id: u32,
+        language: String,
+        synced: bool,
+    }
+
+    #[test]
+    fn sd_element_roundtrip() {
+        let meta = Meta {
+            sequence_id: 7,
+            language: r#"a"b\c]d e"#.into(),
+            synced: true,
+        };
+        let element = to_sd_element("meta", &meta).unwrap();
+
+        assert_eq!(
+            element,
+            r#"[meta sequenceId="7" language="a\"b\\c\]d e" synced="true"]"#
+        );
+        assert_eq!(from_sd_element(&element).unwrap(), ("meta", meta));
+        assert_eq!(
+            to_sd_element("empty", &BTreeMap::<String, String>::new()).unwrap(),
+            "[empty]"
+        );
+        assert!(to_sd_element("a b", &BTreeMap::<String, String>::new()).is_err());
+    }
+
+    #[test]
+    fn sd_element_syntax() {
+        let (id, params): (_, BTreeMap<String, String>) =
+            from_sd_element(r#"[x@1 a="\n\]" b=""]"#).unwrap();
+        assert_eq!(id, "x@1");
+        assert_eq!(
+            params,
+            BTreeMap::from([("a".into(), r"\n]".into()), ("b".into(), "".into())])
+        );
+        let (_, params): (_, BTreeMap<String, String>) = from_sd_element("[x]").unwrap();
+        assert!(params.is_empty());
+
+        for (input, kind, pos) in [
+            (r#"x a="1""#, ErrorKind::ExpectedOpenBracket, 0),
+            (r#"[x a="1]"#, ErrorKind::ExpectedCloseBracket, 8),
+            (r#"[x a="1"]]"#, ErrorKind::TrailingCharacters, 9),
+            (r#"[ a="1"]"#, ErrorKind::ExpectedIdentifier, 1),
+            (r#"[x a=1]"#, ErrorKind::ExpectedString, 5),
+        ] {
+            assert_eq!(
+                from_sd_element::<BTreeMap<String, String>>(input).unwrap_err(),
+                ParseError { kind, pos },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn sd_elements_split() {
+        assert_eq!(
+            sd_elements(r#"[a x="]"][b]"#).unwrap(),
+            [r#"[a x="]"]"#, "[b]"]
+        );
+        assert_eq!(
+            sd_elements(r#"[a][b"#).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedCloseBracket,
+                pos: 5,
+            }
+        );
+        assert_eq!(
+            sd_elements(r#"[a] [b]"#).unwrap_err(),
+            ParseError {
+                kind: ErrorKind::ExpectedOpenBracket,
+                pos: 3,
+            }
+        );
+    }
+}
@@ -0,0 +1,434 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Helpers checking that values survive a trip through the key-values format, and generating
+//! values and key-values strings from raw bytes for fuzzing. With the `arbitrary` feature,
+//! [`Value`] and [`Document`] implement the `Arbitrary` trait of the
+//! [arbitrary](https://docs.rs/arbitrary) crate with these generators.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use serde_keyvalue::testing::assert_roundtrip;
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Net {
+//!     tap: String,
+//!     mtu: u32,
+//!     vhost: bool,
+//! }
+//!
+//! assert_roundtrip(&Net { tap: "tap0".into(), mtu: 1500, vhost: true });
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::fmt::Write;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::diff_with;
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+use crate::KeyValueSerializer;
+use crate::Map;
+use crate::Quoting;
+use crate::SerializeError;
+use crate::SerializeOptions;
+use crate::Value;
+
+/// Serializes `value` with the default options, deserializes the output and panics if the result
+/// is not equal to `value`.
+///
+/// The panic message contains the serialized string and the lines of the pretty-printed values
+/// that differ. Since the default options do not quote strings, strings containing spaces only
+/// survive the trip with [`assert_roundtrip_with`] and options quoting them.
+#[track_caller]
+pub fn assert_roundtrip<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    assert_roundtrip_with(value, SerializeOptions::default())
+}
+
+/// Serializes `value` according to `options`, which can also be a [`Preset`](crate::Preset),
+/// deserializes the output with the matching [`DeserializeOptions`] and panics if the result is
+/// not equal to `value`. See [`assert_roundtrip`] for details.
+///
+/// ```should_panic
+/// use serde::{Deserialize, Serialize};
+/// use serde_keyvalue::testing::assert_roundtrip_with;
+/// use serde_keyvalue::Preset;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Ratio {
+///     value: f32,
+/// }
+///
+/// // NaN is never equal to itself.
+/// assert_roundtrip_with(&Ratio { value: f32::NAN }, Preset::Logfmt);
+/// ```
+#[track_caller]
+pub fn assert_roundtrip_with<T>(value: &T, options: impl Into<SerializeOptions>)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let options = options.into();
+    let deserialize_options = DeserializeOptions::from(&options);
+
+    let output = match serialize(value, &options) {
+        Ok(output) => output,
+        Err(e) => panic!("cannot serialize {:?}: {}", value, e),
+    };
+    let parsed: T = match from_key_values_with(&output, deserialize_options.clone()) {
+        Ok(parsed) => parsed,
+        Err(e) => panic!("cannot deserialize `{}`: {}", output, e),
+    };
+    if parsed == *value {
+        return;
+    }
+
+    let mut message = format!("roundtrip mismatch for `{}`\n", output);
+    if let Ok(reserialized) = serialize(&parsed, &options) {
+        if let Ok(diff) = diff_with(&output, &reserialized, deserialize_options) {
+            if !diff.is_empty() {
+                let _ = writeln!(message, "reserialized pairs: {}", diff);
+            }
+        }
+    }
+    message.push_str(&debug_diff(
+        &format!("{:#?}", value),
+        &format!("{:#?}", parsed),
+    ));
+    panic!("{}", message);
+}
+
+fn serialize<T: Serialize>(
+    value: &T,
+    options: &SerializeOptions,
+) -> Result<String, SerializeError> {
+    let mut serializer = KeyValueSerializer::with_options(options.clone());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_output())
+}
+
+/// Returns the lines between the common leading and trailing lines of `expected` and `actual`,
+/// prefixed with `-` and `+` respectively.
+fn debug_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let prefix = expected
+        .iter()
+        .zip(&actual)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut diff = String::new();
+    for line in &expected[prefix..expected.len() - suffix] {
+        let _ = writeln!(diff, "-{}", line);
+    }
+    for line in &actual[prefix..actual.len() - suffix] {
+        let _ = writeln!(diff, "+{}", line);
+    }
+    diff
+}
+
+/// Maximum nesting of the lists and maps generated by [`arbitrary_value`].
+const MAX_DEPTH: usize = 3;
+
+/// Consumes and returns the first byte of `data`, or 0 once it is exhausted.
+fn take_byte(data: &mut &[u8]) -> u8 {
+    match data.split_first() {
+        Some((&byte, rest)) => {
+            *data = rest;
+            byte
+        }
+        None => 0,
+    }
+}
+
+/// Consumes up to 8 bytes of `data` as a little-endian integer.
+fn take_u64(data: &mut &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    for byte in &mut bytes {
+        *byte = take_byte(data);
+    }
+    u64::from_le_bytes(bytes)
+}
+
+/// Consumes bytes of `data` as a string of up to 15 Latin-1 characters, including control
+/// characters, quotes and separators.
+fn take_string(data: &mut &[u8]) -> String {
+    let len = take_byte(data) % 16;
+    (0..len).map(|_| char::from(take_byte(data))).collect()
+}
+
+/// Consumes bytes of `data` as a key made of a lowercase letter followed by up to 7 lowercase
+/// letters, digits or underscores.
+fn take_key(data: &mut &[u8]) -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_";
+
+    let len = take_byte(data) % 8;
+    let mut key = String::from(char::from(b'a' + take_byte(data) % 26));
+    key.extend((0..len).map(|_| char::from(CHARS[usize::from(take_byte(data)) % CHARS.len()])));
+    key
+}
+
+fn take_value(data: &mut &[u8], depth: usize, maps: bool) -> Value {
+    let kinds = if maps { 7 } else { 6 };
+    match take_byte(data) % kinds {
+        1 => Value::Int(take_u64(data) as i64),
+        2 => Value::UInt(take_u64(data) | 1 << 63),
+        3 => Value::Float(f64::from_bits(take_u64(data))),
+        4 => Value::String(take_string(data)),
+        5 if depth < MAX_DEPTH => {
+            let len = take_byte(data) % 4;
+            Value::List(
+                (0..len)
+                    .map(|_| take_value(data, depth + 1, maps))
+                    .collect(),
+            )
+        }
+        6 if depth < MAX_DEPTH => Value::Map(take_map(data, depth + 1, maps)),
+        _ => Value::Bool(take_byte(data) & 1 != 0),
+    }
+}
+
+fn take_map(data: &mut &[u8], depth: usize, maps: bool) -> Map {
+    let len = take_byte(data) % 8;
+    (0..len)
+        .map(|_| (take_key(data), take_value(data, depth, maps)))
+        .collect()
+}
+
+/// Builds a [`Value`] from the bytes at the start of `data`, consuming them, so fuzzers
+/// providing raw bytes can explore the value model.
+///
+/// Any input produces a value, and exhausted input produces `false` booleans, so generation
+/// always terminates. Lists and maps are nested at most 3 levels deep, strings may contain any
+/// Latin-1 character, and floats may be NaN or infinite. This is how [`Value`] implements
+/// `Arbitrary` with the `arbitrary` feature.
+pub fn arbitrary_value(data: &mut &[u8]) -> Value {
+    take_value(data, 0, true)
+}
+
+/// Builds a syntactically valid key-values string from the bytes at the start of `data`,
+/// consuming them, so fuzzers providing raw bytes can exercise the parser beyond its first
+/// syntax error.
+///
+/// The string contains up to 7 pairs with unique keys and quoted strings, booleans, numbers or
+/// lists as values, and can always be deserialized into a [`Map`] with the default options:
+///
+/// ```
+/// use serde_keyvalue::testing::arbitrary_document;
+/// use serde_keyvalue::{from_key_values, Map};
+///
+/// let mut data: &[u8] = b"\x02\x00\x00\x00\x01\x00\x01\x04\x03x y";
+/// let document = arbitrary_document(&mut data);
+/// assert_eq!(document, r#"a=True,b="x y""#);
+/// assert!(from_key_values::<Map>(&document).is_ok());
+/// ```
+///
+/// This is how [`Document`] implements `Arbitrary` with the `arbitrary` feature.
+pub fn arbitrary_document(data: &mut &[u8]) -> String {
+    let map = take_map(data, 1, false);
+    let mut serializer = SerializeOptions::new()
+        .pair_separator(",")
+        .quoting(Quoting::WhenNeeded)
+        .build();
+    // Maps of scalars and lists can always be serialized.
+    map.serialize(&mut serializer).unwrap();
+    serializer.into_output()
+}
+
+/// A syntactically valid key-values string, as built by [`arbitrary_document`].
+///
+/// With the `arbitrary` feature, it can be the input of a
+/// [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz) target, e.g.
+/// `fuzz_target!(|document: Document| { ... })`:
+///
+/// ```
+/// # #[cfg(feature = "arbitrary")]
+/// # {
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use serde_keyvalue::testing::Document;
+/// use serde_keyvalue::{from_key_values, Map};
+///
+/// let mut u = Unstructured::new(b"\x02\x00\x00\x00\x01\x00\x01\x04\x03x y");
+/// let document = Document::arbitrary(&mut u).unwrap();
+/// assert_eq!(document.0, r#"a=True,b="x y""#);
+/// assert!(from_key_values::<Map>(&document.0).is_ok());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document(pub String);
+
+/// Runs `generate` on the bytes of `u`, and consumes those it used.
+#[cfg(feature = "arbitrary")]
+fn take_arbitrary<'a, T>(
+    u: &mut arbitrary::Unstructured<'a>,
+    generate: impl FnOnce(&mut &'a [u8]) -> T,
+) -> arbitrary::Result<T> {
+    let bytes = u.peek_bytes(u.len()).unwrap_or_default();
+    let mut data = bytes;
+    let value = generate(&mut data);
+    u.bytes(bytes.len() - data.len())?;
+    Ok(value)
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        take_arbitrary(u, arbitrary_value)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Document {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        take_arbitrary(u, |data| Document(arbitrary_document(data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::panic;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::from_key_values;
+    use crate::Preset;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        size: u32,
+        #[serde(skip)]
+        cached: bool,
+    }
+
+    fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn roundtrip_ok() {
+        let config = Config {
+            name: "a b, c".into(),
+            size: 3,
+            cached: false,
+        };
+        assert_roundtrip_with(&config, Preset::Logfmt);
+        assert_roundtrip(&BTreeMap::from([("k".to_string(), 1.5)]));
+
+        let message = panic_message(|| assert_roundtrip(&config));
+        assert!(message.starts_with("cannot deserialize `name=a b, c size=3`"));
+    }
+
+    #[test]
+    fn roundtrip_mismatch() {
+        let message = panic_message(|| {
+            assert_roundtrip(&Config {
+                name: "a".into(),
+                size: 3,
+                cached: true,
+            })
+        });
+        assert_eq!(
+            message,
+            "roundtrip mismatch for `name=a size=3`\n-    cached: true,\n+    cached: false,\n"
+        );
+    }
+
+    /// Returns `len` pseudo-random bytes.
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_values() {
+        assert_eq!(arbitrary_value(&mut &[][..]), Value::Bool(false));
+        assert_eq!(
+            arbitrary_value(&mut &[4, 2, b'h', b'i', 9][..]),
+            Value::String("hi".into())
+        );
+
+        let mut data: &[u8] = &[5, 2, 1, 7, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0];
+        assert_eq!(
+            arbitrary_value(&mut data),
+            Value::List(vec![Value::Int(7), Value::UInt(1 << 63)])
+        );
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn arbitrary_documents() {
+        for seed in 0..2000 {
+            let bytes = random_bytes(seed, 256);
+            let document = arbitrary_document(&mut &bytes[..]);
+            if let Err(e) = from_key_values::<Map>(&document) {
+                panic!("cannot parse `{}`: {}", document.escape_debug(), e);
+            }
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_impls() {
+        use arbitrary::Arbitrary;
+        use arbitrary::Unstructured;
+
+        let mut u = Unstructured::new(&[4, 2, b'h', b'i', 0, 1]);
+        assert_eq!(
+            Value::arbitrary(&mut u).unwrap(),
+            Value::String("hi".into())
+        );
+        // Only the bytes used by the first value are consumed.
+        assert_eq!(Value::arbitrary(&mut u).unwrap(), Value::Bool(true));
+        assert!(u.is_empty());
+
+        for seed in 0..200 {
+            let bytes = random_bytes(seed, 256);
+            let document = Document::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            assert_eq!(document.0, arbitrary_document(&mut &bytes[..]));
+            from_key_values::<Map>(&document.0).unwrap();
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Renamed {
+        #[serde(rename(serialize = "w"))]
+        v: u32,
+    }
+
+    #[test]
+    fn roundtrip_errors() {
+        let message = panic_message(|| assert_roundtrip(&Renamed { v: 1 }));
+        assert!(message.starts_with("cannot deserialize `w=1`"));
+
+        let nested = BTreeMap::from([("k".to_string(), BTreeMap::from([(1, 2)]))]);
+        let message = panic_message(|| assert_roundtrip(&nested));
+        assert!(message.starts_with("cannot serialize"));
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Tokenization of key-values strings.
+
+use alloc::borrow::Cow;
+use alloc::vec;
+use core::ops::Range;
+
+use crate::key_values::strip_delimiter;
+use crate::options::DeserializeOptions;
+use crate::scanner::Scanner;
+use crate::ParseError;
+
+/// A lexical element of a key-values string, along with the range of the input it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// The key of a pair.
+    Key {
+        /// Name of the key.
+        name: &'a str,
+        /// Range of the key in the input.
+        span: Range<usize>,
+    },
+    /// The delimiter between a key and its value, including the whitespace around it if the
+    /// options allow it.
+    Delim {
+        /// Range of the delimiter in the input.
+        span: Range<usize>,
+    },
+    /// The value of a pair.
+    Value {
+        /// Text of the value as written in the input, including quotes and brackets.
+        raw: &'a str,
+        /// Unquoted and unescaped content of the value. Values enclosed in brackets are kept
+        /// verbatim.
+        value: Cow<'a, str>,
+        /// Range of the value in the input.
+        span: Range<usize>,
+    },
+    /// The separator between two pairs.
+    Separator {
+        /// Range of the separator in the input.
+        span: Range<usize>,
+    },
+}
+
+impl Token<'_> {
+    /// Returns the range of the input covered by the token.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Token::Key { span, .. }
+            | Token::Delim { span }
+            | Token::Value { span, .. }
+            | Token::Separator { span } => span.clone(),
+        }
+    }
+}
+
+/// Returns an iterator over the tokens of `input`, parsed with the default options of the
+/// deserializer.
+///
+/// The tokens follow the same quoting and escaping rules as the deserializer, which makes them
+/// suitable for syntax highlighting or linting. Keys given without a value, i.e. flags, produce a
+/// single [`Token::Key`]. The iteration stops after the first error.
+///
+/// ```
+/// use serde_keyvalue::{tokenize, Token};
+///
+/// let tokens: Vec<_> = tokenize(r#"ro,name="a b""#).collect::<Result<_, _>>().unwrap();
+///
+/// assert_eq!(tokens[0], Token::Key { name: "ro", span: 0..2 });
+/// assert_eq!(tokens[1], Token::Separator { span: 2..3 });
+/// assert_eq!(tokens[2], Token::Key { name: "name", span: 3..7 });
+/// assert_eq!(tokens[3], Token::Delim { span: 7..8 });
+/// assert_eq!(
+///     tokens[4],
+///     Token::Value { raw: r#""a b""#, value: "a b".into(), span: 8..13 }
+/// );
+/// ```
+pub fn tokenize(input: &str) -> impl Iterator<Item = Result<Token<'_>, ParseError>> {
+    tokenize_with(input, DeserializeOptions::default())
+}
+
+/// Returns an iterator over the tokens of `input`, parsed with the syntax described by `options`.
+/// See [`tokenize`] for details.
+pub fn tokenize_with(
+    input: &str,
+    options: impl Into<DeserializeOptions>,
+) -> impl Iterator<Item = Result<Token<'_>, ParseError>> {
+    let options = options.into();
+
+    Scanner::new(input, options.clone()).flat_map(move |pair| {
+        let pair = match pair {
+            Ok(pair) => pair,
+            Err(e) => return vec![Err(e)],
+        };
+
+        let key_end = pair.span.start + pair.key.len();
+        let mut tokens = vec![Ok(Token::Key {
+            name: pair.key,
+            span: pair.span.start..key_end,
+        })];
+
+        if let Some(remainder) = strip_delimiter(&input[key_end..pair.span.end], &options) {
+            let value_start = pair.span.end - remainder.len();
+            tokens.push(Ok(Token::Delim {
+                span: key_end..value_start,
+            }));
+            tokens.push(Ok(Token::Value {
+                raw: remainder,
+                value: pair.value.into_text(),
+                span: value_start..pair.span.end,
+            }));
+        }
+
+        if let Some(c) = input[pair.span.end..].chars().next() {
+            tokens.push(Ok(Token::Separator {
+                span: pair.span.end..pair.span.end + c.len_utf8(),
+            }));
+        }
+
+        tokens
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DelimiterStyle;
+    use crate::ErrorKind;
+
+    #[test]
+    fn tokenize_pairs() {
+        let options = DeserializeOptions::new().delimiter_style(DelimiterStyle::Spaced);
+        let tokens: Vec<_> = tokenize_with("a = [1,2] b = 'x'", options).collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Ok(Token::Key {
+                    name: "a",
+                    span: 0..1
+                }),
+                Ok(Token::Delim { span: 1..4 }),
+                Ok(Token::Value {
+                    raw: "[1,2]",
+                    value: "[1,2]".into(),
+                    span: 4..9
+                }),
+                Ok(Token::Separator { span: 9..10 }),
+                Ok(Token::Key {
+                    name: "b",
+                    span: 10..11
+                }),
+                Ok(Token::Delim { span: 11..14 }),
+                Ok(Token::Value {
+                    raw: "'x'",
+                    value: "x".into(),
+                    span: 14..17
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_errors() {
+        let tokens: Vec<_> = tokenize("a=,=2").collect();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[3].as_ref().unwrap().span(), 2..3);
+        assert_eq!(
+            tokens[4],
+            Err(ParseError {
+                kind: ErrorKind::ExpectedIdentifier,
+                pos: 3,
+            })
+        );
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Discovery of the keys accepted by a struct from its `Deserialize` implementation.
+//!
+//! The struct is deserialized from a deserializer that records the fields it asks for and the
+//! type requested for each of their values, and feeds it placeholder values.
+
+use alloc::vec::Vec;
+
+use serde::de;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::DeserializeSeed;
+use serde::de::EnumAccess;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::VariantAccess;
+use serde::de::Visitor;
+use serde::forward_to_deserialize_any;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use crate::schema::ValueType;
+use crate::ParseError;
+
+type Result<T> = core::result::Result<T, ParseError>;
+
+/// What was recorded about the value of a field.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FieldTrace {
+    /// Type requested for the value, `None` if the field has not been reached.
+    pub value_type: Option<ValueType>,
+    /// Whether the value is an `Option`.
+    pub optional: bool,
+    /// Names of the variants, if the value is an enum.
+    pub variants: &'static [&'static str],
+}
+
+/// Returns the names of the fields of `T` along with what was recorded about their values, or
+/// `None` if `T` is not deserialized as a struct, e.g. because it has flattened fields.
+///
+/// Fields that come after a field whose placeholder value was rejected, e.g. by a
+/// `deserialize_with` function, are returned without a type.
+pub(crate) fn trace_fields<T>() -> Option<Vec<(&'static str, FieldTrace)>>
+where
+    T: Deserialize<'static>,
+{
+    let mut root = RootTracer {
+        fields: None,
+        traces: Vec::new(),
+    };
+    // Errors only mean that some fields could not be reached.
+    let _ = T::deserialize(&mut root);
+
+    let mut traces = root.traces.into_iter();
+    root.fields.map(|fields| {
+        fields
+            .iter()
+            .map(|&name| (name, traces.next().unwrap_or_default()))
+            .collect()
+    })
+}
+
+struct RootTracer {
+    fields: Option<&'static [&'static str]>,
+    traces: Vec<FieldTrace>,
+}
+
+impl<'de> Deserializer<'de> for &mut RootTracer {
+    type Error = ParseError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.fields = Some(fields);
+        visitor.visit_map(StructTracer {
+            fields,
+            traces: Some(&mut self.traces),
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Feeds the fields of a struct to its visitor, recording their values in `traces` if set.
+struct StructTracer<'a> {
+    fields: &'static [&'static str],
+    traces: Option<&'a mut Vec<FieldTrace>>,
+}
+
+impl<'de> MapAccess<'de> for StructTracer<'_> {
+    type Error = ParseError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.fields.split_first() {
+            Some((&field, rest)) => {
+                self.fields = rest;
+                seed.deserialize(BorrowedStrDeserializer::new(field))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let trace = self.traces.as_mut().map(|traces| {
+            traces.push(FieldTrace::default());
+            traces.last_mut().unwrap()
+        });
+        seed.deserialize(ValueTracer { trace })
+    }
+}
+
+/// Records the type requested for a value in `trace` if set, and provides a placeholder of that
+/// type.
+struct ValueTracer<'a> {
+    trace: Option<&'a mut FieldTrace>,
+}
+
+impl ValueTracer<'_> {
+    /// Returns a tracer for values whose type is not recorded.
+    fn untraced() -> Self {
+        ValueTracer { trace: None }
+    }
+
+    fn record(&mut self, value_type: ValueType) {
+        if let Some(trace) = &mut self.trace {
+            trace.value_type.get_or_insert(value_type);
+        }
+    }
+}
+
+/// Implements deserialization methods by calling another one accepting the same visitor.
+macro_rules! forward_to {
+    ($($method:ident => $target:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                self.$target(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueTracer<'_> {
+    type Error = ParseError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::Any);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::Bool);
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::Int);
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::UInt);
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::Float);
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::String);
+        visitor.visit_char('a')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::String);
+        visitor.visit_borrowed_str("")
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::String);
+        visitor.visit_borrowed_bytes(b"")
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        if let Some(trace) = &mut self.trace {
+            trace.optional = true;
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_tuple(0, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(mut self, len: usize, visitor: V) -> Result<V::Value> {
+        self.record(ValueType::List);
+        visitor.visit_seq(SeqTracer { remaining: len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_struct("", &[], visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.record(ValueType::Any);
+        visitor.visit_map(StructTracer {
+            fields,
+            traces: None,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.record(ValueType::String);
+        if let Some(trace) = &mut self.trace {
+            trace.variants = variants;
+        }
+        visitor.visit_enum(EnumTracer { variants })
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to! {
+        deserialize_i8 => deserialize_i64,
+        deserialize_i16 => deserialize_i64,
+        deserialize_i32 => deserialize_i64,
+        deserialize_i128 => deserialize_i64,
+        deserialize_u8 => deserialize_u64,
+        deserialize_u16 => deserialize_u64,
+        deserialize_u32 => deserialize_u64,
+        deserialize_u128 => deserialize_u64,
+        deserialize_f32 => deserialize_f64,
+        deserialize_string => deserialize_str,
+        deserialize_byte_buf => deserialize_bytes,
+        deserialize_identifier => deserialize_str,
+    }
+}
+
+/// Feeds `remaining` placeholder elements to the visitor of a sequence or tuple.
+struct SeqTracer {
+    remaining: usize,
+}
+
+impl<'de> SeqAccess<'de> for SeqTracer {
+    type Error = ParseError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(ValueTracer::untraced()).map(Some)
+    }
+}
+
+/// Feeds the first variant of an enum to its visitor.
+struct EnumTracer {
+    variants: &'static [&'static str],
+}
+
+impl<'de> EnumAccess<'de> for EnumTracer {
+    type Error = ParseError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = self
+            .variants
+            .first()
+            .ok_or_else(|| de::Error::custom("enum without variants"))?;
+        let value = seed.deserialize(BorrowedStrDeserializer::new(variant))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for EnumTracer {
+    type Error = ParseError;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(ValueTracer::untraced())
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(SeqTracer { remaining: len })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_map(StructTracer {
+            fields,
+            traces: None,
+        })
+    }
+}
@@ -0,0 +1,1012 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Dynamically-typed representation of key-values strings.
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Index;
+use core::ops::IndexMut;
+
+use serde::de;
+use serde::de::value::MapDeserializer;
+use serde::de::value::SeqDeserializer;
+use serde::de::DeserializeSeed;
+use serde::de::EnumAccess;
+use serde::de::IntoDeserializer;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::VariantAccess;
+use serde::de::Visitor;
+use serde::forward_to_deserialize_any;
+use serde::ser::SerializeMap;
+use serde::ser::SerializeSeq;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::ParseError;
+
+#[cfg(not(feature = "preserve_order"))]
+type MapImpl<K, V> = alloc::collections::BTreeMap<K, V>;
+#[cfg(feature = "preserve_order")]
+type MapImpl<K, V> = indexmap::IndexMap<K, V>;
+
+/// Any value that can be found in a key-values string.
+///
+/// Since the input carries no type information, the type of a value is guessed from its content,
+/// the same way as for `flatten`ed fields: a key without a value is a `true` boolean, numbers are
+/// integers, and values enclosed in brackets are lists.
+///
+/// ```
+/// use serde_keyvalue::{from_key_values, Map, Value};
+///
+/// let map: Map = from_key_values("path=/some/path,ro,sizes=[1,2]").unwrap();
+///
+/// assert_eq!(map.get("path"), Some(&Value::String("/some/path".into())));
+/// assert_eq!(map.get("ro"), Some(&Value::Bool(true)));
+/// assert_eq!(
+///     map.get("sizes"),
+///     Some(&Value::List(vec![Value::Int(1), Value::Int(2)]))
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A boolean, or a key given without a value.
+    Bool(bool),
+    /// A signed integer.
+    Int(i64),
+    /// An unsigned integer too large to be represented as an `i64`.
+    UInt(u64),
+    /// A floating point number.
+    Float(f64),
+    /// A string.
+    String(String),
+    /// A list of values.
+    List(Vec<Value>),
+    /// A nested set of key-value pairs.
+    Map(Map),
+}
+
+impl Value {
+    /// Returns the value at `path`, made of the keys and list indices leading to it separated by
+    /// dots, e.g. `net.routes.0.gw`, similarly to a JSON Pointer. An empty path designates the
+    /// value itself. Keys containing dots, e.g. the `net.ipv4.ip_forward` keys of sysctl files,
+    /// are matched as a whole.
+    ///
+    /// ```
+    /// use serde_keyvalue::{from_key_values, Value};
+    ///
+    /// let value: Value = from_key_values("net=[routes=[[gw=10.0.0.1],[gw=10.0.0.2]]]").unwrap();
+    /// assert_eq!(
+    ///     value.pointer("net.routes.1.gw"),
+    ///     Some(&Value::String("10.0.0.2".into()))
+    /// );
+    /// assert_eq!(value.pointer("net.routes.2"), None);
+    /// ```
+    pub fn pointer(&self, path: &str) -> Option<&Value> {
+        if path.is_empty() {
+            return Some(self);
+        }
+
+        match self {
+            Value::Map(map) => map.pointer(path),
+            Value::List(list) => {
+                let (index, rest) = split_segment(path);
+                list.get(index.parse::<usize>().ok()?)?.pointer(rest)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value at `path`. See [`Value::pointer`] for details.
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut Value> {
+        if path.is_empty() {
+            return Some(self);
+        }
+
+        match self {
+            Value::Map(map) => map.pointer_mut(path),
+            Value::List(list) => {
+                let (index, rest) = split_segment(path);
+                list.get_mut(index.parse::<usize>().ok()?)?
+                    .pointer_mut(rest)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the values whose path matches `pattern`, along with their path, e.g. to extract
+    /// data from a parsed document without defining structs.
+    ///
+    /// Patterns are paths as accepted by [`Value::pointer`] whose segments may contain `*`
+    /// wildcards matching any sequence of characters within a segment, so `labels.*` matches all
+    /// the pairs of the `labels` map and `routes.*.gw` the `gw` key of each element of the `routes`
+    /// list. Values are returned in the order of the maps and lists containing them.
+    ///
+    /// ```
+    /// use serde_keyvalue::{from_key_values, Value};
+    ///
+    /// let value: Value = from_key_values("labels=[app=web,tier=db],name=x").unwrap();
+    /// assert_eq!(
+    ///     value.select("labels.*"),
+    ///     [
+    ///         ("labels.app".to_string(), &Value::String("web".into())),
+    ///         ("labels.tier".to_string(), &Value::String("db".into())),
+    ///     ]
+    /// );
+    /// ```
+    pub fn select(&self, pattern: &str) -> Vec<(String, &Value)> {
+        let mut matches = Vec::new();
+        select_into(self, pattern, String::new(), &mut matches);
+        matches
+    }
+}
+
+/// Key-value pairs of a key-values string.
+///
+/// By default the pairs are sorted by key. With the `preserve_order` feature, they are kept in the
+/// order in which they were inserted, so a string that is deserialized into a `Map` and serialized
+/// back keeps its original order.
+///
+/// The keys are parsed as identifiers, so unlike a `BTreeMap<String, Value>`, a `Map` can be
+/// deserialized from the top level of a key-values string.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map {
+    map: MapImpl<String, Value>,
+}
+
+impl Map {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of pairs in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns `true` if the map contains a value for `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Returns a reference to the value of `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.map.get(key)
+    }
+
+    /// Returns a mutable reference to the value of `key`, if any.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.map.get_mut(key)
+    }
+
+    /// Inserts a pair into the map, returning the previous value of `key` if any.
+    ///
+    /// With the `preserve_order` feature, replacing the value of an existing key keeps its
+    /// position.
+    pub fn insert(&mut self, key: impl Into<String>, value: Value) -> Option<Value> {
+        self.map.insert(key.into(), value)
+    }
+
+    /// Removes `key` from the map, returning its value if it was present.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        #[cfg(feature = "preserve_order")]
+        return self.map.shift_remove(key);
+        #[cfg(not(feature = "preserve_order"))]
+        return self.map.remove(key);
+    }
+
+    /// Returns an iterator over the pairs of the map.
+    pub fn iter(&self) -> <&MapImpl<String, Value> as IntoIterator>::IntoIter {
+        self.map.iter()
+    }
+
+    /// Returns an iterator over the keys of the map.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.map.keys()
+    }
+
+    /// Returns an iterator over the values of the map.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.map.values()
+    }
+
+    /// Returns the value at `path`, starting with one of the keys of the map. See
+    /// [`Value::pointer`] for details.
+    pub fn pointer(&self, path: &str) -> Option<&Value> {
+        self.keys_prefixing(path)
+            .find_map(|(_, value, rest)| value.pointer(rest))
+    }
+
+    /// Returns a mutable reference to the value at `path`, starting with one of the keys of the
+    /// map. See [`Value::pointer`] for details.
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut Value> {
+        let (key, rest) = self
+            .keys_prefixing(path)
+            .find(|(_, value, rest)| value.pointer(rest).is_some())
+            .map(|(key, _, rest)| (key.clone(), rest))?;
+        self.map.get_mut(&key)?.pointer_mut(rest)
+    }
+
+    /// Returns the values whose path, starting with one of the keys of the map, matches
+    /// `pattern`. See [`Value::select`] for details.
+    pub fn select(&self, pattern: &str) -> Vec<(String, &Value)> {
+        let mut matches = Vec::new();
+        select_pairs(self, pattern, "", &mut matches);
+        matches
+    }
+
+    /// Returns the pairs whose key starts `path`, along with the rest of the path. Keys may span
+    /// several segments of the path if they contain dots.
+    fn keys_prefixing<'a, 'p>(
+        &'a self,
+        path: &'p str,
+    ) -> impl Iterator<Item = (&'a String, &'a Value, &'p str)> {
+        self.map.iter().filter_map(move |(key, value)| {
+            let rest = path.strip_prefix(key.as_str())?;
+            let rest = if rest.is_empty() {
+                rest
+            } else {
+                rest.strip_prefix('.')?
+            };
+            Some((key, value, rest))
+        })
+    }
+}
+
+/// Splits the first segment of `path` from the rest of it.
+fn split_segment(path: &str) -> (&str, &str) {
+    path.split_once('.').unwrap_or((path, ""))
+}
+
+/// Returns `true` if `s` matches `pattern`, where `*` matches any sequence of characters.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == s,
+        Some((prefix, rest)) => {
+            let Some(s) = s.strip_prefix(prefix) else {
+                return false;
+            };
+            s.char_indices()
+                .map(|(i, _)| i)
+                .chain([s.len()])
+                .any(|i| glob_match(rest, &s[i..]))
+        }
+    }
+}
+
+/// Returns the rest of `pattern` once its leading segments have matched the segments of `key`.
+fn strip_key_pattern<'p>(pattern: &'p str, key: &str) -> Option<&'p str> {
+    let mut rest = pattern;
+    for key_segment in key.split('.') {
+        if rest.is_empty() {
+            return None;
+        }
+        let (segment, remainder) = split_segment(rest);
+        if !glob_match(segment, key_segment) {
+            return None;
+        }
+        rest = remainder;
+    }
+    Some(rest)
+}
+
+/// Appends `segment` to `path`.
+fn join_path(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_owned()
+    } else {
+        format!("{}.{}", path, segment)
+    }
+}
+
+/// Appends the values of `value` whose path, relative to `value` at `path`, matches `pattern` to
+/// `matches`.
+fn select_into<'a>(
+    value: &'a Value,
+    pattern: &str,
+    path: String,
+    matches: &mut Vec<(String, &'a Value)>,
+) {
+    if pattern.is_empty() {
+        matches.push((path, value));
+        return;
+    }
+
+    match value {
+        Value::Map(map) => select_pairs(map, pattern, &path, matches),
+        Value::List(list) => {
+            let (segment, rest) = split_segment(pattern);
+            for (i, element) in list.iter().enumerate() {
+                let index = i.to_string();
+                if glob_match(segment, &index) {
+                    select_into(element, rest, join_path(&path, &index), matches);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Appends the values of the pairs of `map` whose path matches `pattern` to `matches`.
+fn select_pairs<'a>(
+    map: &'a Map,
+    pattern: &str,
+    path: &str,
+    matches: &mut Vec<(String, &'a Value)>,
+) {
+    if pattern.is_empty() {
+        return;
+    }
+    for (key, value) in map {
+        if let Some(rest) = strip_key_pattern(pattern, key) {
+            select_into(value, rest, join_path(path, key), matches);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Map {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = <&'a MapImpl<String, Value> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (String, Value);
+    type IntoIter = <MapImpl<String, Value> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+impl<K: Into<String>> FromIterator<(K, Value)> for Map {
+    fn from_iter<I: IntoIterator<Item = (K, Value)>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+        }
+    }
+}
+
+impl<K: Into<String>> Extend<(K, Value)> for Map {
+    fn extend<I: IntoIterator<Item = (K, Value)>>(&mut self, iter: I) {
+        self.map
+            .extend(iter.into_iter().map(|(k, v)| (k.into(), v)));
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::Int(v) => serializer.serialize_i64(*v),
+            Value::UInt(v) => serializer.serialize_u64(*v),
+            Value::Float(v) => serializer.serialize_f64(*v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::List(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Value::Map(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Map {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Map {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a key-values value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(i64::try_from(v).map_or(Value::UInt(v), Value::Int))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Value, A::Error> {
+        MapVisitor.visit_map(map).map(Value::Map)
+    }
+}
+
+struct MapVisitor;
+
+impl<'de> Visitor<'de> for MapVisitor {
+    type Value = Map;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("key-value pairs")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Map, A::Error> {
+        let mut map = Map::new();
+        while let Some(key) = access.next_key_seed(KeySeed)? {
+            let value = access.next_value()?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+/// Deserializes a key as an identifier rather than as a string value, which would otherwise
+/// consume the key-value delimiter following it.
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_identifier(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl Visitor<'_> for KeyVisitor {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a key")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<String, E> {
+        Ok(v.to_owned())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<String, E> {
+        Ok(v)
+    }
+}
+
+impl Index<&str> for Value {
+    type Output = Value;
+
+    /// Returns the value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not a map or if it has no value for `key`.
+    fn index(&self, key: &str) -> &Value {
+        match self {
+            Value::Map(map) => &map[key],
+            _ => panic!("cannot index a value that is not a map with key `{}`", key),
+        }
+    }
+}
+
+impl IndexMut<&str> for Value {
+    /// Returns a mutable reference to the value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not a map or if it has no value for `key`.
+    fn index_mut(&mut self, key: &str) -> &mut Value {
+        match self {
+            Value::Map(map) => &mut map[key],
+            _ => panic!("cannot index a value that is not a map with key `{}`", key),
+        }
+    }
+}
+
+impl Index<usize> for Value {
+    type Output = Value;
+
+    /// Returns the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not a list or if `index` is out of bounds.
+    fn index(&self, index: usize) -> &Value {
+        match self {
+            Value::List(list) => &list[index],
+            _ => panic!("cannot index a value that is not a list with {}", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for Value {
+    /// Returns a mutable reference to the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is not a list or if `index` is out of bounds.
+    fn index_mut(&mut self, index: usize) -> &mut Value {
+        match self {
+            Value::List(list) => &mut list[index],
+            _ => panic!("cannot index a value that is not a list with {}", index),
+        }
+    }
+}
+
+impl Index<&str> for Map {
+    type Output = Value;
+
+    /// Returns the value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map has no value for `key`.
+    fn index(&self, key: &str) -> &Value {
+        self.get(key)
+            .unwrap_or_else(|| panic!("no value for key `{}`", key))
+    }
+}
+
+impl IndexMut<&str> for Map {
+    /// Returns a mutable reference to the value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map has no value for `key`.
+    fn index_mut(&mut self, key: &str) -> &mut Value {
+        self.get_mut(key)
+            .unwrap_or_else(|| panic!("no value for key `{}`", key))
+    }
+}
+
+/// A `Value` can be deserialized into any type, e.g. to extract a typed struct from a part of a
+/// document whose other parts are only known at runtime. Since values carry no position, errors
+/// are reported at position 0.
+///
+/// Keys without a value, which are `true` booleans, can be deserialized into unit types, strings
+/// into unit enum variants, and maps containing a single pair into the variant named by its key.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_keyvalue::{from_key_values, Value};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Route {
+///     gw: String,
+///     metric: u32,
+/// }
+///
+/// let value: Value = from_key_values("name=eth0,route=[gw=10.0.0.1,metric=100]").unwrap();
+/// let route = Route::deserialize(value["route"].clone()).unwrap();
+/// assert_eq!(route, Route { gw: "10.0.0.1".into(), metric: 100 });
+/// ```
+impl<'de> Deserializer<'de> for Value {
+    type Error = ParseError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        match self {
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Int(v) => visitor.visit_i64(v),
+            Value::UInt(v) => visitor.visit_u64(v),
+            Value::Float(v) => visitor.visit_f64(v),
+            Value::String(v) => visitor.visit_string(v),
+            Value::List(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter())),
+            Value::Map(v) => v.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        match self {
+            Value::Bool(true) => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        let (variant, value) = match self {
+            Value::String(variant) => (variant, None),
+            Value::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.into_iter().next().unwrap();
+                (variant, Some(value))
+            }
+            _ => return self.deserialize_any(visitor),
+        };
+        visitor.visit_enum(VariantDeserializer { variant, value })
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf seq
+        tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ParseError> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A `Map` can be deserialized into any type deserialized from a map, like a struct.
+impl<'de> Deserializer<'de> for Map {
+    type Error = ParseError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        let mut access = MapDeserializer::new(self.into_iter());
+        let value = visitor.visit_map(&mut access)?;
+        access.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ParseError> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ParseError> for Map {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Provides the variant of an enum deserialized from a `Value`, along with its value if any.
+struct VariantDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for VariantDeserializer {
+    type Error = ParseError;
+    type Variant = VariantValue;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantValue), ParseError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantValue(self.value)))
+    }
+}
+
+/// Value of an enum variant deserialized from a `Value`, `None` for variants given as strings.
+struct VariantValue(Option<Value>);
+
+impl<'de> VariantAccess<'de> for VariantValue {
+    type Error = ParseError;
+
+    fn unit_variant(self) -> Result<(), ParseError> {
+        match self.0 {
+            None | Some(Value::Bool(true)) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(unexpected(&value), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, ParseError> {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        match self.0 {
+            Some(value) => value.deserialize_seq(visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ParseError> {
+        match self.0 {
+            Some(value) => value.deserialize_map(visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+/// Describes `value` in invalid type errors.
+fn unexpected(value: &Value) -> de::Unexpected<'_> {
+    match value {
+        Value::Bool(v) => de::Unexpected::Bool(*v),
+        Value::Int(v) => de::Unexpected::Signed(*v),
+        Value::UInt(v) => de::Unexpected::Unsigned(*v),
+        Value::Float(v) => de::Unexpected::Float(*v),
+        Value::String(v) => de::Unexpected::Str(v),
+        Value::List(_) => de::Unexpected::Seq,
+        Value::Map(_) => de::Unexpected::Map,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::from_key_values;
+    use crate::KeyValueSerializer;
+
+    fn to_output(map: &Map) -> String {
+        let mut serializer = KeyValueSerializer::new();
+        map.serialize(&mut serializer).unwrap();
+        serializer.into_output()
+    }
+
+    #[test]
+    fn deserialize_map() {
+        let map: Map = from_key_values("name=disk size=4096 big=18446744073709551615 ro").unwrap();
+
+        assert_eq!(
+            map,
+            Map::from_iter([
+                ("name", Value::String("disk".into())),
+                ("size", Value::Int(4096)),
+                ("big", Value::UInt(u64::MAX)),
+                ("ro", Value::Bool(true)),
+            ])
+        );
+    }
+
+    #[test]
+    fn serialize_map() {
+        let map: Map = from_key_values("b=2,a=[x,y],c=False").unwrap();
+
+        #[cfg(not(feature = "preserve_order"))]
+        assert_eq!(to_output(&map), "a=[x,y] b=2 c=False");
+        #[cfg(feature = "preserve_order")]
+        assert_eq!(to_output(&map), "b=2 a=[x,y] c=False");
+    }
+
+    #[test]
+    fn value_pointer() {
+        let mut map: Map =
+            from_key_values("net=[routes=[[gw=10.0.0.1,dev=eth0],[gw=10.0.0.2]]],ro").unwrap();
+        // Keys containing dots, as in sysctl files.
+        map.insert("net.ipv4.ip_forward", Value::Int(1));
+        let mut value = Value::Map(map);
+
+        let gw = |s: &str| Some(Value::String(s.into()));
+        assert_eq!(value.pointer("net.routes.0.gw").cloned(), gw("10.0.0.1"));
+        assert_eq!(value.pointer("net.ipv4.ip_forward"), Some(&Value::Int(1)));
+        assert_eq!(value.pointer("ro"), Some(&Value::Bool(true)));
+        assert_eq!(value.pointer(""), Some(&value));
+        for path in [
+            "net.routes.2",
+            "net.routes.x",
+            "ro.x",
+            "net.ipv4",
+            "n",
+            "net.routes.1.dev",
+        ] {
+            assert_eq!(value.pointer(path), None, "{}", path);
+        }
+
+        *value.pointer_mut("net.routes.1.gw").unwrap() = Value::String("10.0.0.3".into());
+        assert_eq!(value.pointer("net.routes.1.gw").cloned(), gw("10.0.0.3"));
+        assert!(value.pointer_mut("net.routes.1.dev").is_none());
+    }
+
+    #[test]
+    fn value_select() {
+        let mut map: Map =
+            from_key_values("labels=[app=web,team=a,tier=db],routes=[[gw=x],[dev=eth0],[gw=y]]")
+                .unwrap();
+        map.insert("kernel.pid_max", Value::Int(9));
+
+        let paths = |pattern| {
+            map.select(pattern)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths("labels.t*"), ["labels.team", "labels.tier"]);
+        assert_eq!(paths("routes.*.gw"), ["routes.0.gw", "routes.2.gw"]);
+        assert_eq!(paths("kernel.*"), ["kernel.pid_max"]);
+        assert_eq!(paths("*.app"), ["labels.app"]);
+        assert_eq!(paths("*e*"), ["labels", "routes"]);
+        assert_eq!(paths("*.*_max"), ["kernel.pid_max"]);
+        assert!(paths("").is_empty());
+        assert!(paths("labels.*.x").is_empty());
+        assert_eq!(
+            map.select("routes.1.*"),
+            [("routes.1.dev".to_string(), &Value::String("eth0".into()))]
+        );
+    }
+
+    #[test]
+    fn value_into_deserializer() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        enum Mode {
+            Bridge,
+            Tap(String),
+            Vlan { id: u16 },
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Net {
+            name: String,
+            mtu: Option<u16>,
+            modes: Vec<Mode>,
+            vhost: (),
+            addrs: BTreeMap<String, u8>,
+        }
+
+        let value: Value = from_key_values(
+            "net=[name=eth0,modes=[Bridge,[Tap=tap0],[Vlan=[id=10]]],vhost,addrs=[a=1,b=2]]",
+        )
+        .unwrap();
+        let net = Net::deserialize(value["net"].clone()).unwrap();
+        assert_eq!(
+            net,
+            Net {
+                name: "eth0".into(),
+                mtu: None,
+                modes: vec![
+                    Mode::Bridge,
+                    Mode::Tap("tap0".into()),
+                    Mode::Vlan { id: 10 }
+                ],
+                vhost: (),
+                addrs: BTreeMap::from([("a".into(), 1), ("b".into(), 2)]),
+            }
+        );
+
+        let err = Net::deserialize(value["net"]["addrs"].clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                kind: crate::ErrorKind::SerdeError("missing field `name`".into()),
+                pos: 0,
+            }
+        );
+        assert!(Mode::deserialize(Value::Int(1)).is_err());
+        assert!(Mode::deserialize(Value::String("Tap".into())).is_err());
+    }
+
+    #[test]
+    fn value_index() {
+        let mut value: Value = from_key_values("a=[b=[1,2]],c=x").unwrap();
+        assert_eq!(value["a"]["b"][1], Value::Int(2));
+        value["a"]["b"][0] = Value::Int(3);
+        value["c"] = Value::Bool(true);
+        assert_eq!(value, from_key_values("a=[b=[3,2]],c").unwrap());
+
+        let Value::Map(mut map) = value else {
+            unreachable!()
+        };
+        map["c"] = Value::Int(1);
+        assert_eq!(map["c"], Value::Int(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "no value for key `d`")]
+    fn value_index_missing() {
+        let value: Value = from_key_values("a=1").unwrap();
+        let _ = &value["d"];
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn preserve_order() {
+        let mut map: Map = from_key_values("z=1 y=2 x=3").unwrap();
+        map.insert("y", Value::Int(4));
+        map.insert("w", Value::Int(5));
+        map.remove("z");
+
+        assert_eq!(to_output(&map), "y=4 x=3 w=5");
+    }
+}
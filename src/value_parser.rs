@@ -0,0 +1,168 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Command-line arguments given as key-values strings, parsed with [clap](https://docs.rs/clap).
+
+use std::ffi::OsStr;
+use std::marker::PhantomData;
+
+use clap::builder::TypedValueParser;
+use serde::de::DeserializeOwned;
+
+use crate::from_key_values_with;
+use crate::DeserializeOptions;
+
+/// Value parser of clap deserializing `T` from the key-values string given as the value of an
+/// argument.
+///
+/// Errors are reported by clap like those of its own value parsers, along with the argument and
+/// its value, e.g. `invalid value 'ro=maybe' for '--disk <DISK>': expected a boolean at position
+/// 3`.
+///
+/// ```
+/// use std::path::PathBuf;
+///
+/// use clap::Parser;
+/// use serde::Deserialize;
+/// use serde_keyvalue::KeyValueParser;
+///
+/// #[derive(Debug, Clone, PartialEq, Deserialize)]
+/// struct Disk {
+///     path: PathBuf,
+///     #[serde(default)]
+///     ro: bool,
+/// }
+///
+/// #[derive(Debug, Parser)]
+/// struct Args {
+///     /// Disk to attach, e.g. `--disk path=/x,ro=true`.
+///     #[arg(long, value_parser = KeyValueParser::<Disk>::new())]
+///     disk: Vec<Disk>,
+/// }
+///
+/// let args = Args::try_parse_from(["vm", "--disk", "path=/x,ro=true", "--disk", "path=/y"])
+///     .unwrap();
+/// assert_eq!(args.disk[0], Disk { path: "/x".into(), ro: true });
+/// assert_eq!(args.disk[1], Disk { path: "/y".into(), ro: false });
+///
+/// let err = Args::try_parse_from(["vm", "--disk", "path=/x,ro=maybe"]).unwrap_err();
+/// assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+/// ```
+pub struct KeyValueParser<T> {
+    options: DeserializeOptions,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> KeyValueParser<T> {
+    /// Creates a value parser using the default options of the deserializer.
+    pub fn new() -> Self {
+        Self::with_options(DeserializeOptions::default())
+    }
+
+    /// Creates a value parser accepting the syntax described by `options`, which can also be a
+    /// [`Preset`](crate::Preset).
+    pub fn with_options(options: impl Into<DeserializeOptions>) -> Self {
+        Self {
+            options: options.into(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for KeyValueParser<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for KeyValueParser<T> {
+    fn clone(&self) -> Self {
+        Self::with_options(self.options.clone())
+    }
+}
+
+impl<T> TypedValueParser for KeyValueParser<T>
+where
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    type Value = T;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<T, clap::Error> {
+        // Closures are value parsers reporting their errors along with the argument.
+        let options = self.options.clone();
+        let parse = move |value: &str| from_key_values_with::<T>(value, options.clone());
+        parse.parse_ref(cmd, arg, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::Preset;
+
+    #[derive(Debug, Clone, PartialEq, Deserialize)]
+    struct Tap {
+        name: String,
+        mtu: u32,
+    }
+
+    fn command(parser: KeyValueParser<Tap>) -> clap::Command {
+        clap::Command::new("vm").arg(
+            clap::Arg::new("tap")
+                .long("tap")
+                .action(clap::ArgAction::Append)
+                .value_parser(parser),
+        )
+    }
+
+    #[test]
+    fn parse_arguments() {
+        let matches = command(KeyValueParser::new())
+            .try_get_matches_from([
+                "vm",
+                "--tap",
+                "name=tap0,mtu=1500",
+                "--tap=name=tap1,mtu=9000",
+            ])
+            .unwrap();
+        let taps: Vec<&Tap> = matches.get_many("tap").unwrap().collect();
+        assert_eq!(
+            taps,
+            [
+                &Tap {
+                    name: "tap0".into(),
+                    mtu: 1500,
+                },
+                &Tap {
+                    name: "tap1".into(),
+                    mtu: 9000,
+                },
+            ]
+        );
+
+        let matches = command(KeyValueParser::with_options(Preset::Logfmt))
+            .try_get_matches_from(["vm", "--tap", "name=\"tap 0\" mtu=1500"])
+            .unwrap();
+        assert_eq!(matches.get_one::<Tap>("tap").unwrap().name, "tap 0");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = command(KeyValueParser::new())
+            .try_get_matches_from(["vm", "--tap", "name=tap0,mtu=x"])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+        let message = err.to_string();
+        assert!(message.contains("'name=tap0,mtu=x'"), "{}", message);
+        assert!(message.contains("'--tap <tap>'"), "{}", message);
+        assert!(message.contains("at position 14"), "{}", message);
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! JavaScript bindings built with wasm-bindgen.
+//!
+//! The functions are exported to JavaScript when a `cdylib` crate depending on this crate with the
+//! `wasm` feature is built for WebAssembly, e.g. with `wasm-pack`:
+//!
+//! ```js
+//! import { fromStr, toString, canonicalize } from "./pkg/options.js";
+//!
+//! JSON.parse(fromStr('mtu=1500,name="tap 0",vhost')); // { mtu: 1500, name: "tap 0", vhost: true }
+//! toString(JSON.stringify({ mtu: 9000, queues: [1, 2] })); // "mtu=9000 queues=[1,2]"
+//! canonicalize("b=2 a='x y'"); // 'a="x y" b=2'
+//! ```
+//!
+//! Objects are passed as JSON text, and errors are thrown as `Error`s whose message is the one of
+//! the Rust error. The strings are parsed and written in the default dialect, as by `skv` without
+//! `--preset`: pairs separated by spaces or commas, written separated by spaces with values quoted
+//! when needed.
+
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsError;
+
+use crate::options::Quoting;
+use crate::DeserializeOptions;
+use crate::SerializeOptions;
+
+/// Returns the options of the dialect of the bindings.
+fn serialize_options() -> SerializeOptions {
+    SerializeOptions::new().quoting(Quoting::WhenNeeded)
+}
+
+/// Parses the key-values string `input` and returns its pairs as the text of a JSON object, with
+/// the types of the values guessed as by [`to_json`](crate::to_json). Exported as `fromStr`.
+#[wasm_bindgen(js_name = fromStr)]
+pub fn from_str(input: &str) -> Result<String, JsError> {
+    json_from_str(input).map_err(|e| JsError::new(&e))
+}
+
+/// Writes the JSON object whose text is `json` as a key-values string. Exported as `toString`.
+#[wasm_bindgen(js_name = toString)]
+pub fn to_string(json: &str) -> Result<String, JsError> {
+    string_from_json(json).map_err(|e| JsError::new(&e))
+}
+
+/// Returns the canonical form of the key-values string `input`, as by
+/// [`canonicalize`](crate::canonicalize). Exported as `canonicalize`.
+#[wasm_bindgen(js_name = canonicalize)]
+pub fn canonicalize(input: &str) -> Result<String, JsError> {
+    crate::canonicalize(input, serialize_options()).map_err(|e| JsError::new(&e.to_string()))
+}
+
+// `JsError`s can only be created on WebAssembly targets, so the conversions are done by the
+// following functions, which can be tested on the host.
+
+fn json_from_str(input: &str) -> Result<String, String> {
+    crate::to_json_with(input, DeserializeOptions::default())
+        .map(|value| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn string_from_json(json: &str) -> Result<String, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    crate::to_string_from_json_with(&value, serialize_options())
+        .map_err(|_| "expected a JSON object without nested objects".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wasm_conversions() {
+        assert_eq!(
+            json_from_str(r#"mtu=1500,name="tap 0",vhost"#).unwrap(),
+            r#"{"mtu":1500,"name":"tap 0","vhost":true}"#
+        );
+        assert_eq!(
+            json_from_str("a=\"x").unwrap_err(),
+            "expected a string at position 2"
+        );
+        assert_eq!(
+            string_from_json(r#"{"name":"tap 0","queues":[1,2]}"#).unwrap(),
+            r#"name="tap 0" queues=[1,2]"#
+        );
+        assert!(string_from_json(r#"{"a":{"b":1}}"#).is_err());
+        assert!(string_from_json("{").is_err());
+        assert_eq!(
+            crate::canonicalize("b=2 a='x y'", serialize_options()).unwrap(),
+            r#"a="x y" b=2"#
+        );
+    }
+}
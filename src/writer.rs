@@ -0,0 +1,605 @@
+// Copyright 2022 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Serialization into a caller-provided writer or buffer without allocating.
+
+use alloc::string::String;
+use core::fmt;
+use core::fmt::Write;
+
+use serde::ser::Impossible;
+use serde::ser::SerializeMap;
+use serde::ser::SerializeStruct;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::SerializeError;
+use crate::Unsupported;
+
+/// Serializes `value` into `writer` without allocating, producing the same output as
+/// [`KeyValueSerializer::new`](crate::KeyValueSerializer::new), e.g. `key1=value1 key2=42`.
+///
+/// Only structs and maps whose values are scalars are supported: booleans, numbers, characters,
+/// strings, unit enum variants, and options of those, `None` values being omitted. Other values,
+/// such as sequences and nested structs, and per-field formats set with the `keyvalue` attribute
+/// are reported as [`SerializeError::Unsupported`] errors, as supporting them requires
+/// allocations. The key of these errors is left empty for the same reason.
+///
+/// Since nothing is allocated, this can be used where the heap is not available, e.g. to write
+/// key-value pairs to a serial console from an interrupt handler.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_writer;
+///
+/// #[derive(Serialize)]
+/// struct Status {
+///     cpu: u32,
+///     temp: f32,
+///     fan: Option<u32>,
+///     state: &'static str,
+/// }
+///
+/// let mut output = String::new();
+/// to_writer(&mut output, &Status { cpu: 3, temp: 41.5, fan: None, state: "idle" }).unwrap();
+/// assert_eq!(output, "cpu=3 temp=41.5 state=idle");
+/// ```
+pub fn to_writer<W, T>(writer: &mut W, value: &T) -> Result<(), SerializeError>
+where
+    W: Write + ?Sized,
+    T: Serialize + ?Sized,
+{
+    value.serialize(TopWriter(writer))
+}
+
+/// Serializes `value` into `buf` as [`to_writer`] does, and returns the number of bytes written.
+///
+/// [`SerializeError::Write`] is returned if the output does not fit in `buf`, in which case the
+/// content of `buf` is unspecified.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::to_slice;
+///
+/// #[derive(Serialize)]
+/// struct Status {
+///     cpu: u32,
+///     ok: bool,
+/// }
+///
+/// let mut buf = [0u8; 32];
+/// let len = to_slice(&mut buf, &Status { cpu: 3, ok: true }).unwrap();
+/// assert_eq!(&buf[..len], b"cpu=3 ok=True");
+///
+/// assert!(to_slice(&mut buf[..8], &Status { cpu: 3, ok: true }).is_err());
+/// ```
+pub fn to_slice<T: Serialize + ?Sized>(buf: &mut [u8], value: &T) -> Result<usize, SerializeError> {
+    let mut writer = SliceWriter { buf, len: 0 };
+    to_writer(&mut writer, value)?;
+
+    Ok(writer.len)
+}
+
+/// Wrapper whose `Display` implementation serializes the wrapped value as key-value pairs, e.g. to
+/// log it with `info!("config: {}", KeyValueDisplay(&config))`.
+///
+/// The value is serialized once with [`KeyValueSerializer::new`](crate::KeyValueSerializer::new)
+/// into a temporary string, which is then written to the formatter, so nothing is written for
+/// values it cannot serialize, which make formatting fail. As `Display` can only report
+/// [`fmt::Error`], use
+/// [`ToKeyValue::to_key_value`](crate::ToKeyValue::to_key_value) to get the [`SerializeError`]
+/// explaining why.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_keyvalue::KeyValueDisplay;
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     threads: u32,
+///     queues: Vec<u32>,
+/// }
+///
+/// let config = Config { threads: 4, queues: vec![1, 2] };
+/// assert_eq!(format!("config: {}", KeyValueDisplay(&config)), "config: threads=4 queues=[1,2]");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct KeyValueDisplay<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Serialize + ?Sized> fmt::Display for KeyValueDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut serializer = crate::KeyValueSerializer::new();
+        self.0.serialize(&mut serializer)?;
+        f.write_str(serializer.output())
+    }
+}
+
+/// Writer filling a byte slice.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    /// Number of bytes written so far.
+    len: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+}
+
+/// Serializer accepting only the top-level struct or map.
+struct TopWriter<'w, W: ?Sized>(&'w mut W);
+
+/// Serializer of the pairs of the top-level struct or map.
+struct PairsWriter<'w, W: ?Sized> {
+    writer: &'w mut W,
+    /// Whether a pair has been written, so the next one must be preceded by a separator.
+    written: bool,
+}
+
+/// Serializer of a scalar value, which writes its key right before it so that `None` values can be
+/// omitted.
+struct ValueWriter<'a, 'w, W: ?Sized, K: ?Sized> {
+    pairs: &'a mut PairsWriter<'w, W>,
+    key: &'a K,
+}
+
+/// Serializer of map keys, which must be strings or other scalars.
+struct KeyWriter<'a, W: ?Sized>(&'a mut W);
+
+/// Returns an error naming the type of the value from serializer methods for unsupported values.
+macro_rules! unsupported {
+    ($($method:ident($($arg:ty),*) -> $ret:ty = $shape:expr;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ret, SerializeError> {
+                Err(unsupported($shape))
+            }
+        )*
+    };
+}
+
+/// Returns the error for a value of type `shape` that cannot be written without allocating.
+///
+/// The key of the pair is not recorded, as that would require allocating.
+fn unsupported(shape: &'static str) -> SerializeError {
+    SerializeError::Unsupported(Unsupported {
+        shape,
+        key: String::new(),
+    })
+}
+
+/// Implements the methods of `Serializer` for scalar values by writing their textual form with
+/// `$write`, called as `$write(self, value)`.
+macro_rules! scalars {
+    ($write:ident) => {
+        fn serialize_bool(self, v: bool) -> Result<(), SerializeError> {
+            $write(self, if v { "True" } else { "False" })
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_char(self, v: char) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_str(self, v: &str) -> Result<(), SerializeError> {
+            $write(self, v)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+        ) -> Result<(), SerializeError> {
+            $write(self, variant)
+        }
+    };
+}
+
+impl<'w, W: Write + ?Sized> Serializer for TopWriter<'w, W> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<(), SerializeError>;
+    type SerializeTuple = Impossible<(), SerializeError>;
+    type SerializeTupleStruct = Impossible<(), SerializeError>;
+    type SerializeTupleVariant = Impossible<(), SerializeError>;
+    type SerializeMap = PairsWriter<'w, W>;
+    type SerializeStruct = PairsWriter<'w, W>;
+    type SerializeStructVariant = Impossible<(), SerializeError>;
+
+    unsupported! {
+        serialize_bool(bool) -> () = "scalar at the top level";
+        serialize_i8(i8) -> () = "scalar at the top level";
+        serialize_i16(i16) -> () = "scalar at the top level";
+        serialize_i32(i32) -> () = "scalar at the top level";
+        serialize_i64(i64) -> () = "scalar at the top level";
+        serialize_u8(u8) -> () = "scalar at the top level";
+        serialize_u16(u16) -> () = "scalar at the top level";
+        serialize_u32(u32) -> () = "scalar at the top level";
+        serialize_u64(u64) -> () = "scalar at the top level";
+        serialize_f32(f32) -> () = "scalar at the top level";
+        serialize_f64(f64) -> () = "scalar at the top level";
+        serialize_char(char) -> () = "scalar at the top level";
+        serialize_str(&str) -> () = "scalar at the top level";
+        serialize_bytes(&[u8]) -> () = "bytes";
+        serialize_none() -> () = "scalar at the top level";
+        serialize_unit() -> () = "scalar at the top level";
+        serialize_unit_struct(&'static str) -> () = "scalar at the top level";
+        serialize_unit_variant(&'static str, u32, &'static str) -> () = "scalar at the top level";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq = "sequence at the top level";
+        serialize_tuple(usize) -> Self::SerializeTuple = "sequence at the top level";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "sequence at the top level";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant = "tuple variant";
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant = "struct variant";
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerializeError> {
+        Err(unsupported("newtype variant at the top level"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerializeError> {
+        Ok(PairsWriter {
+            writer: self.0,
+            written: false,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerializeError> {
+        Ok(PairsWriter {
+            writer: self.0,
+            written: false,
+        })
+    }
+}
+
+impl<W: Write + ?Sized> SerializeStruct for PairsWriter<'_, W> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(ValueWriter { pairs: self, key })
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> SerializeMap for PairsWriter<'_, W> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    // Keys are only written along with their value.
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, _key: &T) -> Result<(), SerializeError> {
+        Err(unsupported("map key serialized without its value"))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, _value: &T) -> Result<(), SerializeError> {
+        Err(unsupported("map value serialized without its key"))
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), SerializeError>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        value.serialize(ValueWriter { pairs: self, key })
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+/// Writes the separator with the previous pair, the key and the delimiter, then `value`.
+fn write_pair<W, K, V>(writer: ValueWriter<'_, '_, W, K>, value: V) -> Result<(), SerializeError>
+where
+    W: Write + ?Sized,
+    K: Serialize + ?Sized,
+    V: fmt::Display,
+{
+    let pairs = writer.pairs;
+    if pairs.written {
+        pairs.writer.write_char(' ')?;
+    }
+    writer.key.serialize(KeyWriter(&mut *pairs.writer))?;
+    write!(pairs.writer, "={}", value)?;
+    pairs.written = true;
+
+    Ok(())
+}
+
+impl<W: Write + ?Sized, K: Serialize + ?Sized> Serializer for ValueWriter<'_, '_, W, K> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<(), SerializeError>;
+    type SerializeTuple = Impossible<(), SerializeError>;
+    type SerializeTupleStruct = Impossible<(), SerializeError>;
+    type SerializeTupleVariant = Impossible<(), SerializeError>;
+    type SerializeMap = Impossible<(), SerializeError>;
+    type SerializeStruct = Impossible<(), SerializeError>;
+    type SerializeStructVariant = Impossible<(), SerializeError>;
+
+    scalars!(write_pair);
+
+    unsupported! {
+        serialize_bytes(&[u8]) -> () = "bytes";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq = "sequence";
+        serialize_tuple(usize) -> Self::SerializeTuple = "tuple";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "tuple struct";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant = "tuple variant";
+        serialize_map(Option<usize>) -> Self::SerializeMap = "nested map";
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct = "nested struct";
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant = "struct variant";
+    }
+
+    fn serialize_none(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerializeError> {
+        write_pair(self, "")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerializeError> {
+        write_pair(self, "")
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        // Per-field formats cannot be applied without allocating.
+        if name.starts_with(crate::serializer::FIELD_FORMAT) {
+            return Err(unsupported("value with a per-field format"));
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+}
+
+/// Writes a map key.
+fn write_key<W: Write + ?Sized>(
+    writer: KeyWriter<'_, W>,
+    key: impl fmt::Display,
+) -> Result<(), SerializeError> {
+    Ok(write!(writer.0, "{}", key)?)
+}
+
+impl<W: Write + ?Sized> Serializer for KeyWriter<'_, W> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<(), SerializeError>;
+    type SerializeTuple = Impossible<(), SerializeError>;
+    type SerializeTupleStruct = Impossible<(), SerializeError>;
+    type SerializeTupleVariant = Impossible<(), SerializeError>;
+    type SerializeMap = Impossible<(), SerializeError>;
+    type SerializeStruct = Impossible<(), SerializeError>;
+    type SerializeStructVariant = Impossible<(), SerializeError>;
+
+    scalars!(write_key);
+
+    unsupported! {
+        serialize_bytes(&[u8]) -> () = "bytes";
+        serialize_none() -> () = "`None` as a map key";
+        serialize_unit() -> () = "unit as a map key";
+        serialize_unit_struct(&'static str) -> () = "unit struct as a map key";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq = "compound map key";
+        serialize_tuple(usize) -> Self::SerializeTuple = "compound map key";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "compound map key";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant = "tuple variant";
+        serialize_map(Option<usize>) -> Self::SerializeMap = "compound map key";
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct = "compound map key";
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant = "struct variant";
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerializeError> {
+        Err(unsupported("newtype variant as a map key"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::KeyValueSerializer;
+
+    #[derive(Serialize)]
+    enum Mode {
+        Fast,
+    }
+
+    #[derive(Serialize)]
+    struct Record {
+        name: &'static str,
+        id: i64,
+        ratio: f32,
+        ok: bool,
+        skipped: Option<u8>,
+        mode: Mode,
+        initial: char,
+    }
+
+    #[test]
+    fn writer_matches_serializer() {
+        let record = Record {
+            name: "disk0",
+            id: -3,
+            ratio: 0.1,
+            ok: false,
+            skipped: None,
+            mode: Mode::Fast,
+            initial: 'd',
+        };
+
+        let mut serializer = KeyValueSerializer::new();
+        record.serialize(&mut serializer).unwrap();
+        let expected = serializer.into_output();
+
+        let mut output = String::new();
+        to_writer(&mut output, &record).unwrap();
+        assert_eq!(output, expected);
+
+        let mut buf = [0u8; 64];
+        let len = to_slice(&mut buf, &record).unwrap();
+        assert_eq!(&buf[..len], expected.as_bytes());
+
+        let map = BTreeMap::from([(1, "a"), (2, "b")]);
+        let mut output = String::new();
+        to_writer(&mut output, &map).unwrap();
+        assert_eq!(output, "1=a 2=b");
+    }
+
+    #[test]
+    fn writer_errors() {
+        #[derive(Serialize)]
+        struct Nested {
+            sizes: Vec<u32>,
+        }
+
+        let mut output = String::new();
+        assert_eq!(
+            to_writer(&mut output, &Nested { sizes: vec![1] }),
+            Err(unsupported("sequence"))
+        );
+        assert_eq!(
+            to_writer(&mut output, &42),
+            Err(unsupported("scalar at the top level"))
+        );
+
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            to_slice(&mut buf, &BTreeMap::from([("key", "value")])),
+            Err(SerializeError::Write)
+        );
+    }
+
+    #[test]
+    fn key_value_display() {
+        let map = BTreeMap::from([("a", Some(1)), ("b", None)]);
+        assert_eq!(KeyValueDisplay(&map).to_string(), "a=1");
+
+        let nested = BTreeMap::from([("a", vec![1, 2])]);
+        assert_eq!(format!("[{}]", KeyValueDisplay(&nested)), "[a=[1,2]]");
+
+        let unsupported = BTreeMap::from([("a", BTreeMap::from([("b", 1)]))]);
+        let mut output = String::new();
+        assert!(write!(output, "{}", KeyValueDisplay(&unsupported)).is_err());
+        assert!(output.is_empty());
+    }
+}